@@ -0,0 +1,194 @@
+//! Derive macro for the `ShellVars` trait in [git-status-vars][].
+//!
+//! Use this through the `derive` feature of `git-status-vars` rather than
+//! depending on it directly:
+//!
+//! ```rust,ignore
+//! use git_status_vars::ShellVars;
+//!
+//! #[derive(ShellVars)]
+//! struct Battery {
+//!     level: u8,
+//!     #[shell_vars(rename = "state")]
+//!     charging_state: String,
+//!     #[shell_vars(skip)]
+//!     raw: Vec<u8>,
+//! }
+//! ```
+//!
+//! Each field is written as a variable named after the field. Fields are
+//! written with `ShellWriter::try_write_var()`, so their types must implement
+//! `Display`, except:
+//!
+//! * `Option<T>` fields are written if they are `Some`, and passed to
+//!   `ShellWriter::try_write_missing()` if they are `None`.
+//! * `#[shell_vars(debug)]` fields are written with
+//!   `ShellWriter::try_write_var_debug()`.
+//! * `#[shell_vars(group)]` fields must implement `ShellVars` themselves, and
+//!   are written in a group named after the field.
+//!
+//! `#[shell_vars(rename = "name")]` changes the variable or group name, and
+//! `#[shell_vars(skip)]` leaves the field out.
+//!
+//! [git-status-vars]: https://github.com/danielparks/git-status-vars
+
+// Most lint configuration is in lints.toml, but that isn’t supported by
+// cargo-geiger, and it only supports deny, not forbid.
+#![forbid(unsafe_code)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, LitStr, Type};
+
+/// Derive `ShellVars` for a struct with named fields. See the
+/// [crate documentation][crate].
+#[proc_macro_derive(ShellVars, attributes(shell_vars))]
+pub fn derive_shell_vars(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generate the `ShellVars` implementation.
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "ShellVars can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "ShellVars can only be derived for structs with named fields",
+        ));
+    };
+
+    let writes = fields
+        .named
+        .iter()
+        .map(write_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) =
+        input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::git_status_vars::ShellVars
+            for #name #type_generics #where_clause
+        {
+            fn write_to_shell<W: ::std::io::Write>(
+                &self,
+                out: &::git_status_vars::ShellWriter<W>,
+            ) -> ::std::io::Result<()> {
+                #(#writes)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// How a field should be written.
+#[derive(Default)]
+struct FieldOptions {
+    /// The variable or group name, if it’s not the field name.
+    rename: Option<String>,
+
+    /// Leave the field out.
+    skip: bool,
+
+    /// Write the field with `Debug` instead of `Display`.
+    debug: bool,
+
+    /// Write the field as a group of variables with its own `ShellVars`.
+    group: bool,
+}
+
+impl FieldOptions {
+    /// Parse the `#[shell_vars(...)]` attributes on a field.
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut options = Self::default();
+        for attr in &field.attrs {
+            if !attr.path().is_ident("shell_vars") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    let name: LitStr = meta.value()?.parse()?;
+                    options.rename = Some(name.value());
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else if meta.path.is_ident("debug") {
+                    options.debug = true;
+                } else if meta.path.is_ident("group") {
+                    options.group = true;
+                } else {
+                    return Err(meta.error(
+                        "expected `rename = \"...\"`, `skip`, `debug`, or \
+                            `group`",
+                    ));
+                }
+                Ok(())
+            })?;
+        }
+        if options.debug && options.group {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`debug` and `group` can’t be used together",
+            ));
+        }
+        Ok(options)
+    }
+}
+
+/// Generate the code to write one field.
+fn write_field(field: &Field) -> syn::Result<TokenStream2> {
+    let options = FieldOptions::parse(field)?;
+    if options.skip {
+        return Ok(TokenStream2::new());
+    }
+
+    let ident = field.ident.as_ref().expect("fields should be named");
+    let name = options
+        .rename
+        .unwrap_or_else(|| ident.to_string().trim_start_matches("r#").into());
+    let write = |value: TokenStream2| {
+        if options.group {
+            quote! { out.group(#name).try_write_vars(#value)?; }
+        } else if options.debug {
+            quote! { out.try_write_var_debug(#name, #value)?; }
+        } else {
+            quote! { out.try_write_var(#name, #value)?; }
+        }
+    };
+
+    if is_option(&field.ty) {
+        let write = write(quote! { value });
+        Ok(quote! {
+            if let ::std::option::Option::Some(value) = &self.#ident {
+                #write
+            } else {
+                out.try_write_missing(#name)?;
+            }
+        })
+    } else {
+        Ok(write(quote! { &self.#ident }))
+    }
+}
+
+/// Check if a type is `Option<T>`.
+///
+/// This only looks at the name, so a type alias for `Option` isn’t detected.
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option")
+}
@@ -0,0 +1,560 @@
+//! Cache the output of a summary so that unchanged repositories can be
+//! summarized without running status again.
+//!
+//! The cache is stored in the git directory of the repository. It contains a
+//! key line describing the state of the repository when the summary was made,
+//! a line with the [`Condition`] of the repository, and then the summary
+//! itself as unprefixed shell variables.
+//!
+//! Branches without an upstream are also recorded in a separate file, so that
+//! the configuration doesn’t need to be searched for them every time. See
+//! [`no_upstream()`].
+
+use crate::schema::{self, VarKind};
+use crate::{head_only, vars, Condition, ShellWriter, SummarizeOptions, Value};
+use crate::{GitError, UntrackedFiles};
+use git2::{Branch, ErrorClass, ErrorCode, Oid, Repository};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The name of the cache file within the git directory.
+const CACHE_FILE: &str = "git-status-vars-cache";
+
+/// The name of the file within the git directory that lists branches without
+/// an upstream.
+const NO_UPSTREAM_FILE: &str = "git-status-vars-no-upstream";
+
+/// The files and directories in the git directory that show an operation is
+/// in progress. See [`Repository::state()`].
+const STATE_FILES: [&str; 7] = [
+    "MERGE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REVERT_HEAD",
+    "BISECT_LOG",
+    "rebase-apply",
+    "rebase-merge",
+    "sequencer",
+];
+
+/// The files in the git directory that record the progress of a rebase or
+/// `git am`. See [`ps1_state()`][crate::git2_interop::ps1_state].
+///
+/// These are rewritten in place, which doesn’t change the directory they’re in.
+const PROGRESS_FILES: [&str; 4] = [
+    "rebase-merge/msgnum",
+    "rebase-merge/end",
+    "rebase-apply/next",
+    "rebase-apply/last",
+];
+
+/// A description of the repository state used to validate the cache.
+///
+/// If any of these change, the cached summary is stale.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Key {
+    /// The options that affect the output of the summary. See
+    /// [`options_key()`].
+    options: String,
+
+    /// Whether the summary includes `unset` lines. See
+    /// [`ShellWriter::with_unset_missing()`].
+    unset_missing: bool,
+
+    /// The name of the reference `HEAD` points to, or `""`.
+    head_name: String,
+
+    /// The commit `HEAD` resolves to, or `""`.
+    head_oid: String,
+
+    /// The commit the upstream of `HEAD` (or [`SummarizeOptions::upstream_ref`])
+    /// resolves to, or `""`.
+    upstream_oid: String,
+
+    /// The checksum at the end of the index, or `""` if it doesn’t exist.
+    index_checksum: String,
+
+    /// A hash of the modification times and sizes of the files tracked in the
+    /// index, the directories that contain them, and any untracked directories
+    /// that aren’t ignored.
+    workdir_signature: u64,
+
+    /// A hash of the modification times and sizes of the files that list
+    /// ignored files, other than `.gitignore` files.
+    exclude_signature: u64,
+
+    /// A hash of the modification times and sizes of the files that show an
+    /// operation, such as a merge or rebase, is in progress, and of the files
+    /// that record its progress.
+    state_signature: u64,
+
+    /// A hash of the modification time and size of the stash reflog.
+    stash_signature: u64,
+
+    /// A hash of the modification times and sizes of the files that can make
+    /// the history inexact. See [`Upstream::exact`][crate::Upstream::exact].
+    history_signature: u64,
+}
+
+impl Key {
+    /// Calculate the cache key for a repository.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`git2::Error`] if the index could not be read.
+    pub fn for_repository(
+        repository: &Repository,
+        options: &SummarizeOptions,
+        unset_missing: bool,
+    ) -> Result<Self, git2::Error> {
+        let (head_name, head_oid) = match repository.head() {
+            Ok(head) => (
+                head.name().unwrap_or_default().to_owned(),
+                head.target().map(|oid| oid.to_string()).unwrap_or_default(),
+            ),
+            Err(_) => (String::new(), String::new()),
+        };
+        let upstream_oid = match &options.upstream_ref {
+            Some(upstream_ref) => repository
+                .revparse_single(upstream_ref)
+                .and_then(|object| object.peel_to_commit())
+                .map(|commit| commit.id())
+                .ok(),
+            None => repository
+                .head()
+                .ok()
+                .filter(|head| {
+                    head.name().map_or(true, |name| {
+                        no_upstream(repository, name).is_none()
+                    })
+                })
+                .and_then(|head| Branch::wrap(head).upstream().ok())
+                .and_then(|upstream| upstream.get().target()),
+        }
+        .map(|oid| oid.to_string())
+        .unwrap_or_default();
+
+        let index_checksum = index_checksum(&repository.path().join("index"));
+
+        // Operations in progress are recorded in the worktree’s git directory,
+        // except for the sequencer, which git has kept in both places.
+        let common_dir = head_only::common_dir(repository.path());
+        let mut state_hasher = DefaultHasher::new();
+        for path in STATE_FILES.iter().chain(&PROGRESS_FILES) {
+            hash_stat(&mut state_hasher, &repository.path().join(path));
+        }
+        hash_stat(&mut state_hasher, &common_dir.join("sequencer"));
+
+        // Stashes are shared by all worktrees, so the stash reflog is in the
+        // common directory rather than the worktree’s git directory.
+        let mut stash_hasher = DefaultHasher::new();
+        hash_stat(&mut stash_hasher, &common_dir.join("logs/refs/stash"));
+
+        // Replace references may be packed, so check packed-refs too.
+        let mut history_hasher = DefaultHasher::new();
+        for path in ["shallow", "info/grafts", "refs/replace", "packed-refs"] {
+            hash_stat(&mut history_hasher, &common_dir.join(path));
+        }
+
+        let untracked = options.untracked() != UntrackedFiles::No;
+        Ok(Self {
+            options: options_key(options),
+            unset_missing,
+            head_name,
+            head_oid,
+            upstream_oid,
+            index_checksum,
+            workdir_signature: workdir_signature(repository, untracked)?,
+            exclude_signature: exclude_signature(repository),
+            state_signature: state_hasher.finish(),
+            stash_signature: stash_hasher.finish(),
+            history_signature: history_hasher.finish(),
+        })
+    }
+
+    /// Format the key as a single line for the cache file.
+    fn to_line(&self) -> String {
+        format!(
+            "options={:?} unset_missing={} head_name={:?} head_oid={} \
+                upstream_oid={} index={} workdir={:016x} exclude={:016x} \
+                state={:016x} stash={:016x} history={:016x}",
+            self.options,
+            self.unset_missing,
+            self.head_name,
+            self.head_oid,
+            self.upstream_oid,
+            self.index_checksum,
+            self.workdir_signature,
+            self.exclude_signature,
+            self.state_signature,
+            self.stash_signature,
+            self.history_signature,
+        )
+    }
+}
+
+/// Describe the options that affect the output of a summary.
+///
+/// Options that only affect how the summary is computed, like
+/// [`SummarizeOptions::cancel`], are left out so that they don’t invalidate
+/// the cache.
+#[allow(clippy::unneeded_field_pattern)] // So new options aren’t forgotten.
+fn options_key(options: &SummarizeOptions) -> String {
+    let SummarizeOptions {
+        cache: _,
+        sections,
+        timing,
+        untracked_files,
+        include_submodules,
+        include_ignored,
+        detect_renames,
+        update_index,
+        fsmonitor: _,
+        parallel: _,
+        budgets,
+        bare,
+        upstream_ref,
+        max_entries,
+        count_limit,
+        skip_counts_above,
+        start_path,
+        both_workdirs,
+        path_style,
+        color_ansi,
+        glyphs,
+        two_phase,
+        cancel: _,
+    } = options;
+    format!(
+        "sections={sections:?} timing={timing} \
+            untracked_files={untracked_files:?} \
+            include_submodules={include_submodules} \
+            include_ignored={include_ignored} \
+            detect_renames={detect_renames:?} update_index={update_index} \
+            budgets={budgets:?} bare={bare:?} upstream_ref={upstream_ref:?} \
+            max_entries={max_entries:?} count_limit={count_limit:?} \
+            skip_counts_above={skip_counts_above:?} \
+            start_path={start_path:?} both_workdirs={both_workdirs} \
+            path_style={path_style:?} color_ansi={color_ansi} \
+            glyphs={glyphs:?} two_phase={two_phase}"
+    )
+}
+
+/// Read the checksum of the index file as hex, or `""` if it can’t be read.
+///
+/// The index ends with a SHA-1 hash of its contents, so this changes whenever
+/// the index does, even if its size and modification time stay the same.
+fn index_checksum(path: &Path) -> String {
+    let mut checksum = [0; 20];
+    let result = fs::File::open(path).and_then(|mut file| {
+        file.seek(SeekFrom::End(-20))?;
+        file.read_exact(&mut checksum)
+    });
+    result
+        .ok()
+        .and_then(|()| Oid::from_bytes(&checksum).ok())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default()
+}
+
+/// Get the modification time from metadata, if available.
+fn mtime(metadata: &fs::Metadata) -> Option<SystemTime> {
+    metadata.modified().ok()
+}
+
+/// Hash the stat information of the files in the index and their directories.
+///
+/// Modifying a tracked file changes its modification time or size, and adding
+/// or removing a file changes the modification time of its directory. If
+/// `untracked` is set, untracked directories that aren’t ignored are included
+/// too, since adding a file to one doesn’t change any tracked directory. The
+/// `.gitignore` file in each directory is included whether it’s tracked or not.
+fn workdir_signature(
+    repository: &Repository,
+    untracked: bool,
+) -> Result<u64, git2::Error> {
+    let mut hasher = DefaultHasher::new();
+    let Some(workdir) = repository.workdir() else {
+        return Ok(hasher.finish());
+    };
+
+    let mut directories = BTreeSet::new();
+    let mut tracked = BTreeSet::new();
+    directories.insert(PathBuf::new());
+    for entry in repository.index()?.iter() {
+        entry.path.hash(&mut hasher);
+        let Ok(path) = std::str::from_utf8(&entry.path) else {
+            continue;
+        };
+        let path = Path::new(path);
+        hash_stat(&mut hasher, &workdir.join(path));
+        directories.extend(path.ancestors().skip(1).map(Path::to_path_buf));
+        tracked.insert(path.to_path_buf());
+    }
+
+    if untracked {
+        let mut pending: Vec<PathBuf> = directories.iter().cloned().collect();
+        while let Some(directory) = pending.pop() {
+            for subdirectory in untracked_subdirectories(
+                repository,
+                workdir,
+                &directory,
+                &directories,
+                &tracked,
+            ) {
+                pending.push(subdirectory.clone());
+                directories.insert(subdirectory);
+            }
+        }
+    }
+
+    for directory in directories {
+        directory.hash(&mut hasher);
+        hash_stat(&mut hasher, &workdir.join(&directory));
+        hash_stat(&mut hasher, &workdir.join(directory).join(".gitignore"));
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Find the subdirectories of `directory` (relative to `workdir`) that aren’t
+/// in `directories`, aren’t tracked (e.g. submodules), and aren’t ignored.
+///
+/// Git directories are skipped, as are subdirectories that can’t be read.
+fn untracked_subdirectories(
+    repository: &Repository,
+    workdir: &Path,
+    directory: &Path,
+    directories: &BTreeSet<PathBuf>,
+    tracked: &BTreeSet<PathBuf>,
+) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(workdir.join(directory)) else {
+        return vec![];
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+                && entry.file_name() != ".git"
+        })
+        .map(|entry| directory.join(entry.file_name()))
+        .filter(|path| !directories.contains(path) && !tracked.contains(path))
+        .filter(|path| {
+            // Directory patterns like `target/` only match with a trailing `/`.
+            !repository.is_path_ignored(path.join("")).unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Hash the modification times and sizes of `info/exclude` and the file named
+/// by `core.excludesFile`.
+fn exclude_signature(repository: &Repository) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let common_dir = head_only::common_dir(repository.path());
+    hash_stat(&mut hasher, &common_dir.join("info/exclude"));
+    if let Some(path) = excludes_file(repository) {
+        path.hash(&mut hasher);
+        hash_stat(&mut hasher, &path);
+    }
+    hasher.finish()
+}
+
+/// Get the path of the global excludes file, like git does.
+///
+/// This is `core.excludesFile` if it’s set, or `$XDG_CONFIG_HOME/git/ignore`
+/// or `$HOME/.config/git/ignore` otherwise.
+fn excludes_file(repository: &Repository) -> Option<PathBuf> {
+    repository
+        .config()
+        .ok()
+        .and_then(|config| config.get_path("core.excludesFile").ok())
+        .or_else(|| {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME")
+                        .map(|home| PathBuf::from(home).join(".config"))
+                })
+                .map(|config| config.join("git/ignore"))
+        })
+}
+
+/// Add the modification time and size of a path to a hash.
+fn hash_stat<H: Hasher>(hasher: &mut H, path: &Path) {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            mtime(&metadata).hash(hasher);
+            metadata.len().hash(hasher);
+        }
+        Err(error) => error.kind().hash(hasher),
+    }
+}
+
+/// Get the path to the cache file for a repository.
+fn cache_path(repository: &Repository) -> PathBuf {
+    repository.path().join(CACHE_FILE)
+}
+
+/// Read the cached condition and summary for a repository if it matches
+/// `key`.
+///
+/// Returns `None` if there is no cache, if the cache is stale, or if the cache
+/// could not be read.
+#[must_use]
+pub fn read(repository: &Repository, key: &Key) -> Option<(Condition, String)> {
+    let contents = fs::read_to_string(cache_path(repository)).ok()?;
+    let (line, rest) = contents.split_once('\n')?;
+    if line != key.to_line() {
+        return None;
+    }
+    let (condition, summary) = rest.split_once('\n')?;
+    Some((condition.parse().ok()?, summary.to_owned()))
+}
+
+/// Write a summary to the cache for a repository.
+///
+/// # Errors
+///
+/// This will return [`io::Error`] if the cache file could not be written.
+pub fn write(
+    repository: &Repository,
+    key: &Key,
+    condition: Condition,
+    summary: &str,
+) -> io::Result<()> {
+    // Write to a temporary file first so that concurrent readers never see a
+    // partially written cache.
+    let path = cache_path(repository);
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(
+        &temporary,
+        format!("{}\n{}\n{}", key.to_line(), condition, summary),
+    )?;
+    fs::rename(&temporary, &path)
+}
+
+/// Hash the modification times and sizes of the repository configuration.
+///
+/// Only the repository’s own configuration files are checked; changes to the
+/// global configuration are not noticed.
+fn config_signature(repository: &Repository) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let common_dir = head_only::common_dir(repository.path());
+    hash_stat(&mut hasher, &common_dir.join("config"));
+    hash_stat(&mut hasher, &repository.path().join("config.worktree"));
+    hasher.finish()
+}
+
+/// Check if `branch` (e.g. `"refs/heads/main"`) was recorded as having no
+/// upstream by [`record_no_upstream()`].
+///
+/// Returns the error originally returned for the lookup, or `None` if the
+/// branch wasn’t recorded or the configuration has changed since.
+#[must_use]
+pub fn no_upstream(
+    repository: &Repository,
+    branch: &str,
+) -> Option<git2::Error> {
+    let path = repository.path().join(NO_UPSTREAM_FILE);
+    let contents = fs::read_to_string(path).ok()?;
+    let mut lines = contents.lines();
+    if lines.next()? != format!("config={:016x}", config_signature(repository))
+    {
+        return None;
+    }
+    lines
+        .filter_map(|line| line.split_once('\t'))
+        .find(|(name, _)| *name == branch)
+        .map(|(_, message)| {
+            git2::Error::new(ErrorCode::NotFound, ErrorClass::Config, message)
+        })
+}
+
+/// Record that `branch` has no upstream because of `error`.
+///
+/// The record is discarded when the configuration changes. Branch names can’t
+/// contain control characters, so each branch is saved on a line with the
+/// error message after a tab.
+///
+/// # Errors
+///
+/// This will return [`io::Error`] if the file could not be written.
+pub fn record_no_upstream(
+    repository: &Repository,
+    branch: &str,
+    error: &GitError,
+) -> io::Result<()> {
+    let path = repository.path().join(NO_UPSTREAM_FILE);
+    let signature = format!("config={:016x}", config_signature(repository));
+    let mut contents = fs::read_to_string(&path)
+        .ok()
+        .filter(|contents| contents.lines().next() == Some(&signature))
+        .unwrap_or_else(|| format!("{signature}\n"));
+    contents.push_str(branch);
+    contents.push('\t');
+    contents.push_str(&error.message().replace('\n', " "));
+    contents.push('\n');
+
+    // See `write()`.
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, &path)
+}
+
+/// Replay a cached summary to a [`ShellWriter`].
+///
+/// `unset var` lines are passed to [`ShellWriter::write_missing()`]. Values are
+/// written with the type [`schema::VARS`] gives them, so that e.g. PowerShell
+/// output is the same as without the cache. The cached `fetch_age_seconds`
+/// variable is replaced with the current value
+/// passed in, since it changes even if the repository doesn’t.
+///
+/// # Errors
+///
+/// This will return an error if the summary could not be parsed.
+pub fn replay<W: io::Write>(
+    out: &ShellWriter<W>,
+    summary: &str,
+    fetch_age_seconds: Option<u64>,
+) -> Result<(), shell_words::ParseError> {
+    let mut words = shell_words::split(summary)?.into_iter();
+    while let Some(word) = words.next() {
+        if word == "unset" {
+            if let Some(var) = words.next() {
+                if var == vars::FETCH_AGE_SECONDS {
+                    out.write_value(var, fetch_age_seconds);
+                } else {
+                    out.write_missing(var);
+                }
+            }
+        } else if let Some((var, value)) = word.split_once('=') {
+            if var == vars::FETCH_AGE_SECONDS {
+                out.write_value(var, fetch_age_seconds);
+            } else {
+                out.write_value(var, typed_value(var, value));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convert a value read from a cached summary back to the type of `var`.
+fn typed_value(var: &str, value: &str) -> Value {
+    let kind = schema::find_var(var).map(|def| def.kind);
+    match (kind, value) {
+        (Some(VarKind::Int | VarKind::Count | VarKind::Bool), "") => {
+            Value::Null
+        }
+        (Some(VarKind::Bool), "true") => Value::Bool(true),
+        (Some(VarKind::Bool), "false") => Value::Bool(false),
+        (Some(VarKind::Int | VarKind::Count), _) => value
+            .parse()
+            .map_or_else(|_| Value::from(value), Value::Int),
+        _ => Value::from(value),
+    }
+}
@@ -0,0 +1,90 @@
+//! Cancel summarizing a repository from another thread or after a deadline.
+
+use crate::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A token to stop summarizing a repository early. See
+/// [`SummarizeOptions::cancel`][crate::SummarizeOptions::cancel].
+///
+/// Clones of a token share the same state, so one clone can be passed in the
+/// options while another is used to cancel. Cancellation is checked between
+/// sections and while waiting for sections computed in other threads, so a
+/// section that is already running in the current thread will finish first.
+///
+/// ```rust
+/// use git_status_vars::CancelToken;
+/// use std::time::Duration;
+///
+/// let token = CancelToken::new().with_timeout(Duration::from_millis(50));
+/// let clone = token.clone();
+/// assert!(!token.is_cancelled());
+/// clone.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    /// Set by [`Self::cancel()`].
+    cancelled: Arc<AtomicBool>,
+
+    /// When to give up, if ever.
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    /// Create a token that is only cancelled by [`Self::cancel()`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also cancel once `deadline` has passed.
+    #[must_use]
+    pub const fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Also cancel once `timeout` has passed from now.
+    #[must_use]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.with_deadline(deadline),
+            None => self,
+        }
+    }
+
+    /// Get the deadline, if there is one.
+    #[must_use]
+    pub const fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Cancel this token and all of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Check if [`Self::cancel()`] was called or the deadline has passed.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Return [`Error::Cancelled`] if the token has been cancelled.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`Error::Cancelled`] if [`Self::is_cancelled()`].
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,455 @@
+//! The error type for summarizing repositories.
+
+use crate::ShellWriter;
+use git2::{ErrorClass, ErrorCode};
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+/// A [`Result`][std::result::Result] with [`Error`] as the default error.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error encountered while summarizing a repository.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An error from libgit2, e.g. the repository could not be read.
+    Git(GitError),
+
+    /// An error reading or writing a file or stream.
+    Io(io::Error),
+
+    /// Summarizing took longer than the timeout.
+    Timeout(Duration),
+
+    /// Summarizing was stopped with a [`CancelToken`][crate::CancelToken].
+    Cancelled,
+
+    /// The options can’t be used with the repository, e.g. a working tree is
+    /// required but the repository is bare.
+    InvalidOptions(String),
+}
+
+impl Error {
+    /// Get the underlying libgit2 error, if there is one.
+    #[must_use]
+    pub const fn git(&self) -> Option<&GitError> {
+        match self {
+            Self::Git(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Check if this is the libgit2 error for there being no repository to
+    /// summarize.
+    ///
+    /// Other “not found” errors, e.g. for a missing object, return `false`.
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        self.git().is_some_and(|error| {
+            error.code == "not_found" && error.class == "repository"
+        })
+    }
+
+    /// Get a stable identifier for what went wrong, e.g. `"not_found"`.
+    ///
+    /// This is one of [`ERROR_CODES`]. libgit2 error codes are converted to
+    /// snake case, e.g. `ErrorCode::UnbornBranch` is `"unborn_branch"`.
+    ///
+    /// ```rust
+    /// use git_status_vars::Error;
+    ///
+    /// assert_eq!(Error::Cancelled.code(), "cancelled");
+    /// ```
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::Git(error) => error.code(),
+            Self::Io(_) => "io",
+            Self::Timeout(_) => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::InvalidOptions(_) => "invalid_options",
+        }
+    }
+
+    /// Get a stable identifier for where the error happened, e.g.
+    /// `"reference"`.
+    ///
+    /// This is one of [`ERROR_CLASSES`]. libgit2 error classes are converted
+    /// to snake case, e.g. `ErrorClass::FetchHead` is `"fetch_head"`.
+    #[must_use]
+    pub const fn class(&self) -> &'static str {
+        match self {
+            Self::Git(error) => error.class(),
+            Self::Io(_) => "os",
+            Self::Timeout(_) | Self::Cancelled => "summary",
+            Self::InvalidOptions(_) => "options",
+        }
+    }
+
+    /// Get a human-readable message without the code or class.
+    #[must_use]
+    pub fn message(&self) -> String {
+        match self {
+            Self::Git(error) => error.message.clone(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Git(error) => error.fmt(f),
+            Self::Io(error) => error.fmt(f),
+            Self::Timeout(timeout) => write!(f, "Timed out after {timeout:?}"),
+            Self::Cancelled => f.write_str("Cancelled"),
+            Self::InvalidOptions(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Git(error) => Some(error),
+            Self::Io(error) => Some(error),
+            Self::Timeout(_) | Self::Cancelled | Self::InvalidOptions(_) => {
+                None
+            }
+        }
+    }
+}
+
+impl From<git2::Error> for Error {
+    fn from(error: git2::Error) -> Self {
+        Self::Git(GitError::from(&error))
+    }
+}
+
+/// An error from libgit2. See [`Error::Git`].
+///
+/// This has the same information as a `git2::Error`, so that callers don’t
+/// need to depend on the same version of [`git2`] as this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitError {
+    /// A human-readable message.
+    message: String,
+
+    /// What went wrong. See [`Self::code()`].
+    code: &'static str,
+
+    /// Where it went wrong. See [`Self::class()`].
+    class: &'static str,
+}
+
+impl GitError {
+    /// Get a human-readable message without the code or class.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get a stable identifier for what went wrong, e.g. `"not_found"`. See
+    /// [`Error::code()`].
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Get a stable identifier for where the error happened, e.g.
+    /// `"reference"`. See [`Error::class()`].
+    #[must_use]
+    pub const fn class(&self) -> &'static str {
+        self.class
+    }
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Like `git2::Error`, but with the stable identifiers.
+        f.write_str(&self.message)?;
+        if self.class != "none" {
+            write!(f, "; class={}", self.class)?;
+        }
+        if self.code != "generic" {
+            write!(f, "; code={}", self.code)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GitError {}
+
+impl From<&git2::Error> for GitError {
+    fn from(error: &git2::Error) -> Self {
+        Self {
+            message: error.message().to_owned(),
+            code: git_code(error.code()),
+            class: git_class(error.class()),
+        }
+    }
+}
+
+impl From<&ErrorInfo> for GitError {
+    fn from(error: &ErrorInfo) -> Self {
+        Self {
+            message: error.message.clone(),
+            code: error.code,
+            class: error.class,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Every value of `*_error_code` variables: the values of [`Error::code()`],
+/// and `""` for no error.
+pub const ERROR_CODES: &[&str] = &[
+    "generic",
+    "not_found",
+    "exists",
+    "ambiguous",
+    "buffer_size",
+    "user",
+    "bare_repository",
+    "unborn_branch",
+    "unmerged",
+    "not_fast_forward",
+    "invalid_spec",
+    "conflict",
+    "locked",
+    "modified",
+    "auth",
+    "certificate",
+    "applied",
+    "peel",
+    "eof",
+    "invalid",
+    "uncommitted",
+    "directory",
+    "merge_conflict",
+    "hashsum_mismatch",
+    "index_dirty",
+    "apply_fail",
+    "owner",
+    "timeout",
+    "io",
+    "cancelled",
+    "invalid_options",
+    "",
+];
+
+/// Every value of `*_error_class` variables: the values of [`Error::class()`],
+/// and `""` for no error.
+pub const ERROR_CLASSES: &[&str] = &[
+    "none",
+    "no_memory",
+    "os",
+    "invalid",
+    "reference",
+    "zlib",
+    "repository",
+    "config",
+    "regex",
+    "odb",
+    "index",
+    "object",
+    "net",
+    "tag",
+    "tree",
+    "indexer",
+    "ssl",
+    "submodule",
+    "thread",
+    "stash",
+    "checkout",
+    "fetch_head",
+    "merge",
+    "ssh",
+    "filter",
+    "revert",
+    "callback",
+    "cherry_pick",
+    "describe",
+    "rebase",
+    "filesystem",
+    "patch",
+    "worktree",
+    "sha1",
+    "http",
+    "summary",
+    "options",
+    "",
+];
+
+/// Get the identifier for a libgit2 error code. See [`Error::code()`].
+const fn git_code(code: ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::GenericError => "generic",
+        ErrorCode::NotFound => "not_found",
+        ErrorCode::Exists => "exists",
+        ErrorCode::Ambiguous => "ambiguous",
+        ErrorCode::BufSize => "buffer_size",
+        ErrorCode::User => "user",
+        ErrorCode::BareRepo => "bare_repository",
+        ErrorCode::UnbornBranch => "unborn_branch",
+        ErrorCode::Unmerged => "unmerged",
+        ErrorCode::NotFastForward => "not_fast_forward",
+        ErrorCode::InvalidSpec => "invalid_spec",
+        ErrorCode::Conflict => "conflict",
+        ErrorCode::Locked => "locked",
+        ErrorCode::Modified => "modified",
+        ErrorCode::Auth => "auth",
+        ErrorCode::Certificate => "certificate",
+        ErrorCode::Applied => "applied",
+        ErrorCode::Peel => "peel",
+        ErrorCode::Eof => "eof",
+        ErrorCode::Invalid => "invalid",
+        ErrorCode::Uncommitted => "uncommitted",
+        ErrorCode::Directory => "directory",
+        ErrorCode::MergeConflict => "merge_conflict",
+        ErrorCode::HashsumMismatch => "hashsum_mismatch",
+        ErrorCode::IndexDirty => "index_dirty",
+        ErrorCode::ApplyFail => "apply_fail",
+        ErrorCode::Owner => "owner",
+        ErrorCode::Timeout => "timeout",
+    }
+}
+
+/// Get the identifier for a libgit2 error class. See [`Error::class()`].
+const fn git_class(class: ErrorClass) -> &'static str {
+    match class {
+        ErrorClass::None => "none",
+        ErrorClass::NoMemory => "no_memory",
+        ErrorClass::Os => "os",
+        ErrorClass::Invalid => "invalid",
+        ErrorClass::Reference => "reference",
+        ErrorClass::Zlib => "zlib",
+        ErrorClass::Repository => "repository",
+        ErrorClass::Config => "config",
+        ErrorClass::Regex => "regex",
+        ErrorClass::Odb => "odb",
+        ErrorClass::Index => "index",
+        ErrorClass::Object => "object",
+        ErrorClass::Net => "net",
+        ErrorClass::Tag => "tag",
+        ErrorClass::Tree => "tree",
+        ErrorClass::Indexer => "indexer",
+        ErrorClass::Ssl => "ssl",
+        ErrorClass::Submodule => "submodule",
+        ErrorClass::Thread => "thread",
+        ErrorClass::Stash => "stash",
+        ErrorClass::Checkout => "checkout",
+        ErrorClass::FetchHead => "fetch_head",
+        ErrorClass::Merge => "merge",
+        ErrorClass::Ssh => "ssh",
+        ErrorClass::Filter => "filter",
+        ErrorClass::Revert => "revert",
+        ErrorClass::Callback => "callback",
+        ErrorClass::CherryPick => "cherry_pick",
+        ErrorClass::Describe => "describe",
+        ErrorClass::Rebase => "rebase",
+        ErrorClass::Filesystem => "filesystem",
+        ErrorClass::Patch => "patch",
+        ErrorClass::Worktree => "worktree",
+        ErrorClass::Sha1 => "sha1",
+        ErrorClass::Http => "http",
+    }
+}
+
+/// An error as it’s output in shell variables like `repo_error`: a message,
+/// and stable identifiers for its code and class.
+///
+/// The default value means there was no error, and is output as `''`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorInfo {
+    /// A human-readable message, e.g. `"reference 'refs/heads/main' not
+    /// found"`.
+    pub message: String,
+
+    /// What went wrong, e.g. `"not_found"`. See [`Error::code()`].
+    pub code: &'static str,
+
+    /// Where it went wrong, e.g. `"reference"`. See [`Error::class()`].
+    pub class: &'static str,
+}
+
+impl ErrorInfo {
+    /// Check if there was no error.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.message.is_empty() && self.code.is_empty()
+    }
+
+    /// Write `{var}`, `{var}_code`, and `{var}_class`.
+    ///
+    /// # Errors
+    ///
+    /// This will return an error if the output stream does.
+    pub fn write_as<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+        var: &str,
+    ) -> io::Result<()> {
+        out.try_write_value(var, &self.message)?;
+        out.try_write_value(format!("{var}_code"), self.code)?;
+        out.try_write_value(format!("{var}_class"), self.class)
+    }
+
+    /// Note that `{var}`, `{var}_code`, and `{var}_class` are missing.
+    ///
+    /// # Errors
+    ///
+    /// This will return an error if the output stream does.
+    pub fn write_missing<W: io::Write>(
+        out: &ShellWriter<W>,
+        var: &str,
+    ) -> io::Result<()> {
+        out.try_write_missing(var)?;
+        out.try_write_missing(format!("{var}_code"))?;
+        out.try_write_missing(format!("{var}_class"))
+    }
+}
+
+impl From<&Error> for ErrorInfo {
+    fn from(error: &Error) -> Self {
+        Self {
+            message: error.message(),
+            code: error.code(),
+            class: error.class(),
+        }
+    }
+}
+
+impl From<Error> for ErrorInfo {
+    fn from(error: Error) -> Self {
+        Self::from(&error)
+    }
+}
+
+impl From<git2::Error> for ErrorInfo {
+    fn from(error: git2::Error) -> Self {
+        Self::from(&error)
+    }
+}
+
+impl From<&git2::Error> for ErrorInfo {
+    fn from(error: &git2::Error) -> Self {
+        Self::from(&GitError::from(error))
+    }
+}
+
+impl From<&GitError> for ErrorInfo {
+    fn from(error: &GitError) -> Self {
+        Self {
+            message: error.message.clone(),
+            code: error.code,
+            class: error.class,
+        }
+    }
+}
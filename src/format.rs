@@ -0,0 +1,841 @@
+//! Output formats for a [`RepoSummary`].
+//!
+//! [`summarize_path()`][crate::summarize_path] produces a [`RepoSummary`], and
+//! an [`OutputFormat`] renders it. [`ShellFormat`] produces the same shell
+//! variables as the command line tool. Other formats can be added by
+//! implementing [`OutputFormat`]. [`PromptFormat`] renders a single line to
+//! display in a prompt. [`VcsInfoFormat`] and [`GitstatusFormat`] render the
+//! variables set by zsh’s `vcs_info` and by gitstatus for powerlevel10k.
+//!
+//! Implementing a format:
+//!
+//! ```no_run
+//! use git_status_vars::format::OutputFormat;
+//! use git_status_vars::{summarize_path, RepoSummary, SummarizeOptions};
+//! use std::io;
+//! use std::path::Path;
+//!
+//! struct BranchOnly;
+//!
+//! impl OutputFormat for BranchOnly {
+//!     fn render<W: io::Write>(
+//!         &self,
+//!         summary: &RepoSummary,
+//!         out: &mut W,
+//!     ) -> io::Result<()> {
+//!         let head = summary.head.as_ref();
+//!         let branch = head.and_then(|head| head.trail.last());
+//!         let branch = branch.map_or("", |reference| reference.short());
+//!         writeln!(out, "{branch}")
+//!     }
+//! }
+//!
+//! let options = SummarizeOptions::default();
+//! let summary = summarize_path(Path::new("."), &options).unwrap();
+//! BranchOnly.render(&summary, &mut io::stdout()).unwrap();
+//! ```
+
+use crate::schema::{json_string, SCHEMA_VERSION};
+use crate::{
+    vars, ChangeKind, Dialect, FmtWriter, RepoState, RepoSummary, ShellWriter,
+    Value,
+};
+use std::io;
+
+/// A way to write a [`RepoSummary`].
+pub trait OutputFormat {
+    /// Write `summary` to `out`.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `out` could not be written to.
+    fn render<W: io::Write>(
+        &self,
+        summary: &RepoSummary,
+        out: &mut W,
+    ) -> io::Result<()>;
+
+    /// Render `summary` into a `String`.
+    ///
+    /// ```rust
+    /// use git_status_vars::format::{OutputFormat, ShellFormat};
+    /// use git_status_vars::RepoSummary;
+    ///
+    /// fn prompt_vars(summary: &RepoSummary) -> String {
+    ///     ShellFormat::new("local ").render_to_string(summary).unwrap()
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the format writes invalid UTF-8 or
+    /// otherwise fails. [`ShellFormat`] always writes valid UTF-8.
+    fn render_to_string(&self, summary: &RepoSummary) -> io::Result<String> {
+        let mut out = FmtWriter::new(String::new());
+        self.render(summary, &mut out)?;
+        Ok(out.into_inner())
+    }
+}
+
+/// Render a summary as shell variables, like the command line tool.
+///
+/// ```rust
+/// use git_status_vars::format::ShellFormat;
+/// let format = ShellFormat::new("local ").with_unset_missing(true);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ShellFormat {
+    /// The prefix to add before every line, e.g. `"local "` or `""`.
+    prefix: String,
+
+    /// Whether to write `unset var` for empty or missing variables. See
+    /// [`ShellWriter::with_unset_missing()`].
+    unset_missing: bool,
+
+    /// The shell syntax to write. See [`ShellWriter::with_dialect()`].
+    dialect: Dialect,
+}
+
+impl ShellFormat {
+    /// Create a new `ShellFormat` that prepends `prefix` to every line.
+    #[must_use]
+    pub fn new<P: Into<String>>(prefix: P) -> Self {
+        Self {
+            prefix: prefix.into(),
+            unset_missing: false,
+            dialect: Dialect::Sh,
+        }
+    }
+
+    /// Output `unset var` instead of `var=''` for empty and missing variables.
+    /// See [`ShellWriter::with_unset_missing()`].
+    #[must_use]
+    pub const fn with_unset_missing(mut self, unset_missing: bool) -> Self {
+        self.unset_missing = unset_missing;
+        self
+    }
+
+    /// Output variables in the syntax of `dialect`. See
+    /// [`ShellWriter::with_dialect()`].
+    #[must_use]
+    pub const fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+}
+
+impl OutputFormat for ShellFormat {
+    fn render<W: io::Write>(
+        &self,
+        summary: &RepoSummary,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let writer = ShellWriter::new(out, &self.prefix)
+            .with_unset_missing(self.unset_missing)
+            .with_dialect(self.dialect);
+        writer.try_write_vars(summary)?;
+        writer.try_flush()
+    }
+}
+
+/// Render a summary as a single line to display in a prompt, e.g.
+/// `main ↑1 ↓2 +1 !3 ?2 $1`.
+///
+/// The line has the branch (or the short hash if `HEAD` is detached) followed
+/// by the repository state if an operation is in progress, then a marker with
+/// a count for each of: commits ahead of and behind upstream, staged,
+/// unstaged, untracked, and conflicted files, and stashes. Markers with a
+/// count of 0 are left out, so a clean repository that’s up to date with its
+/// upstream is just the branch. The markers are set with
+/// [`PromptFormat::with_symbols()`].
+///
+/// Sections that weren’t computed are left out. Nothing is rendered for a
+/// repository that couldn’t be summarized.
+///
+/// ```rust
+/// use git_status_vars::format::{PromptFormat, PromptSymbols};
+///
+/// let mut symbols = PromptSymbols::default();
+/// symbols.set("untracked", "…").unwrap();
+/// let format = PromptFormat::new().with_symbols(symbols);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PromptFormat {
+    /// The markers to use.
+    symbols: PromptSymbols,
+}
+
+impl PromptFormat {
+    /// Create a new `PromptFormat` with the default symbols.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `symbols` for the markers.
+    #[must_use]
+    pub fn with_symbols(mut self, symbols: PromptSymbols) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    /// Get the prompt line for `summary`, without a trailing newline.
+    #[must_use]
+    pub fn prompt(&self, summary: &RepoSummary) -> String {
+        let symbols = &self.symbols;
+        let mut parts: Vec<String> = vec![];
+
+        if let Some(head) = &summary.head {
+            let name = head
+                .branch_name()
+                .map(str::to_owned)
+                .or_else(|| {
+                    (!head.hash.is_empty()).then(|| head.short_hash(7).into())
+                })
+                .or_else(|| head.trail.last().map(|r| r.short().to_owned()))
+                .unwrap_or_default();
+            match &summary.ps1_state {
+                Some(state) => {
+                    parts.push(format!("{name}{}{state}", symbols.state));
+                }
+                None => parts.push(name),
+            }
+        } else if let Some(state) = &summary.ps1_state {
+            parts.push(format!("{}{state}", symbols.state));
+        }
+
+        let mut marker = |symbol: &str, count: Option<Value>| {
+            if let Some(count) = count.filter(|count| count.as_int() != Some(0))
+            {
+                parts.push(format!("{symbol}{count}"));
+            }
+        };
+        if let Some(upstream) = &summary.upstream {
+            marker(&symbols.ahead, upstream.ahead.map(Value::from));
+            marker(&symbols.behind, upstream.behind.map(Value::from));
+        }
+        if let Some(changes) = &summary.changes {
+            // A truncated count of 0 is output as `0+`, but nothing was found.
+            let count = |kind| {
+                changes.value(kind).filter(|_| changes.get(kind) != Some(0))
+            };
+            marker(&symbols.staged, count(ChangeKind::Staged));
+            marker(&symbols.unstaged, count(ChangeKind::Unstaged));
+            marker(&symbols.untracked, count(ChangeKind::Untracked));
+            marker(&symbols.conflicted, count(ChangeKind::Conflicted));
+        }
+        marker(&symbols.stash, summary.stash_count.map(Value::from));
+
+        parts.retain(|part| !part.is_empty());
+        parts.join(" ")
+    }
+}
+
+impl OutputFormat for PromptFormat {
+    fn render<W: io::Write>(
+        &self,
+        summary: &RepoSummary,
+        out: &mut W,
+    ) -> io::Result<()> {
+        writeln!(out, "{}", self.prompt(summary))
+    }
+}
+
+/// The markers used by [`PromptFormat`].
+///
+/// Each marker is followed by its count, e.g. `?2` for two untracked files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PromptSymbols {
+    /// Commits ahead of upstream. Default: `↑`.
+    pub ahead: String,
+
+    /// Commits behind upstream. Default: `↓`.
+    pub behind: String,
+
+    /// Staged files. Default: `+`.
+    pub staged: String,
+
+    /// Unstaged files. Default: `!`.
+    pub unstaged: String,
+
+    /// Untracked files. Default: `?`.
+    pub untracked: String,
+
+    /// Files with conflicts. Default: `=`.
+    pub conflicted: String,
+
+    /// Stashes. Default: `$`.
+    pub stash: String,
+
+    /// Goes between the branch and the repository state, e.g. `main|MERGING`.
+    /// Default: `|`.
+    pub state: String,
+}
+
+impl PromptSymbols {
+    /// The names of the symbols, as used by [`Self::set()`].
+    pub const NAMES: [&'static str; 8] = [
+        "ahead",
+        "behind",
+        "staged",
+        "unstaged",
+        "untracked",
+        "conflicted",
+        "stash",
+        "state",
+    ];
+
+    /// Set the symbol called `name` (see [`Self::NAMES`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error message if `name` isn’t the name of a symbol.
+    pub fn set<S: Into<String>>(
+        &mut self,
+        name: &str,
+        symbol: S,
+    ) -> Result<(), String> {
+        let field = match name {
+            "ahead" => &mut self.ahead,
+            "behind" => &mut self.behind,
+            "staged" => &mut self.staged,
+            "unstaged" => &mut self.unstaged,
+            "untracked" => &mut self.untracked,
+            "conflicted" => &mut self.conflicted,
+            "stash" => &mut self.stash,
+            "state" => &mut self.state,
+            _ => {
+                return Err(format!(
+                    "expected one of: {}",
+                    Self::NAMES.join(", ")
+                ))
+            }
+        };
+        *field = symbol.into();
+        Ok(())
+    }
+}
+
+impl Default for PromptSymbols {
+    fn default() -> Self {
+        Self {
+            ahead: "↑".to_owned(),
+            behind: "↓".to_owned(),
+            staged: "+".to_owned(),
+            unstaged: "!".to_owned(),
+            untracked: "?".to_owned(),
+            conflicted: "=".to_owned(),
+            stash: "$".to_owned(),
+            state: "|".to_owned(),
+        }
+    }
+}
+
+/// Render a summary as the variables set by zsh’s `vcs_info`, so that themes
+/// built on it can use git-status-vars instead.
+///
+/// Each format is expanded into `vcs_info_msg_{n}_`, like the `formats` and
+/// `actionformats` styles: [`Self::with_formats()`] is used normally, and
+/// [`Self::with_action_formats()`] if an operation like a merge is in
+/// progress. The expansions are:
+///
+///   * `%s`: the VCS, which is always `git`.
+///   * `%b`: the branch, or the short hash followed by `...` if `HEAD` is
+///     detached.
+///   * `%a`: the operation in progress, e.g. `merge` or `rebase-i`.
+///   * `%u`: the unstaged string (default `U`) if there are unstaged changes.
+///   * `%c`: the staged string (default `S`) if there are staged changes.
+///   * `%i`: the hash of `HEAD`.
+///   * `%r`: the name of the working tree.
+///   * `%R`: the path to the working tree.
+///   * `%m`: nothing, since it’s only set by `vcs_info` hooks.
+///
+/// Like `zformat`, an expansion can have a minimum and maximum width, e.g.
+/// `%7.7i` for the first 7 characters of the hash. Other sequences, e.g.
+/// `%F{red}`, are left for the prompt to expand.
+///
+/// The parts are also output as `vcs_info_branch`, `vcs_info_action`,
+/// `vcs_info_unstaged`, `vcs_info_staged`, and `vcs_info_revision`, named
+/// after the keys `vcs_info` hooks see in `hook_com`.
+///
+/// ```rust
+/// use git_status_vars::format::VcsInfoFormat;
+///
+/// let format = VcsInfoFormat::new()
+///     .with_formats(["%F{green}%b%f%u%c"])
+///     .with_unstaged_str("*");
+/// ```
+#[derive(Clone, Debug)]
+pub struct VcsInfoFormat {
+    /// The `formats` style.
+    formats: Vec<String>,
+
+    /// The `actionformats` style.
+    action_formats: Vec<String>,
+
+    /// The `stagedstr` style.
+    staged_str: String,
+
+    /// The `unstagedstr` style.
+    unstaged_str: String,
+}
+
+impl VcsInfoFormat {
+    /// Create a new `VcsInfoFormat` with the default styles of `vcs_info`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `formats` when no operation is in progress.
+    ///
+    /// Default: `" (%s)-[%b]%u%c-"`.
+    #[must_use]
+    pub fn with_formats<I, S>(mut self, formats: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.formats = formats.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Use `formats` when an operation like a merge is in progress.
+    ///
+    /// Default: `" (%s)-[%b|%a]%u%c-"`.
+    #[must_use]
+    pub fn with_action_formats<I, S>(mut self, formats: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.action_formats = formats.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Expand `%c` to `staged_str` if there are staged changes.
+    ///
+    /// Default: `"S"`.
+    #[must_use]
+    pub fn with_staged_str<S: Into<String>>(mut self, staged_str: S) -> Self {
+        self.staged_str = staged_str.into();
+        self
+    }
+
+    /// Expand `%u` to `unstaged_str` if there are unstaged changes.
+    ///
+    /// Default: `"U"`.
+    #[must_use]
+    pub fn with_unstaged_str<S: Into<String>>(
+        mut self,
+        unstaged_str: S,
+    ) -> Self {
+        self.unstaged_str = unstaged_str.into();
+        self
+    }
+
+    /// Write the variables `vcs_info` sets outside of a repository, which are
+    /// all empty.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `out` could not be written to.
+    pub fn render_not_found<W: io::Write>(
+        &self,
+        out: &mut W,
+    ) -> io::Result<()> {
+        self.write(out, &[], ["", "", "", "", ""])
+    }
+
+    /// Write `messages` as `vcs_info_msg_{n}_`, with empty messages up to the
+    /// number of formats, and then the `parts` for `hook_com`.
+    fn write<W: io::Write>(
+        &self,
+        out: &mut W,
+        messages: &[String],
+        parts: [&str; 5],
+    ) -> io::Result<()> {
+        let writer = ShellWriter::new(out, "");
+        let count = self.formats.len().max(self.action_formats.len());
+        for n in 0..count {
+            writer.try_write_value(
+                format!("vcs_info_msg_{n}_"),
+                messages.get(n).map_or("", String::as_str),
+            )?;
+        }
+        let names = ["branch", "action", "unstaged", "staged", "revision"];
+        for (name, part) in names.into_iter().zip(parts) {
+            writer.try_write_value(format!("vcs_info_{name}"), part)?;
+        }
+        writer.try_flush()
+    }
+}
+
+impl Default for VcsInfoFormat {
+    fn default() -> Self {
+        Self {
+            formats: vec![" (%s)-[%b]%u%c-".to_owned()],
+            action_formats: vec![" (%s)-[%b|%a]%u%c-".to_owned()],
+            staged_str: "S".to_owned(),
+            unstaged_str: "U".to_owned(),
+        }
+    }
+}
+
+impl OutputFormat for VcsInfoFormat {
+    fn render<W: io::Write>(
+        &self,
+        summary: &RepoSummary,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let head = summary.head.as_ref();
+        let branch = head
+            .and_then(|head| {
+                head.branch_name().map(str::to_owned).or_else(|| {
+                    (!head.hash.is_empty())
+                        .then(|| format!("{}...", head.short_hash(7)))
+                })
+            })
+            .unwrap_or_default();
+        let action = vcs_info_action(summary.state).unwrap_or_default();
+        let revision = head.map_or("", |head| head.hash.as_str());
+
+        // `git diff` and `git diff --cached` both show conflicted files.
+        let changed = |kind| {
+            summary.changes.as_ref().is_some_and(|changes| {
+                changes.get(kind).unwrap_or(0) > 0 || changes.conflicted() > 0
+            })
+        };
+        let unstaged = if changed(ChangeKind::Unstaged) {
+            self.unstaged_str.as_str()
+        } else {
+            ""
+        };
+        let staged = if changed(ChangeKind::Staged) {
+            self.staged_str.as_str()
+        } else {
+            ""
+        };
+
+        let base =
+            summary.workdir.as_deref().map_or_else(String::new, |path| {
+                path.display()
+                    .to_string()
+                    .trim_end_matches(['/', '\\'])
+                    .to_owned()
+            });
+        let base_name = summary
+            .workdir
+            .as_deref()
+            .and_then(|path| path.file_name())
+            .map_or_else(String::new, |name| name.to_string_lossy().into());
+
+        let fields = [
+            ('s', "git"),
+            ('b', branch.as_str()),
+            ('a', action),
+            ('u', unstaged),
+            ('c', staged),
+            ('i', revision),
+            ('r', base_name.as_str()),
+            ('R', base.as_str()),
+            ('m', ""),
+        ];
+        let formats = if action.is_empty() {
+            &self.formats
+        } else {
+            &self.action_formats
+        };
+
+        let messages: Vec<String> = formats
+            .iter()
+            .map(|format| zformat(format, &fields))
+            .collect();
+        self.write(
+            out,
+            &messages,
+            [branch.as_str(), action, unstaged, staged, revision],
+        )
+    }
+}
+
+/// Get the name `vcs_info` uses for the operation in progress in `%a`, or
+/// `None` if the repository is clean.
+const fn vcs_info_action(state: RepoState) -> Option<&'static str> {
+    match state {
+        RepoState::Clean => None,
+        RepoState::Merge => Some("merge"),
+        RepoState::Revert => Some("revert"),
+        RepoState::RevertSequence => Some("revert-seq"),
+        RepoState::CherryPick => Some("cherry"),
+        RepoState::CherryPickSequence => Some("cherry-seq"),
+        RepoState::Bisect => Some("bisect"),
+        RepoState::Rebase => Some("rebase"),
+        RepoState::RebaseInteractive => Some("rebase-i"),
+        RepoState::RebaseMerge => Some("rebase-m"),
+        RepoState::ApplyMailbox => Some("am"),
+        RepoState::ApplyMailboxOrRebase => Some("am/rebase"),
+    }
+}
+
+/// Expand `%x` in `format` with the value for `x` in `fields`, like zsh’s
+/// `zformat -f`.
+///
+/// Expansions can have a minimum and maximum width, e.g. `%7.7x`. Sequences
+/// that don’t match a field, including `%%`, are left as they are so that the
+/// prompt can expand them.
+fn zformat(format: &str, fields: &[(char, &str)]) -> String {
+    let mut output = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('%') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("%%") {
+            output.push_str("%%");
+            rest = &rest[2..];
+            continue;
+        }
+
+        // Parse `%[min][.max]x`.
+        let spec = &rest[1..];
+        let digits =
+            |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (min, spec) = spec.split_at(digits(spec));
+        let (max, spec) = match spec.strip_prefix('.') {
+            Some(spec) => {
+                let (max, spec) = spec.split_at(digits(spec));
+                (Some(max), spec)
+            }
+            None => (None, spec),
+        };
+        let field = spec
+            .chars()
+            .next()
+            .and_then(|key| fields.iter().find(|(field, _)| *field == key));
+
+        let Some((key, value)) = field else {
+            // Not a field: copy the `%` and continue after it.
+            output.push('%');
+            rest = &rest[1..];
+            continue;
+        };
+        let value: String = match max.and_then(|max| max.parse().ok()) {
+            Some(max) => value.chars().take(max).collect(),
+            None => (*value).to_owned(),
+        };
+        let min: usize = min.parse().unwrap_or(0);
+        output.push_str(&value);
+        for _ in value.chars().count()..min {
+            output.push(' ');
+        }
+        rest = &spec[key.len_utf8()..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Render a summary as the `VCS_STATUS_*` variables set by gitstatus, which
+/// powerlevel10k uses for its `vcs` prompt segment.
+///
+/// `VCS_STATUS_RESULT` is `ok-sync`. Counts that weren’t computed, e.g.
+/// because of [`SummarizeOptions::skip_counts_above`], are 0 and the matching
+/// `VCS_STATUS_HAS_*` variables are -1, like gitstatus does when the index is
+/// too large. The remote, the tag, and the push remote aren’t looked up, so
+/// `VCS_STATUS_REMOTE_*` and `VCS_STATUS_TAG` are empty and
+/// `VCS_STATUS_PUSH_COMMITS_*` are 0.
+///
+/// Use [`Self::render_result()`] if there’s no repository or summarizing it
+/// timed out.
+///
+/// [`SummarizeOptions::skip_counts_above`]: crate::SummarizeOptions::skip_counts_above
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GitstatusFormat;
+
+impl GitstatusFormat {
+    /// The variables written by [`OutputFormat::render()`], in order.
+    pub const VARS: [&'static str; 22] = [
+        "VCS_STATUS_RESULT",
+        "VCS_STATUS_WORKDIR",
+        "VCS_STATUS_COMMIT",
+        "VCS_STATUS_LOCAL_BRANCH",
+        "VCS_STATUS_REMOTE_NAME",
+        "VCS_STATUS_REMOTE_BRANCH",
+        "VCS_STATUS_REMOTE_URL",
+        "VCS_STATUS_ACTION",
+        "VCS_STATUS_HAS_STAGED",
+        "VCS_STATUS_HAS_UNSTAGED",
+        "VCS_STATUS_HAS_CONFLICTED",
+        "VCS_STATUS_HAS_UNTRACKED",
+        "VCS_STATUS_NUM_STAGED",
+        "VCS_STATUS_NUM_UNSTAGED",
+        "VCS_STATUS_NUM_CONFLICTED",
+        "VCS_STATUS_NUM_UNTRACKED",
+        "VCS_STATUS_COMMITS_AHEAD",
+        "VCS_STATUS_COMMITS_BEHIND",
+        "VCS_STATUS_PUSH_COMMITS_AHEAD",
+        "VCS_STATUS_PUSH_COMMITS_BEHIND",
+        "VCS_STATUS_STASHES",
+        "VCS_STATUS_TAG",
+    ];
+
+    /// Write `VCS_STATUS_RESULT` as `result`, e.g. `norepo-sync` if there’s
+    /// no repository or `tout` if summarizing it timed out, and unset the
+    /// other variables like gitstatus does.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `out` could not be written to.
+    pub fn render_result<W: io::Write>(
+        &self,
+        out: &mut W,
+        result: &str,
+    ) -> io::Result<()> {
+        let writer = ShellWriter::new(out, "").with_unset_missing(true);
+        writer.try_write_value(Self::VARS[0], result)?;
+        for var in &Self::VARS[1..] {
+            writer.try_write_missing(var)?;
+        }
+        writer.try_flush()
+    }
+}
+
+impl OutputFormat for GitstatusFormat {
+    fn render<W: io::Write>(
+        &self,
+        summary: &RepoSummary,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let head = summary.head.as_ref();
+        let workdir =
+            summary.workdir.as_deref().map_or_else(String::new, |path| {
+                path.display()
+                    .to_string()
+                    .trim_end_matches(['/', '\\'])
+                    .to_owned()
+            });
+        let count = |kind| {
+            summary
+                .changes
+                .as_ref()
+                .and_then(|changes| changes.get(kind))
+        };
+        let has = |kind| {
+            count(kind).map_or(Value::Int(-1), |n| Value::Int(i64::from(n > 0)))
+        };
+        let num = |kind| Value::from(count(kind).unwrap_or(0));
+        let upstream = summary.upstream.as_ref();
+
+        let values: [Value; 22] = [
+            "ok-sync".into(),
+            workdir.into(),
+            head.map_or("", |head| head.hash.as_str()).into(),
+            head.and_then(|head| head.branch_name())
+                .unwrap_or("")
+                .into(),
+            "".into(),
+            "".into(),
+            "".into(),
+            vcs_info_action(summary.state).unwrap_or("").into(),
+            has(ChangeKind::Staged),
+            has(ChangeKind::Unstaged),
+            has(ChangeKind::Conflicted),
+            has(ChangeKind::Untracked),
+            num(ChangeKind::Staged),
+            num(ChangeKind::Unstaged),
+            num(ChangeKind::Conflicted),
+            num(ChangeKind::Untracked),
+            upstream
+                .and_then(|upstream| upstream.ahead)
+                .unwrap_or(0)
+                .into(),
+            upstream
+                .and_then(|upstream| upstream.behind)
+                .unwrap_or(0)
+                .into(),
+            Value::Int(0),
+            Value::Int(0),
+            summary.stash_count.unwrap_or(0).into(),
+            "".into(),
+        ];
+
+        let writer = ShellWriter::new(out, "");
+        for (var, value) in Self::VARS.into_iter().zip(values) {
+            writer.try_write_value(var, value)?;
+        }
+        writer.try_flush()
+    }
+}
+
+/// Render a summary as a JSON object of its variables, which is described by
+/// [`json_schema()`][crate::schema::json_schema].
+///
+/// The object has the same variables as the shell output, starting with
+/// `schema_version`, but values keep their type: counts are numbers, flags are
+/// booleans, and missing values are `null`.
+///
+/// ```rust
+/// use git_status_vars::format::JsonFormat;
+/// use git_status_vars::Value;
+///
+/// let mut json = vec![];
+/// JsonFormat::render_vars(
+///     &mut json,
+///     [("repo_state".to_owned(), Value::from("NotFound"))],
+/// )
+/// .unwrap();
+/// assert_eq!(json, b"{\"repo_state\": \"NotFound\"}\n");
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonFormat;
+
+impl JsonFormat {
+    /// Write `vars` as a JSON object on one line.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `out` could not be written to.
+    pub fn render_vars<W, I>(out: &mut W, vars: I) -> io::Result<()>
+    where
+        W: io::Write,
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        let members: Vec<String> = vars
+            .into_iter()
+            .map(|(name, value)| {
+                format!("{}: {}", json_string(&name), json_value(&value))
+            })
+            .collect();
+        writeln!(out, "{{{}}}", members.join(", "))
+    }
+}
+
+impl OutputFormat for JsonFormat {
+    fn render<W: io::Write>(
+        &self,
+        summary: &RepoSummary,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let schema_version = (
+            vars::SCHEMA_VERSION.to_owned(),
+            Value::Int(i64::from(SCHEMA_VERSION)),
+        );
+        Self::render_vars(
+            out,
+            std::iter::once(schema_version).chain(summary.iter_vars()),
+        )
+    }
+}
+
+/// Convert `value` to JSON.
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Str(s) => json_string(s),
+        Value::Int(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_owned(),
+        Value::List(values) => {
+            let values: Vec<String> = values.iter().map(json_value).collect();
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Use a `core.fsmonitor` hook or git’s builtin fsmonitor daemon to avoid
+//! checking every file for changes.
+//!
+//! The hook or daemon reports the paths that changed since the last time it
+//! was queried, which is identified by a token. The status of every changed
+//! file is saved in the git directory along with the token, so if only tracked
+//! files changed since then, just those files need to be checked.
+//!
+//! The daemon (`core.fsmonitor = true`, started with `git fsmonitor--daemon
+//! start`) is queried over its Unix socket, `fsmonitor--daemon.ipc` in the git
+//! directory. Its named pipe on Windows isn’t supported.
+
+use crate::repository::statuses_retrying_lock;
+use crate::{trace, SummarizeOptions};
+use git2::{Repository, Status, StatusOptions};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+/// The name of the state file within the git directory.
+const STATE_FILE: &str = "git-status-vars-fsmonitor";
+
+/// The name of the builtin daemon’s socket within the git directory.
+const DAEMON_SOCKET: &str = "fsmonitor--daemon.ipc";
+
+/// The token git sends the daemon when it has no token from before. The
+/// daemon answers it with a new token and reports that everything changed.
+#[cfg(unix)]
+const DAEMON_FAKE_TOKEN: &str = "builtin:fake";
+
+/// How long to wait for the daemon to answer a query.
+#[cfg(unix)]
+const DAEMON_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The longest pkt-line payload git sends or accepts.
+#[cfg(unix)]
+const MAX_PKT_PAYLOAD: usize = 65_516;
+
+/// The mode of a submodule entry in the index.
+const GITLINK_MODE: u32 = 0o160_000;
+
+/// The status of each changed path. Untracked directories end with `/`.
+pub type Entries = BTreeMap<String, Status>;
+
+/// Where to ask which files changed.
+enum Source {
+    /// A hook like `query-watchman`, run with protocol version 2.
+    Hook(PathBuf),
+
+    /// The socket of git’s builtin fsmonitor daemon.
+    Daemon(PathBuf),
+}
+
+/// The statuses of changed files as of a token from the hook or daemon.
+struct State {
+    /// The token to pass to the hook or daemon next time.
+    token: String,
+
+    /// A description of the repository state the statuses are relative to. If
+    /// this changes, the statuses can’t be updated incrementally.
+    key: String,
+
+    /// The status of every file that isn’t current.
+    entries: Entries,
+}
+
+/// Get the status of every changed file with help from the `core.fsmonitor`
+/// hook or daemon.
+///
+/// Returns `None` if neither is configured, or if the hook failed or the
+/// daemon isn’t running, in which case the caller should get the statuses
+/// itself. Otherwise, returns the status of each changed path and whether the
+/// index was locked; see [`statuses_retrying_lock()`]. If the hook or daemon
+/// reports changes to anything but tracked files, or there is no saved state
+/// to update, this gets the statuses with `status_options`.
+///
+/// # Errors
+///
+/// This will return [`git2::Error`] if there was an error getting status
+/// information from the repository.
+pub fn statuses(
+    repository: &Repository,
+    options: &SummarizeOptions,
+    status_options: &mut StatusOptions,
+) -> Result<Option<(Entries, bool)>, git2::Error> {
+    let (Some(workdir), Some(source)) =
+        (repository.workdir(), source(repository))
+    else {
+        return Ok(None);
+    };
+
+    let previous = read_state(repository);
+    let key = key(repository, options);
+
+    // Query before checking any files so that changes made while checking
+    // are reported next time.
+    let previous_token = previous.as_ref().map_or("", |state| &state.token);
+    let queried = match &source {
+        Source::Hook(hook) => {
+            let _span = trace::span("fsmonitor_hook");
+            query(workdir, hook, previous_token)
+        }
+        Source::Daemon(socket) => {
+            let _span = trace::span("fsmonitor_daemon");
+            query_daemon(socket, previous_token)
+        }
+    };
+    let Some((token, changed)) = queried else {
+        return Ok(None);
+    };
+
+    let entries = match (previous, changed) {
+        (Some(state), Some(changed)) if state.key == key => {
+            update(repository, state.entries, &changed)?
+        }
+        _ => None,
+    };
+    let (entries, index_locked) = match entries {
+        Some(entries) => (entries, false),
+        None => scan(repository, options, status_options)?,
+    };
+
+    let state = State {
+        token,
+        key,
+        entries,
+    };
+    // The state is only an optimization, so ignore errors saving it.
+    let _ = write_state(repository, &state);
+
+    Ok(Some((state.entries, index_locked)))
+}
+
+/// Get the `core.fsmonitor` hook or the builtin daemon’s socket, if either is
+/// configured.
+fn source(repository: &Repository) -> Option<Source> {
+    let config = repository.config().ok()?;
+
+    // A boolean means the builtin daemon is (or isn’t) used.
+    match config.get_bool("core.fsmonitor") {
+        Ok(true) => Some(Source::Daemon(repository.path().join(DAEMON_SOCKET))),
+        Ok(false) => None,
+        Err(_) => config.get_path("core.fsmonitor").ok().map(Source::Hook),
+    }
+}
+
+/// Run the hook with protocol version 2.
+///
+/// Like git, a hook path without a separator (e.g. `query-watchman`) is
+/// looked up on `$PATH`; other relative paths are relative to the working tree.
+///
+/// Returns the new token and the changed paths, or `None` for the paths if
+/// everything should be assumed to have changed. Returns `None` if the hook
+/// failed.
+fn query(
+    workdir: &Path,
+    hook: &Path,
+    token: &str,
+) -> Option<(String, Option<Vec<String>>)> {
+    let program = if hook.components().nth(1).is_some() {
+        workdir.join(hook)
+    } else {
+        hook.to_path_buf()
+    };
+    let output = Command::new(program)
+        .args(["2", token])
+        .current_dir(workdir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_response(&output.stdout)
+}
+
+/// Ask the builtin daemon listening on `socket` what changed since `token`.
+///
+/// This speaks git’s simple IPC protocol: the token is sent as pkt-lines
+/// followed by a flush packet, and the answer comes back the same way. The
+/// answer is in the same format as the output of a hook; see
+/// [`parse_response()`].
+///
+/// Returns `None` if the daemon isn’t running or didn’t answer.
+#[cfg(unix)]
+fn query_daemon(
+    socket: &Path,
+    token: &str,
+) -> Option<(String, Option<Vec<String>>)> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let token = if token.is_empty() {
+        DAEMON_FAKE_TOKEN
+    } else {
+        token
+    };
+
+    let mut stream = UnixStream::connect(socket).ok()?;
+    stream.set_read_timeout(Some(DAEMON_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(DAEMON_TIMEOUT)).ok()?;
+
+    let mut request = vec![];
+    for chunk in token.as_bytes().chunks(MAX_PKT_PAYLOAD) {
+        // The length includes the 4 bytes of the length itself.
+        let length = chunk.len().saturating_add(4);
+        request.extend(format!("{length:04x}").as_bytes());
+        request.extend(chunk);
+    }
+    request.extend(b"0000");
+    stream.write_all(&request).ok()?;
+
+    let mut response = vec![];
+    loop {
+        let mut length = [0; 4];
+        stream.read_exact(&mut length).ok()?;
+        // `from_str_radix()` alone would accept a sign, e.g. `+fff`.
+        if !length.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        let length = std::str::from_utf8(&length).ok()?;
+        let length = usize::from_str_radix(length, 16).ok()?;
+        if length == 0 {
+            // A flush packet ends the answer.
+            break;
+        }
+        let payload = length.checked_sub(4).filter(|payload| *payload > 0)?;
+        let start = response.len();
+        response.resize(start.checked_add(payload)?, 0);
+        stream.read_exact(response.get_mut(start..)?).ok()?;
+    }
+    parse_response(&response)
+}
+
+/// The daemon’s socket is only supported on Unix.
+#[cfg(not(unix))]
+fn query_daemon(
+    _socket: &Path,
+    _token: &str,
+) -> Option<(String, Option<Vec<String>>)> {
+    None
+}
+
+/// Parse the answer from a hook or the daemon: the new token, then each
+/// changed path, all NUL-terminated. A path of `/` means everything should be
+/// assumed to have changed, in which case `None` is returned for the paths.
+///
+/// Returns `None` if the answer isn’t valid.
+fn parse_response(response: &[u8]) -> Option<(String, Option<Vec<String>>)> {
+    let mut fields = response.split(|byte| *byte == 0);
+    let token = String::from_utf8(fields.next()?.to_vec()).ok()?;
+    let mut paths = vec![];
+    for field in fields.filter(|field| !field.is_empty()) {
+        if field == b"/" {
+            return Some((token, None));
+        }
+        paths.push(String::from_utf8(field.to_vec()).ok()?);
+    }
+    Some((token, Some(paths)))
+}
+
+/// Update the statuses of the changed paths.
+///
+/// Returns `None` if any of the paths isn’t a tracked file, since then
+/// untracked files might have been added.
+fn update(
+    repository: &Repository,
+    mut entries: Entries,
+    changed: &[String],
+) -> Result<Option<Entries>, git2::Error> {
+    let index = repository.index()?;
+    for path in changed {
+        let index_entries: Vec<_> = (0..=3)
+            .filter_map(|stage| index.get_path(Path::new(path), stage))
+            .collect();
+        if index_entries.is_empty()
+            || index_entries.iter().any(|entry| entry.mode == GITLINK_MODE)
+        {
+            return Ok(None);
+        }
+
+        let Ok(status) = repository.status_file(Path::new(path)) else {
+            return Ok(None);
+        };
+        if status.is_empty() {
+            entries.remove(path);
+        } else {
+            entries.insert(path.clone(), status);
+        }
+    }
+    Ok(Some(entries))
+}
+
+/// Get the status of every changed file the slow way, and whether the index
+/// was locked.
+fn scan(
+    repository: &Repository,
+    options: &SummarizeOptions,
+    status_options: &mut StatusOptions,
+) -> Result<(Entries, bool), git2::Error> {
+    let (statuses, index_locked) =
+        statuses_retrying_lock(repository, options, status_options)?;
+    let entries = statuses
+        .iter()
+        .filter_map(|entry| Some((entry.path()?.to_owned(), entry.status())))
+        .collect();
+    Ok((entries, index_locked))
+}
+
+/// Describe the state of `HEAD` and the index, and the options used.
+fn key(repository: &Repository, options: &SummarizeOptions) -> String {
+    let head_oid = repository
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default();
+    let index_metadata = fs::metadata(repository.path().join("index")).ok();
+    let index_mtime = index_metadata
+        .as_ref()
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos().to_string())
+        .unwrap_or_default();
+    let index_size = index_metadata.map_or(0, |metadata| metadata.len());
+    format!(
+        "options={options:?} head_oid={head_oid} index_mtime={index_mtime} \
+            index_size={index_size}"
+    )
+}
+
+/// Get the path to the state file for a repository.
+fn state_path(repository: &Repository) -> PathBuf {
+    repository.path().join(STATE_FILE)
+}
+
+/// Read the saved state.
+///
+/// The file contains NUL-terminated fields: the token, the key, and then the
+/// status bits and path of each entry.
+fn read_state(repository: &Repository) -> Option<State> {
+    let contents = fs::read_to_string(state_path(repository)).ok()?;
+    let mut fields = contents.split_terminator('\0');
+    let token = fields.next()?.to_owned();
+    let key = fields.next()?.to_owned();
+    let mut entries = BTreeMap::new();
+    while let Some(bits) = fields.next() {
+        let status = Status::from_bits_truncate(bits.parse().ok()?);
+        entries.insert(fields.next()?.to_owned(), status);
+    }
+    Some(State {
+        token,
+        key,
+        entries,
+    })
+}
+
+/// Save the state. See [`read_state()`].
+fn write_state(repository: &Repository, state: &State) -> std::io::Result<()> {
+    let mut contents = format!("{}\0{}\0", state.token, state.key);
+    for (path, status) in &state.entries {
+        write!(contents, "{}\0{path}\0", status.bits()).unwrap();
+    }
+    fs::write(state_path(repository), contents)
+}
@@ -0,0 +1,135 @@
+//! Functions that take or return [`git2`] types.
+//!
+//! The rest of the API doesn’t use [`git2`], so callers don’t need to depend
+//! on the same version of it as this crate. Use these if you already have a
+//! [`Repository`], e.g. because you opened it with special flags, or if you
+//! want individual sections of the summary. Otherwise,
+//! [`summarize_path()`][crate::summarize_path] is simpler.
+//!
+//! [`Error`], [`ErrorInfo`][crate::ErrorInfo], [`ObjectId`][crate::ObjectId],
+//! and [`RepoState`][crate::RepoState] can also be converted from their
+//! [`git2`] equivalents with [`From`].
+//!
+//! Upgrading [`git2`] may change the types used here.
+
+use crate::{BareRepository, Error, SummarizeOptions, UntrackedFiles};
+use git2::{Repository, StatusOptions, StatusShow};
+
+pub use crate::remote::{fetch_upstream, remote_tip};
+pub use crate::repository::{
+    ahead_behind, count_changes, count_changes_with, count_stashes, fetch_age,
+    get_upstream_difference, head_info, ps1_state, summarize,
+    summarize_opened_repository, summarize_repository, time_sections,
+    upstream_difference_for, upstream_info,
+};
+pub use crate::stash::stash_info;
+pub use crate::submodule::{submodule_info, superproject_info};
+pub use crate::worktree::worktree_info;
+
+/// Fill in options that weren’t set explicitly from the configuration of
+/// `repository`.
+///
+/// [`SummarizeOptions::untracked_files`] is read from
+/// `status.showUntrackedFiles` and [`SummarizeOptions::detect_renames`] from
+/// `status.renames` or `diff.renames`. Invalid or unreadable configuration is
+/// ignored, and the default is used instead.
+#[must_use]
+pub fn with_config(
+    mut options: SummarizeOptions,
+    repository: &Repository,
+) -> SummarizeOptions {
+    if options.untracked_files.is_some() && options.detect_renames.is_some() {
+        return options;
+    }
+    let Ok(config) = repository.config() else {
+        return options;
+    };
+    if options.untracked_files.is_none() {
+        options.untracked_files = untracked_from_config(&config);
+    }
+    if options.detect_renames.is_none() {
+        options.detect_renames = renames_from_config(&config, "status.renames")
+            .or_else(|| renames_from_config(&config, "diff.renames"));
+    }
+    options
+}
+
+/// Read `status.showUntrackedFiles` from `config`.
+///
+/// Like git, this accepts boolean values as well as mode names: true values
+/// mean [`UntrackedFiles::Normal`] and false values mean
+/// [`UntrackedFiles::No`]. Returns `None` if the setting is missing or
+/// invalid.
+fn untracked_from_config(config: &git2::Config) -> Option<UntrackedFiles> {
+    let value = config.get_string("status.showUntrackedFiles").ok()?;
+    value.parse().ok().or_else(|| {
+        git2::Config::parse_bool(value).ok().map(|show| {
+            if show {
+                UntrackedFiles::Normal
+            } else {
+                UntrackedFiles::No
+            }
+        })
+    })
+}
+
+/// Read a rename detection setting like `diff.renames` from `config`.
+///
+/// Like git, this accepts `copies` (or `copy`) as well as boolean values.
+/// Copies aren’t counted differently from other new files, so they just mean
+/// that renames should be detected. Returns `None` if the setting is missing
+/// or invalid.
+fn renames_from_config(config: &git2::Config, name: &str) -> Option<bool> {
+    let value = config.get_string(name).ok()?;
+    if value == "copies" || value == "copy" {
+        Some(true)
+    } else {
+        git2::Config::parse_bool(value).ok()
+    }
+}
+
+/// Build the [`StatusOptions`] used to count changes.
+///
+/// This doesn’t read the repository configuration; call [`with_config()`]
+/// first for that.
+#[must_use]
+pub fn status_options(options: &SummarizeOptions) -> StatusOptions {
+    let untracked = options.untracked();
+    let mut status_options = StatusOptions::new();
+    status_options
+        .show(StatusShow::IndexAndWorkdir)
+        .include_untracked(untracked != UntrackedFiles::No)
+        .recurse_untracked_dirs(untracked == UntrackedFiles::All)
+        .exclude_submodules(!options.include_submodules)
+        .include_ignored(options.include_ignored)
+        .renames_head_to_index(options.detect_renames.unwrap_or(true))
+        .update_index(options.update_index);
+    status_options
+}
+
+/// Get the repository to summarize in place of `repository`.
+///
+/// This returns `repository` unchanged if it isn’t bare.
+///
+/// # Errors
+///
+/// This will return [`Error::InvalidOptions`] if a worktree is required but
+/// `repository` is bare, or [`Error::Git`] if the named worktree could not be
+/// opened.
+pub fn resolve_bare(
+    bare: &BareRepository,
+    repository: Repository,
+) -> Result<Repository, Error> {
+    if !repository.is_bare() {
+        return Ok(repository);
+    }
+    match bare {
+        BareRepository::Allow => Ok(repository),
+        BareRepository::RequireWorktree => Err(Error::InvalidOptions(
+            "bare repository has no working tree".to_owned(),
+        )),
+        BareRepository::Worktree(name) => Ok(Repository::open_from_worktree(
+            &repository.find_worktree(name)?,
+        )?),
+    }
+}
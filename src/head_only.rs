@@ -0,0 +1,308 @@
+//! Summarize only `HEAD` by reading files in the git directory directly.
+//!
+//! This avoids opening a [`Repository`][git2::Repository] entirely, which is
+//! useful for prompts that want to show the branch name as fast as possible
+//! and fill in the rest later. Both the files and reftable backends for
+//! references are supported.
+
+use crate::{
+    reftable, trace, write_missing_sections, Condition, Head, Reference,
+};
+use crate::{vars, ErrorInfo, Section, ShellWriter};
+use git2::{ErrorClass, ErrorCode, Oid};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How many symbolic references to follow before giving up.
+pub(crate) const MAX_DEPTH: usize = 10;
+
+/// The length of a SHA-1 object ID in hex.
+const SHA1_HEX_LEN: usize = 40;
+
+/// The length of a SHA-256 object ID in hex.
+const SHA256_HEX_LEN: usize = 64;
+
+/// Find the git directory for `start` without opening the repository.
+///
+/// This looks for a `.git` directory or file in `start` and (if `search` is
+/// set) its parents, stopping before any of the directories in `ceilings`.
+/// `start` itself may also be a git directory.
+#[must_use]
+pub fn find_git_dir(
+    start: &Path,
+    ceilings: &[PathBuf],
+    search: bool,
+) -> Option<PathBuf> {
+    let _span = trace::span("find_git_dir");
+    let start = start.canonicalize().ok()?;
+    for directory in start.ancestors() {
+        if directory != start && ceilings.iter().any(|c| c == directory) {
+            break;
+        }
+        if let Some(git_dir) = git_dir_in(directory) {
+            return Some(git_dir);
+        }
+        if !search {
+            break;
+        }
+    }
+    None
+}
+
+/// Get the git directory in `directory`, or `directory` itself if it’s a git
+/// directory.
+fn git_dir_in(directory: &Path) -> Option<PathBuf> {
+    let dot_git = directory.join(".git");
+    if dot_git.is_dir() && is_git_dir(&dot_git) {
+        return Some(dot_git);
+    }
+    if let Ok(contents) = fs::read_to_string(&dot_git) {
+        // A linked worktree or submodule: “gitdir: path”.
+        let git_dir = directory.join(contents.strip_prefix("gitdir:")?.trim());
+        return is_git_dir(&git_dir).then_some(git_dir);
+    }
+    is_git_dir(directory).then(|| directory.to_path_buf())
+}
+
+/// Check if `path` looks like a git directory.
+fn is_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file()
+        && (path.join("commondir").is_file() || path.join("objects").is_dir())
+}
+
+/// Get the directory shared by all worktrees, which contains most references.
+pub(crate) fn common_dir(git_dir: &Path) -> PathBuf {
+    fs::read_to_string(git_dir.join("commondir")).map_or_else(
+        |_| git_dir.to_path_buf(),
+        |contents| git_dir.join(contents.trim()),
+    )
+}
+
+/// Trace `HEAD` by reading files in `git_dir`.
+///
+/// This produces the same [`Head`] as
+/// [`head_info()`][crate::git2_interop::head_info], except that it doesn’t
+/// check that the commit `HEAD` points to exists.
+#[must_use]
+pub fn read_head(git_dir: &Path) -> Head {
+    let _span = trace::span("read_head");
+    let common_dir = common_dir(git_dir);
+    let mut current = "HEAD".to_owned();
+    let mut head = Head::default();
+    for _ in 0..MAX_DEPTH {
+        match read_reference(git_dir, &common_dir, &current) {
+            Ok(Some(Target::Symbolic(target))) => {
+                head.trail.push(Reference::symbolic(&current));
+                current = target;
+            }
+            Ok(Some(Target::Direct(hash))) => {
+                head.trail.push(Reference::direct(&current));
+                head.resolved = to_oid(&hash)
+                    .map(|oid| (current, oid.into()))
+                    .map_err(ErrorInfo::from);
+                head.hash = hash;
+                return head;
+            }
+            Ok(None) => {
+                let error = git2::Error::new(
+                    ErrorCode::NotFound,
+                    ErrorClass::Reference,
+                    format!("reference '{current}' not found"),
+                );
+                // Like `Repository::head()`, report a symbolic reference to a
+                // missing branch as an unborn branch.
+                let code = if head.trail.is_empty() {
+                    ErrorCode::NotFound
+                } else {
+                    ErrorCode::UnbornBranch
+                };
+                head.resolved = Err(ErrorInfo::from(git2::Error::new(
+                    code,
+                    ErrorClass::Reference,
+                    error.message(),
+                )));
+                // An unborn branch isn’t an error; see `Head::is_unborn()`.
+                head.trail.push(if code == ErrorCode::UnbornBranch {
+                    Reference::new(current, "")
+                } else {
+                    Reference::new_with_error(current, "", error)
+                });
+                return head;
+            }
+            Err(error) => {
+                head.resolved = Err(ErrorInfo::from(&error));
+                head.trail
+                    .push(Reference::new_with_error(current, "", error));
+                return head;
+            }
+        }
+    }
+
+    let message = format!("too many nested symbolic references at '{current}'");
+    head.resolved = Err(ErrorInfo::from(git2::Error::from_str(&message)));
+    head.trail.push(Reference::new_with_error(
+        current,
+        "",
+        git2::Error::from_str(&message),
+    ));
+    head
+}
+
+/// What a reference points to.
+pub(crate) enum Target {
+    /// Another reference.
+    Symbolic(String),
+
+    /// A commit (or other object), as a SHA-1 or SHA-256 hash in hex.
+    Direct(String),
+}
+
+/// Read a reference from its loose file or from `packed-refs`.
+///
+/// Returns `Ok(None)` if the reference doesn’t exist.
+fn read_reference(
+    git_dir: &Path,
+    common_dir: &Path,
+    name: &str,
+) -> Result<Option<Target>, git2::Error> {
+    if name
+        .split('/')
+        .any(|part| part.is_empty() || part.starts_with('.'))
+    {
+        return Err(git2::Error::new(
+            ErrorCode::InvalidSpec,
+            ErrorClass::Reference,
+            format!("the given reference name '{name}' is not valid"),
+        ));
+    }
+
+    // Pseudo-references like `HEAD` are per worktree.
+    let directory = if name.contains('/') {
+        common_dir
+    } else {
+        git_dir
+    };
+    if common_dir.join("reftable").is_dir() {
+        return reftable::read_reference(&directory.join("reftable"), name);
+    }
+    match fs::read_to_string(directory.join(name)) {
+        Ok(contents) => parse_reference(name, contents.trim_end()).map(Some),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            read_packed_reference(common_dir, name)
+        }
+        Err(error) => Err(git2::Error::from_str(&format!(
+            "could not read reference '{name}': {error}"
+        ))),
+    }
+}
+
+/// Parse the contents of a loose reference file.
+fn parse_reference(name: &str, contents: &str) -> Result<Target, git2::Error> {
+    if let Some(target) = contents.strip_prefix("ref:") {
+        return Ok(Target::Symbolic(target.trim().to_owned()));
+    }
+    if matches!(contents.len(), SHA1_HEX_LEN | SHA256_HEX_LEN)
+        && contents.bytes().all(|byte| byte.is_ascii_hexdigit())
+    {
+        Ok(Target::Direct(contents.to_ascii_lowercase()))
+    } else {
+        Err(git2::Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Reference,
+            format!("the reference '{name}' is corrupt"),
+        ))
+    }
+}
+
+/// Convert a hash in hex to an [`Oid`].
+///
+/// libgit2 is built without SHA-256 support, so [`Oid`] can only hold SHA-1
+/// hashes.
+fn to_oid(hash: &str) -> Result<Oid, git2::Error> {
+    if hash.len() == SHA1_HEX_LEN {
+        Oid::from_str(hash)
+    } else {
+        Err(git2::Error::new(
+            ErrorCode::GenericError,
+            ErrorClass::Object,
+            "SHA-256 object IDs are not supported by libgit2",
+        ))
+    }
+}
+
+/// Look up a reference in `packed-refs`.
+fn read_packed_reference(
+    common_dir: &Path,
+    name: &str,
+) -> Result<Option<Target>, git2::Error> {
+    let Ok(packed) = fs::read_to_string(common_dir.join("packed-refs")) else {
+        return Ok(None);
+    };
+    for line in packed.lines() {
+        // Skip the header and peeled tags (`^oid`).
+        if line.starts_with(['#', '^']) {
+            continue;
+        }
+        if let Some((oid, packed_name)) = line.split_once(' ') {
+            if packed_name == name {
+                return parse_reference(name, oid).map(Some);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Summarize `HEAD` in the repository at `git_dir`, or output
+/// `repo_state=NotFound` if it’s `None`.
+///
+/// Since the state of the repository isn’t checked, `repo_state` is `Unknown`
+/// when the repository is found. The `head_*` variables are the same as the
+/// ones output by
+/// [`summarize_repository()`][crate::git2_interop::summarize_repository].
+///
+/// # Panics
+///
+/// This will panic if the output stream returns an error.
+#[allow(clippy::must_use_candidate)] // Most callers don’t need the condition.
+pub fn summarize_head_only<W: io::Write>(
+    out: &ShellWriter<W>,
+    git_dir: Option<&Path>,
+) -> Condition {
+    let _span = trace::span("summarize_head_only");
+    out.write_var(
+        vars::REPO_STATE,
+        if git_dir.is_some() {
+            "Unknown"
+        } else {
+            "NotFound"
+        },
+    );
+    for var in [
+        vars::REPO_WORKDIR,
+        vars::REPO_WORKDIR_RAW,
+        vars::REPO_WORKDIR_LOGICAL,
+        vars::REPO_WORKDIR_PHYSICAL,
+        vars::REPO_EMPTY,
+        vars::REPO_BARE,
+        vars::FETCH_AGE_SECONDS,
+    ] {
+        out.write_missing(var);
+    }
+    ErrorInfo::write_missing(out, vars::REPO_ERROR).unwrap();
+    let Some(git_dir) = git_dir else {
+        write_missing_sections(out, Section::ALL).unwrap();
+        return Condition::NotFound;
+    };
+    out.group("head").write_vars(&read_head(git_dir));
+    ErrorInfo::write_missing(out, vars::HEAD_ERROR).unwrap();
+    write_missing_sections(
+        out,
+        Section::ALL
+            .into_iter()
+            .filter(|section| *section != Section::Head),
+    )
+    .unwrap();
+    out.flush();
+    Condition::Clean
+}
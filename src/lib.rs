@@ -1,8 +1,29 @@
 //! This is primarily a command line utility. The documentation for the command
 //! line interface is in [README.md][].
 //!
-//! The primary entrance to this code is [`summarize_repository()`]. It opens a
-//! [`Repository`], then calls [`summarize_opened_repository()`] on it.
+//! The primary entrance to this code is [`summarize_path()`]. It finds the
+//! repository containing a path and returns a [`RepoSummary`], which can be
+//! written as shell variables with [`ShellWriter::write_vars()`] or rendered
+//! with an [`OutputFormat`][format::OutputFormat].
+//!
+//! The API doesn’t use [`git2`] types except in [`git2_interop`], which has
+//! functions that take a `git2::Repository`, e.g. to summarize a repository
+//! that’s already open, or to get individual sections.
+//!
+//! # Features
+//!
+//! * `cli` (default): build the command line tool. Library users can turn this
+//!   off with `default-features = false` to avoid compiling its dependencies.
+//! * `serde`: implement `serde::Serialize` for [`RepoSummary`] and the types
+//!   it contains.
+//! * `derive`: provide `#[derive(ShellVars)]` to implement [`ShellVars`] for
+//!   structs with named fields. See the `git-status-vars-derive` crate.
+//! * `test-support`: provide [`TempRepo`][test_support::TempRepo] to build
+//!   temporary repositories for integration tests.
+//! * `network`: build libgit2 with HTTPS and SSH support, so that
+//!   [`git2_interop::remote_tip()`] and [`git2_interop::fetch_upstream()`]
+//!   work with most remotes. This
+//!   requires OpenSSL and libssh2.
 //!
 //! Currently the minimum supported Rust version (MSRV) is **1.74.1**.
 //!
@@ -19,30 +40,732 @@
 // cargo-geiger, and it only supports deny, not forbid.
 #![forbid(unsafe_code)]
 
-use git2::Branch;
-use git2::ReferenceType;
-use git2::Repository;
-use git2::{ErrorClass, ErrorCode};
-use git2::{Status, StatusOptions, StatusShow};
+use git2::{Repository, RepositoryOpenFlags, Status};
+use git2_interop::resolve_bare;
+use repository::{fetch_age, ps1_state, summarize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::io;
-use std::path::Path;
+use std::num::NonZeroUsize;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// Manage outputting shell variables.
 mod shell_writer;
 pub use shell_writer::*;
 
+/// Derive [`ShellVars`] for a struct with named fields.
+#[cfg(feature = "derive")]
+pub use git_status_vars_derive::ShellVars;
+
+mod cache;
+mod cancel;
+pub use cancel::*;
+mod error;
+pub use error::*;
+pub mod format;
+mod fsmonitor;
+pub mod git2_interop;
+pub mod head_only;
+mod object_id;
+pub use object_id::*;
+mod path_style;
+pub use path_style::*;
+mod reftable;
+mod remote;
+pub use remote::{RemoteTip, FETCH_BUDGET, REMOTE_BUDGET};
+mod repo_state;
+pub use repo_state::*;
+mod repository;
+pub mod schema;
+#[cfg(feature = "serde")]
+mod serialize;
+mod stash;
+pub use stash::{StashEntry, StashInfo};
+mod submodule;
+pub use submodule::{SubmoduleSummary, SuperprojectSummary};
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub mod trace;
+mod value;
+pub use value::*;
+pub mod vars;
+mod worktree;
+pub use worktree::WorktreeSummary;
+
+/// A section of the summary that can be included or excluded.
+///
+/// The basic repository information (`repo_state`, etc.) is always included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Section {
+    /// Fetch the remote of the upstream before computing the other sections
+    /// (`fetch_error`). This isn’t included by default, and it has a budget of
+    /// [`FETCH_BUDGET`] unless another is set. See
+    /// [`git2_interop::fetch_upstream()`].
+    Fetch,
+
+    /// The trail of references from `HEAD` to a commit (`head_ref*`,
+    /// `head_hash`, `head_unborn`).
+    Head,
+
+    /// How far `HEAD` is ahead of and behind its upstream (`head_ahead`,
+    /// `head_behind`, `head_upstream_error`).
+    Upstream,
+
+    /// Counts of changes in the working tree and index (`*_count`).
+    Counts,
+
+    /// The number of stashes (`stash_count`).
+    Stash,
+
+    /// The superproject if this is a submodule (`superproject_*`). This isn’t
+    /// included by default.
+    Superproject,
+
+    /// The upstream branch as it is on the remote (`remote_tip_hash`,
+    /// `head_behind_remote_tip`). This connects to the remote, so it isn’t
+    /// included by default, and it has a budget of [`REMOTE_BUDGET`] unless
+    /// another is set.
+    Remote,
+}
+
+impl Section {
+    /// All sections in output order.
+    pub const ALL: [Self; 7] = [
+        Self::Fetch,
+        Self::Head,
+        Self::Upstream,
+        Self::Counts,
+        Self::Stash,
+        Self::Superproject,
+        Self::Remote,
+    ];
+
+    /// The sections included by default. See [`SummarizeOptions::sections`].
+    pub const DEFAULT: [Self; 4] =
+        [Self::Head, Self::Upstream, Self::Counts, Self::Stash];
+
+    /// The name of the section as used on the command line.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Fetch => "fetch",
+            Self::Head => "head",
+            Self::Upstream => "upstream",
+            Self::Counts => "counts",
+            Self::Stash => "stash",
+            Self::Superproject => "superproject",
+            Self::Remote => "remote",
+        }
+    }
+
+    /// How long the section may take if it has no budget in
+    /// [`SummarizeOptions::budgets`].
+    const fn default_budget(self) -> Option<Duration> {
+        match self {
+            Self::Fetch => Some(FETCH_BUDGET),
+            Self::Remote => Some(REMOTE_BUDGET),
+            _ => None,
+        }
+    }
+
+    /// The variables that the section outputs, not including the variable
+    /// number of `head_ref{n}_*` variables.
+    fn var_names(self) -> impl Iterator<Item = &'static str> {
+        schema::VARS
+            .iter()
+            .filter(move |var| {
+                var.section == Some(self) && !var.name.contains("{n}")
+            })
+            .map(|var| var.name)
+    }
+}
+
+/// Note that the variables in `sections` are not part of the output.
+///
+/// See [`ShellWriter::write_missing()`].
+fn write_missing_sections<W, I>(
+    out: &ShellWriter<W>,
+    sections: I,
+) -> io::Result<()>
+where
+    W: io::Write,
+    I: IntoIterator<Item = Section>,
+{
+    for section in sections {
+        for var in section.var_names() {
+            out.try_write_missing(var)?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Section {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|section| section.name() == input)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.map(Self::name).into();
+                format!("expected one of: {}", names.join(", "))
+            })
+    }
+}
+
+/// The overall condition of a repository, used for the exit code.
+///
+/// These are ordered from best to worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Condition {
+    /// There are no changes, or changes were not counted.
+    Clean,
+
+    /// There are untracked, unstaged, or staged changes.
+    Dirty,
+
+    /// There are conflicts, or an operation like a merge or rebase is in
+    /// progress.
+    Conflicted,
+
+    /// The repository was not found.
+    NotFound,
+
+    /// There was an error summarizing the repository.
+    Error,
+}
+
+impl Condition {
+    /// Determine the condition of a repository from its state and changes.
+    #[must_use]
+    pub fn new(state: RepoState, changes: Option<&ChangeCounters>) -> Self {
+        let changes = changes.map_or((0, 0), |changes| {
+            (
+                changes.conflicted(),
+                changes
+                    .untracked()
+                    .saturating_add(changes.unstaged())
+                    .saturating_add(changes.staged()),
+            )
+        });
+        match changes {
+            _ if state != RepoState::Clean => Self::Conflicted,
+            (conflicted, _) if conflicted > 0 => Self::Conflicted,
+            (_, dirty) if dirty > 0 => Self::Dirty,
+            _ => Self::Clean,
+        }
+    }
+
+    /// Get the condition from an exit code produced by [`Self::exit_code()`].
+    #[must_use]
+    pub const fn from_exit_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(Self::Clean),
+            1 => Some(Self::Dirty),
+            2 => Some(Self::Conflicted),
+            3 => Some(Self::NotFound),
+            4 => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// The exit code for this condition when `--exit-code` is used.
+    #[must_use]
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Clean => 0,
+            Self::Dirty => 1,
+            Self::Conflicted => 2,
+            Self::NotFound => 3,
+            Self::Error => 4,
+        }
+    }
+
+    /// The color to show this condition in, as output in `color_hint`:
+    /// `green` for clean, `yellow` for dirty, and `red` for conflicted or in
+    /// progress and for errors. There’s no color for
+    /// [`Condition::NotFound`].
+    #[must_use]
+    pub const fn color_hint(self) -> Option<&'static str> {
+        match self {
+            Self::Clean => Some("green"),
+            Self::Dirty => Some("yellow"),
+            Self::Conflicted | Self::Error => Some("red"),
+            Self::NotFound => None,
+        }
+    }
+
+    /// The ANSI escape sequence that sets the foreground to
+    /// [`Self::color_hint()`], as output in `color_ansi`.
+    ///
+    /// ```rust
+    /// use git_status_vars::Condition;
+    /// assert_eq!(Condition::Dirty.color_ansi(), Some("\x1b[33m"));
+    /// ```
+    #[must_use]
+    pub const fn color_ansi(self) -> Option<&'static str> {
+        match self {
+            Self::Clean => Some("\x1b[32m"),
+            Self::Dirty => Some("\x1b[33m"),
+            Self::Conflicted | Self::Error => Some("\x1b[31m"),
+            Self::NotFound => None,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl std::str::FromStr for Condition {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        [
+            Self::Clean,
+            Self::Dirty,
+            Self::Conflicted,
+            Self::NotFound,
+            Self::Error,
+        ]
+        .into_iter()
+        .find(|condition| condition.to_string() == input)
+        .ok_or(())
+    }
+}
+
+/// How long each section of a summary took to compute.
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    /// The time taken by each section, in the order they were computed.
+    pub sections: Vec<(Section, Duration)>,
+
+    /// The sections that ran out of time. See [`SummarizeOptions::budgets`].
+    pub timed_out: Vec<Section>,
+}
+
+impl Timings {
+    /// Run `f` and record how long it took for `section`.
+    pub fn time<T, F: FnOnce() -> T>(&mut self, section: Section, f: F) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.sections.push((section, start.elapsed()));
+        result
+    }
+}
+
+impl ShellVars for Timings {
+    // Output the timings in microseconds with a prefix (e.g. "timing_").
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        for section in Section::ALL {
+            if let Some((_, duration)) =
+                self.sections.iter().find(|(s, _)| *s == section)
+            {
+                let micros = duration.as_micros();
+                out.try_write_value(
+                    format!("{section}_us"),
+                    i64::try_from(micros).unwrap_or(i64::MAX),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Options that control how a repository is summarized.
+#[derive(Clone, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct SummarizeOptions {
+    /// Reuse the previous summary if the repository hasn’t changed, and save
+    /// the summary for next time. The cache is stored in the git directory.
+    ///
+    /// Branches without an upstream are also remembered until the repository
+    /// configuration changes, so the configuration isn’t searched every time.
+    ///
+    /// Checking whether the repository has changed stats every tracked file,
+    /// so using the cache costs about as much as getting the status.
+    ///
+    /// The cache isn’t used if [`Self::include_ignored`] or
+    /// [`Self::include_submodules`] is set, or if [`Section::Superproject`] is
+    /// included.
+    pub cache: bool,
+
+    /// The sections to compute and output. Sections that are not included are
+    /// skipped entirely.
+    pub sections: BTreeSet<Section>,
+
+    /// Output how long each section took to compute as `timing_*_us`
+    /// variables. This disables the cache.
+    pub timing: bool,
+
+    /// How to look for untracked files, like `git status --untracked-files`.
+    /// If this is `None`, the repository’s `status.showUntrackedFiles`
+    /// configuration is used; see [`git2_interop::with_config()`].
+    pub untracked_files: Option<UntrackedFiles>,
+
+    /// Count submodules with changes as changed files.
+    pub include_submodules: bool,
+
+    /// Count ignored files (`ignored_count`). This disables the cache.
+    pub include_ignored: bool,
+
+    /// Detect renamed files in the index, so that a staged rename counts as
+    /// one change rather than a deletion and an addition. Like `git status`,
+    /// a deleted file is never paired with an untracked file.
+    ///
+    /// If this is `None`, the repository’s `status.renames` or `diff.renames`
+    /// configuration is used, and renames are detected if neither is set,
+    /// like `git status` does. See [`git2_interop::with_config()`].
+    pub detect_renames: Option<bool>,
+
+    /// Write the index after counting changes if the stat information for
+    /// unchanged files was out of date, like `git status` does. This makes
+    /// counting changes faster next time, but means writing to the repository.
+    pub update_index: bool,
+
+    /// Ask the `core.fsmonitor` hook (e.g. `query-watchman`) or git’s builtin
+    /// fsmonitor daemon (`core.fsmonitor = true`, Unix only) which files
+    /// changed, and only check those files if possible. See
+    /// [`git2_interop::count_changes()`].
+    pub fsmonitor: bool,
+
+    /// Count changes in a separate thread while the other sections are
+    /// computed. This opens the repository a second time.
+    pub parallel: bool,
+
+    /// How long each section may take. A section that runs out of time is
+    /// abandoned, and `{section}_timeout=true` is output instead of its
+    /// variables. Sections with budgets are computed in separate threads,
+    /// each of which opens the repository again. The thread is given a
+    /// [`Self::cancel`] token that is cancelled when the budget runs out, so
+    /// sections that check it (e.g. [`Section::Fetch`]) stop early.
+    ///
+    /// The fetch and remote sections always have budgets; see
+    /// [`Section::Fetch`] and [`Section::Remote`].
+    pub budgets: BTreeMap<Section, Duration>,
+
+    /// What to do with a bare repository.
+    pub bare: BareRepository,
+
+    /// Compare `HEAD` to this reference (e.g. `"origin/main"`) instead of its
+    /// configured upstream.
+    pub upstream_ref: Option<String>,
+
+    /// Stop counting changes after this many status entries. See
+    /// [`ChangeCounters::truncated`].
+    pub max_entries: Option<usize>,
+
+    /// Output counts above this many as e.g. `100+`. See
+    /// [`ChangeCounters::limit`].
+    ///
+    /// Counting stops once every count is above the limit, but libgit2 still
+    /// finds every change first, so this saves little time; see
+    /// [`Self::skip_counts_above`] for that.
+    pub count_limit: Option<usize>,
+
+    /// Don’t count changes if the index has more than this many entries, and
+    /// output `counts_skipped=true` instead. This keeps huge repositories from
+    /// hitting a timeout every time.
+    pub skip_counts_above: Option<usize>,
+
+    /// The absolute path the repository was found from, without symlinks
+    /// resolved. If this is set and inside the working tree, `repo_workdir`
+    /// is derived from it so that symlinks are preserved, e.g.
+    /// `/home/user/repo/` rather than `/var/home/user/repo/`.
+    pub start_path: Option<PathBuf>,
+
+    /// Also output the working tree as `repo_workdir_physical`, with symlinks
+    /// resolved, and as `repo_workdir_logical`, derived from
+    /// [`Self::start_path`], so that callers can use whichever they need. If
+    /// `start_path` isn’t set, both are the same as `repo_workdir`.
+    pub both_workdirs: bool,
+
+    /// How to output `repo_workdir` on Windows.
+    pub path_style: PathStyle,
+
+    /// Also output the ANSI escape sequence for `color_hint` as `color_ansi`.
+    /// See [`Condition::color_ansi()`].
+    pub color_ansi: bool,
+
+    /// Also output a compact summary of the counts with these symbols as
+    /// `glyphs`. See [`RepoSummary::glyphs()`].
+    pub glyphs: Option<GlyphSet>,
+
+    /// Write an empty line after the repository and head sections, which are
+    /// cheap, so that an asynchronous prompt can show them before the rest
+    /// of the summary is ready. This disables the cache.
+    pub two_phase: bool,
+
+    /// Stop early with [`Error::Cancelled`] if this is cancelled. It’s checked
+    /// between sections and while waiting for sections computed in other
+    /// threads. Sections already written to the output are not retracted.
+    pub cancel: Option<CancelToken>,
+}
+
+impl SummarizeOptions {
+    /// Check if a section should be computed and output.
+    #[must_use]
+    pub fn includes(&self, section: Section) -> bool {
+        self.sections.contains(&section)
+    }
+
+    /// Get how long a section may take: its budget in [`Self::budgets`], or
+    /// its default budget (see [`Section::Fetch`] and [`Section::Remote`]).
+    #[must_use]
+    pub fn budget(&self, section: Section) -> Option<Duration> {
+        self.budgets
+            .get(&section)
+            .copied()
+            .or_else(|| section.default_budget())
+    }
+
+    /// Set [`Self::untracked_files`].
+    #[must_use]
+    pub const fn with_untracked_files(
+        mut self,
+        untracked_files: UntrackedFiles,
+    ) -> Self {
+        self.untracked_files = Some(untracked_files);
+        self
+    }
+
+    /// Set [`Self::include_submodules`].
+    #[must_use]
+    pub const fn with_submodules(mut self, include_submodules: bool) -> Self {
+        self.include_submodules = include_submodules;
+        self
+    }
+
+    /// Set [`Self::include_ignored`].
+    #[must_use]
+    pub const fn with_ignored(mut self, include_ignored: bool) -> Self {
+        self.include_ignored = include_ignored;
+        self
+    }
+
+    /// Set [`Self::detect_renames`].
+    #[must_use]
+    pub const fn with_renames(mut self, detect_renames: bool) -> Self {
+        self.detect_renames = Some(detect_renames);
+        self
+    }
+
+    /// Set [`Self::upstream_ref`].
+    #[must_use]
+    pub fn with_upstream_ref<R: Into<String>>(
+        mut self,
+        upstream_ref: R,
+    ) -> Self {
+        self.upstream_ref = Some(upstream_ref.into());
+        self
+    }
+
+    /// Set [`Self::max_entries`].
+    #[must_use]
+    pub const fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Set [`Self::count_limit`].
+    #[must_use]
+    pub const fn with_count_limit(mut self, count_limit: usize) -> Self {
+        self.count_limit = Some(count_limit);
+        self
+    }
+
+    /// Set [`Self::cancel`].
+    #[must_use]
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// How to look for untracked files, using the default if
+    /// [`Self::untracked_files`] isn’t set.
+    fn untracked(&self) -> UntrackedFiles {
+        self.untracked_files.unwrap_or_default()
+    }
+
+    /// Return [`Error::Cancelled`] if [`Self::cancel`] has been cancelled.
+    fn check_cancelled(&self) -> Result<(), Error> {
+        self.cancel.as_ref().map_or(Ok(()), CancelToken::check)
+    }
+}
+
+impl Default for SummarizeOptions {
+    fn default() -> Self {
+        Self {
+            cache: false,
+            sections: Section::DEFAULT.into(),
+            timing: false,
+            untracked_files: None,
+            include_submodules: false,
+            include_ignored: false,
+            detect_renames: None,
+            update_index: false,
+            fsmonitor: false,
+            parallel: false,
+            budgets: BTreeMap::new(),
+            bare: BareRepository::default(),
+            upstream_ref: None,
+            max_entries: None,
+            count_limit: None,
+            skip_counts_above: None,
+            start_path: None,
+            both_workdirs: false,
+            path_style: PathStyle::default(),
+            color_ansi: false,
+            glyphs: None,
+            two_phase: false,
+            cancel: None,
+        }
+    }
+}
+
+/// How to look for untracked files. See
+/// [`SummarizeOptions::untracked_files`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum UntrackedFiles {
+    /// Don’t look for untracked files. [`ChangeCounters::untracked`] is
+    /// always 0, but counting changes is faster in large working trees.
+    No,
+
+    /// Count an untracked directory as one file, like `git status` shows it.
+    /// See [`ChangeCounters::untracked_truncated`].
+    #[default]
+    Normal,
+
+    /// Count every file in untracked directories.
+    All,
+}
+
+impl UntrackedFiles {
+    /// The name of the mode as used on the command line and in
+    /// `status.showUntrackedFiles`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::No => "no",
+            Self::Normal => "normal",
+            Self::All => "all",
+        }
+    }
+}
+
+impl fmt::Display for UntrackedFiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for UntrackedFiles {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        [Self::No, Self::Normal, Self::All]
+            .into_iter()
+            .find(|mode| mode.name() == input)
+            .ok_or_else(|| "expected one of: no, normal, all".to_owned())
+    }
+}
+
+/// The symbols used in `glyphs`. See [`SummarizeOptions::glyphs`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GlyphSet {
+    /// Unicode symbols, e.g. `●2 ✚1 …3 ⚑1 ↑2`.
+    #[default]
+    Unicode,
+
+    /// ASCII symbols like git-prompt.sh uses, e.g. `+2 *1 ?3 $1 >2`.
+    Ascii,
+}
+
+impl GlyphSet {
+    /// The name of the set as used on the command line.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Unicode => "unicode",
+            Self::Ascii => "ascii",
+        }
+    }
+
+    /// The symbols for staged, unstaged, untracked, and conflicted files,
+    /// stashes, and commits ahead of and behind upstream, in output order.
+    const fn symbols(self) -> [&'static str; 7] {
+        match self {
+            Self::Unicode => ["●", "✚", "…", "✖", "⚑", "↑", "↓"],
+            Self::Ascii => ["+", "*", "?", "x", "$", ">", "<"],
+        }
+    }
+}
+
+impl fmt::Display for GlyphSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for GlyphSet {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        [Self::Unicode, Self::Ascii]
+            .into_iter()
+            .find(|set| set.name() == input)
+            .ok_or_else(|| "expected one of: unicode, ascii".to_owned())
+    }
+}
+
+/// What to do when summarizing a bare repository.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum BareRepository {
+    /// Summarize the bare repository. Since it has no working tree, all of
+    /// the change counts are 0.
+    #[default]
+    Allow,
+
+    /// Treat a bare repository as an error.
+    RequireWorktree,
+
+    /// Summarize the linked worktree with this name instead.
+    Worktree(String),
+}
+
 /// A reference in a git repository.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Reference {
     /// The name of the reference, e.g. `"refs/heads/my_branch"`.
+    ///
+    /// If the name isn’t valid UTF-8, invalid bytes are replaced with U+FFFD
+    /// and [`Self::raw_name`] is set.
     pub name: String,
 
+    /// The name escaped with [`escape_bytes()`] if it isn’t valid UTF-8.
+    pub raw_name: Option<String>,
+
     /// The kind of reference, e.g. `"symbolic"` or `"direct"`.
     pub kind: String,
 
-    /// An error encountered when trying to resolve the reference, or `""`.
-    pub error: String,
+    /// An error encountered when trying to resolve the reference, or the
+    /// default if there wasn’t one.
+    pub error: ErrorInfo,
 }
 
 impl Reference {
@@ -51,8 +774,21 @@ impl Reference {
     pub fn new<N: fmt::Display, K: fmt::Display>(name: N, kind: K) -> Self {
         Self {
             name: name.to_string(),
+            raw_name: None,
             kind: kind.to_string(),
-            error: "".to_owned(),
+            error: ErrorInfo::default(),
+        }
+    }
+
+    /// Create a new reference without an error from a name that might not be
+    /// valid UTF-8. See [`Self::raw_name`].
+    #[must_use]
+    pub fn from_bytes<K: fmt::Display>(name: &[u8], kind: K) -> Self {
+        Self {
+            raw_name: std::str::from_utf8(name)
+                .is_err()
+                .then(|| escape_bytes(name)),
+            ..Self::new(String::from_utf8_lossy(name), kind)
         }
     }
 
@@ -62,12 +798,11 @@ impl Reference {
     where
         N: fmt::Display,
         K: fmt::Display,
-        E: fmt::Debug,
+        E: Into<ErrorInfo>,
     {
         Self {
-            name: name.to_string(),
-            kind: kind.to_string(),
-            error: format!("{error:?}"),
+            error: error.into(),
+            ..Self::new(name, kind)
         }
     }
 
@@ -96,16 +831,22 @@ impl Reference {
 
 impl ShellVars for Reference {
     // Output the reference information with a prefix (e.g. "ref_").
-    fn write_to_shell<W: io::Write>(&self, out: &ShellWriter<W>) {
-        out.write_var("name", &self.name);
-        out.write_var("short", self.short());
-        out.write_var("kind", &self.kind);
-        out.write_var("error", &self.error);
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        out.try_write_value("name", &self.name)?;
+        if let Some(raw_name) = &self.raw_name {
+            out.try_write_value("name_raw", raw_name)?;
+        }
+        out.try_write_value("short", self.short())?;
+        out.try_write_value("kind", &self.kind)?;
+        self.error.write_as(out, "error")
     }
 }
 
 /// The trail of a `HEAD` reference.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct Head {
     /// The trail of references leading to the actual underlying commit.
     pub trail: Vec<Reference>,
@@ -113,298 +854,1023 @@ pub struct Head {
     /// The hash of the commit.
     pub hash: String,
 
+    /// The name of the direct reference at the end of the trail and the
+    /// commit it points to, or the error that stopped the trail.
+    ///
+    /// This is used to compare `HEAD` to its upstream without resolving it
+    /// again. See [`git2_interop::upstream_info()`].
+    ///
+    /// [`head_only::read_head()`] can read `HEAD` in SHA-256 repositories,
+    /// but [`ObjectId`] can only hold SHA-1 hashes, so this is an error for
+    /// them even though [`Head::hash`] is set.
+    pub resolved: Result<(String, ObjectId), ErrorInfo>,
+}
+
+impl Head {
+    /// Get the short name of the branch `HEAD` points to, e.g. `"main"`, or
+    /// `None` if `HEAD` is detached or points to something other than a
+    /// branch.
+    ///
+    /// This is the branch even if it’s unborn, i.e. has no commits yet. If the
+    /// branch is itself a symbolic reference, this is the name of the first
+    /// branch, like `git symbolic-ref --short HEAD`.
+    #[must_use]
+    pub fn branch_name(&self) -> Option<&str> {
+        self.trail.get(1)?.name.strip_prefix("refs/heads/")
+    }
+
+    /// Check if `HEAD` points to a branch that has no commits yet, e.g. in a
+    /// new repository.
+    ///
+    /// The branch is the last reference in [`Self::trail`], and it has no
+    /// error since it’s expected not to exist.
+    #[must_use]
+    pub fn is_unborn(&self) -> bool {
+        match &self.resolved {
+            Err(error) => error.code == "unborn_branch",
+            Ok(_) => false,
+        }
+    }
+
+    /// Check if `HEAD` points directly to a commit rather than to a branch.
+    #[must_use]
+    pub fn is_detached(&self) -> bool {
+        matches!(self.trail.as_slice(), [head] if head.kind == "direct")
+    }
+
+    /// Get the first `n` characters of the commit hash, or the whole hash if
+    /// it’s shorter. This is `""` if there is no commit.
+    ///
+    /// ```rust
+    /// use git_status_vars::Head;
+    ///
+    /// let head = Head {
+    ///     hash: "2df6b768e60fbf899d8c8dc4a20385f30ee5da24".to_owned(),
+    ///     ..Head::default()
+    /// };
+    /// assert_eq!(head.short_hash(8), "2df6b768");
+    /// ```
+    #[must_use]
+    pub fn short_hash(&self, n: usize) -> &str {
+        self.hash.get(..n).unwrap_or(&self.hash)
+    }
+}
+
+impl Default for Head {
+    fn default() -> Self {
+        Self {
+            trail: vec![],
+            hash: String::new(),
+            resolved: Err(ErrorInfo::from(git2::Error::from_str(
+                "HEAD was not resolved",
+            ))),
+        }
+    }
+}
+
+impl ShellVars for Head {
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        let trail = self.trail.get(1..).unwrap_or(&[]);
+        out.try_write_value("ref_length", trail.len())?;
+        for (i, reference) in trail.iter().enumerate() {
+            // self.trail is actually 1 longer, so i + 1 always fits.
+            #[allow(clippy::arithmetic_side_effects)]
+            out.group_n("ref", i + 1).try_write_vars(reference)?;
+        }
+        out.try_write_value("hash", &self.hash)?;
+        out.try_write_value("unborn", self.is_unborn())
+    }
+}
+
+/// The difference between `HEAD` and its upstream.
+#[derive(Clone, Debug, Default)]
+pub struct Upstream {
     /// How many commits are we ahead of upstream?
     ///
     /// `None` means that there is no upstream, or there is no equivalent branch
     /// in upstream.
-    pub ahead_of_upstream: Option<usize>,
+    pub ahead: Option<usize>,
 
     /// How many commits are we behind upstream?
     ///
     /// `None` means that there is no upstream, or there is no equivalent branch
     /// in upstream.
-    pub behind_upstream: Option<usize>,
+    pub behind: Option<usize>,
+
+    /// Whether [`Self::ahead`] and [`Self::behind`] can be trusted.
+    ///
+    /// This is `Some(false)` if the repository is shallow or its history is
+    /// altered by grafts or replace references, since the counts may be
+    /// wrong. `None` means there was nothing to compare.
+    pub exact: Option<bool>,
 
     /// An error encountered trying to calculate differences with upstream.
-    pub upstream_error: String,
+    ///
+    /// This is the default if there is simply no upstream to compare to. See
+    /// [`git2_interop::get_upstream_difference()`].
+    pub error: ErrorInfo,
 }
 
-impl ShellVars for Head {
-    fn write_to_shell<W: io::Write>(&self, out: &ShellWriter<W>) {
-        let trail = self.trail.get(1..).unwrap_or(&[]);
-        out.write_var("ref_length", trail.len());
-        for (i, reference) in trail.iter().enumerate() {
-            // self.trail is actually 1 longer, so i + 1 always fits.
-            #[allow(clippy::arithmetic_side_effects)]
-            out.group_n("ref", i + 1).write_vars(reference);
+impl ShellVars for Upstream {
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        out.try_write_value("ahead", self.ahead)?;
+        out.try_write_value("behind", self.behind)?;
+        out.try_write_value("ahead_exact", self.exact)?;
+        self.error.write_as(out, "upstream_error")
+    }
+}
+
+/// A summary of a repository. See [`summarize_path()`].
+///
+/// Writing this with [`ShellWriter::write_vars()`] produces the same output as
+/// [`git2_interop::summarize_opened_repository()`].
+#[derive(Clone, Debug)]
+pub struct RepoSummary {
+    /// The state of the repository, e.g. whether a merge is in progress.
+    pub state: RepoState,
+
+    /// The state as `__git_ps1` shows it, without the leading `|`, e.g.
+    /// `REBASE 2/5`, or `None` if the repository is clean. See
+    /// [`git2_interop::ps1_state()`]. The `ps1_state` variable adds the `|`,
+    /// e.g. `|REBASE 2/5`.
+    pub ps1_state: Option<String>,
+
+    /// The working tree, or `None` if the repository is bare. See
+    /// [`SummarizeOptions::start_path`].
+    pub workdir: Option<PathBuf>,
+
+    /// The working tree with symlinks resolved and as it was reached, or
+    /// `None` if the repository is bare or
+    /// [`SummarizeOptions::both_workdirs`] wasn’t set.
+    pub both_workdirs: Option<BothWorkdirs>,
+
+    /// Whether the repository has no commits.
+    pub empty: bool,
+
+    /// Whether the repository is bare.
+    pub bare: bool,
+
+    /// How many seconds ago the repository was last fetched, or `None` if it
+    /// has never been fetched. See [`git2_interop::fetch_age()`].
+    pub fetch_age_seconds: Option<u64>,
+
+    /// The sections that were included. See [`SummarizeOptions::includes()`].
+    pub sections: BTreeSet<Section>,
+
+    /// The trail of `HEAD`, or `None` if the section wasn’t included or ran out
+    /// of time.
+    pub head: Option<Head>,
+
+    /// The difference between `HEAD` and its upstream, or `None` if the section
+    /// wasn’t included or ran out of time.
+    pub upstream: Option<Upstream>,
+
+    /// The changes in the working tree and index, or `None` if the section
+    /// wasn’t included, was skipped, or ran out of time.
+    pub changes: Option<ChangeCounters>,
+
+    /// Whether counting changes was skipped because the repository was too
+    /// large, or `None` if [`SummarizeOptions::skip_counts_above`] wasn’t set.
+    pub counts_skipped: Option<bool>,
+
+    /// The number of stashes, or `None` if the section wasn’t included or ran
+    /// out of time.
+    pub stash_count: Option<usize>,
+
+    /// The superproject, or `None` if the section wasn’t included or ran out
+    /// of time. This is `Some(None)` if the repository isn’t a submodule.
+    pub superproject: Option<Option<SuperprojectSummary>>,
+
+    /// The upstream branch on the remote, or `None` if the section wasn’t
+    /// included or ran out of time. This is `Some(None)` if there’s no
+    /// upstream on a remote to check. See [`git2_interop::remote_tip()`].
+    pub remote: Option<Option<RemoteTip>>,
+
+    /// Whether each section with a budget ran out of time. See
+    /// [`SummarizeOptions::budgets`].
+    pub timeouts: BTreeMap<Section, bool>,
+
+    /// The errors that prevented computing sections. The other sections are
+    /// still computed.
+    pub errors: BTreeMap<Section, ErrorInfo>,
+
+    /// Whether to output `color_ansi`. See [`SummarizeOptions::color_ansi`].
+    pub color_ansi: bool,
+
+    /// The symbols to output `glyphs` with. See [`SummarizeOptions::glyphs`].
+    pub glyphs: Option<GlyphSet>,
+}
+
+impl RepoSummary {
+    /// Start a summary with the basic information about a repository. The
+    /// sections are filled in later.
+    fn new(
+        repository: &Repository,
+        options: &SummarizeOptions,
+    ) -> Result<Self, Error> {
+        let both_workdirs = repository.workdir().map(|physical| {
+            let logical = options
+                .start_path
+                .as_deref()
+                .and_then(|start| logical_workdir(physical, start))
+                .unwrap_or_else(|| physical.to_path_buf());
+            BothWorkdirs {
+                physical: options.path_style.normalize(physical),
+                logical: options.path_style.normalize(&logical),
+            }
+        });
+        Ok(Self {
+            state: repository.state().into(),
+            ps1_state: ps1_state(repository),
+            workdir: both_workdirs
+                .as_ref()
+                .map(|workdirs| workdirs.logical.clone()),
+            both_workdirs: both_workdirs.filter(|_| options.both_workdirs),
+            empty: repository.is_empty()?,
+            bare: repository.is_bare(),
+            fetch_age_seconds: fetch_age(repository).map(|age| age.as_secs()),
+            sections: Section::ALL
+                .into_iter()
+                .filter(|section| options.includes(*section))
+                .collect(),
+            head: None,
+            upstream: None,
+            changes: None,
+            counts_skipped: None,
+            stash_count: None,
+            superproject: None,
+            remote: None,
+            timeouts: BTreeMap::new(),
+            errors: BTreeMap::new(),
+            color_ansi: options.color_ansi,
+            glyphs: options.glyphs,
+        })
+    }
+
+    /// Get the condition of the repository.
+    ///
+    /// This is [`Condition::Error`] if any section had an error.
+    #[must_use]
+    pub fn condition(&self) -> Condition {
+        if self.errors.is_empty() {
+            Condition::new(self.state, self.changes.as_ref())
+        } else {
+            Condition::Error
+        }
+    }
+
+    /// Get a compact summary of the counts, e.g. `●2 ✚1 …3 ⚑1 ↑2`.
+    ///
+    /// This has a symbol followed by the count for each of: staged, unstaged,
+    /// untracked, and conflicted files, stashes, and commits ahead of and
+    /// behind upstream. Counts of 0 and sections that weren’t computed are
+    /// left out, so this is empty for a clean repository.
+    ///
+    /// ```
+    /// use git_status_vars::{ChangeCounters, GlyphSet, RepoSummary};
+    /// # fn example(mut summary: RepoSummary) {
+    /// summary.changes = Some(ChangeCounters::from([3, 1, 2, 0]));
+    /// summary.stash_count = Some(1);
+    /// assert_eq!(summary.glyphs(GlyphSet::Unicode), "●2 ✚1 …3 ⚑1");
+    /// assert_eq!(summary.glyphs(GlyphSet::Ascii), "+2 *1 ?3 $1");
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn glyphs(&self, set: GlyphSet) -> String {
+        // A truncated count of 0 is output as `0+`, but nothing was found.
+        let count = |kind| {
+            let changes = self.changes.as_ref()?;
+            changes.value(kind).filter(|_| changes.get(kind) != Some(0))
+        };
+        let upstream = self.upstream.as_ref();
+        let counts = [
+            count(ChangeKind::Staged),
+            count(ChangeKind::Unstaged),
+            count(ChangeKind::Untracked),
+            count(ChangeKind::Conflicted),
+            self.stash_count.map(Value::from),
+            upstream
+                .and_then(|upstream| upstream.ahead)
+                .map(Value::from),
+            upstream
+                .and_then(|upstream| upstream.behind)
+                .map(Value::from),
+        ];
+
+        set.symbols()
+            .into_iter()
+            .zip(counts)
+            .filter_map(|(symbol, count)| {
+                count
+                    .filter(|count| count.as_int() != Some(0))
+                    .map(|count| format!("{symbol}{count}"))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Get the variables in the summary and their values in output order,
+    /// without writing them anywhere.
+    ///
+    /// The names and values are the same as the shell output (without a
+    /// prefix), except that values keep their type. Variables that would only
+    /// be output as `unset var` with [`ShellWriter::with_unset_missing()`] are
+    /// not included.
+    ///
+    /// ```no_run
+    /// use git_status_vars::{summarize_path, SummarizeOptions, Value};
+    /// use std::path::Path;
+    ///
+    /// let options = SummarizeOptions::default();
+    /// let summary = summarize_path(Path::new("."), &options).unwrap();
+    /// for (name, value) in summary.iter_vars() {
+    ///     if let Value::Int(count) = value {
+    ///         println!("{name} = {count}");
+    ///     }
+    /// }
+    /// ```
+    pub fn iter_vars(&self) -> impl Iterator<Item = (String, Value)> {
+        collect_vars(self).into_iter()
+    }
+
+    /// Record the result of [`compute_section()`]: whether the section ran
+    /// out of time if it has a budget, and its error if it had one.
+    ///
+    /// libgit2 errors only affect the section, so they’re recorded and
+    /// `Ok(None)` is returned. Other errors, e.g. [`Error::Cancelled`], are
+    /// returned.
+    fn note_result<T>(
+        &mut self,
+        options: &SummarizeOptions,
+        section: Section,
+        result: Result<Option<T>, Error>,
+    ) -> Result<Option<T>, Error> {
+        let (result, timed_out) = match result {
+            Ok(value) => {
+                let timed_out = value.is_none();
+                (Ok(value), timed_out)
+            }
+            Err(error @ Error::Git(_)) => {
+                self.errors.insert(section, error.into());
+                (Ok(None), false)
+            }
+            Err(error) => return Err(error),
+        };
+        if options.budget(section).is_some() {
+            self.timeouts.insert(section, timed_out);
+        }
+        result
+    }
+
+    /// Write the `repo_*` variables, and note that the variables for excluded
+    /// sections are missing.
+    fn write_repo<W: io::Write>(&self, out: &ShellWriter<W>) -> io::Result<()> {
+        out.try_write_var_debug(vars::REPO_STATE, self.state)?;
+        out.try_write_value(
+            vars::PS1_STATE,
+            self.ps1_state
+                .as_ref()
+                .map_or_else(String::new, |state| format!("|{state}")),
+        )?;
+        out.try_write_value(
+            vars::REPO_WORKDIR,
+            self.workdir
+                .as_deref()
+                .map(|workdir| workdir.display().to_string()),
+        )?;
+        match self
+            .workdir
+            .as_deref()
+            .filter(|path| path.to_str().is_none())
+        {
+            Some(workdir) => out.try_write_value(
+                vars::REPO_WORKDIR_RAW,
+                escape_bytes(workdir.as_os_str().as_encoded_bytes()),
+            )?,
+            None => out.try_write_missing(vars::REPO_WORKDIR_RAW)?,
+        }
+        if let Some(workdirs) = &self.both_workdirs {
+            out.try_write_vars(workdirs)?;
+        } else {
+            out.try_write_missing(vars::REPO_WORKDIR_LOGICAL)?;
+            out.try_write_missing(vars::REPO_WORKDIR_PHYSICAL)?;
+        }
+        out.try_write_value(vars::REPO_EMPTY, self.empty)?;
+        out.try_write_value(vars::REPO_BARE, self.bare)?;
+        out.try_write_value(vars::FETCH_AGE_SECONDS, self.fetch_age_seconds)?;
+        ErrorInfo::write_missing(out, vars::REPO_ERROR)?;
+        write_missing_sections(
+            out,
+            Section::ALL
+                .into_iter()
+                .filter(|section| !self.sections.contains(section)),
+        )
+    }
+
+    /// Write `{section}_error` if a section had an error, and
+    /// `{section}_timeout=true` if it ran out of time.
+    ///
+    /// If the section had no error, or has a budget but finished in time, this
+    /// notes that the variable is missing. See [`ShellWriter::write_missing()`].
+    fn write_section_status<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+        section: Section,
+    ) -> io::Result<()> {
+        let var = format!("{section}_error");
+        match self.errors.get(&section) {
+            Some(error) => error.write_as(out, &var)?,
+            None => ErrorInfo::write_missing(out, &var)?,
+        }
+
+        let var = format!("{section}_timeout");
+        match self.timeouts.get(&section) {
+            Some(true) => out.try_write_value(var, true),
+            Some(false) => out.try_write_missing(var),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the fetch section if it was included. It only has
+    /// `fetch_error` and `fetch_timeout`.
+    fn write_fetch<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        if !self.sections.contains(&Section::Fetch) {
+            return Ok(());
+        }
+        self.write_section_status(out, Section::Fetch)
+    }
+
+    /// Write the head section if it was included.
+    fn write_head<W: io::Write>(&self, out: &ShellWriter<W>) -> io::Result<()> {
+        if !self.sections.contains(&Section::Head) {
+            return Ok(());
+        }
+        if let Some(head) = &self.head {
+            out.group("head").try_write_vars(head)?;
+        }
+        self.write_section_status(out, Section::Head)
+    }
+
+    /// Write the upstream section if it was included.
+    fn write_upstream<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        if !self.sections.contains(&Section::Upstream) {
+            return Ok(());
+        }
+        if let Some(upstream) = &self.upstream {
+            out.group("head").try_write_vars(upstream)?;
+        }
+        self.write_section_status(out, Section::Upstream)
+    }
+
+    /// Write the counts section if it was included.
+    fn write_counts<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        if !self.sections.contains(&Section::Counts) {
+            return Ok(());
+        }
+        if self.counts_skipped == Some(true) {
+            write_missing_sections(out, [Section::Counts])?;
+            return out.try_write_value(vars::COUNTS_SKIPPED, true);
+        }
+        if let Some(changes) = &self.changes {
+            out.try_write_vars(changes)?;
+        }
+        self.write_section_status(out, Section::Counts)?;
+        if self.counts_skipped.is_some() {
+            out.try_write_missing(vars::COUNTS_SKIPPED)?;
+        }
+        Ok(())
+    }
+
+    /// Write the stash section if it was included.
+    fn write_stash<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        if !self.sections.contains(&Section::Stash) {
+            return Ok(());
+        }
+        if let Some(stash_count) = self.stash_count {
+            out.try_write_value(vars::STASH_COUNT, stash_count)?;
+        }
+        self.write_section_status(out, Section::Stash)
+    }
+
+    /// Write `color_hint` for the condition of the repository, and
+    /// `color_ansi` if it was requested.
+    fn write_color<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        let condition = self.condition();
+        out.try_write_value(vars::COLOR_HINT, condition.color_hint())?;
+        if self.color_ansi {
+            out.try_write_value(vars::COLOR_ANSI, condition.color_ansi())
+        } else {
+            out.try_write_missing(vars::COLOR_ANSI)
+        }
+    }
+
+    /// Write `glyphs` if it was requested. See [`Self::glyphs()`].
+    fn write_glyphs<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        match self.glyphs {
+            Some(set) => out.try_write_value(vars::GLYPHS, self.glyphs(set)),
+            None => out.try_write_missing(vars::GLYPHS),
         }
-        out.write_var("hash", &self.hash);
-        out.write_var("ahead", display_option(self.ahead_of_upstream));
-        out.write_var("behind", display_option(self.behind_upstream));
-        out.write_var("upstream_error", &self.upstream_error);
+    }
+
+    /// Write the superproject section if it was included.
+    fn write_superproject<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        if !self.sections.contains(&Section::Superproject) {
+            return Ok(());
+        }
+        match &self.superproject {
+            Some(Some(superproject)) => {
+                out.try_write_value(
+                    vars::SUPERPROJECT_WORKDIR,
+                    superproject.workdir.display().to_string(),
+                )?;
+                out.try_write_value(
+                    vars::SUPERPROJECT_PATH,
+                    superproject.path.display().to_string(),
+                )?;
+                out.try_write_value(
+                    vars::SUPERPROJECT_DRIFTED,
+                    superproject.drifted(),
+                )?;
+            }
+            Some(None) => {
+                out.try_write_value(vars::SUPERPROJECT_WORKDIR, "")?;
+                out.try_write_value(vars::SUPERPROJECT_PATH, "")?;
+                out.try_write_value(vars::SUPERPROJECT_DRIFTED, "")?;
+            }
+            None => {}
+        }
+        self.write_section_status(out, Section::Superproject)
+    }
+
+    /// Write the remote section if it was included.
+    fn write_remote<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        if !self.sections.contains(&Section::Remote) {
+            return Ok(());
+        }
+        match &self.remote {
+            Some(Some(tip)) => {
+                out.try_write_value(vars::REMOTE_TIP_HASH, tip.id.to_string())?;
+                out.try_write_value(vars::HEAD_BEHIND_REMOTE_TIP, tip.behind)?;
+            }
+            Some(None) => {
+                out.try_write_value(vars::REMOTE_TIP_HASH, "")?;
+                out.try_write_value(vars::HEAD_BEHIND_REMOTE_TIP, "")?;
+            }
+            None => {}
+        }
+        self.write_section_status(out, Section::Remote)
+    }
+}
+
+impl ShellVars for RepoSummary {
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        self.write_repo(out)?;
+        self.write_fetch(out)?;
+        self.write_head(out)?;
+        self.write_upstream(out)?;
+        self.write_counts(out)?;
+        self.write_stash(out)?;
+        self.write_superproject(out)?;
+        self.write_remote(out)?;
+        self.write_color(out)?;
+        self.write_glyphs(out)
     }
 }
 
-/// Summarize information about a repository.
+/// Summarize the repositories at each of `paths`.
 ///
-/// This takes the `Result` from one of the `Repository::open()` functions.
+/// Each path is opened and summarized with [`summarize_path()`], so the
+/// environment (e.g. `$GIT_CEILING_DIRECTORIES`) is ignored. Errors are
+/// returned for each repository separately, and the results are in the same
+/// order as `paths`.
 ///
-/// # Example
+/// If [`SummarizeOptions::parallel`] is set, the repositories are summarized
+/// in multiple threads. [`SummarizeOptions::start_path`] is ignored, since it
+/// can’t apply to every repository.
 ///
 /// ```no_run
-/// use git_status_vars::{summarize_repository, ShellWriter};
-/// use git2::Repository;
+/// use git_status_vars::{summarize_paths, SummarizeOptions};
+/// use std::path::PathBuf;
 ///
-/// summarize_repository(&ShellWriter::default(), Repository::open_from_env());
+/// let paths = [PathBuf::from("one"), PathBuf::from("two")];
+/// let options = SummarizeOptions {
+///     parallel: true,
+///     ..SummarizeOptions::default()
+/// };
+/// for (path, result) in paths.iter().zip(summarize_paths(&paths, &options)) {
+///     match result {
+///         Ok(summary) => println!("{}: {}", path.display(), summary.condition()),
+///         Err(error) => println!("{}: {error}", path.display()),
+///     }
+/// }
 /// ```
 ///
 /// # Panics
 ///
-/// This may panic if it can’t resolve a symbolic reference to a symbolic
-/// target.
-pub fn summarize_repository<W: std::io::Write>(
-    out: &ShellWriter<W>,
-    opened: Result<Repository, git2::Error>,
-) {
-    let result = match opened {
-        Ok(repository) => summarize_opened_repository(out, &repository),
-        Err(error)
-            if error.code() == ErrorCode::NotFound
-                && error.class() == ErrorClass::Repository =>
-        {
-            out.write_var("repo_state", "NotFound");
-            Ok(())
-        }
-        Err(error) => Err(error),
+/// If summarizing a repository in another thread panics, this panics with the
+/// same payload.
+#[must_use]
+pub fn summarize_paths(
+    paths: &[PathBuf],
+    options: &SummarizeOptions,
+) -> Vec<Result<RepoSummary, Error>> {
+    let _span = trace::span("summarize_paths");
+    let options = SummarizeOptions {
+        start_path: None,
+        ..options.clone()
     };
-
-    if let Err(error) = result {
-        out.write_var("repo_state", "Error");
-        out.write_var_debug("repo_error", error);
+    let summarize_path = |path: &PathBuf| summarize_path(path, &options);
+    if !options.parallel || paths.len() < 2 {
+        return paths.iter().map(summarize_path).collect();
     }
+
+    let threads = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<RepoSummary, Error>>> =
+        paths.iter().map(|_| None).collect();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    // Take the next path until there are none left.
+                    let mut done = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else {
+                            return done;
+                        };
+                        done.push((index, summarize_path(path)));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            let done = worker
+                .join()
+                .unwrap_or_else(|error| panic::resume_unwind(error));
+            for (index, result) in done {
+                results[index] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.expect("every path should be summarized"))
+        .collect()
 }
 
-/// Summarize information about a successfully opened repository.
+/// Open the repository at or above `path` and summarize it.
 ///
-/// # Example
+/// This is like [`git2_interop::summarize()`], but opens the repository
+/// itself, so callers don’t need to depend on the same version of [`git2`] as
+/// this crate. The repository is found by searching `path` and its parents,
+/// stopping at filesystem boundaries, like `git -C path` does. Unlike `git`,
+/// this ignores the environment, e.g. `$GIT_DIR`, `$GIT_WORK_TREE`, and
+/// `$GIT_CEILING_DIRECTORIES`. To respect them, open the repository with
+/// [`git2::Repository::open_ext()`] and use [`git2_interop::summarize()`].
 ///
 /// ```no_run
-/// use git_status_vars::{summarize_opened_repository, ShellWriter};
-/// use git2::Repository;
+/// use git_status_vars::{summarize_path, SummarizeOptions};
+/// use std::path::Path;
 ///
-/// summarize_opened_repository(
-///     &ShellWriter::default(),
-///     &Repository::open_from_env().unwrap(),
-/// ).unwrap();
+/// match summarize_path(Path::new("."), &SummarizeOptions::default()) {
+///     Ok(summary) => println!("{}", summary.condition()),
+///     Err(error) if error.is_not_found() => println!("not a repository"),
+///     Err(error) => println!("error: {error}"),
+/// }
 /// ```
 ///
 /// # Errors
 ///
-/// This will return a [`git2::Error`] if there were problems getting repository
-/// information. This is careful to load all repository information (and thus
-/// encountering any errors) before generating any output.
-///
-/// # Panics
-///
-/// This may panic if it can’t resolve a symbolic reference to a symbolic
-/// target.
-pub fn summarize_opened_repository<W: std::io::Write>(
-    out: &ShellWriter<W>,
-    repository: &Repository,
-) -> Result<(), git2::Error> {
-    let state = repository.state();
-    let workdir = display_option(repository.workdir().map(Path::display));
-    let empty = repository.is_empty()?;
-    let bare = repository.is_bare();
-    let head = &head_info(repository);
-    let changes = &count_changes(repository)?;
-
-    out.write_var_debug("repo_state", state);
-    out.write_var("repo_workdir", workdir);
-    out.write_var("repo_empty", empty);
-    out.write_var("repo_bare", bare);
-    out.group("head").write_vars(head);
-    out.write_vars(changes);
+/// This will return an [`Error`] if no repository was found (see
+/// [`Error::is_not_found()`]), or there were problems getting repository
+/// information.
+pub fn summarize_path(
+    path: &Path,
+    options: &SummarizeOptions,
+) -> Result<RepoSummary> {
+    let _span = trace::span("summarize_path");
+    Repository::open_ext(path, RepositoryOpenFlags::empty(), EMPTY_PATHS)
+        .map_err(Error::from)
+        .and_then(|repository| resolve_bare(&options.bare, repository))
+        .and_then(|repository| summarize(&repository, options))
+}
 
-    Ok(())
+/// No ceiling directories for [`Repository::open_ext()`].
+const EMPTY_PATHS: [&str; 0] = [];
+
+/// A working tree both as it was reached and with symlinks resolved. See
+/// [`SummarizeOptions::both_workdirs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BothWorkdirs {
+    /// The working tree as it was reached, without resolving symlinks, e.g.
+    /// `/home/user/repo/`.
+    pub logical: PathBuf,
+
+    /// The working tree with symlinks resolved, e.g. `/var/home/user/repo/`.
+    pub physical: PathBuf,
+}
+
+impl ShellVars for BothWorkdirs {
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        out.try_write_value(
+            vars::REPO_WORKDIR_LOGICAL,
+            self.logical.display().to_string(),
+        )?;
+        out.try_write_value(
+            vars::REPO_WORKDIR_PHYSICAL,
+            self.physical.display().to_string(),
+        )
+    }
 }
 
-/// Trace the `HEAD` reference for a repository.
+/// Find the working tree without resolving symlinks in `start`.
 ///
-/// # Panics
+/// `start` is a path inside `workdir`, which has symlinks resolved. This
+/// returns the ancestor of `start` that corresponds to `workdir`, with a
+/// trailing separator, or `None` if `start` isn’t inside `workdir`.
+fn logical_workdir(workdir: &Path, start: &Path) -> Option<PathBuf> {
+    let depth = start
+        .canonicalize()
+        .ok()?
+        .strip_prefix(workdir)
+        .ok()?
+        .components()
+        .count();
+    Some(start.ancestors().nth(depth)?.join(""))
+}
+
+/// Format `Option<impl fmt::Display>` for display. `None` becomes `""`.
+fn display_option<V: fmt::Display>(s: Option<V>) -> String {
+    s.map(|s| s.to_string()).unwrap_or_else(|| "".to_owned())
+}
+
+/// A kind of change counted by [`ChangeCounters`].
 ///
-/// This may panic if it can’t resolve a symbolic reference to a symbolic
-/// target.
-#[allow(clippy::similar_names)]
-#[must_use]
-pub fn head_info(repository: &Repository) -> Head {
-    let mut current = "HEAD".to_owned();
-    let mut head = Head::default();
-    loop {
-        match repository.find_reference(&current) {
-            Ok(reference) => match reference.kind() {
-                Some(ReferenceType::Direct) => {
-                    head.trail.push(Reference::direct(display_option(
-                        reference.name(),
-                    )));
-                    head.hash = display_option(reference.target());
-                    break;
-                }
-                Some(ReferenceType::Symbolic) => {
-                    head.trail.push(Reference::symbolic(display_option(
-                        reference.name(),
-                    )));
-                    let target = reference
-                        .symbolic_target()
-                        .expect("Symbolic ref should have symbolic target");
-                    target.clone_into(&mut current);
-                }
-                None => {
-                    head.trail.push(Reference::new(
-                        display_option(reference.name()),
-                        "unknown",
-                    ));
-                    break;
-                }
-            },
-            Err(error) => {
-                head.trail
-                    .push(Reference::new_with_error(current, "", error));
-                break;
-            }
-        };
-    }
+/// New kinds may be added in the future. They will come after the existing
+/// kinds in [`Self::ALL`] so that the output order doesn’t change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ChangeKind {
+    /// Untracked files (not in the index).
+    Untracked,
 
-    match get_upstream_difference(repository) {
-        Ok(Some((ahead, behind))) => {
-            head.ahead_of_upstream = Some(ahead);
-            head.behind_upstream = Some(behind);
-        }
-        Ok(None) => {}
-        Err(error) => {
-            head.upstream_error = format!("{error:?}");
+    /// Files that have been modified, but haven’t been staged.
+    Unstaged,
+
+    /// Files that have been staged.
+    Staged,
+
+    /// Files with conflicts.
+    Conflicted,
+
+    /// Ignored files. These are only counted if
+    /// [`SummarizeOptions::include_ignored`] is set.
+    Ignored,
+}
+
+impl ChangeKind {
+    /// All kinds in output order.
+    pub const ALL: [Self; 5] = [
+        Self::Untracked,
+        Self::Unstaged,
+        Self::Staged,
+        Self::Conflicted,
+        Self::Ignored,
+    ];
+
+    /// The name of the kind, e.g. `"untracked"`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Untracked => "untracked",
+            Self::Unstaged => "unstaged",
+            Self::Staged => "staged",
+            Self::Conflicted => "conflicted",
+            Self::Ignored => "ignored",
         }
     }
 
-    head
-}
+    /// The name of the variable the count is output as, e.g.
+    /// `"untracked_count"`.
+    #[must_use]
+    pub const fn var_name(self) -> &'static str {
+        match self {
+            Self::Untracked => vars::UNTRACKED_COUNT,
+            Self::Unstaged => vars::UNSTAGED_COUNT,
+            Self::Staged => vars::STAGED_COUNT,
+            Self::Conflicted => vars::CONFLICTED_COUNT,
+            Self::Ignored => vars::IGNORED_COUNT,
+        }
+    }
 
-/// Get the (ahead, behind) count of HEAD versus its upstream branch.
-///
-/// # Errors
-///
-/// This will return [`git2::Error`] if there were problems resolving the
-/// the repository head, or if there was an error finding the upstream branch
-/// (but it will return `Ok(None)` if there simply is no upstream or upstream
-/// branch).
-pub fn get_upstream_difference(
-    repository: &Repository,
-) -> Result<Option<(usize, usize)>, git2::Error> {
-    let local_ref = repository.head()?.resolve()?;
-    if let Some(local_oid) = local_ref.target() {
-        Branch::wrap(local_ref)
-            .upstream()?
-            .get()
-            .target()
-            .map(|upstream_oid| {
-                repository.graph_ahead_behind(local_oid, upstream_oid)
-            })
-            .transpose()
-    } else {
-        Ok(None)
+    /// The statuses that are counted as this kind of change.
+    const fn statuses(self) -> Status {
+        match self {
+            Self::Untracked => Status::WT_NEW,
+            Self::Unstaged => Status::WT_MODIFIED
+                .union(Status::WT_DELETED)
+                .union(Status::WT_TYPECHANGE)
+                .union(Status::WT_RENAMED),
+            Self::Staged => Status::INDEX_NEW
+                .union(Status::INDEX_MODIFIED)
+                .union(Status::INDEX_DELETED)
+                .union(Status::INDEX_RENAMED)
+                .union(Status::INDEX_TYPECHANGE),
+            Self::Conflicted => Status::CONFLICTED,
+            Self::Ignored => Status::IGNORED,
+        }
     }
 }
 
-/// Format `Option<impl fmt::Display>` for display. `None` becomes `""`.
-fn display_option<V: fmt::Display>(s: Option<V>) -> String {
-    s.map(|s| s.to_string()).unwrap_or_else(|| "".to_owned())
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 /// Track changes in the working tree and index (staged area).
-#[derive(Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct ChangeCounters {
+    /// The number of files with each kind of change. Kinds that weren’t
+    /// counted, e.g. [`ChangeKind::Ignored`] by default, are left out.
+    pub counts: BTreeMap<ChangeKind, usize>,
+
+    /// Whether counting stopped early because there were more status entries
+    /// than [`SummarizeOptions::max_entries`], or `None` if there was no limit.
+    ///
+    /// If this is `Some(true)`, the counts are lower bounds.
+    pub truncated: Option<bool>,
+
+    /// Whether any untracked directories were counted as one file each rather
+    /// than by the files inside them, or `None` if untracked files weren’t
+    /// looked for.
+    ///
+    /// This is the default, like `git status`, so that an untracked directory
+    /// with thousands of files is quick to count. Untracked repositories are
+    /// always counted as one file. See [`UntrackedFiles::All`].
+    pub untracked_truncated: Option<bool>,
+
+    /// The [`SummarizeOptions::count_limit`] used, if any.
+    ///
+    /// Counts stop at one more than the limit, so a count above the limit
+    /// means “more than the limit” and is output like `100+`. Once every count
+    /// is above the limit, the remaining status entries aren’t examined, so
+    /// [`Self::untracked_truncated`] only reflects the entries before that.
+    pub limit: Option<usize>,
+
+    /// Whether another process had the index locked, so it couldn’t be
+    /// updated, or `None` if [`SummarizeOptions::update_index`] wasn’t set.
+    ///
+    /// The counts are still accurate; only the stat information in the index
+    /// wasn’t refreshed.
+    pub index_locked: Option<bool>,
+}
+
+impl ChangeCounters {
+    /// Get the count for a kind of change, or `None` if it wasn’t counted.
+    #[must_use]
+    pub fn get(&self, kind: ChangeKind) -> Option<usize> {
+        self.counts.get(&kind).copied()
+    }
+
     /// The number of untracked files (not in the index).
-    pub untracked: usize,
+    #[must_use]
+    pub fn untracked(&self) -> usize {
+        self.get(ChangeKind::Untracked).unwrap_or(0)
+    }
 
     /// The number of files that have been modified, but haven’t been staged.
-    pub unstaged: usize,
+    #[must_use]
+    pub fn unstaged(&self) -> usize {
+        self.get(ChangeKind::Unstaged).unwrap_or(0)
+    }
 
     /// The number of files that have been staged.
-    pub staged: usize,
+    #[must_use]
+    pub fn staged(&self) -> usize {
+        self.get(ChangeKind::Staged).unwrap_or(0)
+    }
 
     /// The number of files with conflicts.
-    pub conflicted: usize,
+    #[must_use]
+    pub fn conflicted(&self) -> usize {
+        self.get(ChangeKind::Conflicted).unwrap_or(0)
+    }
+
+    /// The number of ignored files, or `None` if they weren’t counted.
+    #[must_use]
+    pub fn ignored(&self) -> Option<usize> {
+        self.get(ChangeKind::Ignored)
+    }
+
+    /// Get the count for a kind of change as it’s output, or `None` if it
+    /// wasn’t counted.
+    ///
+    /// Counts that are lower bounds because of [`Self::limit`] or
+    /// [`Self::truncated`] are strings like `"100+"`.
+    #[must_use]
+    pub fn value(&self, kind: ChangeKind) -> Option<Value> {
+        let n = self.get(kind)?;
+        Some(match self.limit {
+            Some(limit) if n > limit => Value::Str(format!("{limit}+")),
+            _ if self.truncated == Some(true) => Value::Str(format!("{n}+")),
+            _ => Value::from(n),
+        })
+    }
+}
+
+impl Default for ChangeCounters {
+    /// No changes of the kinds that are always counted.
+    fn default() -> Self {
+        Self::from([0; 4])
+    }
 }
 
+/// Create counters from the untracked, unstaged, staged, and conflicted counts.
 impl From<[usize; 4]> for ChangeCounters {
     fn from(array: [usize; 4]) -> Self {
+        let kinds = [
+            ChangeKind::Untracked,
+            ChangeKind::Unstaged,
+            ChangeKind::Staged,
+            ChangeKind::Conflicted,
+        ];
         Self {
-            untracked: array[0],
-            unstaged: array[1],
-            staged: array[2],
-            conflicted: array[3],
+            counts: kinds.into_iter().zip(array).collect(),
+            truncated: None,
+            untracked_truncated: None,
+            limit: None,
+            index_locked: None,
         }
     }
 }
 
 impl ShellVars for ChangeCounters {
     // Output the tree change information with a prefix (e.g. "tree_").
-    fn write_to_shell<W: io::Write>(&self, out: &ShellWriter<W>) {
-        out.write_var("untracked_count", self.untracked);
-        out.write_var("unstaged_count", self.unstaged);
-        out.write_var("staged_count", self.staged);
-        out.write_var("conflicted_count", self.conflicted);
-    }
-}
-
-/// Count changes in the working tree and index (staged area) of a repository.
-///
-/// # Errors
-///
-/// This will return [`git2::Error`] if there was an error getting status
-/// information from the repository.
-pub fn count_changes(
-    repository: &Repository,
-) -> Result<ChangeCounters, git2::Error> {
-    if repository.is_bare() {
-        // Can't run status on bare repo.
-        return Ok(ChangeCounters::default());
-    }
-
-    let mut options = StatusOptions::new();
-    // exclude_submodules optional?
-    options
-        .show(StatusShow::IndexAndWorkdir)
-        .include_untracked(true)
-        .exclude_submodules(true);
-    let statuses = repository.statuses(Some(&mut options))?;
-
-    let mut counters: [usize; 4] = [0; 4];
-    let buckets = [
-        // Untracked
-        Status::WT_NEW,
-        // Working tree changed
-        Status::WT_MODIFIED
-            | Status::WT_DELETED
-            | Status::WT_TYPECHANGE
-            | Status::WT_RENAMED,
-        // Staged
-        Status::INDEX_NEW
-            | Status::INDEX_MODIFIED
-            | Status::INDEX_DELETED
-            | Status::INDEX_RENAMED
-            | Status::INDEX_TYPECHANGE,
-        // Conflicted
-        Status::CONFLICTED,
-    ];
-
-    for status in statuses.iter() {
-        for (i, bits) in buckets.iter().enumerate() {
-            if status.status().intersects(*bits) {
-                counters[i] = counters[i].saturating_add(1);
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()> {
+        // Truncated counts are lower bounds, e.g. "500+", so they are strings.
+        for kind in ChangeKind::ALL {
+            match self.value(kind) {
+                Some(value) => out.try_write_value(kind.var_name(), value)?,
+                None => out.try_write_missing(kind.var_name())?,
             }
         }
+        if let Some(truncated) = self.truncated {
+            out.try_write_value(vars::COUNTS_TRUNCATED, truncated)?;
+        } else {
+            out.try_write_missing(vars::COUNTS_TRUNCATED)?;
+        }
+        if let Some(truncated) = self.untracked_truncated {
+            out.try_write_value(vars::UNTRACKED_TRUNCATED, truncated)?;
+        } else {
+            out.try_write_missing(vars::UNTRACKED_TRUNCATED)?;
+        }
+        if let Some(index_locked) = self.index_locked {
+            out.try_write_value(vars::REPO_LOCKED, index_locked)
+        } else {
+            out.try_write_missing(vars::REPO_LOCKED)
+        }
     }
-
-    Ok(ChangeCounters::from(counters))
 }
@@ -1,37 +1,653 @@
 //! git-status-vars executable.
 
-use clap::Parser;
-use git2::Repository;
-use git_status_vars::{summarize_repository, ShellWriter};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches};
+use git_status_vars::format::{GitstatusFormat, OutputFormat, PromptFormat};
+use git_status_vars::git2_interop::{
+    self, resolve_bare, summarize_repository, time_sections,
+};
+use git_status_vars::{
+    head_only, schema, trace, vars, Condition, Dialect, Error, ErrorInfo,
+    RepoSummary, Section, ShellWriter, SummarizeOptions,
+};
+use params::{Cli, Command, Params, ServeParams, Shell, WatchParams};
+use std::env;
+use std::io::{self, Read, Write};
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-/// Parameters to configure executable.
-#[derive(Debug, clap::Parser)]
-#[clap(version, about)]
-struct Params {
-    /// The repositories to summarize
-    repositories: Vec<PathBuf>,
+mod params;
+mod serve;
+mod timeout;
+mod watch;
 
-    /// Prefix for each shell var line (e.g. 'local ')
-    #[clap(long, short = 'p')]
-    prefix: Option<String>,
+fn main() -> ExitCode {
+    let matches = Cli::command()
+        .long_version(long_version())
+        .get_matches_from(Cli::args_os());
+    let cli =
+        Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    match cli.command {
+        None => run_summary(cli.summary),
+        Some(Command::Summary(params)) => run_summary(params),
+        Some(Command::Init { shell }) => {
+            print!("{}", init_script(shell));
+            ExitCode::SUCCESS
+        }
+        Some(Command::Doctor(params)) => {
+            if params.trace {
+                trace::enable();
+            }
+            with_buffered_stdout(&timeout::Target::default(), |out| {
+                doctor(&params, out);
+            });
+            ExitCode::SUCCESS
+        }
+        Some(Command::Schema) => {
+            print!("{}", schema::json_schema());
+            ExitCode::SUCCESS
+        }
+        Some(Command::Serve(serve)) => {
+            check_serve_params(&serve);
+            if serve.params.trace {
+                trace::enable();
+            }
+            serve::serve_http(&serve.params, serve.http)
+        }
+        Some(Command::Watch(watch)) => {
+            check_watch_params(&watch);
+            if watch.params.trace {
+                trace::enable();
+            }
+            watch::watch(&watch)
+        }
+    }
+}
+
+/// Get the version information for `--version`, including details about
+/// libgit2 that are useful in bug reports. (`-V` only outputs the version.)
+fn long_version() -> String {
+    let version = git2::Version::get();
+    let (major, minor, patch) = version.libgit2_version();
+    let libgit2_features: Vec<&str> = [
+        (version.vendored(), "vendored"),
+        (version.threads(), "threads"),
+        (version.https(), "https"),
+        (version.ssh(), "ssh"),
+        (version.nsec(), "nsec"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, name)| enabled.then_some(name))
+    .collect();
+    format!(
+        "{}\nlibgit2 {major}.{minor}.{patch} ({})\noutput formats: {}",
+        env!("CARGO_PKG_VERSION"),
+        libgit2_features.join(", "),
+        Dialect::ALL.map(Dialect::name).join(", "),
+    )
+}
+
+/// Run `f` with a writer that collects output in memory, then write the output
+/// to stdout all at once.
+///
+/// This avoids a write for every line, and means that being interrupted never
+/// leaves a partially written line.
+fn with_buffered_stdout<T, F>(target: &timeout::Target, f: F) -> T
+where
+    F: FnOnce(&ShellWriter<&mut Vec<u8>>) -> T,
+{
+    let mut buffer = vec![];
+    let result = f(&target.writer(&mut buffer));
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(&buffer)
+        .and_then(|()| stdout.flush())
+        .unwrap();
+    result
+}
+
+/// Run the `summary` subcommand.
+fn run_summary(mut params: Params) -> ExitCode {
+    check_prefix(&params);
+    if params.trace {
+        trace::enable();
+    }
+    if params.prompt {
+        return run_prompt(&params);
+    }
+    if params.vcs_info {
+        return run_vcs_info(&params);
+    }
+    if params.p10k {
+        return run_p10k(&params);
+    }
+    let target = timeout::Target {
+        prefix: params.prefix.clone().unwrap_or_default(),
+        group: None,
+        dialect: params.dialect,
+    };
+
+    let stdin = (params.stdin || params.stdin0).then(|| {
+        let mut input = vec![];
+        if let Err(error) = io::stdin().read_to_end(&mut input) {
+            Cli::command()
+                .error(ErrorKind::Io, format!("Could not read stdin: {error}"))
+                .exit();
+        }
+        params.add_stdin_repositories(&input);
+        input
+    });
+
+    if let Some(runs) = params.bench {
+        require_one_repository(&params, "--bench");
+        with_buffered_stdout(&target, |out| bench(&params, out, runs.get()));
+        return ExitCode::SUCCESS;
+    }
+    if params.two_phase {
+        require_one_repository(&params, "--two-phase");
+    }
+    let exit_code = params.exit_code;
+
+    // A child started by the process timeout strategy always reports its
+    // condition in its exit code.
+    if params.timeout.is_some() && env::var_os(timeout::CHILD_ENV).is_some() {
+        // Only pass on complete sections.
+        let out = target.writer(timeout::SectionWriter::new(io::stdout()));
+        let condition = match timeout::child_repository() {
+            Some(index) => {
+                let names = params.group_names();
+                summarize_one(&params, &out.group(&names[index]), Some(index))
+            }
+            None => summarize(&params, &out),
+        };
+        return ExitCode::from(condition.exit_code());
+    }
+
+    // Always first, so that scripts can check it before using anything else.
+    target
+        .writer(io::stdout())
+        .write_value(vars::SCHEMA_VERSION, u64::from(schema::SCHEMA_VERSION));
+
+    let condition = match params.timeout {
+        // Write each phase as soon as it’s ready.
+        None if params.two_phase => {
+            summarize(&params, &target.writer(io::stdout()))
+        }
+        None => with_buffered_stdout(&target, |out| summarize(&params, out)),
+        // Apply the timeout to each repository separately so that one slow
+        // repository doesn’t prevent the others from being summarized.
+        Some(duration) if params.repositories.len() > 1 => {
+            let names = params.group_names();
+            let out = target.writer(io::stdout());
+            summarize_each(&params, &out, |_, index| {
+                let target = timeout::Target {
+                    group: Some(names[index].clone()),
+                    ..target.clone()
+                };
+                match params.timeout_strategy {
+                    timeout::Strategy::Thread => {
+                        let params = params.clone();
+                        timeout::run_in_thread(&target, duration, move |out| {
+                            summarize_one(&params, out, Some(index))
+                        })
+                    }
+                    timeout::Strategy::Process => timeout::run_in_process(
+                        &target,
+                        duration,
+                        stdin.as_deref(),
+                        Some(index),
+                    ),
+                }
+            })
+        }
+        Some(duration) => match params.timeout_strategy {
+            timeout::Strategy::Thread => {
+                timeout::run_in_thread(&target, duration, move |out| {
+                    summarize(&params, out)
+                })
+            }
+            timeout::Strategy::Process => timeout::run_in_process(
+                &target,
+                duration,
+                stdin.as_deref(),
+                None,
+            ),
+        },
+    };
+
+    if exit_code {
+        ExitCode::from(condition.exit_code())
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Exit with an error if `--prefix` was passed with a dialect other than `sh`.
+///
+/// The prefix is written at the start of each line, and prefixes like `local `
+/// aren’t valid there in the other dialects.
+fn check_prefix(params: &Params) {
+    if params.prefix.is_some() && params.dialect != Dialect::Sh {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--prefix cannot be used with --dialect {}",
+                    params.dialect
+                ),
+            )
+            .exit();
+    }
+}
+
+/// Exit with an error if more than one repository was passed for `option`.
+fn require_one_repository(params: &Params, option: &str) {
+    if params.repositories.len() > 1 {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("{option} only works with one repository"),
+            )
+            .exit();
+    }
+}
+
+/// Exit with an error if `serve` would listen on a non-loopback address
+/// without `--allow-remote`, or if it was passed an option that writes to the
+/// repository, uses the network, computes sections in other threads, or
+/// changes how the output is produced.
+fn check_serve_params(serve: &ServeParams) {
+    if !serve.allow_remote && !serve.http.ip().is_loopback() {
+        Cli::command()
+            .error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "{} is not a loopback address; pass --allow-remote to \
+                    listen on it anyway",
+                    serve.http,
+                ),
+            )
+            .exit();
+    }
+    let params = &serve.params;
+    for (option, set) in [
+        ("--update-index", params.update_index),
+        ("--cache", params.cache),
+        ("--fetch", params.fetch),
+        ("--online", params.online),
+        ("--budget", !params.budget.is_empty()),
+        ("--parallel", params.parallel),
+        ("--prompt", params.prompt),
+        ("--vcs-info", params.vcs_info),
+        ("--p10k", params.p10k),
+        ("--head-only", params.head_only),
+        ("--two-phase", params.two_phase),
+        ("--bench", params.bench.is_some()),
+        ("--stdin", params.stdin || params.stdin0),
+        ("--exit-code", params.exit_code),
+        ("--prefix", params.prefix.is_some()),
+        ("--dialect", params.dialect != Dialect::default()),
+        ("--unset-missing", params.unset_missing),
+    ] {
+        if set {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{option} cannot be used with serve"),
+                )
+                .exit();
+        }
+    }
+}
+
+/// Exit with an error if `watch` was passed more than one repository, or an
+/// option that changes how the output is produced.
+fn check_watch_params(watch: &WatchParams) {
+    let params = &watch.params;
+    require_one_repository(params, "watch");
+    check_prefix(params);
+    for (option, set) in [
+        ("--prompt", params.prompt),
+        ("--vcs-info", params.vcs_info),
+        ("--p10k", params.p10k),
+        ("--bench", params.bench.is_some()),
+        ("--two-phase", params.two_phase),
+        ("--timeout", params.timeout.is_some()),
+        ("--timing", params.timing),
+        ("--stdin", params.stdin || params.stdin0),
+        ("--exit-code", params.exit_code),
+    ] {
+        if set {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{option} cannot be used with watch"),
+                )
+                .exit();
+        }
+    }
+}
+
+/// Summarize the one repository for an `option` like `--prompt`, giving up
+/// after `--timeout`.
+fn summarize_single(
+    params: &Params,
+    option: &str,
+) -> Result<RepoSummary, Error> {
+    require_one_repository(params, option);
+    summarize_with_timeout(params, params.repositories.first().cloned())
+}
+
+/// Summarize the repository at `path`, or the current directory, giving up
+/// after `--timeout`.
+fn summarize_with_timeout(
+    params: &Params,
+    path: Option<PathBuf>,
+) -> Result<RepoSummary, Error> {
+    let summarize = {
+        let params = params.clone();
+        move || {
+            let options = SummarizeOptions {
+                start_path: params.start_path(path.as_deref()),
+                ..params.summarize_options()
+            };
+            params
+                .open(path.as_deref())
+                .map_err(Error::from)
+                .and_then(|repository| resolve_bare(&options.bare, repository))
+                .and_then(|repository| {
+                    git2_interop::summarize(&repository, &options)
+                })
+        }
+    };
+    match params.timeout {
+        None => summarize(),
+        Some(duration) => {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let _ = sender.send(summarize());
+            });
+            receiver
+                .recv_timeout(duration)
+                .unwrap_or(Err(Error::Timeout(duration)))
+        }
+    }
+}
+
+/// Run `--prompt`: output a single line describing the repository to display
+/// in a prompt, or an empty line if there’s no repository.
+///
+/// `--timeout` always uses a thread, since nothing is output until the line
+/// is complete.
+fn run_prompt(params: &Params) -> ExitCode {
+    let result = summarize_single(params, "--prompt");
+    let format = PromptFormat::new().with_symbols(params.prompt_symbols());
+    let (line, condition) = match result {
+        Ok(summary) => (format.prompt(&summary), summary.condition()),
+        Err(error) if error.is_not_found() => {
+            (String::new(), Condition::NotFound)
+        }
+        Err(_) => (String::new(), Condition::Error),
+    };
+    println!("{line}");
+
+    if params.exit_code {
+        ExitCode::from(condition.exit_code())
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Run `--vcs-info`: output the variables zsh’s `vcs_info` would set, which
+/// are empty if there’s no repository.
+fn run_vcs_info(params: &Params) -> ExitCode {
+    let result = summarize_single(params, "--vcs-info");
+    let format = params.vcs_info_format();
+    let mut out = io::stdout().lock();
+    let condition = match result {
+        Ok(summary) => {
+            format.render(&summary, &mut out).unwrap();
+            summary.condition()
+        }
+        Err(error) => {
+            format.render_not_found(&mut out).unwrap();
+            if error.is_not_found() {
+                Condition::NotFound
+            } else {
+                Condition::Error
+            }
+        }
+    };
+
+    if params.exit_code {
+        ExitCode::from(condition.exit_code())
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Run `--p10k`: output the `VCS_STATUS_*` variables gitstatus would set for
+/// powerlevel10k.
+fn run_p10k(params: &Params) -> ExitCode {
+    let result = summarize_single(params, "--p10k");
+    let mut out = io::stdout().lock();
+    let condition = match result {
+        Ok(summary) => {
+            GitstatusFormat.render(&summary, &mut out).unwrap();
+            summary.condition()
+        }
+        Err(Error::Timeout(_)) => {
+            GitstatusFormat.render_result(&mut out, "tout").unwrap();
+            Condition::Error
+        }
+        Err(error) => {
+            GitstatusFormat
+                .render_result(&mut out, "norepo-sync")
+                .unwrap();
+            if error.is_not_found() {
+                Condition::NotFound
+            } else {
+                Condition::Error
+            }
+        }
+    };
+
+    if params.exit_code {
+        ExitCode::from(condition.exit_code())
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Get shell code that defines `git_status_vars_update`, a function that
+/// updates the variables, and arranges for it to run before each prompt.
+fn init_script(shell: Shell) -> String {
+    let function = "\
+        git_status_vars_update () {\n  \
+          eval \"$(command git-status-vars --unset-missing 2>/dev/null)\"\n\
+        }\n";
+    let hook = match shell {
+        Shell::Sh => "",
+        Shell::Bash => {
+            "PROMPT_COMMAND=\"git_status_vars_update${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"\n"
+        }
+        Shell::Zsh => {
+            "autoload -Uz add-zsh-hook\nadd-zsh-hook precmd git_status_vars_update\n"
+        }
+    };
+    format!("{function}{hook}")
+}
+
+/// Output the versions of git-status-vars and libgit2, and how long each
+/// section takes for the repository in `params`.
+fn doctor<W: io::Write>(params: &Params, out: &ShellWriter<W>) {
+    let (major, minor, patch) = git2::Version::get().libgit2_version();
+    out.write_var("version", env!("CARGO_PKG_VERSION"));
+    out.write_var("libgit2_version", format!("{major}.{minor}.{patch}"));
+
+    let options = params.summarize_options();
+    let opened = params.open(params.repositories.first().map(PathBuf::as_path));
+    let repository = match opened
+        .map_err(Error::from)
+        .and_then(|r| resolve_bare(&options.bare, r))
+    {
+        Ok(repository) => repository,
+        Err(error) => {
+            summarize_repository(out, Err(error), &options);
+            return;
+        }
+    };
+    out.write_var("repo_git_dir", repository.path().display());
+    match time_sections(&repository, &options) {
+        Ok(timings) => out.group("timing").write_vars(&timings),
+        Err(error) => ErrorInfo::from(error)
+            .write_as(out, vars::REPO_ERROR)
+            .unwrap(),
+    }
 }
 
-fn main() {
-    let params = Params::parse();
-    let out = ShellWriter::with_prefix(params.prefix.unwrap_or_default());
+/// Summarize the repository in `params` `runs` times and output statistics
+/// about how long each section took.
+fn bench<W: io::Write>(params: &Params, out: &ShellWriter<W>, runs: usize) {
+    let options = params.summarize_options();
+    let opened = params.open(params.repositories.first().map(PathBuf::as_path));
+    let repository = match opened
+        .map_err(Error::from)
+        .and_then(|r| resolve_bare(&options.bare, r))
+    {
+        Ok(repository) => repository,
+        Err(error) => {
+            summarize_repository(out, Err(error), &options);
+            return;
+        }
+    };
+
+    let mut samples: Vec<(String, Vec<Duration>)> = Section::ALL
+        .iter()
+        .map(|section| (section.to_string(), vec![]))
+        .chain([("total".to_owned(), vec![])])
+        .collect();
+    for _ in 0..runs {
+        let start = Instant::now();
+        let timings = match time_sections(&repository, &options) {
+            Ok(timings) => timings,
+            Err(error) => {
+                out.write_var(vars::REPO_STATE, "Error");
+                ErrorInfo::from(error)
+                    .write_as(out, vars::REPO_ERROR)
+                    .unwrap();
+                return;
+            }
+        };
+        let total = start.elapsed();
+        let timings = timings
+            .sections
+            .iter()
+            .map(|(section, duration)| (section.to_string(), *duration))
+            .chain([("total".to_owned(), total)]);
+        for (name, duration) in timings {
+            if let Some((_, durations)) =
+                samples.iter_mut().find(|(sample, _)| *sample == name)
+            {
+                durations.push(duration);
+            }
+        }
+    }
+
+    out.write_value("bench_runs", runs);
+    let out = out.group("bench");
+    for (name, mut durations) in samples {
+        durations.sort_unstable();
+        let (Some(min), Some(max)) = (durations.first(), durations.last())
+        else {
+            continue;
+        };
+        // Rounding down is fine for the median.
+        #[allow(clippy::integer_division)]
+        let median = durations[durations.len() / 2];
+        let out = out.group(name);
+        for (var, duration) in
+            [("min_us", *min), ("median_us", median), ("max_us", *max)]
+        {
+            let micros = duration.as_micros();
+            out.write_value(var, i64::try_from(micros).unwrap_or(i64::MAX));
+        }
+    }
+}
 
-    if params.repositories.is_empty() {
-        summarize_repository(&out, Repository::open_from_env());
-    } else if params.repositories.len() == 1 {
-        summarize_repository(&out, Repository::open(&params.repositories[0]));
+/// Summarize the repositories in `params` and write the output to `out`.
+fn summarize<W: io::Write>(params: &Params, out: &ShellWriter<W>) -> Condition {
+    if params.repositories.len() > 1 {
+        summarize_each(params, out, |repo_out, index| {
+            summarize_one(params, repo_out, Some(index))
+        })
     } else {
-        out.write_var("repo_count", params.repositories.len());
-        for (i, repo_path) in params.repositories.iter().enumerate() {
-            println!();
-            let repo_out = &out.group_n("repo", i.wrapping_add(1));
-            repo_out.write_var("path", repo_path.display());
-            summarize_repository(repo_out, Repository::open(repo_path));
+        let index = (!params.repositories.is_empty()).then_some(0);
+        summarize_one(params, out, index)
+    }
+}
+
+/// Summarize the repository at `index` in `params.repositories`, or the
+/// repository found from the environment if `index` is `None`.
+fn summarize_one<W: io::Write>(
+    params: &Params,
+    out: &ShellWriter<W>,
+    index: Option<usize>,
+) -> Condition {
+    let path = index
+        .and_then(|index| params.repositories.get(index))
+        .map(PathBuf::as_path);
+    if params.head_only {
+        return head_only::summarize_head_only(
+            &out.with_unset_missing(params.unset_missing),
+            params.find_git_dir(path).as_deref(),
+        );
+    }
+    let options = SummarizeOptions {
+        start_path: params.start_path(path),
+        ..params.summarize_options()
+    };
+    summarize_repository(
+        &out.with_unset_missing(params.unset_missing),
+        params.open(path),
+        &options,
+    )
+}
+
+/// Summarize each of the repositories in `params` with `summarize_one`, and
+/// output the number of repositories and how many had errors or weren’t found.
+///
+/// `summarize_one` is passed a writer for the repository’s group and the index
+/// of the repository.
+fn summarize_each<W, F>(
+    params: &Params,
+    out: &ShellWriter<W>,
+    mut summarize_one: F,
+) -> Condition
+where
+    W: io::Write,
+    F: FnMut(&ShellWriter<W>, usize) -> Condition,
+{
+    out.write_value(vars::REPO_COUNT, params.repositories.len());
+    let mut worst = Condition::Clean;
+    let mut error_count = 0_usize;
+    let names = params.group_names();
+    for (index, (name, repo_path)) in
+        names.iter().zip(&params.repositories).enumerate()
+    {
+        out.write_blank_line();
+        let repo_out = &out.group(name);
+        repo_out.write_var("path", repo_path.display());
+        let condition = summarize_one(repo_out, index);
+        if matches!(condition, Condition::NotFound | Condition::Error) {
+            error_count = error_count.saturating_add(1);
         }
+        worst = worst.max(condition);
     }
+    out.write_blank_line();
+    out.write_value(vars::REPO_ERROR_COUNT, error_count);
+    worst
 }
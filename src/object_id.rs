@@ -0,0 +1,52 @@
+//! The ID of a git object.
+
+use crate::Error;
+use git2::Oid;
+use std::fmt;
+
+/// The ID of a git object, e.g. the hash of a commit.
+///
+/// This is displayed as the full hash in hexadecimal.
+///
+/// ```rust
+/// use git_status_vars::ObjectId;
+///
+/// let hash = "2df6b768e60fbf899d8c8dc4a20385f30ee5da24";
+/// let id: ObjectId = hash.parse().unwrap();
+/// assert_eq!(id.to_string(), hash);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId(Oid);
+
+impl ObjectId {
+    /// Get the raw bytes of the ID.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// Get the libgit2 ID.
+    pub(crate) const fn oid(self) -> Oid {
+        self.0
+    }
+}
+
+impl From<Oid> for ObjectId {
+    fn from(oid: Oid) -> Self {
+        Self(oid)
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for ObjectId {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Oid::from_str(input)?))
+    }
+}
@@ -0,0 +1,843 @@
+//! Command line parameters.
+
+use clap::CommandFactory;
+use git2::{Repository, RepositoryOpenFlags};
+use git_status_vars::format::{PromptSymbols, VcsInfoFormat};
+use git_status_vars::{
+    head_only, sanitize_var_name, trace, BareRepository, Dialect, GlyphSet,
+    PathStyle, Section, SummarizeOptions, UntrackedFiles,
+};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use crate::timeout;
+
+/// Parameters to configure executable.
+#[derive(Clone, Debug, clap::Parser)]
+#[clap(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// What to do. If this is left out, `summary` is used.
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Parameters for `summary` when the subcommand is left out.
+    #[clap(flatten)]
+    pub summary: Params,
+}
+
+impl Cli {
+    /// Get the command line arguments, adding `summary` before a first
+    /// argument that names both a subcommand and an existing path.
+    ///
+    /// Before there were subcommands, `git-status-vars serve` summarized the
+    /// repository in `serve`, so that still works unless another argument is
+    /// only accepted by the subcommand, e.g. `git-status-vars serve --http
+    /// 127.0.0.1:0` or `git-status-vars init zsh`.
+    pub fn args_os() -> Vec<OsString> {
+        let mut args: Vec<OsString> = env::args_os().collect();
+        if args
+            .get(1)
+            .is_some_and(|first| names_path(first, &args[2..]))
+        {
+            args.insert(1, "summary".into());
+        }
+        args
+    }
+}
+
+/// Check if `first` should be treated as a path rather than a subcommand.
+///
+/// `rest` is the arguments after `first`.
+fn names_path(first: &OsStr, rest: &[OsString]) -> bool {
+    let mut cli = Cli::command();
+    cli.build();
+    let Some(subcommand) =
+        first.to_str().and_then(|name| cli.find_subcommand(name))
+    else {
+        return false;
+    };
+    if !Path::new(first).exists() {
+        return false;
+    }
+
+    // Sort the arguments into options and positional arguments, skipping the
+    // values of options that take one.
+    let mut options = vec![];
+    let mut positional = false;
+    let mut rest = rest.iter().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = rest.next() {
+        if arg == "--" {
+            break;
+        }
+        let Some(option) = arg.strip_prefix('-') else {
+            positional = true;
+            continue;
+        };
+        let takes_value = |matches: &dyn Fn(&clap::Arg) -> bool| {
+            cli.get_arguments()
+                .chain(subcommand.get_arguments())
+                .any(|arg| matches(arg) && arg.get_action().takes_values())
+        };
+        if let Some(long) = option.strip_prefix('-') {
+            if let Some((long, _)) = long.split_once('=') {
+                options.push(long.to_owned());
+                continue;
+            }
+            if takes_value(&|arg| arg.get_long() == Some(long)) {
+                rest.next();
+            }
+            options.push(long.to_owned());
+        } else if let [short] = option.as_bytes() {
+            let short = char::from(*short);
+            if takes_value(&|arg| arg.get_short() == Some(short)) {
+                rest.next();
+            }
+        }
+    }
+
+    // Arguments only the subcommand accepts, e.g. `init zsh` or
+    // `serve --http ADDRESS`.
+    let shared: Vec<&clap::Id> =
+        cli.get_arguments().map(clap::Arg::get_id).collect();
+    let mut own = subcommand
+        .get_arguments()
+        .filter(|arg| !shared.contains(&arg.get_id()));
+    !own.any(|arg| match arg.get_long() {
+        Some(long) => options.iter().any(|option| option == long),
+        None => positional,
+    })
+}
+
+/// A subcommand.
+#[derive(Clone, Debug, clap::Subcommand)]
+pub enum Command {
+    /// Output information about repositories as shell variables (default)
+    Summary(Params),
+
+    /// Output shell code that updates the variables before each prompt, e.g.
+    /// eval "$(git-status-vars init zsh)"
+    Init {
+        /// The shell to output code for
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+
+    /// Output information about git-status-vars and how long each section
+    /// takes to help diagnose problems
+    Doctor(Params),
+
+    /// Output a JSON Schema describing the variables
+    Schema,
+
+    /// Answer queries for summaries until killed, e.g. `GET
+    /// /summary?path=/src/repo` with --http
+    Serve(ServeParams),
+
+    /// Output the variables for one repository again every --interval until
+    /// killed, with an empty line after each summary
+    Watch(WatchParams),
+}
+
+/// Parameters for the `serve` subcommand.
+#[derive(Clone, Debug, clap::Args)]
+pub struct ServeParams {
+    /// Listen for HTTP requests on ADDRESS (e.g. '127.0.0.1:7777'), and
+    /// answer `GET /summary?path=PATH` with the variables as JSON
+    #[clap(long, value_name = "ADDRESS")]
+    pub http: SocketAddr,
+
+    /// Allow listening on an address that isn’t loopback. There is no
+    /// authentication, so anyone who can connect can summarize any repository
+    /// the server can read
+    #[clap(long)]
+    pub allow_remote: bool,
+
+    /// Options for summarizing repositories
+    #[clap(flatten)]
+    pub params: Params,
+}
+
+/// Parameters for the `watch` subcommand.
+#[derive(Clone, Debug, clap::Args)]
+pub struct WatchParams {
+    /// How long to wait between summaries (e.g. '500ms' or '2s')
+    #[clap(
+        long,
+        value_name = "DURATION",
+        value_parser = timeout::parse_duration,
+        default_value = "2s"
+    )]
+    pub interval: Duration,
+
+    /// When the variables change, wait until they have stayed the same for
+    /// this long (e.g. '500ms' or '2s') before outputting them. Until then,
+    /// they are checked every --debounce if that’s shorter than --interval
+    #[clap(
+        long,
+        value_name = "DURATION",
+        value_parser = timeout::parse_duration,
+        default_value = "0s"
+    )]
+    pub debounce: Duration,
+
+    /// Only output a summary if at least one variable differs from the last
+    /// summary output
+    #[clap(long)]
+    pub changed_only: bool,
+
+    /// Options for summarizing the repository
+    #[clap(flatten)]
+    pub params: Params,
+}
+
+/// A shell supported by the `init` subcommand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    /// POSIX sh; just defines the function.
+    Sh,
+
+    /// bash; runs the function from `PROMPT_COMMAND`.
+    Bash,
+
+    /// zsh; runs the function from a `precmd` hook.
+    Zsh,
+}
+
+/// Parameters for summarizing repositories.
+#[derive(Clone, Debug, clap::Args)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Params {
+    /// The repositories to summarize
+    pub repositories: Vec<PathBuf>,
+
+    /// Also read repositories to summarize from stdin, one per line
+    #[clap(long, conflicts_with = "stdin0")]
+    pub stdin: bool,
+
+    /// Also read repositories to summarize from stdin, separated by NUL
+    /// characters (e.g. from `find -print0`)
+    #[clap(long)]
+    pub stdin0: bool,
+
+    /// Prefix for each shell var line (e.g. 'local '). Only works with
+    /// --dialect sh
+    #[clap(long, short = 'p')]
+    pub prefix: Option<String>,
+
+    /// Output `unset var` for variables that are empty or not output, so that
+    /// values from a previous run don’t linger
+    #[clap(long)]
+    pub unset_missing: bool,
+
+    /// Do not look for a repository in this directory or its parents (in
+    /// addition to `$GIT_CEILING_DIRECTORIES`)
+    #[clap(long, value_name = "DIR")]
+    pub ceiling: Vec<PathBuf>,
+
+    /// Only look for a repository in the directory passed, not its parents
+    #[clap(long)]
+    pub no_search: bool,
+
+    /// Keep looking for a repository in parent directories on other
+    /// filesystems
+    #[clap(long)]
+    pub cross_fs: bool,
+
+    /// Ignore `$GIT_DIR` and `$GIT_WORK_TREE`
+    #[clap(long)]
+    pub no_git_env: bool,
+
+    /// Reuse the previous output if the repository hasn’t changed
+    #[clap(long)]
+    pub cache: bool,
+
+    /// Only output `HEAD`, reading it directly from the git directory without
+    /// opening the repository (outputs `repo_state=Unknown`)
+    #[clap(long)]
+    pub head_only: bool,
+
+    /// Only output these sections (fetch, head, upstream, counts, stash,
+    /// superproject, remote)
+    #[clap(long, value_delimiter = ',', value_name = "SECTIONS")]
+    pub only: Vec<Section>,
+
+    /// Do not output these sections (fetch, head, upstream, counts, stash,
+    /// superproject, remote)
+    #[clap(long, value_delimiter = ',', value_name = "SECTIONS")]
+    pub exclude: Vec<Section>,
+
+    /// Skip counting changes (same as `--exclude counts`)
+    #[clap(long)]
+    pub no_status: bool,
+
+    /// Skip comparing HEAD to upstream (same as `--exclude upstream`)
+    #[clap(long)]
+    pub no_upstream: bool,
+
+    /// Skip counting stashes (same as `--exclude stash`)
+    #[clap(long)]
+    pub no_stash: bool,
+
+    /// If this is a submodule, also output its superproject (adds the
+    /// superproject section). Disables --cache
+    #[clap(long)]
+    pub superproject: bool,
+
+    /// Ask the remote where the upstream branch is now, like `git ls-remote`
+    /// (adds the remote section; gives up after 2s unless `--budget remote=`
+    /// is set). HTTPS and SSH remotes require building with the `network`
+    /// feature
+    #[clap(long)]
+    pub online: bool,
+
+    /// Quietly fetch the remote of the upstream branch first, so that
+    /// `head_behind` is current (adds the fetch section; gives up after 5s
+    /// unless `--budget fetch=` is set)
+    #[clap(long)]
+    pub fetch: bool,
+
+    /// Exit with a code that reflects the repository condition: 0 for clean,
+    /// 1 for dirty, 2 for conflicted or in progress, 3 for not found, and 4
+    /// for errors
+    #[clap(long)]
+    pub exit_code: bool,
+
+    /// Give up after this long (e.g. '500ms' or '2s') and output
+    /// `repo_state=Error`
+    #[clap(long, value_parser = timeout::parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// How to enforce --timeout
+    #[clap(long, value_enum, default_value_t)]
+    pub timeout_strategy: timeout::Strategy,
+
+    /// Give up on sections that take too long (e.g. 'counts=300ms'), and
+    /// output `{section}_timeout=true` instead of their variables
+    #[clap(
+        long,
+        value_name = "SECTION=DURATION,...",
+        value_delimiter = ',',
+        value_parser = parse_budget
+    )]
+    pub budget: Vec<(Section, Duration)>,
+
+    /// How to output paths on Windows: 'native' or 'slash' (use '/' as the
+    /// separator)
+    #[clap(long, value_name = "STYLE", default_value_t)]
+    pub path_style: PathStyle,
+
+    /// The shell syntax to output: 'sh' (also bash and zsh), 'fish',
+    /// 'powershell', or 'csh' (also tcsh)
+    #[clap(long, value_name = "DIALECT", default_value_t)]
+    pub dialect: Dialect,
+
+    /// Also output `color_ansi`, the ANSI escape sequence for `color_hint`
+    #[clap(long)]
+    pub color_ansi: bool,
+
+    /// Also output `glyphs`, a compact summary of the counts (e.g. '●2 ✚1
+    /// ↑2'), with 'unicode' or 'ascii' symbols
+    #[clap(long, value_name = "SET")]
+    pub glyphs: Option<GlyphSet>,
+
+    /// Output a single line to display in a prompt (e.g. 'main ↑1 +2 ?3')
+    /// instead of shell variables
+    #[clap(
+        long,
+        conflicts_with_all = ["stdin", "stdin0", "head_only", "bench", "cache"]
+    )]
+    pub prompt: bool,
+
+    /// Change the markers used by --prompt (e.g. 'untracked=…,stash=s').
+    /// Names: ahead, behind, staged, unstaged, untracked, conflicted, stash,
+    /// state
+    #[clap(
+        long,
+        value_name = "NAME=SYMBOL,...",
+        value_delimiter = ',',
+        value_parser = parse_prompt_symbol,
+        requires = "prompt"
+    )]
+    pub prompt_symbols: Vec<(String, String)>,
+
+    /// Output the variables zsh’s `vcs_info` sets (`vcs_info_msg_0_`, etc.)
+    /// instead of the usual variables
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "stdin", "stdin0", "head_only", "bench", "cache", "prompt"
+        ]
+    )]
+    pub vcs_info: bool,
+
+    /// The `vcs_info` `formats` style for --vcs-info (e.g. ' (%s)-[%b]%u%c-').
+    /// May be repeated to set `vcs_info_msg_1_`, etc.
+    #[clap(long, value_name = "FORMAT", requires = "vcs_info")]
+    pub vcs_info_formats: Vec<String>,
+
+    /// The `vcs_info` `actionformats` style for --vcs-info (e.g. '
+    /// (%s)-[%b|%a]%u%c-'). May be repeated to set `vcs_info_msg_1_`, etc.
+    #[clap(long, value_name = "FORMAT", requires = "vcs_info")]
+    pub vcs_info_actionformats: Vec<String>,
+
+    /// The `vcs_info` `stagedstr` style for --vcs-info (default 'S')
+    #[clap(long, value_name = "STRING", requires = "vcs_info")]
+    pub vcs_info_stagedstr: Option<String>,
+
+    /// The `vcs_info` `unstagedstr` style for --vcs-info (default 'U')
+    #[clap(long, value_name = "STRING", requires = "vcs_info")]
+    pub vcs_info_unstagedstr: Option<String>,
+
+    /// Output the `VCS_STATUS_*` variables gitstatus sets for powerlevel10k
+    /// instead of the usual variables
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "stdin", "stdin0", "head_only", "bench", "cache", "prompt",
+            "vcs_info"
+        ]
+    )]
+    pub p10k: bool,
+
+    /// Output the repository state and HEAD first, then an empty line, and
+    /// then the rest of the variables once they are ready
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "stdin", "stdin0", "head_only", "bench", "cache", "prompt",
+            "vcs_info", "p10k", "timeout"
+        ]
+    )]
+    pub two_phase: bool,
+
+    /// How to name the variables for each repository when summarizing more
+    /// than one
+    #[clap(long, value_enum, default_value_t)]
+    pub group_by: GroupBy,
+
+    /// Output how long each section took in microseconds (disables --cache)
+    #[clap(long)]
+    pub timing: bool,
+
+    /// Compare HEAD to REF (e.g. 'origin/main') instead of its upstream
+    #[clap(long, value_name = "REF")]
+    pub upstream_ref: Option<String>,
+
+    /// Stop counting changes after N status entries, and output counts like
+    /// '500+' and `counts_truncated=true`
+    #[clap(long, value_name = "N")]
+    pub max_entries: Option<usize>,
+
+    /// Output counts larger than N as 'N+'. This doesn’t make counting
+    /// faster; see --max-entries
+    #[clap(long, value_name = "N")]
+    pub count_limit: Option<usize>,
+
+    /// Don’t count changes if the index has more than N entries, and output
+    /// `counts_skipped=true` instead
+    #[clap(long, value_name = "N")]
+    pub skip_counts_above: Option<usize>,
+
+    /// Refresh out of date stat information in the index so that counting
+    /// changes is faster next time (writes to the repository)
+    #[clap(long)]
+    pub update_index: bool,
+
+    /// Ask the `core.fsmonitor` hook or daemon which files changed, and only
+    /// check those files when possible
+    #[clap(long)]
+    pub fsmonitor: bool,
+
+    /// Count changes in a separate thread while the other sections are
+    /// computed
+    #[clap(long)]
+    pub parallel: bool,
+
+    /// Resolve symlinks in `repo_workdir` (default)
+    #[clap(long, overrides_with = "no_resolve_symlinks")]
+    pub resolve_symlinks: bool,
+
+    /// Keep symlinks in `repo_workdir` from the repository path or the current
+    /// directory (`$PWD`)
+    #[clap(long, overrides_with = "resolve_symlinks")]
+    pub no_resolve_symlinks: bool,
+
+    /// Output both `repo_workdir_logical`, which keeps symlinks, and
+    /// `repo_workdir_physical`, which resolves them (implies
+    /// `--no-resolve-symlinks`)
+    #[clap(long)]
+    pub both_workdirs: bool,
+
+    /// Don’t look for untracked files (`untracked_count` is always 0). Same as
+    /// `--untracked-files no`
+    #[clap(long)]
+    pub no_untracked: bool,
+
+    /// How to look for untracked files, like `git status --untracked-files`:
+    /// no, normal (count each untracked directory as one file), or all
+    /// (count every file in untracked directories) [default: the
+    /// `status.showUntrackedFiles` configuration, or normal]
+    #[clap(long, value_name = "MODE", conflicts_with = "no_untracked")]
+    pub untracked_files: Option<UntrackedFiles>,
+
+    /// Count submodules with changes as changed files. Disables --cache
+    #[clap(long)]
+    pub include_submodules: bool,
+
+    /// Count ignored files (outputs `ignored_count`). Disables --cache
+    #[clap(long)]
+    pub include_ignored: bool,
+
+    /// Count a staged rename as one change rather than a deletion and an
+    /// addition [default: the `status.renames` or `diff.renames`
+    /// configuration, or true]
+    #[clap(long, overrides_with = "no_detect_renames")]
+    pub detect_renames: bool,
+
+    /// Count a staged rename as a deletion and an addition
+    #[clap(long, overrides_with = "detect_renames")]
+    pub no_detect_renames: bool,
+
+    /// Summarize bare repositories even though they have no working tree
+    /// (default)
+    #[clap(long, overrides_with_all = ["require_worktree", "worktree"])]
+    pub allow_bare: bool,
+
+    /// Output `repo_state=Error` for bare repositories
+    #[clap(long, overrides_with_all = ["allow_bare", "worktree"])]
+    pub require_worktree: bool,
+
+    /// Summarize the linked worktree NAME instead of a bare repository
+    #[clap(
+        long,
+        value_name = "NAME",
+        overrides_with_all = ["allow_bare", "require_worktree"]
+    )]
+    pub worktree: Option<String>,
+
+    /// Summarize the repository N times and output the minimum, median, and
+    /// maximum time each section took in microseconds
+    #[clap(long, value_name = "N")]
+    pub bench: Option<NonZeroUsize>,
+
+    /// Output how long each step takes, including calls into libgit2, to
+    /// stderr
+    #[clap(long)]
+    pub trace: bool,
+}
+
+/// How to name the group of variables for each repository when summarizing
+/// more than one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// Number the groups by position: `repo1_`, `repo2_`, etc.
+    #[default]
+    Position,
+
+    /// Name the groups after the repository directory, e.g. `myrepo_`
+    Name,
+}
+
+impl Params {
+    /// Read repository paths from `input` (from stdin) and add them to
+    /// [`Self::repositories`].
+    pub fn add_stdin_repositories(&mut self, input: &[u8]) {
+        let separator = if self.stdin0 { b'\0' } else { b'\n' };
+        self.repositories.extend(
+            input
+                .split(|byte| *byte == separator)
+                .filter(|path| !path.is_empty())
+                .map(path_from_bytes),
+        );
+    }
+
+    /// Get the group name for each repository when summarizing more than one.
+    pub fn group_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for (i, repo_path) in self.repositories.iter().enumerate() {
+            let name = match self.group_by {
+                GroupBy::Position => format!("repo{}", i.wrapping_add(1)),
+                GroupBy::Name => repo_name(repo_path),
+            };
+
+            // Add a suffix if the name is already used.
+            let mut unique = name.clone();
+            let mut suffix = 1_usize;
+            while names.contains(&unique) {
+                suffix = suffix.wrapping_add(1);
+                unique = format!("{name}_{suffix}");
+            }
+            names.push(unique);
+        }
+        names
+    }
+
+    /// Open the repository at or containing `path`, or find the repository
+    /// from the environment like `git` if `path` is `None`.
+    ///
+    /// Like `git -C path`, this respects `$GIT_DIR` and `$GIT_WORK_TREE`
+    /// relative to `path` unless `--no-git-env` was passed.
+    pub fn open(&self, path: Option<&Path>) -> Result<Repository, git2::Error> {
+        let _span = trace::span("open");
+        let mut flags = RepositoryOpenFlags::empty();
+        flags.set(RepositoryOpenFlags::NO_SEARCH, self.no_search);
+        flags.set(RepositoryOpenFlags::CROSS_FS, self.cross_fs);
+
+        let git_dir = self.git_env("GIT_DIR");
+        let work_tree = self.git_env("GIT_WORK_TREE");
+        // `open_from_env()` resolves a relative `$GIT_WORK_TREE` against the
+        // git directory rather than the current directory, unlike `git`.
+        let from_env = path.is_none()
+            && !self.no_git_env
+            && work_tree
+                .as_ref()
+                .map_or(true, |work_tree| Path::new(work_tree).is_absolute())
+            && (git_dir.is_some()
+                || (self.ceiling.is_empty() && flags.is_empty()));
+        let path = path.unwrap_or_else(|| Path::new("."));
+        let repository = if from_env {
+            // `open_from_env()` already respects $GIT_CEILING_DIRECTORIES.
+            Repository::open_from_env()?
+        } else if let Some(git_dir) = git_dir {
+            Repository::open_ext(
+                path.join(git_dir),
+                flags | RepositoryOpenFlags::NO_SEARCH,
+                self.ceilings(),
+            )?
+        } else {
+            Repository::open_ext(path, flags, self.ceilings())?
+        };
+
+        if let Some(work_tree) = work_tree {
+            repository.set_workdir(&path.join(work_tree), false)?;
+        }
+        Ok(repository)
+    }
+
+    /// Find the git directory for `path` without opening the repository, or
+    /// find it from the environment like `git` if `path` is `None`.
+    ///
+    /// Used for `--head-only`. This doesn’t respect `--cross-fs`; it always
+    /// searches across filesystems.
+    pub fn find_git_dir(&self, path: Option<&Path>) -> Option<PathBuf> {
+        let path = path.unwrap_or_else(|| Path::new("."));
+        match self.git_env("GIT_DIR") {
+            Some(git_dir) => {
+                head_only::find_git_dir(&path.join(git_dir), &[], false)
+            }
+            None => {
+                head_only::find_git_dir(path, &self.ceilings(), !self.no_search)
+            }
+        }
+    }
+
+    /// Get a non-empty git environment variable like `$GIT_DIR`, or `None` if
+    /// it’s unset or `--no-git-env` was passed.
+    fn git_env(&self, name: &str) -> Option<OsString> {
+        if self.no_git_env {
+            return None;
+        }
+        env::var_os(name).filter(|value| !value.is_empty())
+    }
+
+    /// Get the directories that repository discovery should not enter.
+    fn ceilings(&self) -> Vec<PathBuf> {
+        let from_env: Vec<PathBuf> = env::var_os("GIT_CEILING_DIRECTORIES")
+            .map(|value| env::split_paths(&value).collect())
+            .unwrap_or_default();
+        from_env
+            .into_iter()
+            .chain(self.ceiling.iter().cloned())
+            .filter(|path| !path.as_os_str().is_empty())
+            // libgit2 compares ceilings to the canonical repository path.
+            .map(|path| path.canonicalize().unwrap_or(path))
+            .collect()
+    }
+
+    /// Get the absolute path to `path` (or the current directory) without
+    /// resolving symlinks, unless symlinks should be resolved.
+    ///
+    /// See [`SummarizeOptions::start_path`].
+    pub fn start_path(&self, path: Option<&Path>) -> Option<PathBuf> {
+        if !self.no_resolve_symlinks && !self.both_workdirs {
+            return None;
+        }
+
+        let path = path.unwrap_or_else(|| Path::new("."));
+        let current_dir = env::current_dir().ok()?;
+        // $PWD preserves symlinks, but it may be stale.
+        let base = env::var_os("PWD")
+            .map(PathBuf::from)
+            .filter(|pwd| {
+                pwd.is_absolute()
+                    && pwd.canonicalize().ok()
+                        == current_dir.canonicalize().ok()
+            })
+            .unwrap_or(current_dir);
+
+        // Resolve `.` and `..` without looking at the filesystem, like a
+        // shell does.
+        let mut absolute = PathBuf::new();
+        for component in base.join(path).components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    absolute.pop();
+                }
+                component => absolute.push(component),
+            }
+        }
+        Some(absolute)
+    }
+
+    /// Get the markers for `--prompt`.
+    pub fn prompt_symbols(&self) -> PromptSymbols {
+        let mut symbols = PromptSymbols::default();
+        for (name, symbol) in &self.prompt_symbols {
+            symbols
+                .set(name, symbol.as_str())
+                .expect("names are checked when parsing");
+        }
+        symbols
+    }
+
+    /// Get the format for `--vcs-info`.
+    pub fn vcs_info_format(&self) -> VcsInfoFormat {
+        let mut format = VcsInfoFormat::new();
+        if !self.vcs_info_formats.is_empty() {
+            format = format.with_formats(&self.vcs_info_formats);
+        }
+        if !self.vcs_info_actionformats.is_empty() {
+            format = format.with_action_formats(&self.vcs_info_actionformats);
+        }
+        if let Some(staged_str) = &self.vcs_info_stagedstr {
+            format = format.with_staged_str(staged_str);
+        }
+        if let Some(unstaged_str) = &self.vcs_info_unstagedstr {
+            format = format.with_unstaged_str(unstaged_str);
+        }
+        format
+    }
+
+    /// Get what to do with bare repositories.
+    fn bare_repository(&self) -> BareRepository {
+        if let Some(name) = &self.worktree {
+            BareRepository::Worktree(name.clone())
+        } else if self.require_worktree {
+            BareRepository::RequireWorktree
+        } else {
+            BareRepository::Allow
+        }
+    }
+
+    /// Get the options to pass to the summarize functions.
+    pub fn summarize_options(&self) -> SummarizeOptions {
+        let mut options = SummarizeOptions {
+            cache: self.cache,
+            timing: self.timing,
+            untracked_files: if self.no_untracked {
+                Some(UntrackedFiles::No)
+            } else {
+                self.untracked_files
+            },
+            include_submodules: self.include_submodules,
+            include_ignored: self.include_ignored,
+            detect_renames: if self.detect_renames {
+                Some(true)
+            } else if self.no_detect_renames {
+                Some(false)
+            } else {
+                None
+            },
+            update_index: self.update_index,
+            fsmonitor: self.fsmonitor,
+            parallel: self.parallel,
+            bare: self.bare_repository(),
+            upstream_ref: self.upstream_ref.clone(),
+            max_entries: self.max_entries,
+            count_limit: self.count_limit,
+            skip_counts_above: self.skip_counts_above,
+            budgets: self.budget.iter().copied().collect(),
+            both_workdirs: self.both_workdirs,
+            path_style: self.path_style,
+            color_ansi: self.color_ansi,
+            glyphs: self.glyphs,
+            two_phase: self.two_phase,
+            ..SummarizeOptions::default()
+        };
+        if !self.only.is_empty() {
+            options.sections = self.only.iter().copied().collect();
+        }
+        if self.superproject {
+            options.sections.insert(Section::Superproject);
+        }
+        if self.online {
+            options.sections.insert(Section::Remote);
+        }
+        if self.fetch {
+            options.sections.insert(Section::Fetch);
+        }
+        for section in &self.exclude {
+            options.sections.remove(section);
+        }
+        for (flag, section) in [
+            (self.no_status, Section::Counts),
+            (self.no_upstream, Section::Upstream),
+            (self.no_stash, Section::Stash),
+        ] {
+            if flag {
+                options.sections.remove(&section);
+            }
+        }
+        options
+    }
+}
+
+/// Parse a section budget like `counts=300ms`.
+fn parse_budget(input: &str) -> Result<(Section, Duration), String> {
+    let (section, duration) = input
+        .split_once('=')
+        .ok_or_else(|| format!("expected SECTION=DURATION, got {input:?}"))?;
+    Ok((section.parse()?, timeout::parse_duration(duration)?))
+}
+
+/// Parse a symbol for `--prompt-symbols` like `untracked=…`.
+fn parse_prompt_symbol(input: &str) -> Result<(String, String), String> {
+    let (name, symbol) = input
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=SYMBOL, got {input:?}"))?;
+    PromptSymbols::default().set(name, symbol)?;
+    Ok((name.to_owned(), symbol.to_owned()))
+}
+
+/// Convert a path read from stdin to a `PathBuf`.
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+/// Convert a path read from stdin to a `PathBuf`.
+///
+/// Paths must be UTF-8 on platforms other than Unix.
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Get a name suitable for a shell variable from a repository path.
+///
+/// This uses the last component of the path, after resolving `.` and `..`.
+fn repo_name(path: &Path) -> String {
+    let name = path.file_name().map(OsStr::to_os_string).or_else(|| {
+        path.canonicalize()
+            .ok()?
+            .file_name()
+            .map(OsStr::to_os_string)
+    });
+    sanitize_var_name(&name.unwrap_or_default().to_string_lossy())
+}
@@ -0,0 +1,97 @@
+//! Normalize how paths are output on Windows.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// How to output paths like `repo_workdir` on Windows. See
+/// [`SummarizeOptions::path_style`][crate::SummarizeOptions::path_style].
+///
+/// Both styles remove the verbatim prefix (`\\?\`) that canonicalized paths
+/// have, since most programs don’t understand it, and make the drive letter
+/// uppercase. Paths are never changed on other platforms, where `\` may be
+/// part of a file name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PathStyle {
+    /// Leave the separators alone.
+    #[default]
+    Native,
+
+    /// Use `/` as the separator, e.g. `C:/Users/name/repo/`. This is easier to
+    /// use in shells like Git Bash, where `\` is an escape character.
+    Slash,
+}
+
+impl PathStyle {
+    /// All styles.
+    pub const ALL: [Self; 2] = [Self::Native, Self::Slash];
+
+    /// The name of the style as used on the command line.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Slash => "slash",
+        }
+    }
+
+    /// Normalize `path` if this is Windows. Otherwise, return it unchanged.
+    #[must_use]
+    pub fn normalize(self, path: &Path) -> PathBuf {
+        if cfg!(windows) {
+            match path.to_str() {
+                Some(path) => self.normalize_windows(path).into(),
+                None => path.to_path_buf(),
+            }
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    /// Normalize a Windows path on any platform.
+    ///
+    /// ```rust
+    /// use git_status_vars::PathStyle;
+    ///
+    /// let native = PathStyle::Native;
+    /// assert_eq!(native.normalize_windows(r"\\?\c:\src\repo\"), r"C:\src\repo\");
+    /// assert_eq!(native.normalize_windows(r"\\?\UNC\server\share"), r"\\server\share");
+    /// assert_eq!(PathStyle::Slash.normalize_windows(r"c:\src"), "C:/src");
+    /// ```
+    #[must_use]
+    pub fn normalize_windows(self, path: &str) -> String {
+        let mut path = if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+            format!(r"\\{unc}")
+        } else {
+            path.strip_prefix(r"\\?\").unwrap_or(path).to_owned()
+        };
+        if path.as_bytes().get(1) == Some(&b':') {
+            if let Some(drive) = path.get_mut(..1) {
+                drive.make_ascii_uppercase();
+            }
+        }
+        match self {
+            Self::Native => path,
+            Self::Slash => path.replace('\\', "/"),
+        }
+    }
+}
+
+impl fmt::Display for PathStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for PathStyle {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|style| style.name() == input)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.map(Self::name).into();
+                format!("expected one of: {}", names.join(", "))
+            })
+    }
+}
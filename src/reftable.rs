@@ -0,0 +1,235 @@
+//! Read references from a reftable stack without libgit2.
+//!
+//! Only what [`head_only`][crate::head_only] needs is implemented: looking up
+//! a single reference in the ref blocks of each table. Log, object, and index
+//! blocks are skipped, and checksums aren’t verified. See
+//! <https://git-scm.com/docs/reftable> for the format.
+
+use crate::head_only::Target;
+use crate::trace;
+use git2::{ErrorClass, ErrorCode};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The magic bytes at the start of every table.
+const MAGIC: &[u8] = b"REFT";
+
+/// Look up `name` in the reftable stack in `directory`.
+///
+/// Tables are searched from newest to oldest, so the first table that has a
+/// record for `name` wins. Returns `Ok(None)` if the reference doesn’t exist
+/// or was deleted.
+pub fn read_reference(
+    directory: &Path,
+    name: &str,
+) -> Result<Option<Target>, git2::Error> {
+    let _span = trace::span("reftable::read_reference");
+    let list =
+        fs::read_to_string(directory.join("tables.list")).map_err(|error| {
+            corrupt(format!("could not read tables.list: {error}"))
+        })?;
+    for table in list.lines().rev() {
+        let data = fs::read(directory.join(table)).map_err(|error| {
+            corrupt(format!("could not read table '{table}': {error}"))
+        })?;
+        let found = Table::parse(&data)
+            .and_then(|table| table.find(name.as_bytes()))
+            .ok_or_else(|| {
+                corrupt(format!("the table '{table}' is corrupt"))
+            })?;
+        if let Found::Record(value) = found {
+            return Ok(value);
+        }
+    }
+    Ok(None)
+}
+
+/// Make an error about a corrupt reftable.
+fn corrupt(message: String) -> git2::Error {
+    git2::Error::new(ErrorCode::GenericError, ErrorClass::Reference, message)
+}
+
+/// A single table, without its footer.
+struct Table<'a> {
+    /// The contents of the table up to the footer.
+    data: &'a [u8],
+
+    /// The offset of the first block after the header.
+    header_len: usize,
+
+    /// The size blocks are padded to, or 0 if they aren’t aligned.
+    block_size: usize,
+
+    /// The length of an object ID in bytes.
+    hash_len: usize,
+}
+
+impl<'a> Table<'a> {
+    /// Parse the header of a table, or return `None` if it’s invalid.
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.get(..4)? != MAGIC {
+            return None;
+        }
+        let block_size = read_u24(data.get(5..8)?);
+        let (header_len, footer_len, hash_len) = match data.get(4)? {
+            1 => (24, 68, 20),
+            2 => {
+                let hash_len = match data.get(24..28)? {
+                    b"sha1" => 20,
+                    b"s256" => 32,
+                    _ => return None,
+                };
+                (28, 72, hash_len)
+            }
+            _ => return None,
+        };
+        Some(Self {
+            data: data.get(..data.len().checked_sub(footer_len)?)?,
+            header_len,
+            block_size,
+            hash_len,
+        })
+    }
+
+    /// Find the record for `name`, or return `None` if the table is corrupt.
+    fn find(&self, name: &[u8]) -> Option<Found> {
+        // The first block includes the file header.
+        let mut block_start: usize = 0;
+        let mut offset = self.header_len;
+        while offset < self.data.len() {
+            if *self.data.get(offset)? != b'r' {
+                // Ref blocks come first, so there are no more refs.
+                break;
+            }
+            let block_len = read_u24(
+                self.data
+                    .get(offset.checked_add(1)?..offset.checked_add(4)?)?,
+            );
+            let block_end = block_start.checked_add(block_len)?;
+            let block = self.data.get(..block_end)?;
+            let restart_count = usize::from(u16::from_be_bytes(
+                block.get(block_end.checked_sub(2)?..)?.try_into().ok()?,
+            ));
+            let records_end = block_end
+                .checked_sub(2)?
+                .checked_sub(restart_count.checked_mul(3)?)?;
+            let records = block.get(offset.checked_add(4)?..records_end)?;
+            match self.find_in_block(records, name)? {
+                Found::Later => {}
+                found => return Some(found),
+            }
+
+            // Aligned blocks are padded with zeros.
+            block_start = block_end;
+            if self.block_size > 0 && self.data.get(block_end) == Some(&0) {
+                block_start = block_end
+                    .checked_next_multiple_of(self.block_size)
+                    .unwrap_or(usize::MAX);
+            }
+            offset = block_start;
+        }
+        Some(Found::Missing)
+    }
+
+    /// Look for `name` in the records of a single ref block.
+    fn find_in_block(&self, mut records: &[u8], name: &[u8]) -> Option<Found> {
+        let mut key: Vec<u8> = vec![];
+        while !records.is_empty() {
+            let prefix_len =
+                usize::try_from(read_varint(&mut records)?).ok()?;
+            let suffix_and_type = read_varint(&mut records)?;
+            let suffix_len = usize::try_from(suffix_and_type >> 3).ok()?;
+            key.truncate(prefix_len);
+            if key.len() != prefix_len {
+                return None;
+            }
+            key.extend_from_slice(take(&mut records, suffix_len)?);
+            read_varint(&mut records)?; // update_index delta
+
+            let value = match suffix_and_type & 7 {
+                0 => None,
+                1 => Some(Target::Direct(hex(take(
+                    &mut records,
+                    self.hash_len,
+                )?))),
+                2 => {
+                    let value = hex(take(&mut records, self.hash_len)?);
+                    take(&mut records, self.hash_len)?; // peeled
+                    Some(Target::Direct(value))
+                }
+                3 => {
+                    let len =
+                        usize::try_from(read_varint(&mut records)?).ok()?;
+                    let target = take(&mut records, len)?;
+                    Some(Target::Symbolic(
+                        String::from_utf8_lossy(target).into_owned(),
+                    ))
+                }
+                _ => return None,
+            };
+
+            match key.as_slice().cmp(name) {
+                std::cmp::Ordering::Less => {}
+                std::cmp::Ordering::Equal => return Some(Found::Record(value)),
+                std::cmp::Ordering::Greater => return Some(Found::Missing),
+            }
+        }
+        Some(Found::Later)
+    }
+}
+
+/// The result of looking for a reference in a block.
+enum Found {
+    /// The record for the reference, or `None` if it was deleted.
+    Record(Option<Target>),
+
+    /// The reference isn’t in the table.
+    Missing,
+
+    /// The reference might be in a later block.
+    Later,
+}
+
+/// Take `len` bytes from the start of `input`.
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Some(head)
+}
+
+/// Read a variable length integer as encoded by git.
+///
+/// Each byte holds 7 bits, most significant first, and the high bit is set
+/// on every byte but the last. Each continuation also adds one, so that
+/// every value has exactly one encoding.
+fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut byte = *take(input, 1)?.first()?;
+    let mut value = u64::from(byte & 0x7f);
+    while byte & 0x80 != 0 {
+        byte = *take(input, 1)?.first()?;
+        value = value
+            .checked_add(1)?
+            .checked_mul(0x80)?
+            .checked_add(u64::from(byte & 0x7f))?;
+    }
+    Some(value)
+}
+
+/// Read a big-endian 24 bit integer from 3 bytes.
+fn read_u24(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | usize::from(*byte))
+}
+
+/// Format bytes as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
@@ -0,0 +1,261 @@
+//! Checking the upstream branch on the remote itself, and fetching it.
+
+use crate::{trace, CancelToken, Error, Head, ObjectId};
+use git2::Repository;
+use git2::{CertificateCheckStatus, Cred, CredentialType, Direction};
+use git2::{ErrorClass, ErrorCode, FetchOptions, Remote, RemoteCallbacks};
+use std::time::Duration;
+
+/// How long to wait for the remote if the remote section has no budget. See
+/// [`SummarizeOptions::budgets`][crate::SummarizeOptions::budgets].
+pub const REMOTE_BUDGET: Duration = Duration::from_secs(2);
+
+/// How long to wait for a fetch if the fetch section has no budget. See
+/// [`SummarizeOptions::budgets`][crate::SummarizeOptions::budgets].
+pub const FETCH_BUDGET: Duration = Duration::from_secs(5);
+
+/// The upstream branch as it is on the remote. See [`remote_tip()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteTip {
+    /// The commit the branch points to on the remote.
+    pub id: ObjectId,
+
+    /// How many commits `HEAD` is behind [`Self::id`], or `None` if that
+    /// commit hasn’t been fetched, so the commits can’t be counted.
+    pub behind: Option<usize>,
+}
+
+/// Ask the remote where the upstream of `HEAD` (or `upstream_ref`, e.g.
+/// `"origin/main"`) is now, like `git ls-remote`.
+///
+/// `head` is the result of [`head_info()`][crate::git2_interop::head_info].
+/// This connects to the remote, so it may be slow; it should usually be run
+/// with a budget.
+///
+/// Returns `Ok(None)` if `HEAD` is detached or unborn, if the upstream isn’t a
+/// remote-tracking branch, or if the branch no longer exists on the remote.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if the remote could not be found or
+/// contacted. Only the transports that libgit2 was built with are available;
+/// HTTPS and SSH require the `network` feature.
+pub fn remote_tip(
+    repository: &Repository,
+    head: &Head,
+    upstream_ref: Option<&str>,
+) -> Result<Option<RemoteTip>, Error> {
+    let _span = trace::span("remote_tip");
+    let Ok((_, local_oid)) = &head.resolved else {
+        return Ok(None);
+    };
+    let Some((mut remote, tracking_name)) =
+        upstream_remote(repository, head, upstream_ref)?
+    else {
+        return Ok(None);
+    };
+    let Some(remote_ref) = remote
+        .refspecs()
+        .filter(|refspec| refspec.direction() == Direction::Fetch)
+        .find(|refspec| refspec.dst_matches(&tracking_name))
+        .and_then(|refspec| refspec.rtransform(&tracking_name).ok())
+        .and_then(|name| name.as_str().map(str::to_owned))
+    else {
+        return Ok(None);
+    };
+
+    let id = {
+        let _span = trace::span("ls_remote");
+        remote.connect_auth(
+            Direction::Fetch,
+            Some(callbacks(repository, None)?),
+            None,
+        )?;
+        let id = remote
+            .list()?
+            .iter()
+            .find(|remote_head| remote_head.name() == remote_ref)
+            .map(git2::RemoteHead::oid);
+        let _ = remote.disconnect();
+        id
+    };
+    let Some(id) = id else {
+        return Ok(None);
+    };
+
+    let behind = if repository.find_commit(id).is_ok() {
+        let _span = trace::span("graph_ahead_behind");
+        Some(repository.graph_ahead_behind(local_oid.oid(), id)?.1)
+    } else {
+        None
+    };
+    Ok(Some(RemoteTip {
+        id: id.into(),
+        behind,
+    }))
+}
+
+/// Fetch the remote of the upstream of `HEAD` (or of `upstream_ref`, e.g.
+/// `"origin/main"`) quietly, like `git fetch REMOTE`.
+///
+/// `head` is the result of [`head_info()`][crate::git2_interop::head_info].
+/// With the `network` feature, credentials are requested from ssh-agent and
+/// git’s credential helpers; nothing is prompted for. If `cancel` is
+/// cancelled, the fetch is abandoned the next time libgit2 calls back: when
+/// checking the certificate, asking for credentials, receiving progress
+/// messages from the remote, or transferring objects. A remote that stops
+/// answering entirely can’t be interrupted, so this should usually be run
+/// with a budget. Does nothing if `HEAD` is detached or unborn, or if the
+/// upstream isn’t a remote-tracking branch.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if the remote could not be found or fetched,
+/// or [`Error::Cancelled`] if `cancel` was cancelled. See [`remote_tip()`] for
+/// the available transports.
+pub fn fetch_upstream(
+    repository: &Repository,
+    head: &Head,
+    upstream_ref: Option<&str>,
+    cancel: Option<&CancelToken>,
+) -> Result<(), Error> {
+    let _span = trace::span("fetch_upstream");
+    cancel.map_or(Ok(()), CancelToken::check)?;
+    let Some((mut remote, _)) =
+        upstream_remote(repository, head, upstream_ref)?
+    else {
+        return Ok(());
+    };
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks(repository, cancel)?);
+    let result = remote.fetch::<&str>(&[], Some(&mut options), None);
+    if cancel.is_some_and(CancelToken::is_cancelled) {
+        return Err(Error::Cancelled);
+    }
+    Ok(result?)
+}
+
+/// Find the remote that the upstream of `HEAD` (or `upstream_ref`) is fetched
+/// from, and the full name of the remote-tracking branch.
+///
+/// Returns `Ok(None)` if `HEAD` is detached or unborn, or if the upstream isn’t
+/// a remote-tracking branch.
+fn upstream_remote<'r>(
+    repository: &'r Repository,
+    head: &Head,
+    upstream_ref: Option<&str>,
+) -> Result<Option<(Remote<'r>, String)>, Error> {
+    let Ok((local_name, _)) = &head.resolved else {
+        return Ok(None);
+    };
+    let Some(tracking_name) =
+        tracking_name(repository, local_name, upstream_ref)?
+    else {
+        return Ok(None);
+    };
+    let remote_name = match repository.branch_remote_name(&tracking_name) {
+        Ok(name) => name,
+        // No remote fetches into `tracking_name`.
+        Err(error) if error.code() == ErrorCode::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let remote_name = remote_name.as_str().ok_or_else(|| {
+        git2::Error::from_str("remote name is not valid UTF-8")
+    })?;
+    Ok(Some((repository.find_remote(remote_name)?, tracking_name)))
+}
+
+/// Make callbacks to authenticate with a remote without prompting, and to
+/// abandon a transfer if `cancel` is cancelled.
+///
+/// Cancellation is checked in every callback that libgit2 calls while
+/// connecting, negotiating, and transferring, not just during the transfer.
+///
+/// Each kind of credential is only tried once, since libgit2 asks again if
+/// authentication fails. The username defaults to `git`, as in SSH URLs like
+/// `git@github.com:user/repo.git`. Keys from ssh-agent and passwords from git’s
+/// credential helpers are only tried with the `network` feature, since HTTPS
+/// and SSH are unavailable without it.
+#[cfg_attr(
+    not(feature = "network"),
+    allow(unused_variables, clippy::unnecessary_wraps)
+)]
+fn callbacks<'a>(
+    repository: &Repository,
+    cancel: Option<&'a CancelToken>,
+) -> Result<RemoteCallbacks<'a>, git2::Error> {
+    #[cfg(feature = "network")]
+    let config = repository.config()?;
+    let is_cancelled = move || cancel.is_some_and(CancelToken::is_cancelled);
+    let mut tried = CredentialType::empty();
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.certificate_check(move |_, _| {
+        if is_cancelled() {
+            Err(git2::Error::from_str("cancelled"))
+        } else {
+            // Let libgit2 check the certificate as usual.
+            Ok(CertificateCheckStatus::CertificatePassthrough)
+        }
+    });
+    callbacks.credentials(move |url, username, allowed| {
+        if is_cancelled() {
+            return Err(git2::Error::from_str("cancelled"));
+        }
+        let untried = allowed.difference(tried);
+        let user = username.unwrap_or("git");
+        if untried.contains(CredentialType::USERNAME) {
+            tried |= CredentialType::USERNAME;
+            return Cred::username(user);
+        }
+        #[cfg(feature = "network")]
+        if untried.contains(CredentialType::SSH_KEY) {
+            tried |= CredentialType::SSH_KEY;
+            return Cred::ssh_key_from_agent(user);
+        }
+        #[cfg(feature = "network")]
+        if untried.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            tried |= CredentialType::USER_PASS_PLAINTEXT;
+            return Cred::credential_helper(&config, url, username);
+        }
+        if untried.contains(CredentialType::DEFAULT) {
+            tried |= CredentialType::DEFAULT;
+            return Cred::default();
+        }
+        Err(git2::Error::from_str("no credentials are available"))
+    });
+    callbacks.sideband_progress(move |_| !is_cancelled());
+    callbacks.transfer_progress(move |_| !is_cancelled());
+    Ok(callbacks)
+}
+
+/// Get the full name of the remote-tracking branch to check, e.g.
+/// `"refs/remotes/origin/main"`.
+///
+/// This is `upstream_ref` if it’s set, or the upstream of `local_name`
+/// otherwise. Returns `Ok(None)` if `HEAD` is detached or has no upstream.
+fn tracking_name(
+    repository: &Repository,
+    local_name: &str,
+    upstream_ref: Option<&str>,
+) -> Result<Option<String>, git2::Error> {
+    if let Some(upstream_ref) = upstream_ref {
+        let (_, reference) = repository.revparse_ext(upstream_ref)?;
+        return Ok(
+            reference.and_then(|reference| reference.name().map(str::to_owned))
+        );
+    }
+    if !local_name.starts_with("refs/heads/") {
+        // `HEAD` is detached.
+        return Ok(None);
+    }
+    match repository.branch_upstream_name(local_name) {
+        Ok(name) => Ok(name.as_str().map(str::to_owned)),
+        Err(error)
+            if error.code() == ErrorCode::NotFound
+                && error.class() == ErrorClass::Config =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
@@ -0,0 +1,66 @@
+//! The state of a repository.
+
+use git2::RepositoryState;
+
+/// Whether a repository is in the middle of an operation, e.g. a merge. See
+/// [`RepoSummary::state`][crate::RepoSummary::state].
+///
+/// This is output as `repo_state`, e.g. `repo_state=RebaseInteractive`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RepoState {
+    /// No operation is in progress.
+    #[default]
+    Clean,
+
+    /// A merge is in progress.
+    Merge,
+
+    /// A revert of a single commit is in progress.
+    Revert,
+
+    /// A revert of multiple commits is in progress.
+    RevertSequence,
+
+    /// A cherry-pick of a single commit is in progress.
+    CherryPick,
+
+    /// A cherry-pick of multiple commits is in progress.
+    CherryPickSequence,
+
+    /// A bisect is in progress.
+    Bisect,
+
+    /// A rebase is in progress.
+    Rebase,
+
+    /// An interactive rebase is in progress.
+    RebaseInteractive,
+
+    /// A rebase with the merge backend is in progress.
+    RebaseMerge,
+
+    /// `git am` is in progress.
+    ApplyMailbox,
+
+    /// Either `git am` or a rebase is in progress, but it isn’t clear which.
+    ApplyMailboxOrRebase,
+}
+
+impl From<RepositoryState> for RepoState {
+    fn from(state: RepositoryState) -> Self {
+        match state {
+            RepositoryState::Clean => Self::Clean,
+            RepositoryState::Merge => Self::Merge,
+            RepositoryState::Revert => Self::Revert,
+            RepositoryState::RevertSequence => Self::RevertSequence,
+            RepositoryState::CherryPick => Self::CherryPick,
+            RepositoryState::CherryPickSequence => Self::CherryPickSequence,
+            RepositoryState::Bisect => Self::Bisect,
+            RepositoryState::Rebase => Self::Rebase,
+            RepositoryState::RebaseInteractive => Self::RebaseInteractive,
+            RepositoryState::RebaseMerge => Self::RebaseMerge,
+            RepositoryState::ApplyMailbox => Self::ApplyMailbox,
+            RepositoryState::ApplyMailboxOrRebase => Self::ApplyMailboxOrRebase,
+        }
+    }
+}
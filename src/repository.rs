@@ -0,0 +1,1320 @@
+//! Summarizing a repository opened with libgit2.
+//!
+//! The public functions here take a [`Repository`], so they’re only public
+//! through [`crate::git2_interop`].
+
+use crate::git2_interop::{resolve_bare, status_options, with_config};
+use crate::remote::{fetch_upstream, remote_tip};
+use crate::stash::stash_info;
+use crate::submodule::superproject_info;
+use crate::{cache, fsmonitor, head_only, trace, vars};
+use crate::{display_option, write_missing_sections};
+use crate::{
+    CancelToken, ChangeCounters, ChangeKind, Condition, Error, ErrorInfo,
+    GitError, Head, Reference, RepoSummary, Section, ShellWriter,
+    SummarizeOptions, Timings, UntrackedFiles, Upstream,
+};
+use git2::ReferenceType;
+use git2::{ErrorClass, ErrorCode, Oid};
+use git2::{Repository, RepositoryState};
+use git2::{Status, StatusOptions, Statuses};
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Summarize information about a repository.
+///
+/// This takes the `Result` from one of the `Repository::open()` functions.
+///
+/// # Example
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::summarize_repository;
+/// use git_status_vars::{ShellWriter, SummarizeOptions};
+/// use git2::Repository;
+///
+/// summarize_repository(
+///     &ShellWriter::default(),
+///     Repository::open_from_env(),
+///     &SummarizeOptions::default(),
+/// );
+/// ```
+///
+/// Returns the [`Condition`] of the repository.
+///
+/// # Panics
+///
+/// This will panic if the output stream returns an error.
+#[allow(clippy::must_use_candidate)] // Most callers don’t need the condition.
+pub fn summarize_repository<W, E>(
+    out: &ShellWriter<W>,
+    opened: Result<Repository, E>,
+    options: &SummarizeOptions,
+) -> Condition
+where
+    W: std::io::Write,
+    E: Into<Error>,
+{
+    let _span = trace::span("summarize_repository");
+    let opened = opened
+        .map_err(Into::into)
+        .and_then(|repository| resolve_bare(&options.bare, repository));
+    let result = match opened {
+        // The remote, the superproject, and submodules change without the
+        // repository changing, so they can’t be cached. Ignored files are
+        // often in huge directories (e.g. `target/`), so the cache doesn’t
+        // check for changes to them.
+        Ok(repository)
+            if options.cache
+                && !options.timing
+                && !options.two_phase
+                && !options.include_ignored
+                && !options.include_submodules
+                && !options.includes(Section::Remote)
+                && !options.includes(Section::Fetch)
+                && !options.includes(Section::Superproject) =>
+        {
+            summarize_cached_repository(out, &repository, options)
+        }
+        Ok(repository) => {
+            summarize_opened_repository(out, &repository, options)
+        }
+        Err(error) if error.is_not_found() => {
+            out.write_var(vars::REPO_STATE, "NotFound");
+            for var in [
+                vars::PS1_STATE,
+                vars::REPO_WORKDIR,
+                vars::REPO_WORKDIR_RAW,
+                vars::REPO_WORKDIR_LOGICAL,
+                vars::REPO_WORKDIR_PHYSICAL,
+                vars::REPO_EMPTY,
+                vars::REPO_BARE,
+                vars::FETCH_AGE_SECONDS,
+            ] {
+                out.write_missing(var);
+            }
+            ErrorInfo::write_missing(out, vars::REPO_ERROR)
+                .and_then(|()| write_missing_sections(out, Section::ALL))
+                .and_then(|()| out.try_write_missing(vars::COLOR_HINT))
+                .and_then(|()| out.try_write_missing(vars::COLOR_ANSI))
+                .and_then(|()| out.try_write_missing(vars::GLYPHS))
+                .map(|()| Condition::NotFound)
+                .map_err(Error::from)
+        }
+        Err(error) => Err(error),
+    };
+
+    result.unwrap_or_else(|error| {
+        out.write_var(vars::REPO_STATE, "Error");
+        ErrorInfo::from(error)
+            .write_as(out, vars::REPO_ERROR)
+            .unwrap();
+        Condition::Error
+    })
+}
+
+/// Summarize a repository using the cache if possible.
+///
+/// If the cache is stale, this summarizes the repository normally and updates
+/// the cache. If the cache can’t be used, e.g. because the cache key can’t be
+/// calculated, this summarizes the repository normally. Problems reading or
+/// writing the cache are ignored.
+fn summarize_cached_repository<W: std::io::Write>(
+    out: &ShellWriter<W>,
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<Condition, Error> {
+    // Resolve the configuration first so that changing it invalidates the
+    // cache, even if it’s in the global configuration.
+    let options = &with_config(options.clone(), repository);
+    let key = {
+        let _span = trace::span("cache_key");
+        cache::Key::for_repository(repository, options, out.unsets_missing())
+    };
+    // If the key can’t be calculated, e.g. because the index is corrupt, the
+    // sections that fail report it.
+    let Ok(key) = key else {
+        return summarize_opened_repository(out, repository, options);
+    };
+    // The age changes with time rather than with the repository, so it’s
+    // replaced with a fresh value when the cache is replayed.
+    let fetch_age_seconds = fetch_age(repository).map(|age| age.as_secs());
+    if let Some((condition, summary)) = cache::read(repository, &key) {
+        if cache::replay(out, &summary, fetch_age_seconds).is_ok() {
+            return Ok(condition);
+        }
+    }
+
+    let mut buffer: Vec<u8> = vec![];
+    let (summary, timings) = summarize_with_timings(
+        &ShellWriter::new(&mut buffer, "")
+            .with_unset_missing(out.unsets_missing()),
+        repository,
+        options,
+    )?;
+    let condition = summary.condition();
+    let index_locked = summary
+        .changes
+        .is_some_and(|changes| changes.index_locked == Some(true));
+    let summary = String::from_utf8_lossy(&buffer);
+    // Don’t save an incomplete summary, or one that should be updated once the
+    // index is unlocked.
+    if timings.timed_out.is_empty()
+        && condition != Condition::Error
+        && !index_locked
+    {
+        let _ = cache::write(repository, &key, condition, &summary);
+    }
+    cache::replay(out, &summary, fetch_age_seconds)
+        .expect("summary should always be valid shell");
+    Ok(condition)
+}
+
+/// Summarize information about a successfully opened repository.
+///
+/// # Example
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::summarize_opened_repository;
+/// use git_status_vars::{ShellWriter, SummarizeOptions};
+/// use git2::Repository;
+///
+/// summarize_opened_repository(
+///     &ShellWriter::default(),
+///     &Repository::open_from_env().unwrap(),
+///     &SummarizeOptions::default(),
+/// ).unwrap();
+/// ```
+///
+/// Returns the [`Condition`] of the repository.
+///
+/// Each section is written as soon as it is complete, and then `out` is flushed
+/// with [`ShellWriter::flush()`]. If the writer only passes on flushed output,
+/// then a partial summary can be recovered if this is interrupted (e.g. by a
+/// timeout).
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there were problems getting basic
+/// repository information. libgit2 errors in individual sections, e.g. reading
+/// a corrupt index while counting changes, are output as `{section}_error`
+/// (see [`RepoSummary::errors`]) and the other sections are output as usual.
+/// If `out` returns an error, this stops and returns [`Error::Io`].
+pub fn summarize_opened_repository<W: std::io::Write>(
+    out: &ShellWriter<W>,
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<Condition, Error> {
+    let (summary, timings) = summarize_with_timings(out, repository, options)?;
+    if options.timing {
+        out.group("timing").write_vars(&timings);
+        out.flush();
+    }
+    Ok(summary.condition())
+}
+
+/// Summarize a repository without producing output, and report how long each
+/// section took.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there were problems getting repository
+/// information.
+pub fn time_sections(
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<Timings, Error> {
+    let out = ShellWriter::new(io::sink(), "");
+    summarize_with_timings(&out, repository, options)
+        .map(|(_, timings)| timings)
+}
+
+/// Summarize a repository and return the summary instead of writing it.
+///
+/// This computes the same information as [`summarize_opened_repository()`],
+/// which can be written later with [`ShellWriter::write_vars()`]. The cache is
+/// not used.
+///
+/// # Example
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::summarize;
+/// use git_status_vars::SummarizeOptions;
+/// use git2::Repository;
+///
+/// let repository = Repository::open_from_env().unwrap();
+/// let summary = summarize(&repository, &SummarizeOptions::default()).unwrap();
+/// if let Some(changes) = &summary.changes {
+///     println!("{} untracked files", changes.untracked());
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there were problems getting repository
+/// information.
+pub fn summarize(
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<RepoSummary, Error> {
+    let out = ShellWriter::new(io::sink(), "");
+    summarize_with_timings(&out, repository, options)
+        .map(|(summary, _)| summary)
+}
+
+/// Summarize a repository and report how long each section took.
+///
+/// Each section is written to `out` as soon as it’s computed. See
+/// [`summarize_opened_repository()`].
+fn summarize_with_timings<W: std::io::Write>(
+    out: &ShellWriter<W>,
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<(RepoSummary, Timings), Error> {
+    let mut timings = Timings::default();
+    options.check_cancelled()?;
+    let skip_counts = if options.includes(Section::Counts) {
+        too_large_to_count(repository, options)
+    } else {
+        Ok(false)
+    };
+    let count =
+        options.includes(Section::Counts) && matches!(skip_counts, Ok(false));
+    // Start counting changes first, since it’s usually the slowest section.
+    let pending_changes = (options.parallel && count && !repository.is_bare())
+        .then(|| spawn_section(repository, options, count_changes));
+    // Fetch before anything that depends on the remote-tracking branches.
+    let fetched = options.includes(Section::Fetch).then(|| {
+        compute_section(
+            repository,
+            options,
+            &mut timings,
+            Section::Fetch,
+            None,
+            fetch_section,
+        )
+    });
+    // This is output last, but it’s fast and might fail.
+    let stash_count = options.includes(Section::Stash).then(|| {
+        compute_section(
+            repository,
+            options,
+            &mut timings,
+            Section::Stash,
+            None,
+            |repository, _| count_stashes(repository),
+        )
+    });
+
+    options.check_cancelled()?;
+    let mut summary = RepoSummary::new(repository, options)?;
+    summary.write_repo(out)?;
+    if let Some(fetched) = fetched {
+        summary.note_result(options, Section::Fetch, fetched)?;
+        summary.write_fetch(out)?;
+    }
+    out.try_flush()?;
+
+    if options.includes(Section::Head) {
+        options.check_cancelled()?;
+        let head = compute_section(
+            repository,
+            options,
+            &mut timings,
+            Section::Head,
+            None,
+            |repository, _| Ok(head_info(repository)),
+        );
+        summary.head = summary.note_result(options, Section::Head, head)?;
+        summary.write_head(out)?;
+        out.try_flush()?;
+    }
+
+    if options.two_phase {
+        out.try_write_blank_line()?;
+        out.try_flush()?;
+    }
+
+    summarize_upstream(out, repository, options, &mut timings, &mut summary)?;
+
+    if options.includes(Section::Counts) {
+        options.check_cancelled()?;
+        if options.skip_counts_above.is_some() {
+            summary.counts_skipped = skip_counts.as_ref().ok().copied();
+        }
+        let changes = match skip_counts {
+            Ok(true) => None,
+            Ok(false) => Some(compute_section(
+                repository,
+                options,
+                &mut timings,
+                Section::Counts,
+                pending_changes,
+                count_changes,
+            )),
+            Err(error) => Some(Err(error.into())),
+        };
+        if let Some(changes) = changes {
+            summary.changes =
+                summary.note_result(options, Section::Counts, changes)?;
+        }
+        summary.write_counts(out)?;
+        out.try_flush()?;
+    }
+
+    if let Some(stash_count) = stash_count {
+        summary.stash_count =
+            summary.note_result(options, Section::Stash, stash_count)?;
+        summary.write_stash(out)?;
+        out.try_flush()?;
+    }
+
+    summarize_superproject(
+        out,
+        repository,
+        options,
+        &mut timings,
+        &mut summary,
+    )?;
+    summarize_remote(out, repository, options, &mut timings, &mut summary)?;
+
+    summary.write_color(out)?;
+    summary.write_glyphs(out)?;
+    out.try_flush()?;
+    Ok((summary, timings))
+}
+
+/// Compute and write the upstream section for [`summarize_with_timings()`]
+/// if it was included.
+fn summarize_upstream<W: std::io::Write>(
+    out: &ShellWriter<W>,
+    repository: &Repository,
+    options: &SummarizeOptions,
+    timings: &mut Timings,
+    summary: &mut RepoSummary,
+) -> Result<(), Error> {
+    if !options.includes(Section::Upstream) {
+        return Ok(());
+    }
+    options.check_cancelled()?;
+    // Share `HEAD` with the head section so they’re consistent. If it couldn’t
+    // be resolved, the head section already reported why.
+    let reported = summary.head.as_ref().is_some_and(|h| h.resolved.is_err());
+    let head = summary
+        .head
+        .clone()
+        .unwrap_or_else(|| head_info(repository));
+    let upstream = compute_section(
+        repository,
+        options,
+        timings,
+        Section::Upstream,
+        None,
+        move |repository, options| {
+            if reported {
+                return Ok(Upstream::default());
+            }
+            Ok(upstream_info(repository, &head, options))
+        },
+    );
+    summary.upstream =
+        summary.note_result(options, Section::Upstream, upstream)?;
+    summary.write_upstream(out)?;
+    out.try_flush()?;
+    Ok(())
+}
+
+/// Compute and write the superproject section for
+/// [`summarize_with_timings()`] if it was included.
+fn summarize_superproject<W: std::io::Write>(
+    out: &ShellWriter<W>,
+    repository: &Repository,
+    options: &SummarizeOptions,
+    timings: &mut Timings,
+    summary: &mut RepoSummary,
+) -> Result<(), Error> {
+    if !options.includes(Section::Superproject) {
+        return Ok(());
+    }
+    options.check_cancelled()?;
+    let superproject = compute_section(
+        repository,
+        options,
+        timings,
+        Section::Superproject,
+        None,
+        |repository, options| {
+            Ok(superproject_info(repository)?.map(|mut superproject| {
+                superproject.workdir =
+                    options.path_style.normalize(&superproject.workdir);
+                superproject
+            }))
+        },
+    );
+    summary.superproject =
+        summary.note_result(options, Section::Superproject, superproject)?;
+    summary.write_superproject(out)?;
+    out.try_flush()?;
+    Ok(())
+}
+
+/// Compute the fetch section for [`summarize_with_timings()`]. See
+/// [`fetch_upstream()`].
+fn fetch_section(
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<(), Error> {
+    fetch_upstream(
+        repository,
+        &head_info(repository),
+        options.upstream_ref.as_deref(),
+        options.cancel.as_ref(),
+    )
+}
+
+/// Compute and write the remote section for [`summarize_with_timings()`] if it
+/// was included.
+fn summarize_remote<W: std::io::Write>(
+    out: &ShellWriter<W>,
+    repository: &Repository,
+    options: &SummarizeOptions,
+    timings: &mut Timings,
+    summary: &mut RepoSummary,
+) -> Result<(), Error> {
+    if !options.includes(Section::Remote) {
+        return Ok(());
+    }
+    options.check_cancelled()?;
+    let head = summary
+        .head
+        .clone()
+        .unwrap_or_else(|| head_info(repository));
+    let remote = compute_section(
+        repository,
+        options,
+        timings,
+        Section::Remote,
+        None,
+        move |repository, options| {
+            remote_tip(repository, &head, options.upstream_ref.as_deref())
+        },
+    );
+    summary.remote = summary.note_result(options, Section::Remote, remote)?;
+    summary.write_remote(out)?;
+    out.try_flush()?;
+    Ok(())
+}
+
+/// Check if the index has more entries than
+/// [`SummarizeOptions::skip_counts_above`].
+fn too_large_to_count(
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<bool, git2::Error> {
+    match options.skip_counts_above {
+        Some(max) if !repository.is_bare() => {
+            Ok(repository.index()?.len() > max)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Compute a section and record how long it took.
+///
+/// If the section has a budget (see [`SummarizeOptions::budget()`]), it’s
+/// computed in another thread, and `None` is returned if it runs out of time. If
+/// `pending` is passed, the section is already being computed in another
+/// thread and `f` is not used.
+fn compute_section<T, F>(
+    repository: &Repository,
+    options: &SummarizeOptions,
+    timings: &mut Timings,
+    section: Section,
+    pending: Option<PendingSection<T>>,
+    f: F,
+) -> Result<Option<T>, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&Repository, &SummarizeOptions) -> Result<T, Error>
+        + Send
+        + 'static,
+{
+    let budget = options.budget(section);
+    let pending = match (pending, budget) {
+        (Some(pending), _) => pending,
+        (None, Some(budget)) => {
+            // Let the section stop itself once the budget runs out, rather
+            // than keep running after nothing is waiting for it.
+            let cancel = options.cancel.clone().unwrap_or_default();
+            let cancel = match Instant::now().checked_add(budget) {
+                Some(deadline)
+                    if cancel.deadline().map_or(true, |d| deadline < d) =>
+                {
+                    cancel.with_deadline(deadline)
+                }
+                _ => cancel,
+            };
+            let options = SummarizeOptions {
+                cancel: Some(cancel),
+                ..options.clone()
+            };
+            spawn_section(repository, &options, f)
+        }
+        (None, None) => {
+            return timings.time(section, || f(repository, options)).map(Some);
+        }
+    };
+
+    match pending.wait(budget, options.cancel.as_ref()) {
+        // The section noticed its budget ran out before we did.
+        Some(Err(Error::Cancelled)) if options.check_cancelled().is_ok() => {
+            timings.timed_out.push(section);
+            Ok(None)
+        }
+        Some(result) => {
+            let (value, duration) = result?;
+            timings.sections.push((section, duration));
+            Ok(Some(value))
+        }
+        None => {
+            timings.timed_out.push(section);
+            Ok(None)
+        }
+    }
+}
+
+/// A section being computed in another thread. See [`spawn_section()`].
+struct PendingSection<T> {
+    /// The thread computing the section.
+    handle: thread::JoinHandle<()>,
+
+    /// Receives the section and how long it took to compute.
+    receiver: mpsc::Receiver<Result<(T, Duration), Error>>,
+}
+
+impl<T> PendingSection<T> {
+    /// Wait for the section to be computed, or until `budget` runs out.
+    ///
+    /// Returns `None` if the budget ran out, or [`Error::Cancelled`] if
+    /// `cancel` was cancelled. In both cases the thread is left to finish on
+    /// its own.
+    fn wait(
+        self,
+        budget: Option<Duration>,
+        cancel: Option<&CancelToken>,
+    ) -> Option<Result<(T, Duration), Error>> {
+        let deadline =
+            budget.and_then(|budget| Instant::now().checked_add(budget));
+        loop {
+            let mut timeout = deadline.map(|deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            if let Some(cancel) = cancel {
+                if cancel.is_cancelled() {
+                    return Some(Err(Error::Cancelled));
+                }
+                // Wake up periodically to check for cancellation.
+                timeout =
+                    Some(timeout.map_or(CANCEL_POLL, |t| t.min(CANCEL_POLL)));
+            }
+            let received = match timeout {
+                Some(timeout) => self.receiver.recv_timeout(timeout),
+                None => self.receiver.recv().map_err(RecvTimeoutError::from),
+            };
+            match received {
+                Ok(result) => return Some(result),
+                Err(RecvTimeoutError::Timeout) => {
+                    if deadline
+                        .is_some_and(|deadline| Instant::now() >= deadline)
+                    {
+                        return None;
+                    }
+                }
+                // The thread always sends a result unless it panics.
+                Err(RecvTimeoutError::Disconnected) => panic::resume_unwind(
+                    self.handle
+                        .join()
+                        .expect_err("section thread should have panicked"),
+                ),
+            }
+        }
+    }
+}
+
+/// How often to check for cancellation while waiting for a section computed
+/// in another thread. See [`SummarizeOptions::cancel`].
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+/// Compute a section in a new thread with its own handle to the repository.
+///
+/// See [`SummarizeOptions::parallel`] and [`SummarizeOptions::budgets`].
+fn spawn_section<T, F>(
+    repository: &Repository,
+    options: &SummarizeOptions,
+    f: F,
+) -> PendingSection<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Repository, &SummarizeOptions) -> Result<T, Error>
+        + Send
+        + 'static,
+{
+    let git_dir = repository.path().to_path_buf();
+    let workdir = repository.workdir().map(Path::to_path_buf);
+    let options = options.clone();
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let start = Instant::now();
+        let result = Repository::open(git_dir)
+            .map_err(Error::from)
+            .and_then(|repository| {
+                if let Some(workdir) = workdir {
+                    // The working tree may have been set by `$GIT_WORK_TREE`.
+                    repository.set_workdir(&workdir, false)?;
+                }
+                f(&repository, &options)
+            })
+            .map(|value| (value, start.elapsed()));
+        // Nothing is listening if the budget ran out.
+        let _ = sender.send(result);
+    });
+    PendingSection { handle, receiver }
+}
+
+/// Trace the `HEAD` reference for a repository.
+///
+/// Problems with a reference, e.g. a corrupt reference file, a symbolic
+/// reference loop, or a commit that doesn’t exist, are recorded in the
+/// [`Reference::error`] of the reference in [`Head::trail`] where the trail
+/// stopped, and in [`Head::resolved`].
+#[allow(clippy::similar_names)]
+#[must_use]
+pub fn head_info(repository: &Repository) -> Head {
+    let _span = trace::span("head_info");
+    let mut current = "HEAD".to_owned();
+    let mut head = Head::default();
+    for _ in 0..head_only::MAX_DEPTH {
+        match repository.find_reference(&current) {
+            Ok(reference) => match reference.kind() {
+                Some(ReferenceType::Direct) => {
+                    let mut direct =
+                        Reference::from_bytes(reference.name_bytes(), "direct");
+                    head.hash = display_option(reference.target());
+                    if let Some(oid) = reference.target() {
+                        head.resolved = object_exists(repository, oid)
+                            .map(|()| (current, oid.into()))
+                            .map_err(ErrorInfo::from);
+                        if let Err(error) = &head.resolved {
+                            direct.error = error.clone();
+                        }
+                    }
+                    head.trail.push(direct);
+                    return head;
+                }
+                Some(ReferenceType::Symbolic) => {
+                    head.trail.push(Reference::from_bytes(
+                        reference.name_bytes(),
+                        "symbolic",
+                    ));
+                    let Some(target) = reference.symbolic_target() else {
+                        // libgit2 can only look up references by UTF-8 name.
+                        let error = ErrorInfo::from(git2::Error::from_str(
+                            "reference name is not valid UTF-8",
+                        ));
+                        head.trail.push(Reference {
+                            error: error.clone(),
+                            ..Reference::from_bytes(
+                                reference
+                                    .symbolic_target_bytes()
+                                    .unwrap_or_default(),
+                                "",
+                            )
+                        });
+                        head.resolved = Err(error);
+                        return head;
+                    };
+                    target.clone_into(&mut current);
+                }
+                None => {
+                    let error = ErrorInfo::from(git2::Error::from_str(
+                        &format!("reference '{current}' has an unknown type"),
+                    ));
+                    head.trail.push(Reference {
+                        error: error.clone(),
+                        ..Reference::from_bytes(
+                            reference.name_bytes(),
+                            "unknown",
+                        )
+                    });
+                    head.resolved = Err(error);
+                    return head;
+                }
+            },
+            Err(error) => {
+                // Like `Repository::head()`, report a symbolic reference to a
+                // missing branch as an unborn branch.
+                let code = if error.code() == ErrorCode::NotFound
+                    && !head.trail.is_empty()
+                {
+                    ErrorCode::UnbornBranch
+                } else {
+                    error.code()
+                };
+                head.resolved = Err(ErrorInfo::from(git2::Error::new(
+                    code,
+                    error.class(),
+                    error.message(),
+                )));
+                // An unborn branch isn’t an error; see `Head::is_unborn()`.
+                head.trail.push(if code == ErrorCode::UnbornBranch {
+                    Reference::new(current, "")
+                } else {
+                    Reference::new_with_error(current, "", error)
+                });
+                return head;
+            }
+        };
+    }
+
+    let message = format!("too many nested symbolic references at '{current}'");
+    let error = ErrorInfo::from(git2::Error::from_str(&message));
+    head.resolved = Err(error.clone());
+    head.trail
+        .push(Reference::new_with_error(current, "", error));
+    head
+}
+
+/// Check that the object `oid` exists.
+///
+/// # Errors
+///
+/// This returns a [`git2::Error`] like libgit2’s if the object doesn’t exist,
+/// or if the object database couldn’t be opened.
+fn object_exists(repository: &Repository, oid: Oid) -> Result<(), git2::Error> {
+    if repository.odb()?.exists(oid) {
+        Ok(())
+    } else {
+        Err(git2::Error::new(
+            ErrorCode::NotFound,
+            ErrorClass::Odb,
+            format!("object not found - no match for id ({oid})"),
+        ))
+    }
+}
+
+/// Compare `HEAD` to its upstream, or to [`SummarizeOptions::upstream_ref`] if
+/// it’s set.
+///
+/// `head` is the result of [`head_info()`], so that `HEAD` is only resolved
+/// once. Errors are recorded in [`Upstream::error`].
+#[must_use]
+pub fn upstream_info(
+    repository: &Repository,
+    head: &Head,
+    options: &SummarizeOptions,
+) -> Upstream {
+    let upstream_ref = options.upstream_ref.as_deref();
+    let difference = if options.cache && upstream_ref.is_none() {
+        cached_upstream_difference(repository, head)
+    } else {
+        get_upstream_difference(repository, head, upstream_ref)
+    };
+    match difference {
+        Ok(Some((ahead, behind))) => Upstream {
+            ahead: Some(ahead),
+            behind: Some(behind),
+            exact: Some(history_is_exact(repository)),
+            error: ErrorInfo::default(),
+        },
+        Ok(None) => Upstream::default(),
+        Err(error) => Upstream {
+            error: error.into(),
+            ..Upstream::default()
+        },
+    }
+}
+
+/// Check whether the commit graph is complete and unaltered, so that ahead and
+/// behind counts are exact.
+///
+/// The history is inexact if the repository is shallow, if it has grafts in
+/// `info/grafts`, or if it has any replace references in `refs/replace/`.
+fn history_is_exact(repository: &Repository) -> bool {
+    let _span = trace::span("history_is_exact");
+    if repository.is_shallow() {
+        return false;
+    }
+    if head_only::common_dir(repository.path())
+        .join("info/grafts")
+        .exists()
+    {
+        return false;
+    }
+    // If the references can’t be read, assume there are replacements.
+    repository
+        .references_glob("refs/replace/*")
+        .is_ok_and(|mut references| references.next().is_none())
+}
+
+/// Like [`get_upstream_difference()`] without an `upstream_ref`, but remember
+/// branches that have no upstream so the configuration doesn’t need to be
+/// searched for them next time. See [`cache::no_upstream()`].
+fn cached_upstream_difference(
+    repository: &Repository,
+    head: &Head,
+) -> Result<Option<(usize, usize)>, Error> {
+    let Ok((local_name, _)) = &head.resolved else {
+        return get_upstream_difference(repository, head, None);
+    };
+    if cache::no_upstream(repository, local_name).is_some() {
+        return Ok(None);
+    }
+    match upstream_difference(repository, head, None) {
+        Err(Error::Git(error)) if is_unconfigured(&error) => {
+            // This is only an optimization, so ignore errors.
+            let _ = cache::record_no_upstream(repository, local_name, &error);
+            Ok(None)
+        }
+        difference => difference,
+    }
+}
+
+/// Get the (ahead, behind) count of HEAD versus its upstream branch, or versus
+/// `upstream_ref` if it’s passed.
+///
+/// `head` is the result of [`head_info()`]. `upstream_ref` may be anything
+/// that `git rev-parse` understands, e.g. `"origin/main"`.
+///
+/// Returns `Ok(None)` if there’s simply nothing to compare to: the branch has
+/// no upstream configured, its upstream branch doesn’t exist (e.g. it was
+/// deleted from the remote), `HEAD` is detached, or the branch is unborn.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there were problems resolving the
+/// the repository head, or if there was an error finding the upstream branch
+/// or `upstream_ref`.
+pub fn get_upstream_difference(
+    repository: &Repository,
+    head: &Head,
+    upstream_ref: Option<&str>,
+) -> Result<Option<(usize, usize)>, Error> {
+    let _span = trace::span("get_upstream_difference");
+    match upstream_difference(repository, head, upstream_ref) {
+        Err(Error::Git(error)) if is_unconfigured(&error) => Ok(None),
+        difference => difference,
+    }
+}
+
+/// Check if `error` means that a branch has no upstream configured.
+fn is_unconfigured(error: &GitError) -> bool {
+    error.code() == "not_found" && error.class() == "config"
+}
+
+/// Like [`get_upstream_difference()`], but return an error if the branch has
+/// no upstream configured. See [`is_unconfigured()`].
+fn upstream_difference(
+    repository: &Repository,
+    head: &Head,
+    upstream_ref: Option<&str>,
+) -> Result<Option<(usize, usize)>, Error> {
+    let (local_name, local_oid) = match &head.resolved {
+        Ok((name, oid)) => (name, oid.oid()),
+        Err(_) if head.is_unborn() => return Ok(None),
+        Err(error) => return Err(Error::Git(GitError::from(error))),
+    };
+    let upstream_oid = if let Some(upstream_ref) = upstream_ref {
+        Some(
+            repository
+                .revparse_single(upstream_ref)?
+                .peel_to_commit()?
+                .id(),
+        )
+    } else if !local_name.starts_with("refs/heads/") {
+        // `HEAD` is detached.
+        return Ok(None);
+    } else {
+        match upstream_target(repository, local_name) {
+            Err(error)
+                if error.code() == ErrorCode::NotFound
+                    && error.class() == ErrorClass::Reference =>
+            {
+                // The upstream branch is gone.
+                None
+            }
+            result => result?,
+        }
+    };
+    upstream_oid
+        .map(|upstream_oid| {
+            let _span = trace::span("graph_ahead_behind");
+            repository.graph_ahead_behind(local_oid, upstream_oid)
+        })
+        .transpose()
+        .map_err(Error::from)
+}
+
+/// Get the commit that the upstream of the branch `local_name` (e.g.
+/// `"refs/heads/main"`) points to.
+///
+/// Returns `Ok(None)` if the upstream reference is symbolic.
+fn upstream_target(
+    repository: &Repository,
+    local_name: &str,
+) -> Result<Option<Oid>, git2::Error> {
+    // This is what `Branch::upstream()` does.
+    let upstream_name = repository.branch_upstream_name(local_name)?;
+    let upstream_name = upstream_name.as_str().ok_or_else(|| {
+        git2::Error::from_str("upstream name is not valid UTF-8")
+    })?;
+    Ok(repository.find_reference(upstream_name)?.target())
+}
+
+/// Get the (ahead, behind) count of a branch versus its upstream branch.
+///
+/// `branch_name` may be a short name like `"main"` or a full reference name
+/// like `"refs/heads/main"`. This is like [`get_upstream_difference()`], but
+/// for any branch rather than `HEAD`.
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::upstream_difference_for;
+/// use git2::Repository;
+///
+/// let repository = Repository::open_from_env().unwrap();
+/// if let Ok(Some((ahead, behind))) =
+///     upstream_difference_for(&repository, "main")
+/// {
+///     println!("main: +{ahead} -{behind}");
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This will return an [`Error`] if the branch doesn’t exist, or if there was
+/// an error finding its upstream branch, e.g. because none is configured.
+pub fn upstream_difference_for(
+    repository: &Repository,
+    branch_name: &str,
+) -> Result<Option<(usize, usize)>, Error> {
+    let _span = trace::span("upstream_difference_for");
+    let local_name = if branch_name.starts_with("refs/") {
+        branch_name.to_owned()
+    } else {
+        format!("refs/heads/{branch_name}")
+    };
+    let local_oid = repository
+        .find_reference(&local_name)?
+        .peel_to_commit()?
+        .id();
+    upstream_target(repository, &local_name)?
+        .map(|upstream_oid| {
+            let _span = trace::span("graph_ahead_behind");
+            repository.graph_ahead_behind(local_oid, upstream_oid)
+        })
+        .transpose()
+        .map_err(Error::from)
+}
+
+/// Get the (ahead, behind) count of `local_ref` versus `other_ref`.
+///
+/// Both may be anything that `git rev-parse` understands, e.g. `"HEAD"`,
+/// `"feature"`, or `"origin/main"`. `ahead` is the number of commits reachable
+/// from `local_ref` but not `other_ref`, and `behind` is the reverse.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if either reference could not be resolved to
+/// a commit.
+pub fn ahead_behind(
+    repository: &Repository,
+    local_ref: &str,
+    other_ref: &str,
+) -> Result<(usize, usize), Error> {
+    let _span = trace::span("ahead_behind");
+    let local = repository.revparse_single(local_ref)?.peel_to_commit()?;
+    let other = repository.revparse_single(other_ref)?.peel_to_commit()?;
+    let _span = trace::span("graph_ahead_behind");
+    Ok(repository.graph_ahead_behind(local.id(), other.id())?)
+}
+
+/// How long ago the repository was last fetched, based on the modification time
+/// of `FETCH_HEAD`.
+///
+/// Returns `None` if `FETCH_HEAD` doesn’t exist, i.e. the repository has never
+/// been fetched. If its modification time is in the future, the age is zero.
+#[must_use]
+pub fn fetch_age(repository: &Repository) -> Option<Duration> {
+    let modified = fs::metadata(repository.path().join("FETCH_HEAD"))
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    Some(modified.elapsed().unwrap_or_default())
+}
+
+/// Describe the state of a repository the way `__git_ps1` from git-prompt.sh
+/// does, without the leading `|`, e.g. `MERGING` or `REBASE 2/5`.
+///
+/// The `ps1_state` variable is this with a leading `|`, e.g. `|MERGING`, just
+/// as `__git_ps1` shows it, or empty if the repository is clean.
+///
+/// The progress of a rebase or `git am` is read from the files git writes in
+/// the repository directory. Returns `None` if no operation is in progress.
+#[must_use]
+pub fn ps1_state(repository: &Repository) -> Option<String> {
+    let dir = repository.path();
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|contents| contents.trim().to_owned())
+            .filter(|contents| !contents.is_empty())
+    };
+    let progress = |subdir: &str, step: &str, total: &str| {
+        let step = read(&dir.join(subdir).join(step))?;
+        let total = read(&dir.join(subdir).join(total))?;
+        Some(format!("{step}/{total}"))
+    };
+
+    let (name, progress) = match repository.state() {
+        RepositoryState::Clean => return None,
+        RepositoryState::Merge => ("MERGING", None),
+        RepositoryState::Revert | RepositoryState::RevertSequence => {
+            ("REVERTING", None)
+        }
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+            ("CHERRY-PICKING", None)
+        }
+        RepositoryState::Bisect => ("BISECTING", None),
+        RepositoryState::RebaseInteractive | RepositoryState::RebaseMerge => {
+            ("REBASE", progress("rebase-merge", "msgnum", "end"))
+        }
+        RepositoryState::Rebase => {
+            ("REBASE", progress("rebase-apply", "next", "last"))
+        }
+        RepositoryState::ApplyMailbox => {
+            ("AM", progress("rebase-apply", "next", "last"))
+        }
+        RepositoryState::ApplyMailboxOrRebase => {
+            ("AM/REBASE", progress("rebase-apply", "next", "last"))
+        }
+    };
+    Some(match progress {
+        Some(progress) => format!("{name} {progress}"),
+        None => name.to_owned(),
+    })
+}
+
+/// Count changes in the working tree and index (staged area) of a repository.
+///
+/// Changes in submodules are only counted if
+/// [`SummarizeOptions::include_submodules`] is set, and ignored files are only
+/// counted if [`SummarizeOptions::include_ignored`] is set. Untracked files are
+/// looked for according to [`SummarizeOptions::untracked_files`], or the
+/// repository’s `status.showUntrackedFiles` configuration if it isn’t set.
+///
+/// If [`SummarizeOptions::update_index`] is set, stat information for unchanged
+/// files is refreshed in the index. Note that libgit2 preserves the untracked
+/// cache and fsmonitor index extensions, but doesn’t use them.
+///
+/// If [`SummarizeOptions::max_entries`] is set, at most that many status
+/// entries are counted. Note that libgit2 still collects all of the status
+/// entries first.
+///
+/// If [`SummarizeOptions::count_limit`] is set, each count stops at one more
+/// than the limit so that it can be output as e.g. `100+`, and counting stops
+/// once every count has reached that. Note that libgit2 still collects all of
+/// the status entries first.
+///
+/// If [`SummarizeOptions::fsmonitor`] is set and `core.fsmonitor` is a hook,
+/// the hook is asked which files changed since the last time, and the status
+/// of every changed file is saved in the git directory. When only tracked
+/// files changed, just those files are checked. Otherwise, or if the hook
+/// fails, all files are checked as usual.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there was an error getting status
+/// information from the repository.
+pub fn count_changes(
+    repository: &Repository,
+    options: &SummarizeOptions,
+) -> Result<ChangeCounters, Error> {
+    if repository.is_bare() {
+        // Can't run status on bare repo.
+        return Ok(ChangeCounters::default());
+    }
+
+    let _span = trace::span("count_changes");
+    let options = &with_config(options.clone(), repository);
+    let mut status_options = status_options(options);
+
+    if options.fsmonitor {
+        if let Some((entries, index_locked)) =
+            fsmonitor::statuses(repository, options, &mut status_options)?
+        {
+            let statuses = entries
+                .iter()
+                .map(|(path, status)| (*status, path.ends_with('/')));
+            let mut changes = tally(statuses, entries.len(), options);
+            changes.index_locked = options.update_index.then_some(index_locked);
+            return Ok(changes);
+        }
+    }
+
+    count_changes_with(repository, options, &mut status_options)
+}
+
+/// Count changes with caller-provided [`StatusOptions`].
+///
+/// This is like [`count_changes()`], but `status_options` is used as is instead
+/// of being built from `options`, so that the caller can control things like
+/// pathspecs and recursion into untracked directories. Start from
+/// [`status_options()`] to keep the usual behavior:
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::{count_changes_with, status_options};
+/// use git_status_vars::SummarizeOptions;
+/// use git2::Repository;
+///
+/// let repository = Repository::open_from_env().unwrap();
+/// let options = SummarizeOptions::default();
+/// let mut statuses = status_options(&options);
+/// statuses.pathspec("src").recurse_untracked_dirs(true);
+/// let changes =
+///     count_changes_with(&repository, &options, &mut statuses).unwrap();
+/// ```
+///
+/// `options` is still used for [`SummarizeOptions::max_entries`] and
+/// [`SummarizeOptions::count_limit`], and `ignored_count` is only reported if
+/// [`SummarizeOptions::include_ignored`] is set. The `core.fsmonitor` hook is
+/// not used.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there was an error getting status
+/// information from the repository.
+pub fn count_changes_with(
+    repository: &Repository,
+    options: &SummarizeOptions,
+    status_options: &mut StatusOptions,
+) -> Result<ChangeCounters, Error> {
+    if repository.is_bare() {
+        // Can't run status on bare repo.
+        return Ok(ChangeCounters::default());
+    }
+
+    let (statuses, index_locked) =
+        statuses_retrying_lock(repository, options, status_options)?;
+    let mut changes = tally(
+        statuses
+            .iter()
+            .map(|entry| (entry.status(), entry.path_bytes().ends_with(b"/"))),
+        statuses.len(),
+        options,
+    );
+    changes.index_locked = options.update_index.then_some(index_locked);
+    Ok(changes)
+}
+
+/// How long to keep trying to update the index while another process has it
+/// locked, e.g. a background `git fetch` or an editor.
+const LOCK_RETRY_TIME: Duration = Duration::from_millis(100);
+
+/// How long to wait between attempts to update a locked index.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Get the statuses of changed files, retrying briefly if
+/// [`SummarizeOptions::update_index`] is set and another process has the
+/// index locked.
+///
+/// Retrying stops at the deadline of [`SummarizeOptions::cancel`], if that’s
+/// sooner. If the index is still locked, the statuses are gotten without
+/// updating the index, and `true` is returned with them.
+pub fn statuses_retrying_lock<'r>(
+    repository: &'r Repository,
+    options: &SummarizeOptions,
+    status_options: &mut StatusOptions,
+) -> Result<(Statuses<'r>, bool), git2::Error> {
+    let _span = trace::span("statuses");
+    let give_up = Instant::now().checked_add(LOCK_RETRY_TIME);
+    let give_up = match options.cancel.as_ref().and_then(CancelToken::deadline)
+    {
+        Some(deadline) => give_up.map(|give_up| give_up.min(deadline)),
+        None => give_up,
+    };
+    loop {
+        match repository.statuses(Some(status_options)) {
+            Err(error) if error.code() == ErrorCode::Locked => {}
+            result => return result.map(|statuses| (statuses, false)),
+        }
+        let retry_at = Instant::now().checked_add(LOCK_RETRY_INTERVAL);
+        if retry_at >= give_up || options.check_cancelled().is_err() {
+            break;
+        }
+        thread::sleep(LOCK_RETRY_INTERVAL);
+    }
+
+    status_options.update_index(false);
+    repository
+        .statuses(Some(status_options))
+        .map(|statuses| (statuses, true))
+}
+
+/// Count the statuses of changed files in each bucket.
+///
+/// `statuses` has the status of each entry and whether it’s a directory, i.e.
+/// its path ends with `/`. `len` is the total number of statuses, which is used
+/// to tell if the counts were truncated by [`SummarizeOptions::max_entries`].
+fn tally<I: Iterator<Item = (Status, bool)>>(
+    statuses: I,
+    len: usize,
+    options: &SummarizeOptions,
+) -> ChangeCounters {
+    let _span = trace::span("tally");
+    let mut counts = ChangeCounters::default().counts;
+    if options.include_ignored {
+        counts.insert(ChangeKind::Ignored, 0);
+    }
+
+    let limit = options.max_entries.unwrap_or(usize::MAX);
+    // Counts above the count limit are all output the same way.
+    let cap = options
+        .count_limit
+        .map_or(usize::MAX, |n| n.saturating_add(1));
+    let mut untracked_dirs = false;
+    for (status, is_dir) in statuses.take(limit) {
+        untracked_dirs |= is_dir && status.contains(Status::WT_NEW);
+        for (kind, count) in &mut counts {
+            if status.intersects(kind.statuses()) && *count < cap {
+                *count = count.saturating_add(1);
+            }
+        }
+        // The rest of the entries can’t change how the counts are output.
+        if counts.values().all(|count| *count >= cap) {
+            break;
+        }
+    }
+
+    ChangeCounters {
+        counts,
+        truncated: options.max_entries.map(|max| len > max),
+        untracked_truncated: (options.untracked() != UntrackedFiles::No)
+            .then_some(untracked_dirs),
+        limit: options.count_limit,
+        index_locked: None,
+    }
+}
+
+/// Count the stashes in a repository. See [`stash_info()`] for details about
+/// each stash.
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there was an error reading the stash
+/// reflog.
+pub fn count_stashes(repository: &Repository) -> Result<usize, Error> {
+    let _span = trace::span("count_stashes");
+    stash_info(repository).map(|stashes| stashes.count())
+}
@@ -0,0 +1,708 @@
+//! The variables in the output, and how changes to them are versioned.
+//!
+//! The command line tool outputs `schema_version=N` as its first variable.
+//! [`SCHEMA_VERSION`] is incremented in each release that adds, removes, or
+//! renames a variable, or changes the values it can have. Scripts that `eval`
+//! the output can check it to detect changes they don’t know how to handle.
+//!
+//! [`VARS`] lists every variable for a single repository. When summarizing
+//! more than one repository, each of these is prefixed with the name of the
+//! repository’s group (e.g. `repo1_head_hash`), and `{group}_path`,
+//! `repo_count`, and `repo_error_count` are added.
+
+use crate::{vars, Section, ERROR_CLASSES, ERROR_CODES};
+use std::fmt::Write;
+
+/// The version of the set of variables in [`VARS`].
+///
+/// Changing [`VARS`] requires incrementing this once in the next release, not
+/// once per change.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The kind of value a variable holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VarKind {
+    /// Any string.
+    Str,
+
+    /// An integer, or `''` if it could not be determined.
+    Int,
+
+    /// `true` or `false`.
+    Bool,
+
+    /// A count of files. If counting stopped early (see `--max-entries` and
+    /// `--count-limit`), this is the count so far followed by `+`, e.g. `100+`.
+    Count,
+
+    /// One of a fixed set of strings.
+    Enum(&'static [&'static str]),
+}
+
+/// The definition of an output variable. See [`VARS`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VarDef {
+    /// The name of the variable. `{n}` stands for a number starting from 1.
+    pub name: &'static str,
+
+    /// The kind of value the variable holds.
+    pub kind: VarKind,
+
+    /// The section that outputs the variable, if the variable is only output
+    /// when that section is included.
+    pub section: Option<Section>,
+
+    /// A short description of the variable.
+    pub description: &'static str,
+}
+
+impl VarDef {
+    /// Define a variable that is not part of a section.
+    const fn new(
+        name: &'static str,
+        kind: VarKind,
+        description: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            kind,
+            section: None,
+            description,
+        }
+    }
+
+    /// Define a variable that is part of `section`.
+    const fn in_section(
+        section: Section,
+        name: &'static str,
+        kind: VarKind,
+        description: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            kind,
+            section: Some(section),
+            description,
+        }
+    }
+
+    /// Check if `name` is this variable, replacing `{n}` with a number.
+    #[must_use]
+    pub fn matches(&self, name: &str) -> bool {
+        match self.name.split_once("{n}") {
+            None => self.name == name,
+            Some((before, after)) => name
+                .strip_prefix(before)
+                .and_then(|rest| rest.strip_suffix(after))
+                .is_some_and(|number| {
+                    !number.is_empty()
+                        && number.bytes().all(|b| b.is_ascii_digit())
+                }),
+        }
+    }
+}
+
+/// The possible values of `repo_state`.
+const REPO_STATES: &[&str] = &[
+    "Clean",
+    "Merge",
+    "Revert",
+    "RevertSequence",
+    "CherryPick",
+    "CherryPickSequence",
+    "Bisect",
+    "Rebase",
+    "RebaseInteractive",
+    "RebaseMerge",
+    "ApplyMailbox",
+    "ApplyMailboxOrRebase",
+    "NotFound",
+    "Error",
+    "Unknown",
+];
+
+/// Every variable output for a repository, in output order.
+///
+/// Variables are only listed once even though `--timeout` and `--budget` may
+/// cause some of them to be output before others.
+pub const VARS: &[VarDef] = {
+    use Section::{Counts, Fetch, Head, Remote, Stash, Superproject, Upstream};
+    use VarKind::{Bool, Count, Enum, Int, Str};
+    &[
+        VarDef::new(
+            vars::SCHEMA_VERSION,
+            Int,
+            "The version of the set of variables.",
+        ),
+        VarDef::new(
+            vars::REPO_STATE,
+            Enum(REPO_STATES),
+            "The state of the repository, e.g. `Merge` when merging.",
+        ),
+        VarDef::new(
+            vars::PS1_STATE,
+            Str,
+            "The state as `__git_ps1` shows it, e.g. `|REBASE 2/5`, or empty \
+             if the repository is clean.",
+        ),
+        VarDef::new(
+            vars::REPO_WORKDIR,
+            Str,
+            "The working tree, if there is one.",
+        ),
+        VarDef::new(
+            vars::REPO_WORKDIR_RAW,
+            Str,
+            "The working tree with `%XX` escapes, if it isn’t valid UTF-8.",
+        ),
+        VarDef::new(
+            vars::REPO_WORKDIR_LOGICAL,
+            Str,
+            "The working tree without resolving symlinks (`--both-workdirs`).",
+        ),
+        VarDef::new(
+            vars::REPO_WORKDIR_PHYSICAL,
+            Str,
+            "The working tree with symlinks resolved (`--both-workdirs`).",
+        ),
+        VarDef::new(vars::REPO_EMPTY, Bool, "Whether the repository is empty."),
+        VarDef::new(vars::REPO_BARE, Bool, "Whether the repository is bare."),
+        VarDef::new(
+            vars::FETCH_AGE_SECONDS,
+            Int,
+            "How many seconds ago the repository was last fetched, based on \
+             the modification time of `FETCH_HEAD`. Empty if it has never \
+             been fetched.",
+        ),
+        VarDef::new(
+            vars::REPO_ERROR,
+            Str,
+            "The error that prevented summarizing the repository.",
+        ),
+        VarDef::new(
+            vars::REPO_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `repo_error`, e.g. `not_found`.",
+        ),
+        VarDef::new(
+            vars::REPO_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `repo_error` happened, e.g. `reference`.",
+        ),
+        VarDef::in_section(
+            Fetch,
+            vars::FETCH_ERROR,
+            Str,
+            "The error fetching the remote of the upstream, if any \
+             (`--fetch`).",
+        ),
+        VarDef::in_section(
+            Fetch,
+            vars::FETCH_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `fetch_error`, e.g. `auth`.",
+        ),
+        VarDef::in_section(
+            Fetch,
+            vars::FETCH_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `fetch_error` happened, e.g. `net`.",
+        ),
+        VarDef::new(
+            vars::FETCH_TIMEOUT,
+            Bool,
+            "Whether the fetch section ran out of time (`--budget`).",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_LENGTH,
+            Int,
+            "The number of references from `HEAD` to a commit.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_NAME,
+            Str,
+            "The full name of a reference, e.g. `refs/heads/main`.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_NAME_RAW,
+            Str,
+            "The full name with `%XX` escapes, if it isn’t valid UTF-8.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_SHORT,
+            Str,
+            "The short name of a reference, e.g. `main`.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_KIND,
+            Enum(&["direct", "symbolic", ""]),
+            "The kind of a reference, or `''` if it could not be resolved.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_ERROR,
+            Str,
+            "The error resolving a reference, or `''`.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `head_ref{n}_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_REF_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `head_ref{n}_error` happened, e.g. \
+             `reference`.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_HASH,
+            Str,
+            "The commit `HEAD` points to, or `''` if there isn’t one.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_UNBORN,
+            Bool,
+            "Whether `HEAD` points to a branch with no commits yet, e.g. in a \
+             new repository. The branch is still in `head_ref{n}_name`.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_ERROR,
+            Str,
+            "The error that prevented computing the head section, if any.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `head_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Head,
+            vars::HEAD_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `head_error` happened, e.g. `reference`.",
+        ),
+        VarDef::new(
+            vars::HEAD_TIMEOUT,
+            Bool,
+            "Whether the head section ran out of time (`--budget`).",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::HEAD_AHEAD,
+            Int,
+            "How many commits `HEAD` is ahead of its upstream.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::HEAD_BEHIND,
+            Int,
+            "How many commits `HEAD` is behind its upstream.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::HEAD_AHEAD_EXACT,
+            Bool,
+            "Whether `head_ahead` and `head_behind` are exact. This is `false` \
+             if the repository is shallow or has grafts or replace \
+             references.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::HEAD_UPSTREAM_ERROR,
+            Str,
+            "The error comparing `HEAD` with its upstream, or `''` if there \
+             is none or there is no upstream.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::HEAD_UPSTREAM_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `head_upstream_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::HEAD_UPSTREAM_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `head_upstream_error` happened, e.g. \
+             `reference`.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::UPSTREAM_ERROR,
+            Str,
+            "The error that prevented computing the upstream section, if any.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::UPSTREAM_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `upstream_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Upstream,
+            vars::UPSTREAM_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `upstream_error` happened, e.g. `reference`.",
+        ),
+        VarDef::new(
+            vars::UPSTREAM_TIMEOUT,
+            Bool,
+            "Whether the upstream section ran out of time (`--budget`).",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::UNTRACKED_COUNT,
+            Count,
+            "The number of untracked files.",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::UNSTAGED_COUNT,
+            Count,
+            "The number of files with unstaged changes.",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::STAGED_COUNT,
+            Count,
+            "The number of files with staged changes.",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::CONFLICTED_COUNT,
+            Count,
+            "The number of conflicted files.",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::IGNORED_COUNT,
+            Count,
+            "The number of ignored files (`--include-ignored`).",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::COUNTS_TRUNCATED,
+            Bool,
+            "Whether counting stopped early (`--max-entries`).",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::UNTRACKED_TRUNCATED,
+            Bool,
+            "Whether untracked directories were counted as one file each \
+             rather than by the files in them (see `--untracked-files`).",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::REPO_LOCKED,
+            Bool,
+            "Whether another process had the index locked, so it wasn’t \
+             updated (`--update-index`).",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::COUNTS_ERROR,
+            Str,
+            "The error that prevented counting changes, if any.",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::COUNTS_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `counts_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Counts,
+            vars::COUNTS_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `counts_error` happened, e.g. `reference`.",
+        ),
+        VarDef::new(
+            vars::COUNTS_TIMEOUT,
+            Bool,
+            "Whether the counts section ran out of time (`--budget`).",
+        ),
+        VarDef::new(
+            vars::COUNTS_SKIPPED,
+            Bool,
+            "Whether counting was skipped (`--skip-counts-above`).",
+        ),
+        VarDef::in_section(
+            Stash,
+            vars::STASH_COUNT,
+            Int,
+            "The number of stashes.",
+        ),
+        VarDef::in_section(
+            Stash,
+            vars::STASH_ERROR,
+            Str,
+            "The error that prevented counting stashes, if any.",
+        ),
+        VarDef::in_section(
+            Stash,
+            vars::STASH_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `stash_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Stash,
+            vars::STASH_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `stash_error` happened, e.g. `reference`.",
+        ),
+        VarDef::new(
+            vars::STASH_TIMEOUT,
+            Bool,
+            "Whether the stash section ran out of time (`--budget`).",
+        ),
+        VarDef::in_section(
+            Superproject,
+            vars::SUPERPROJECT_WORKDIR,
+            Str,
+            "The working tree of the superproject, or `''` if this isn’t a \
+             submodule (`--superproject`).",
+        ),
+        VarDef::in_section(
+            Superproject,
+            vars::SUPERPROJECT_PATH,
+            Str,
+            "The path of this submodule within the superproject.",
+        ),
+        VarDef::in_section(
+            Superproject,
+            vars::SUPERPROJECT_DRIFTED,
+            Bool,
+            "Whether a different commit is checked out or staged than the one \
+             recorded in the superproject’s `HEAD`.",
+        ),
+        VarDef::in_section(
+            Superproject,
+            vars::SUPERPROJECT_ERROR,
+            Str,
+            "The error that prevented finding the superproject, if any.",
+        ),
+        VarDef::in_section(
+            Superproject,
+            vars::SUPERPROJECT_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `superproject_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Superproject,
+            vars::SUPERPROJECT_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `superproject_error` happened, e.g. \
+             `submodule`.",
+        ),
+        VarDef::new(
+            vars::SUPERPROJECT_TIMEOUT,
+            Bool,
+            "Whether the superproject section ran out of time (`--budget`).",
+        ),
+        VarDef::in_section(
+            Remote,
+            vars::REMOTE_TIP_HASH,
+            Str,
+            "The commit the upstream branch points to on the remote, or `''` \
+             if there’s no upstream on a remote (`--online`).",
+        ),
+        VarDef::in_section(
+            Remote,
+            vars::HEAD_BEHIND_REMOTE_TIP,
+            Int,
+            "How many commits `HEAD` is behind `remote_tip_hash`. Empty if \
+             that commit hasn’t been fetched.",
+        ),
+        VarDef::in_section(
+            Remote,
+            vars::REMOTE_ERROR,
+            Str,
+            "The error checking the upstream branch on the remote, if any.",
+        ),
+        VarDef::in_section(
+            Remote,
+            vars::REMOTE_ERROR_CODE,
+            Enum(ERROR_CODES),
+            "The kind of error in `remote_error`, e.g. `not_found`.",
+        ),
+        VarDef::in_section(
+            Remote,
+            vars::REMOTE_ERROR_CLASS,
+            Enum(ERROR_CLASSES),
+            "Where the error in `remote_error` happened, e.g. `net`.",
+        ),
+        VarDef::new(
+            vars::REMOTE_TIMEOUT,
+            Bool,
+            "Whether the remote section ran out of time (`--budget`, 2 \
+             seconds by default).",
+        ),
+        VarDef::new(
+            vars::COLOR_HINT,
+            Enum(&["green", "yellow", "red"]),
+            "The color to show the repository in: `green` if it’s clean, \
+             `yellow` if it’s dirty, and `red` if it has conflicts, an \
+             operation is in progress, or there was an error.",
+        ),
+        VarDef::new(
+            vars::COLOR_ANSI,
+            Str,
+            "The ANSI escape sequence that sets the foreground to \
+             `color_hint` (`--color-ansi`).",
+        ),
+        VarDef::new(
+            vars::GLYPHS,
+            Str,
+            "A compact summary of the counts, e.g. `●2 ✚1 …3 ⚑1 ↑2` \
+             (`--glyphs`).",
+        ),
+        VarDef::new(
+            vars::TIMING_FETCH_US,
+            Int,
+            "Microseconds spent on the fetch section (`--timing`).",
+        ),
+        VarDef::new(
+            vars::TIMING_HEAD_US,
+            Int,
+            "Microseconds spent on the head section (`--timing`).",
+        ),
+        VarDef::new(
+            vars::TIMING_UPSTREAM_US,
+            Int,
+            "Microseconds spent on the upstream section (`--timing`).",
+        ),
+        VarDef::new(
+            vars::TIMING_COUNTS_US,
+            Int,
+            "Microseconds spent on the counts section (`--timing`).",
+        ),
+        VarDef::new(
+            vars::TIMING_STASH_US,
+            Int,
+            "Microseconds spent on the stash section (`--timing`).",
+        ),
+        VarDef::new(
+            vars::TIMING_SUPERPROJECT_US,
+            Int,
+            "Microseconds spent on the superproject section (`--timing`).",
+        ),
+        VarDef::new(
+            vars::TIMING_REMOTE_US,
+            Int,
+            "Microseconds spent on the remote section (`--timing`).",
+        ),
+    ]
+};
+
+/// Find the definition of the variable `name`.
+///
+/// ```rust
+/// use git_status_vars::schema::{find_var, VarKind};
+///
+/// assert_eq!(find_var("head_ref2_short").unwrap().kind, VarKind::Str);
+/// assert!(find_var("head_refs_short").is_none());
+/// ```
+#[must_use]
+pub fn find_var(name: &str) -> Option<&'static VarDef> {
+    VARS.iter().find(|var| var.matches(name))
+}
+
+/// Generate a [JSON Schema](https://json-schema.org) describing the variables.
+///
+/// The schema describes the variables for a repository as a JSON object, e.g.
+/// the output of [`RepoSummary::iter_vars()`][crate::RepoSummary::iter_vars]
+/// after it has been converted to JSON.
+///
+/// This is generated from [`VARS`]. Variables with `{n}` in their names are
+/// described with `patternProperties`. Since shell output can’t distinguish an
+/// empty string from a missing value, strings and integers may also be `null`.
+///
+/// ```rust
+/// use git_status_vars::schema::json_schema;
+///
+/// assert!(json_schema().contains(r#""repo_state": {"#));
+/// ```
+#[must_use]
+pub fn json_schema() -> String {
+    let (patterns, names): (Vec<&VarDef>, Vec<_>) =
+        VARS.iter().partition(|var| var.name.contains("{n}"));
+    let properties = names
+        .iter()
+        .map(|var| {
+            format!("    {}: {}", json_string(var.name), var_schema(var))
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    let pattern_properties = patterns
+        .iter()
+        .map(|var| {
+            let pattern = format!("^{}$", var.name.replace("{n}", "[0-9]+"));
+            format!("    {}: {}", json_string(&pattern), var_schema(var))
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!(
+        "{{\n  \
+        \"$schema\": \"https://json-schema.org/draft/2020-12/schema\",\n  \
+        \"title\": \"git-status-vars schema version {SCHEMA_VERSION}\",\n  \
+        \"type\": \"object\",\n  \
+        \"properties\": {{\n{properties}\n  }},\n  \
+        \"patternProperties\": {{\n{pattern_properties}\n  }},\n  \
+        \"required\": [\"repo_state\"],\n  \
+        \"additionalProperties\": false\n\
+        }}\n"
+    )
+}
+
+/// Generate the JSON Schema for a single variable.
+fn var_schema(var: &VarDef) -> String {
+    let kind = match var.kind {
+        VarKind::Str => r#""type": ["string", "null"]"#.to_owned(),
+        VarKind::Int => r#""type": ["integer", "null"]"#.to_owned(),
+        VarKind::Bool => r#""type": "boolean""#.to_owned(),
+        VarKind::Count => {
+            r#""type": ["integer", "string"], "pattern": "^[0-9]+\\+$""#
+                .to_owned()
+        }
+        VarKind::Enum(values) => {
+            let values: Vec<_> =
+                values.iter().map(|v| json_string(v)).collect();
+            format!(r#""enum": [{}]"#, values.join(", "))
+        }
+    };
+    format!(
+        "{{{kind}, \"description\": {}}}",
+        json_string(var.description)
+    )
+}
+
+/// Quote a string for JSON.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len().saturating_add(2));
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,269 @@
+//! [`Serialize`] implementations, enabled with the `serde` feature.
+//!
+//! Fields are serialized with the same names as the struct fields. Enums like
+//! [`Section`] are serialized as the strings used in the shell output, and
+//! [`Head::resolved`] is left out since it duplicates the trail. [`Value`] is
+//! serialized as the underlying string, number, boolean, null, or sequence.
+
+use crate::{
+    BothWorkdirs, ChangeCounters, ChangeKind, Condition, ErrorInfo, Head,
+    Reference, RepoSummary,
+};
+use crate::{RemoteTip, SuperprojectSummary, Upstream};
+use crate::{Section, StashEntry, StashInfo, SubmoduleSummary};
+use crate::{Value, WorktreeSummary};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+impl Serialize for Section {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Str(value) => serializer.serialize_str(value),
+            Self::Int(value) => serializer.serialize_i64(*value),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Null => serializer.serialize_none(),
+            Self::List(values) => serializer.collect_seq(values),
+        }
+    }
+}
+
+impl Serialize for ErrorInfo {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ErrorInfo", 3)?;
+        state.serialize_field("message", &self.message)?;
+        state.serialize_field("code", self.code)?;
+        state.serialize_field("class", self.class)?;
+        state.end()
+    }
+}
+
+impl Serialize for BothWorkdirs {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BothWorkdirs", 2)?;
+        state.serialize_field("logical", &self.logical)?;
+        state.serialize_field("physical", &self.physical)?;
+        state.end()
+    }
+}
+
+impl Serialize for Reference {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Reference", 4)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("raw_name", &self.raw_name)?;
+        state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("error", &self.error)?;
+        state.end()
+    }
+}
+
+impl Serialize for Head {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Head", 3)?;
+        state.serialize_field("trail", &self.trail)?;
+        state.serialize_field("hash", &self.hash)?;
+        state.serialize_field("unborn", &self.is_unborn())?;
+        state.end()
+    }
+}
+
+impl Serialize for Upstream {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Upstream", 4)?;
+        state.serialize_field("ahead", &self.ahead)?;
+        state.serialize_field("behind", &self.behind)?;
+        state.serialize_field("exact", &self.exact)?;
+        state.serialize_field("error", &self.error)?;
+        state.end()
+    }
+}
+
+impl Serialize for ChangeCounters {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct(
+            "ChangeCounters",
+            ChangeKind::ALL.len().saturating_add(4),
+        )?;
+        for kind in ChangeKind::ALL {
+            state.serialize_field(kind.name(), &self.get(kind))?;
+        }
+        state.serialize_field("truncated", &self.truncated)?;
+        state.serialize_field(
+            "untracked_truncated",
+            &self.untracked_truncated,
+        )?;
+        state.serialize_field("limit", &self.limit)?;
+        state.serialize_field("index_locked", &self.index_locked)?;
+        state.end()
+    }
+}
+
+impl Serialize for StashInfo {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("StashInfo", 2)?;
+        state.serialize_field("count", &self.count())?;
+        state.serialize_field("entries", &self.entries)?;
+        state.end()
+    }
+}
+
+impl Serialize for StashEntry {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("StashEntry", 4)?;
+        state.serialize_field("id", &self.id.to_string())?;
+        state.serialize_field("message", &self.message)?;
+        state.serialize_field("branch", &self.branch)?;
+        state.serialize_field("time", &self.time)?;
+        state.end()
+    }
+}
+
+impl Serialize for SubmoduleSummary {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SubmoduleSummary", 7)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field(
+            "head_id",
+            &self.head_id.map(|id| id.to_string()),
+        )?;
+        state.serialize_field(
+            "recorded_id",
+            &self.recorded_id.map(|id| id.to_string()),
+        )?;
+        state.serialize_field("dirty", &self.dirty)?;
+        state.serialize_field("initialized", &self.initialized)?;
+        state.serialize_field("drifted", &self.drifted())?;
+        state.end()
+    }
+}
+
+impl Serialize for SuperprojectSummary {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state =
+            serializer.serialize_struct("SuperprojectSummary", 6)?;
+        state.serialize_field("workdir", &self.workdir)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field(
+            "head_id",
+            &self.head_id.map(|id| id.to_string()),
+        )?;
+        state.serialize_field(
+            "recorded_id",
+            &self.recorded_id.map(|id| id.to_string()),
+        )?;
+        state.serialize_field(
+            "index_id",
+            &self.index_id.map(|id| id.to_string()),
+        )?;
+        state.serialize_field("drifted", &self.drifted())?;
+        state.end()
+    }
+}
+
+impl Serialize for RemoteTip {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("RemoteTip", 2)?;
+        state.serialize_field("id", &self.id.to_string())?;
+        state.serialize_field("behind", &self.behind)?;
+        state.end()
+    }
+}
+
+impl Serialize for WorktreeSummary {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("WorktreeSummary", 6)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("branch", &self.branch)?;
+        state.serialize_field("locked", &self.locked)?;
+        state.serialize_field("lock_reason", &self.lock_reason)?;
+        state.serialize_field("prunable", &self.prunable)?;
+        state.end()
+    }
+}
+
+impl Serialize for RepoSummary {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("RepoSummary", 18)?;
+        state.serialize_field("state", &format!("{:?}", self.state))?;
+        state.serialize_field("ps1_state", &self.ps1_state)?;
+        state.serialize_field("workdir", &self.workdir)?;
+        state.serialize_field("both_workdirs", &self.both_workdirs)?;
+        state.serialize_field("empty", &self.empty)?;
+        state.serialize_field("bare", &self.bare)?;
+        state.serialize_field("fetch_age_seconds", &self.fetch_age_seconds)?;
+        state.serialize_field("sections", &self.sections)?;
+        state.serialize_field("head", &self.head)?;
+        state.serialize_field("upstream", &self.upstream)?;
+        state.serialize_field("changes", &self.changes)?;
+        state.serialize_field("counts_skipped", &self.counts_skipped)?;
+        state.serialize_field("stash_count", &self.stash_count)?;
+        state.serialize_field("superproject", &self.superproject)?;
+        state.serialize_field("remote", &self.remote)?;
+        state.serialize_field("timeouts", &self.timeouts)?;
+        state.serialize_field("errors", &self.errors)?;
+        state.serialize_field("condition", &self.condition())?;
+        state.end()
+    }
+}
@@ -0,0 +1,333 @@
+//! Answer queries for summaries until killed.
+//!
+//! With `serve --http ADDRESS`, `GET /summary?path=PATH` is answered with the
+//! variables for the repository at `PATH` as a JSON object (see
+//! [`JsonFormat`]). Connections are handled by a fixed pool of worker threads
+//! and closed after one response.
+//!
+//! This uses threads rather than an async runtime because the work is almost
+//! all libgit2 calls, which block. A fixed pool bounds the number of
+//! repositories summarized at once, and the accept loop waits for a worker
+//! when they’re all busy. Workers never start threads of their own, so
+//! `--timeout` is checked between sections (see [`summarize()`]), and options
+//! that compute sections in other threads are refused.
+
+use crate::params::Params;
+use git_status_vars::format::{JsonFormat, OutputFormat};
+use git_status_vars::git2_interop::{self, resolve_bare};
+use git_status_vars::{
+    schema, vars, CancelToken, Error, ErrorInfo, RepoSummary, SummarizeOptions,
+    Value,
+};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for a client to send its request or accept the response.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest request line or header line that will be read, including the
+/// line ending.
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// The most header lines that will be read.
+const MAX_HEADERS: usize = 100;
+
+/// Listen for HTTP requests on `address` until killed.
+///
+/// The address actually listened on (useful with port 0) is written to stderr
+/// as `listening on http://ADDRESS`.
+pub fn serve_http(params: &Params, address: SocketAddr) -> ExitCode {
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("Could not listen on {address}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Ok(address) = listener.local_addr() {
+        eprintln!("listening on http://{address}");
+    }
+
+    let workers = thread::available_parallelism().map_or(4, NonZeroUsize::get);
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(workers);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let params = Arc::new(params.clone());
+    for _ in 0..workers {
+        let receiver = Arc::clone(&receiver);
+        let params = Arc::clone(&params);
+        thread::spawn(move || {
+            while let Some(stream) = next_stream(&receiver) {
+                // The client may have hung up; there’s nobody to tell.
+                let _ = handle(&params, stream);
+            }
+        });
+    }
+
+    for stream in listener.incoming().flatten() {
+        // Blocks while every worker is busy and the queue is full.
+        if sender.send(stream).is_err() {
+            eprintln!("All workers have stopped");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Wait for the next connection for a worker to handle.
+///
+/// Returns `None` if the accept loop has stopped or another worker panicked
+/// while waiting.
+fn next_stream(receiver: &Mutex<Receiver<TcpStream>>) -> Option<TcpStream> {
+    receiver.lock().ok()?.recv().ok()
+}
+
+/// Read one request from `stream` and answer it.
+fn handle(params: &Params, mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if !read_line(&mut reader, &mut request_line)? {
+        return reject(&mut stream, "414 URI Too Long", "line too long\n");
+    }
+    // Nothing in the headers changes the response.
+    if !skip_headers(&mut reader)? {
+        return reject(
+            &mut stream,
+            "431 Request Header Fields Too Large",
+            "headers too large\n",
+        );
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next(), parts.next().unwrap_or(""));
+    let (route, query) = target.split_once('?').unwrap_or((target, ""));
+    if route != "/summary" {
+        return respond(&mut stream, "404 Not Found", "not found\n");
+    }
+    if method != Some("GET") {
+        return respond(&mut stream, "405 Method Not Allowed", "use GET\n");
+    }
+    let Some(path) = query_param(query, "path") else {
+        return respond(&mut stream, "400 Bad Request", "expected ?path=\n");
+    };
+
+    let mut body = vec![];
+    match summarize(params, &PathBuf::from(path)) {
+        Ok(summary) => JsonFormat.render(&summary, &mut body)?,
+        Err(error) => {
+            let schema_version = i64::from(schema::SCHEMA_VERSION);
+            let state = if error.is_not_found() {
+                "NotFound"
+            } else {
+                "Error"
+            };
+            let mut output = vec![
+                (vars::SCHEMA_VERSION.to_owned(), Value::Int(schema_version)),
+                (vars::REPO_STATE.to_owned(), Value::from(state)),
+            ];
+            if !error.is_not_found() {
+                let info = ErrorInfo::from(error);
+                output.extend([
+                    (vars::REPO_ERROR.to_owned(), Value::from(info.message)),
+                    (vars::REPO_ERROR_CODE.to_owned(), Value::from(info.code)),
+                    (
+                        vars::REPO_ERROR_CLASS.to_owned(),
+                        Value::from(info.class),
+                    ),
+                ]);
+            }
+            JsonFormat::render_vars(&mut body, output)?;
+        }
+    }
+    respond(&mut stream, "200 OK", &String::from_utf8_lossy(&body))
+}
+
+/// Summarize the repository at `path`, giving up once `--timeout` has passed.
+///
+/// Unlike [`crate::summarize_with_timeout()`], this doesn’t start a thread
+/// that would keep running after the worker gives up. Instead, the timeout is
+/// checked between sections, so a section that is already running finishes
+/// first.
+fn summarize(params: &Params, path: &Path) -> Result<RepoSummary, Error> {
+    let options = SummarizeOptions {
+        start_path: params.start_path(Some(path)),
+        cancel: params
+            .timeout
+            .map(|timeout| CancelToken::new().with_timeout(timeout)),
+        ..params.summarize_options()
+    };
+    params
+        .open(Some(path))
+        .map_err(Error::from)
+        .and_then(|repository| resolve_bare(&options.bare, repository))
+        .and_then(|repository| git2_interop::summarize(&repository, &options))
+        .map_err(|error| match (error, params.timeout) {
+            (Error::Cancelled, Some(timeout)) => Error::Timeout(timeout),
+            (error, _) => error,
+        })
+}
+
+/// Read a line of at most [`MAX_LINE_LENGTH`] bytes from `reader` into `line`.
+///
+/// Returns `Ok(false)` if the line is longer than that, in which case the rest
+/// of it is left unread.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<bool> {
+    let limit = u64::try_from(MAX_LINE_LENGTH).unwrap_or(u64::MAX);
+    let length = reader.by_ref().take(limit).read_line(line)?;
+    Ok(length < MAX_LINE_LENGTH || line.ends_with('\n'))
+}
+
+/// Read and discard headers up to the empty line that ends them.
+///
+/// Returns `Ok(false)` if there are more than [`MAX_HEADERS`] or one is too
+/// long.
+fn skip_headers(reader: &mut impl BufRead) -> io::Result<bool> {
+    for _ in 0..=MAX_HEADERS {
+        let mut header = String::new();
+        if !read_line(reader, &mut header)? {
+            return Ok(false);
+        }
+        // An empty string means the client closed the connection.
+        if header.trim().is_empty() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Respond to a request that was too large to read, then read and discard
+/// some of the rest of it.
+///
+/// Closing a connection with unread data resets it, which can discard the
+/// response before the client reads it.
+fn reject(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    respond(stream, status, body)?;
+    stream.shutdown(Shutdown::Write)?;
+    let limit = u64::try_from(MAX_HEADERS * MAX_LINE_LENGTH).unwrap_or(0);
+    io::copy(&mut Read::by_ref(stream).take(limit), &mut io::sink())?;
+    Ok(())
+}
+
+/// Write a response with `status` (e.g. `"200 OK"`) and `body`, which is JSON
+/// if the request succeeded and plain text otherwise.
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    let content_type = if status.starts_with("200") {
+        "application/json"
+    } else {
+        "text/plain; charset=utf-8"
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+        Content-Type: {content_type}\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\
+        \r\n\
+        {body}",
+        body.len(),
+    )?;
+    stream.flush()
+}
+
+/// Find the first parameter called `name` in a URL query string, e.g.
+/// `path=%2Fsrc%2Frepo`, and decode it.
+///
+/// Returns `None` if the parameter is missing or isn’t valid UTF-8 once
+/// decoded.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    let value = query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (key == name).then_some(value)
+    })?;
+    percent_decode(value)
+}
+
+/// Decode `%XX` escapes and `+` (a space) in a query string value.
+///
+/// Returns `None` if an escape is invalid or the result isn’t valid UTF-8.
+fn percent_decode(input: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut rest = input.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                // `from_str_radix()` alone would accept a sign, e.g. `%+1`.
+                let hex = rest.get(..2)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                let hex = std::str::from_utf8(hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &rest[2..];
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decode_valid() {
+        assert_eq!(percent_decode("a%2Fb+c%e2%80%99").unwrap(), "a/b c’");
+        assert_eq!(percent_decode("").unwrap(), "");
+    }
+
+    #[test]
+    fn percent_decode_sign() {
+        assert_eq!(percent_decode("%+1"), None);
+        assert_eq!(percent_decode("%-1"), None);
+    }
+
+    #[test]
+    fn percent_decode_not_hex() {
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%4g"), None);
+    }
+
+    #[test]
+    fn percent_decode_truncated() {
+        assert_eq!(percent_decode("%4"), None);
+        assert_eq!(percent_decode("a%"), None);
+    }
+
+    #[test]
+    fn percent_decode_invalid_utf8() {
+        assert_eq!(percent_decode("%ff"), None);
+    }
+
+    #[test]
+    fn read_line_limit() {
+        let long = "a".repeat(MAX_LINE_LENGTH);
+        let mut line = String::new();
+        assert!(!read_line(&mut long.as_bytes(), &mut line).unwrap());
+
+        let input = format!("{}\nnext\n", &long[1..]);
+        let mut line = String::new();
+        assert!(read_line(&mut input.as_bytes(), &mut line).unwrap());
+        assert_eq!(line.len(), MAX_LINE_LENGTH);
+    }
+
+    #[test]
+    fn skip_headers_limit() {
+        let headers = "Host: test\r\n".repeat(MAX_HEADERS);
+        let input = format!("{headers}\r\nbody");
+        assert!(skip_headers(&mut input.as_bytes()).unwrap());
+
+        let input = format!("{headers}Extra: header\r\n\r\n");
+        assert!(!skip_headers(&mut input.as_bytes()).unwrap());
+    }
+}
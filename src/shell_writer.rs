@@ -1,20 +1,64 @@
-use std::cell::RefCell;
-use std::fmt::{self, Debug, Display};
+use crate::Value;
+use std::borrow::Cow;
+use std::fmt::{self, Debug, Display, Write as _};
 use std::io;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
 /// A writer of var=value pairs.
 ///
 /// See [`ShellWriter::new()`].
-#[derive(Clone)]
+///
+/// Clones and groups share the same output stream, which is protected by a
+/// mutex, so a writer can be sent to other threads if `W` is [`Send`]. `W`
+/// doesn’t need to be [`Clone`], since the stream itself isn’t cloned. Each
+/// line is written while holding the lock, so lines from different threads
+/// are never interleaved.
+///
+/// ```rust
+/// use git_status_vars::ShellWriter;
+/// use std::thread;
+///
+/// let mut buffer: Vec<u8> = vec![];
+/// let out = ShellWriter::new(&mut buffer, "");
+/// thread::scope(|scope| {
+///     for n in 1..=2 {
+///         let out = out.group_n("repo", n);
+///         scope.spawn(move || out.write_var("n", n));
+///     }
+/// });
+/// drop(out);
+/// let mut lines: Vec<_> = buffer.split(|c| *c == b'\n').collect();
+/// lines.sort_unstable();
+/// assert_eq!(lines, [&b""[..], b"repo1_n=1", b"repo2_n=2"]);
+/// ```
 pub struct ShellWriter<W: io::Write> {
     /// The output stream to write to.
-    writer: Rc<RefCell<W>>,
+    writer: Arc<Mutex<W>>,
+
+    /// The prefix to add before every line, e.g. `"local "` or `""`.
+    prefix: Arc<str>,
 
     /// The prefix to add before every key, e.g. `"group_"` or `""`.
-    prefix: String,
+    group: String,
+
+    /// Whether to write `unset var` for empty or missing variables.
+    unset_missing: bool,
+
+    /// The shell syntax to write.
+    dialect: Dialect,
+
+    /// A buffer to format values into before quoting them, shared with
+    /// sub-writers so that it can be reused.
+    scratch: Arc<Mutex<String>>,
+
+    /// If set, variables are recorded here instead of being written. See
+    /// [`collect_vars()`].
+    record: Option<Record>,
 }
 
+/// Variables recorded by [`collect_vars()`].
+type Record = Arc<Mutex<Vec<(String, Value)>>>;
+
 impl<W: io::Write> ShellWriter<W> {
     /// Create a new `ShellWriter`. The `prefix` will be prepended anytime a
     /// var is outputted, e.g. `prefixvar=value`.
@@ -32,35 +76,352 @@ impl<W: io::Write> ShellWriter<W> {
     #[must_use]
     pub fn new<P: Display>(writer: W, prefix: P) -> Self {
         Self {
-            writer: Rc::new(RefCell::new(writer)),
-            prefix: prefix.to_string(),
+            writer: Arc::new(Mutex::new(writer)),
+            prefix: prefix.to_string().into(),
+            group: String::new(),
+            unset_missing: false,
+            dialect: Dialect::default(),
+            scratch: Arc::default(),
+            record: None,
+        }
+    }
+
+    /// Generate a writer that outputs `unset var` instead of `var=''` for
+    /// empty values, and for variables passed to [`Self::write_missing()`].
+    ///
+    /// This prevents values from a previous run from lingering in a shell that
+    /// `eval`s the output repeatedly. The prefix is not used for `unset`
+    /// lines.
+    ///
+    /// ```rust
+    /// use git_status_vars::ShellWriter;
+    /// let mut buffer: Vec<u8> = vec![];
+    /// let out = ShellWriter::new(&mut buffer, "").with_unset_missing(true);
+    /// out.write_var("a", "");
+    /// out.write_missing("b");
+    /// drop(out);
+    /// assert_eq!(buffer, b"unset a\nunset b\n");
+    /// ```
+    #[must_use]
+    pub fn with_unset_missing(&self, unset_missing: bool) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            prefix: self.prefix.clone(),
+            group: self.group.clone(),
+            unset_missing,
+            dialect: self.dialect,
+            scratch: self.scratch.clone(),
+            record: self.record.clone(),
+        }
+    }
+
+    /// Generate a writer that outputs variables in the syntax of `dialect`.
+    ///
+    /// The prefix passed to [`Self::new()`] is written at the start of each
+    /// line as is. Prefixes like `"local "` and `"export "` only work in
+    /// [`Dialect::Sh`], so with other dialects it should usually be empty.
+    ///
+    /// ```rust
+    /// use git_status_vars::{Dialect, ShellWriter};
+    /// let mut buffer: Vec<u8> = vec![];
+    /// let out = ShellWriter::new(&mut buffer, "")
+    ///     .with_dialect(Dialect::Fish)
+    ///     .with_unset_missing(true);
+    /// out.write_var("a", "it's");
+    /// out.write_missing("b");
+    /// drop(out);
+    /// assert_eq!(buffer, b"set a 'it\\'s'\nset -e b\n");
+    /// ```
+    #[must_use]
+    pub fn with_dialect(&self, dialect: Dialect) -> Self {
+        Self {
+            dialect,
+            ..self.with_unset_missing(self.unset_missing)
         }
     }
 
+    /// Get the shell syntax this writer outputs. See [`Self::with_dialect()`].
+    #[must_use]
+    pub const fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Check if this writer outputs `unset var` for empty or missing
+    /// variables. See [`Self::with_unset_missing()`].
+    #[must_use]
+    pub const fn unsets_missing(&self) -> bool {
+        self.unset_missing
+    }
+
     /// Write var=value with a value that was already quoted.
-    fn write_raw<K: Display, V: Display>(&self, var: K, raw: V) {
-        writeln!(self.writer.borrow_mut(), "{}{}={}", self.prefix, var, raw)
-            .unwrap();
+    fn write_raw<K: Display, V: Display>(
+        &self,
+        var: K,
+        raw: V,
+    ) -> io::Result<()> {
+        let (prefix, group) = (&self.prefix, &self.group);
+        let mut writer = lock(&self.writer);
+        match self.dialect {
+            Dialect::Sh => writeln!(writer, "{prefix}{group}{var}={raw}"),
+            Dialect::Fish => writeln!(writer, "{prefix}set {group}{var} {raw}"),
+            Dialect::PowerShell => {
+                writeln!(writer, "{prefix}${group}{var} = {raw}")
+            }
+            Dialect::Csh => {
+                writeln!(writer, "{prefix}set {group}{var} = {raw}")
+            }
+        }
+    }
+
+    /// Flush the output stream.
+    ///
+    /// This marks the end of a complete section of output. See
+    /// [`git2_interop::summarize_opened_repository()`].
+    ///
+    /// [`git2_interop::summarize_opened_repository()`]:
+    ///     crate::git2_interop::summarize_opened_repository
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the output stream returns an error. See
+    /// [`Self::try_flush()`].
+    pub fn flush(&self) {
+        self.try_flush().unwrap();
+    }
+
+    /// Flush the output stream. See [`Self::flush()`].
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    pub fn try_flush(&self) -> io::Result<()> {
+        lock(&self.writer).flush()
+    }
+
+    /// Write an empty line, e.g. to separate groups of variables.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the output stream returns an error.
+    pub fn write_blank_line(&self) {
+        self.try_write_blank_line().unwrap();
+    }
+
+    /// Write an empty line. See [`Self::write_blank_line()`].
+    ///
+    /// Nothing is written while recording variables for [`collect_vars()`].
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    pub fn try_write_blank_line(&self) -> io::Result<()> {
+        if self.record.is_some() {
+            return Ok(());
+        }
+        writeln!(lock(&self.writer))
     }
 
     /// Write var=value. `value` will be turned into a string, then quoted for
     /// safe shell insertion. `var` will be assumed to be a valid name for a
     /// shell variable.
+    ///
+    /// If [`Self::with_unset_missing()`] is enabled and `value` is empty, this
+    /// writes `unset var` instead.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the output stream returns an error. See
+    /// [`Self::try_write_var()`].
     pub fn write_var<K: Display, V: Display>(&self, var: K, value: V) {
-        self.write_raw(var, shell_quote(value));
+        self.try_write_var(var, value).unwrap();
+    }
+
+    /// Write var=value. See [`Self::write_var()`].
+    ///
+    /// ```rust
+    /// use git_status_vars::ShellWriter;
+    /// use std::io;
+    ///
+    /// struct ClosedPipe;
+    ///
+    /// impl io::Write for ClosedPipe {
+    ///     fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+    ///         Err(io::ErrorKind::BrokenPipe.into())
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let out = ShellWriter::new(ClosedPipe, "");
+    /// let error = out.try_write_var("var", "value").unwrap_err();
+    /// assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    pub fn try_write_var<K: Display, V: Display>(
+        &self,
+        var: K,
+        value: V,
+    ) -> io::Result<()> {
+        if self.record.is_some() {
+            return self.try_write_value(var, value.to_string());
+        }
+        let mut scratch = lock(&self.scratch);
+        scratch.clear();
+        write!(scratch, "{value}").unwrap();
+        if self.unset_missing && scratch.is_empty() {
+            drop(scratch);
+            self.try_write_missing(var)
+        } else {
+            // Values that don’t need quoting are written without copying.
+            self.write_raw(var, self.dialect.quote(&scratch))
+        }
+    }
+
+    /// Note that var is not part of the output. This writes `unset var` if
+    /// [`Self::with_unset_missing()`] is enabled, and nothing otherwise.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the output stream returns an error. See
+    /// [`Self::try_write_missing()`].
+    pub fn write_missing<K: Display>(&self, var: K) {
+        self.try_write_missing(var).unwrap();
+    }
+
+    /// Note that var is not part of the output. See [`Self::write_missing()`].
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    pub fn try_write_missing<K: Display>(&self, var: K) -> io::Result<()> {
+        if !self.unset_missing {
+            return Ok(());
+        }
+        let group = &self.group;
+        let mut writer = lock(&self.writer);
+        match self.dialect {
+            Dialect::Sh | Dialect::Csh => {
+                writeln!(writer, "unset {group}{var}")
+            }
+            Dialect::Fish => writeln!(writer, "set -e {group}{var}"),
+            Dialect::PowerShell => writeln!(
+                writer,
+                "Remove-Variable {group}{var} -ErrorAction SilentlyContinue"
+            ),
+        }
+    }
+
+    /// Write var=value with a typed [`Value`]. The shell output is the same as
+    /// [`Self::write_var()`]; [`Value::Null`] is written as an empty string.
+    /// In [`Dialect::PowerShell`], booleans are written as `$true` and
+    /// `$false`, and integers are written without quotes.
+    ///
+    /// ```rust
+    /// use git_status_vars::ShellWriter;
+    /// let mut buffer: Vec<u8> = vec![];
+    /// let out = ShellWriter::new(&mut buffer, "");
+    /// out.write_value("count", 3_usize);
+    /// out.write_value("ahead", None::<usize>);
+    /// drop(out);
+    /// assert_eq!(buffer, b"count=3\nahead=''\n");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the output stream returns an error. See
+    /// [`Self::try_write_value()`].
+    pub fn write_value<K: Display, V: Into<Value>>(&self, var: K, value: V) {
+        self.try_write_value(var, value).unwrap();
+    }
+
+    /// Write var=value with a typed [`Value`]. See [`Self::write_value()`].
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    pub fn try_write_value<K: Display, V: Into<Value>>(
+        &self,
+        var: K,
+        value: V,
+    ) -> io::Result<()> {
+        let value = value.into();
+        if let Some(record) = &self.record {
+            let var = format!("{}{}", self.group, var);
+            lock(record).push((var, value));
+            return Ok(());
+        }
+        // PowerShell has its own booleans and numbers, so they aren’t quoted.
+        match (self.dialect, value) {
+            (Dialect::PowerShell, Value::Bool(true)) => {
+                self.write_raw(var, "$true")
+            }
+            (Dialect::PowerShell, Value::Bool(false)) => {
+                self.write_raw(var, "$false")
+            }
+            (Dialect::PowerShell, Value::Int(value)) => {
+                self.write_raw(var, value)
+            }
+            (_, value) => self.try_write_var(var, value),
+        }
     }
 
     /// Write var=value. `value` will be formatted into a string using
     /// [`Debug`], then quoted for safe shell insertion. `var` will be assumed
     /// to be a valid name for a shell variable.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the output stream returns an error. See
+    /// [`Self::try_write_var_debug()`].
     pub fn write_var_debug<K: Display, V: Debug>(&self, var: K, value: V) {
-        self.write_raw(var, shell_quote_debug(value));
+        self.try_write_var_debug(var, value).unwrap();
+    }
+
+    /// Write var=value using [`Debug`]. See [`Self::write_var_debug()`].
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    // `scratch` is borrowed by `write_raw()`, so it can’t be dropped earlier.
+    #[allow(clippy::significant_drop_tightening)]
+    pub fn try_write_var_debug<K: Display, V: Debug>(
+        &self,
+        var: K,
+        value: V,
+    ) -> io::Result<()> {
+        if self.record.is_some() {
+            return self.try_write_value(var, format!("{value:?}"));
+        }
+        let mut scratch = lock(&self.scratch);
+        scratch.clear();
+        write!(scratch, "{value:?}").unwrap();
+        self.write_raw(var, self.dialect.quote(&scratch))
     }
 
     /// Write an object with the [`ShellVars`] trait. Mostly used with
     /// [`Self::group()`] and [`Self::group_n()`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the output stream returns an error. See
+    /// [`Self::try_write_vars()`].
     pub fn write_vars<V: ShellVars>(&self, vars: &V) {
-        vars.write_to_shell(self);
+        self.try_write_vars(vars).unwrap();
+    }
+
+    /// Write an object with the [`ShellVars`] trait. See
+    /// [`Self::write_vars()`].
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    pub fn try_write_vars<V: ShellVars>(&self, vars: &V) -> io::Result<()> {
+        vars.write_to_shell(self)
     }
 
     /// Generate a sub-writer with this group name. Example output:
@@ -72,7 +433,12 @@ impl<W: io::Write> ShellWriter<W> {
     pub fn group<G: Display>(&self, group: G) -> Self {
         Self {
             writer: self.writer.clone(),
-            prefix: format!("{}{}_", self.prefix, group),
+            prefix: self.prefix.clone(),
+            group: format!("{}{}_", self.group, group),
+            unset_missing: self.unset_missing,
+            dialect: self.dialect,
+            scratch: self.scratch.clone(),
+            record: self.record.clone(),
         }
     }
 
@@ -102,22 +468,261 @@ impl Default for ShellWriter<io::Stdout> {
     }
 }
 
+// Derived `Clone` would require `W: Clone`, but only the `Arc` is cloned.
+impl<W: io::Write> Clone for ShellWriter<W> {
+    fn clone(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            prefix: self.prefix.clone(),
+            group: self.group.clone(),
+            unset_missing: self.unset_missing,
+            dialect: self.dialect,
+            scratch: self.scratch.clone(),
+            record: self.record.clone(),
+        }
+    }
+}
+
 impl<W: io::Write + Debug> Debug for ShellWriter<W> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("ShellWriter")
             .field("writer", &self.writer)
             .field("prefix", &self.prefix)
-            .finish()
+            .field("group", &self.group)
+            .field("unset_missing", &self.unset_missing)
+            .field("dialect", &self.dialect)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Get the variables that `vars` would write and their values, in order,
+/// without writing them.
+///
+/// Variables passed to [`ShellWriter::write_missing()`] are not included.
+/// Values written with [`ShellWriter::write_var()`] are converted to strings.
+///
+/// ```rust
+/// use git_status_vars::{collect_vars, Upstream, Value};
+/// let upstream = Upstream { ahead: Some(2), ..Upstream::default() };
+/// assert_eq!(
+///     collect_vars(&upstream),
+///     [
+///         ("ahead".to_owned(), Value::Int(2)),
+///         ("behind".to_owned(), Value::Null),
+///         ("ahead_exact".to_owned(), Value::Null),
+///         ("upstream_error".to_owned(), Value::from("")),
+///         ("upstream_error_code".to_owned(), Value::from("")),
+///         ("upstream_error_class".to_owned(), Value::from("")),
+///     ],
+/// );
+/// ```
+///
+/// # Panics
+///
+/// This will panic if [`ShellVars::write_to_shell()`] returns an error even
+/// though nothing is actually written.
+#[must_use]
+pub fn collect_vars<V: ShellVars>(vars: &V) -> Vec<(String, Value)> {
+    let record: Record = Arc::default();
+    let out = ShellWriter {
+        record: Some(Arc::clone(&record)),
+        ..ShellWriter::new(io::sink(), "")
+    };
+    out.try_write_vars(vars)
+        .expect("collecting variables should not do I/O");
+    drop(out);
+    let mut record = lock(&record);
+    std::mem::take(&mut *record)
+}
+
+/// Lock a mutex shared between writers.
+///
+/// A panic in another thread while it held the lock can at worst leave a
+/// partial line in the output, so poisoning is ignored.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Adapt a [`fmt::Write`], like a [`String`], to [`io::Write`] so that a
+/// [`ShellWriter`] can write to it.
+///
+/// ```rust
+/// use git_status_vars::{FmtWriter, ShellWriter};
+/// let mut output = String::new();
+/// ShellWriter::new(FmtWriter::new(&mut output), "").write_var("a", "b c");
+/// assert_eq!(output, "a='b c'\n");
+/// ```
+///
+/// [`ShellWriter`] always writes complete UTF-8 strings. Writing bytes that
+/// aren’t valid UTF-8 returns an [`io::ErrorKind::InvalidData`] error.
+#[derive(Clone, Debug, Default)]
+pub struct FmtWriter<F: fmt::Write> {
+    /// The destination.
+    inner: F,
+}
+
+impl<F: fmt::Write> FmtWriter<F> {
+    /// Wrap a [`fmt::Write`].
+    pub const fn new(inner: F) -> Self {
+        Self { inner }
+    }
+
+    /// Get the wrapped [`fmt::Write`].
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: fmt::Write> io::Write for FmtWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s = std::str::from_utf8(buf).map_err(|error| {
+            io::Error::new(io::ErrorKind::InvalidData, error)
+        })?;
+        self.inner.write_str(s).map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
 /// An object that can be written as a group of shell variables.
 pub trait ShellVars {
     /// Write `self` to the shell writer `out`.
-    fn write_to_shell<W: io::Write>(&self, out: &ShellWriter<W>);
+    ///
+    /// Implementations should use the `try_` methods on [`ShellWriter`], e.g.
+    /// [`ShellWriter::try_write_var()`], and pass errors on.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the output stream returns an error.
+    fn write_to_shell<W: io::Write>(
+        &self,
+        out: &ShellWriter<W>,
+    ) -> io::Result<()>;
 }
 
-/// Quote a value for safe shell insertion.
+/// The shell syntax that a [`ShellWriter`] outputs. See
+/// [`ShellWriter::with_dialect()`].
+///
+/// Each dialect assigns variables and quotes values the way its shell expects,
+/// so that the output can be evaluated safely whatever the values contain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    /// POSIX `sh` and compatible shells like bash and zsh: `var='value'` and
+    /// `unset var`.
+    #[default]
+    Sh,
+
+    /// fish: `set var 'value'` and `set -e var`.
+    Fish,
+
+    /// PowerShell: `$var = 'value'` and `Remove-Variable var`. Booleans and
+    /// integers written with [`ShellWriter::write_value()`] are written as
+    /// `$true`, `$false`, and e.g. `3`.
+    PowerShell,
+
+    /// csh and tcsh: `set var = 'value'` and `unset var`.
+    Csh,
+}
+
+impl Dialect {
+    /// All dialects.
+    pub const ALL: [Self; 4] =
+        [Self::Sh, Self::Fish, Self::PowerShell, Self::Csh];
+
+    /// The name of the dialect as used on the command line.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Sh => "sh",
+            Self::Fish => "fish",
+            Self::PowerShell => "powershell",
+            Self::Csh => "csh",
+        }
+    }
+
+    /// Quote a value so that the shell reads it back exactly.
+    ///
+    /// Values that don’t need quoting are returned unchanged, except in
+    /// PowerShell, where a bare word would be run as a command.
+    ///
+    /// ```rust
+    /// use git_status_vars::Dialect;
+    /// assert_eq!(Dialect::Sh.quote("it's"), r"'it'\''s'");
+    /// assert_eq!(Dialect::Fish.quote(r"it's \o/"), r"'it\'s \\o/'");
+    /// assert_eq!(Dialect::PowerShell.quote("it's"), "'it''s'");
+    /// assert_eq!(Dialect::Csh.quote("a!\nb"), "'a\\!\\\nb'");
+    /// assert_eq!(Dialect::Fish.quote("main"), "main");
+    /// ```
+    #[must_use]
+    pub fn quote(self, value: &str) -> Cow<'_, str> {
+        if self == Self::Sh {
+            return shell_words::quote(value);
+        }
+        if self != Self::PowerShell && is_bare_word(value) {
+            return Cow::Borrowed(value);
+        }
+        let mut quoted = String::with_capacity(value.len().saturating_add(2));
+        quoted.push('\'');
+        for c in value.chars() {
+            match (self, c) {
+                // In csh, history substitution happens even inside quotes,
+                // and a quoted newline must be escaped.
+                (Self::Fish, '\\' | '\'') | (Self::Csh, '!' | '\n') => {
+                    quoted.push('\\');
+                }
+                // PowerShell also ends strings with typographic quotes.
+                (Self::PowerShell, '\'' | '‘' | '’' | '‚' | '‛') => {
+                    quoted.push(c);
+                }
+                (Self::Csh, '\'') => {
+                    quoted.push_str("'\\'");
+                }
+                _ => {}
+            }
+            quoted.push(c);
+        }
+        quoted.push('\'');
+        Cow::Owned(quoted)
+    }
+}
+
+/// Check if `value` can be output without quotes in fish and csh.
+fn is_bare_word(value: &str) -> bool {
+    !value.is_empty()
+        && value.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(
+                    c,
+                    '_' | '-' | '.' | '/' | ':' | '@' | '+' | ',' | '='
+                )
+        })
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|dialect| dialect.name() == input)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.map(Self::name).into();
+                format!("expected one of: {}", names.join(", "))
+            })
+    }
+}
+
+/// Quote a value for safe insertion in a POSIX shell. See [`Dialect::quote()`]
+/// for other shells.
 ///
 /// ```rust
 /// use git_status_vars::shell_quote;
@@ -127,6 +732,67 @@ pub fn shell_quote<V: Display>(value: V) -> String {
     shell_words::quote(&value.to_string()).into()
 }
 
+/// Convert a string into a valid shell variable name.
+///
+/// Characters other than ASCII letters, digits, and `_` are replaced with `_`.
+/// If the result would be empty or start with a digit, `_` is prepended.
+///
+/// ```rust
+/// use git_status_vars::sanitize_var_name;
+/// assert_eq!(sanitize_var_name("my-repo.git"), "my_repo_git");
+/// assert_eq!(sanitize_var_name("2024"), "_2024");
+/// ```
+#[must_use]
+pub fn sanitize_var_name(input: &str) -> String {
+    let name: String = input
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+/// Escape bytes that aren’t valid UTF-8, and `%`, as `%XX`.
+///
+/// This is used for the `*_raw` variables, which are output when a path or
+/// reference name isn’t valid UTF-8. Decoding the `%XX` escapes gets back the
+/// original bytes.
+///
+/// ```rust
+/// use git_status_vars::escape_bytes;
+/// assert_eq!(escape_bytes(b"caf\xe9 100%"), "caf%E9 100%25");
+/// assert_eq!(escape_bytes("café".as_bytes()), "café");
+/// ```
+#[must_use]
+pub fn escape_bytes(mut bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    while !bytes.is_empty() {
+        let (valid, invalid, rest) = match std::str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..], &[][..]),
+            Err(error) => {
+                let (valid, rest) = bytes.split_at(error.valid_up_to());
+                let invalid_len = error.error_len().unwrap_or(rest.len());
+                let (invalid, rest) = rest.split_at(invalid_len);
+                // Everything before `valid_up_to()` is valid UTF-8.
+                (
+                    std::str::from_utf8(valid).unwrap_or_default(),
+                    invalid,
+                    rest,
+                )
+            }
+        };
+        escaped.push_str(&valid.replace('%', "%25"));
+        for byte in invalid {
+            let _ = write!(escaped, "%{byte:02X}");
+        }
+        bytes = rest;
+    }
+    escaped
+}
+
 /// Format a value with [`Debug`] and quote it for safe shell insertion.
 pub fn shell_quote_debug<V: Debug>(value: V) -> String {
     shell_words::quote(&format!("{value:?}")).into()
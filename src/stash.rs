@@ -0,0 +1,93 @@
+//! Information about stashes.
+
+use crate::{head_only, trace, Error, ObjectId};
+use git2::Repository;
+
+/// The stashes in a repository. See [`stash_info()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StashInfo {
+    /// The stashes, newest (`stash@{0}`) first.
+    pub entries: Vec<StashEntry>,
+}
+
+impl StashInfo {
+    /// The number of stashes.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// A single stash. See [`StashInfo`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StashEntry {
+    /// The stash commit.
+    pub id: ObjectId,
+
+    /// The stash message, e.g. `"WIP on main: 1234abc Commit message"`.
+    pub message: String,
+
+    /// The branch the stash was made on, or `None` if it can’t be determined
+    /// from the message. This is `"(no branch)"` if `HEAD` was detached.
+    pub branch: Option<String>,
+
+    /// When the stash was made, in seconds since the Unix epoch.
+    pub time: i64,
+}
+
+impl StashEntry {
+    /// Parse the branch name out of a stash message.
+    ///
+    /// `git stash` uses `"WIP on {branch}: ..."` without a message, and
+    /// `"On {branch}: {message}"` with one.
+    fn branch_from_message(message: &str) -> Option<String> {
+        let rest = message
+            .strip_prefix("WIP on ")
+            .or_else(|| message.strip_prefix("On "))?;
+        rest.split_once(": ").map(|(branch, _)| branch.to_owned())
+    }
+}
+
+/// Get information about each stash in a repository.
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::stash_info;
+/// use git2::Repository;
+///
+/// let repository = Repository::open_from_env().unwrap();
+/// for (i, entry) in stash_info(&repository).unwrap().entries.iter().enumerate()
+/// {
+///     println!("stash@{{{i}}}: {}", entry.message);
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This will return an [`Error`] if there was an error reading the stash
+/// reflog.
+pub fn stash_info(repository: &Repository) -> Result<StashInfo, Error> {
+    let _span = trace::span("stash_info");
+
+    // libgit2 creates an empty reflog when asked to read one that doesn’t
+    // exist, which would change the repository just by looking at it.
+    let reflog =
+        head_only::common_dir(repository.path()).join("logs/refs/stash");
+    if matches!(reflog.try_exists(), Ok(false)) {
+        return Ok(StashInfo::default());
+    }
+
+    let entries = repository
+        .reflog("refs/stash")?
+        .iter()
+        .map(|entry| {
+            let message = entry.message().unwrap_or_default().to_owned();
+            StashEntry {
+                id: entry.id_new().into(),
+                branch: StashEntry::branch_from_message(&message),
+                message,
+                time: entry.committer().when().seconds(),
+            }
+        })
+        .collect();
+    Ok(StashInfo { entries })
+}
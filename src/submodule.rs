@@ -0,0 +1,202 @@
+//! Information about submodules.
+
+use crate::{trace, Error, ObjectId};
+use git2::{ErrorCode, Repository, SubmoduleIgnore, SubmoduleStatus};
+use std::path::{Path, PathBuf};
+
+/// The state of a submodule. See [`submodule_info()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubmoduleSummary {
+    /// The name of the submodule in `.gitmodules`.
+    pub name: String,
+
+    /// The path of the submodule relative to the working tree.
+    pub path: PathBuf,
+
+    /// The commit checked out in the submodule, or `None` if it isn’t checked
+    /// out.
+    pub head_id: Option<ObjectId>,
+
+    /// The commit recorded for the submodule in `HEAD` of the superproject, or
+    /// `None` if it was just added.
+    pub recorded_id: Option<ObjectId>,
+
+    /// Whether the submodule’s working tree or index has changes, including
+    /// untracked files. Changes to which commit is checked out are reported by
+    /// [`Self::drifted()`] instead.
+    pub dirty: bool,
+
+    /// Whether the submodule has been initialized (`git submodule init`) and
+    /// checked out.
+    pub initialized: bool,
+}
+
+impl SubmoduleSummary {
+    /// Check if the commit checked out in the submodule is different from the
+    /// one recorded in the superproject.
+    #[must_use]
+    pub fn drifted(&self) -> bool {
+        self.initialized && self.head_id != self.recorded_id
+    }
+}
+
+/// Get the state of each submodule in a repository.
+///
+/// Submodule statuses are computed regardless of the `ignore` setting in
+/// `.gitmodules`, so this may report changes that
+/// [`SummarizeOptions::include_submodules`][crate::SummarizeOptions::include_submodules]
+/// doesn’t count.
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::submodule_info;
+/// use git2::Repository;
+///
+/// let repository = Repository::open_from_env().unwrap();
+/// for submodule in submodule_info(&repository).unwrap() {
+///     if submodule.drifted() {
+///         println!("{} is not at the recorded commit", submodule.path.display());
+///     }
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This will return an [`Error`] if the submodules could not be read, e.g.
+/// because `.gitmodules` is invalid.
+pub fn submodule_info(
+    repository: &Repository,
+) -> Result<Vec<SubmoduleSummary>, Error> {
+    let _span = trace::span("submodule_info");
+    let dirty = SubmoduleStatus::WD_INDEX_MODIFIED
+        | SubmoduleStatus::WD_WD_MODIFIED
+        | SubmoduleStatus::WD_UNTRACKED;
+    repository
+        .submodules()?
+        .iter()
+        .map(|submodule| {
+            let name = submodule.name().unwrap_or_default().to_owned();
+            let status =
+                repository.submodule_status(&name, SubmoduleIgnore::None)?;
+            Ok(SubmoduleSummary {
+                path: submodule.path().to_path_buf(),
+                head_id: submodule.workdir_id().map(ObjectId::from),
+                recorded_id: submodule.head_id().map(ObjectId::from),
+                dirty: status.intersects(dirty),
+                initialized: status.contains(SubmoduleStatus::IN_CONFIG)
+                    && !status.contains(SubmoduleStatus::WD_UNINITIALIZED),
+                name,
+            })
+        })
+        .collect()
+}
+
+/// The superproject of a submodule, and how it sees the submodule. See
+/// [`superproject_info()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuperprojectSummary {
+    /// The working tree of the superproject.
+    pub workdir: PathBuf,
+
+    /// The path of the submodule relative to [`Self::workdir`].
+    pub path: PathBuf,
+
+    /// The commit checked out in the submodule, or `None` if it’s unborn.
+    pub head_id: Option<ObjectId>,
+
+    /// The commit recorded for the submodule in `HEAD` of the superproject, or
+    /// `None` if it was just added.
+    pub recorded_id: Option<ObjectId>,
+
+    /// The commit recorded for the submodule in the index of the superproject.
+    pub index_id: Option<ObjectId>,
+}
+
+impl SuperprojectSummary {
+    /// Check if the superproject would show the submodule as changed because
+    /// a different commit is checked out or staged than the one recorded in
+    /// its `HEAD`.
+    #[must_use]
+    pub fn drifted(&self) -> bool {
+        self.head_id != self.recorded_id || self.index_id != self.recorded_id
+    }
+}
+
+/// Find the superproject of a submodule.
+///
+/// The superproject is the repository containing the parent directory of the
+/// working tree, if it has a submodule at that path. Returns `Ok(None)` if
+/// `repository` is bare or isn’t a submodule.
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::superproject_info;
+/// use git2::Repository;
+///
+/// let repository = Repository::open_from_env().unwrap();
+/// if let Some(superproject) = superproject_info(&repository).unwrap() {
+///     println!("submodule of {}", superproject.workdir.display());
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This will return an [`Error`] if the superproject was found but its
+/// submodules could not be read, e.g. because `.gitmodules` is invalid.
+pub fn superproject_info(
+    repository: &Repository,
+) -> Result<Option<SuperprojectSummary>, Error> {
+    let _span = trace::span("superproject_info");
+    let Some(workdir) = repository.workdir() else {
+        return Ok(None);
+    };
+    let Some(parent) = workdir.parent() else {
+        return Ok(None);
+    };
+    let superproject = match Repository::discover(parent) {
+        Ok(superproject) => superproject,
+        Err(error) if error.code() == ErrorCode::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let Some(super_workdir) = superproject.workdir() else {
+        return Ok(None);
+    };
+
+    // Paths from libgit2 may not have symlinks resolved.
+    let (Ok(workdir), Ok(super_workdir)) =
+        (workdir.canonicalize(), super_workdir.canonicalize())
+    else {
+        return Ok(None);
+    };
+    let Some(path) = workdir
+        .strip_prefix(&super_workdir)
+        .ok()
+        .and_then(Path::to_str)
+    else {
+        return Ok(None);
+    };
+
+    let submodule = match superproject.find_submodule(path) {
+        Ok(submodule) => submodule,
+        // Exists means there’s a repository at `path`, but it’s not a
+        // submodule.
+        Err(error)
+            if matches!(
+                error.code(),
+                ErrorCode::NotFound | ErrorCode::Exists
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(error) => return Err(error.into()),
+    };
+    Ok(Some(SuperprojectSummary {
+        path: submodule.path().to_path_buf(),
+        head_id: repository
+            .head()
+            .ok()
+            .and_then(|head| head.target())
+            .map(ObjectId::from),
+        recorded_id: submodule.head_id().map(ObjectId::from),
+        index_id: submodule.index_id().map(ObjectId::from),
+        workdir: super_workdir.join(""),
+    }))
+}
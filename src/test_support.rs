@@ -0,0 +1,266 @@
+//! Build temporary repositories for tests, enabled with the `test-support`
+//! feature.
+//!
+//! [`TempRepo`] runs the `git` command line tool, which must be installed, with
+//! a throwaway configuration so that the user’s settings don’t affect the
+//! result.
+//!
+//! ```rust
+//! use git_status_vars::test_support::TempRepo;
+//! use git_status_vars::{summarize_path, SummarizeOptions};
+//!
+//! let repo = TempRepo::new()?
+//!     .commit("first")?
+//!     .write("untracked", "contents")?;
+//! let summary = summarize_path(repo.path(), &SummarizeOptions::default())?;
+//! assert_eq!(summary.changes.unwrap().untracked(), 1);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The git configuration used for every [`TempRepo`].
+///
+/// `user.name` and `user.email` must be set for commits to work. Setting
+/// `init.defaultBranch` avoids a warning and makes the initial branch `main`.
+const GITCONFIG: &str = "[user]\n\
+    name = Name\n\
+    email = name@example.com\n\
+    [init]\n\
+    defaultBranch = main\n\
+    [advice]\n\
+    detachedHead = false\n\
+    skippedCherryPicks = false\n";
+
+/// The branch [`TempRepo::conflict()`] merges from.
+pub const CONFLICT_BRANCH: &str = "conflicting";
+
+/// Used to give each [`TempRepo`] in a process a unique directory.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A git repository in a temporary directory, which is deleted when this is
+/// dropped.
+///
+/// The builder methods take and return `self` so that they can be chained:
+///
+/// ```rust
+/// use git_status_vars::test_support::TempRepo;
+///
+/// let repo = TempRepo::new()?
+///     .commit("first")?
+///     .branch("feature")?
+///     .commit("second")?
+///     .checkout("main")?
+///     .write("a", "changed")?
+///     .stash()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct TempRepo {
+    /// The temporary directory containing the configuration and repository.
+    root: PathBuf,
+
+    /// The working tree of the repository.
+    workdir: PathBuf,
+}
+
+impl TempRepo {
+    /// Create an empty repository on the `main` branch.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the temporary directory could not be
+    /// created or `git init` failed.
+    pub fn new() -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.subsec_nanos());
+        let root = std::env::temp_dir().join(format!(
+            "git-status-vars-{}-{}-{nanos}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+        fs::create_dir_all(&root)?;
+        let repo = Self {
+            workdir: root.join("repo"),
+            root,
+        };
+        fs::write(repo.root.join(".gitconfig"), GITCONFIG)?;
+        fs::create_dir(&repo.workdir)?;
+        repo.git(["init", "--quiet"])?;
+        Ok(repo)
+    }
+
+    /// Get the working tree of the repository.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.workdir
+    }
+
+    /// Run `git` in the working tree and return its output.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `git` could not be run or exited with
+    /// an error.
+    pub fn git<I, S>(&self, args: I) -> io::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = self.command(args).output()?;
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(io::Error::other(format!(
+                "git failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            )))
+        }
+    }
+
+    /// Set up a `git` command in the working tree with the test configuration.
+    fn command<I, S>(&self, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new("git");
+        command
+            .args(args)
+            .current_dir(&self.workdir)
+            .env("HOME", &self.root)
+            .env("GIT_CONFIG_GLOBAL", self.root.join(".gitconfig"))
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CEILING_DIRECTORIES", &self.root)
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_WORK_TREE")
+            .env_remove("GIT_INDEX_FILE");
+        command
+    }
+
+    /// Write `contents` to `path` in the working tree, creating parent
+    /// directories as needed.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if the file could not be written.
+    pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(
+        self,
+        path: P,
+        contents: C,
+    ) -> io::Result<Self> {
+        let path = self.workdir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+        Ok(self)
+    }
+
+    /// Stage `path` with `git add`.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `git add` failed.
+    pub fn stage<P: AsRef<OsStr>>(self, path: P) -> io::Result<Self> {
+        self.git([OsStr::new("add"), path.as_ref()])?;
+        Ok(self)
+    }
+
+    /// Write files `a` and `b` with contents based on `message`, stage all
+    /// changes, and commit them.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if writing the files or committing
+    /// failed.
+    pub fn commit(self, message: &str) -> io::Result<Self> {
+        self.write("a", format!("{message} a\n"))?
+            .write("b", format!("{message} b\n"))?
+            .commit_all(message)
+    }
+
+    /// Stage all changes and commit them without writing any files.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `git commit` failed, e.g. because
+    /// there was nothing to commit.
+    pub fn commit_all(self, message: &str) -> io::Result<Self> {
+        self.git(["add", "--all"])?;
+        self.git(["commit", "--quiet", "--message", message])?;
+        Ok(self)
+    }
+
+    /// Create a branch and switch to it.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `git checkout -b` failed.
+    pub fn branch(self, name: &str) -> io::Result<Self> {
+        self.git(["checkout", "--quiet", "-b", name])?;
+        Ok(self)
+    }
+
+    /// Switch to a branch or commit.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `git checkout` failed.
+    pub fn checkout(self, target: &str) -> io::Result<Self> {
+        self.git(["checkout", "--quiet", target])?;
+        Ok(self)
+    }
+
+    /// Stash the changes in the working tree and index.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if `git stash` failed.
+    pub fn stash(self) -> io::Result<Self> {
+        self.git(["stash", "--quiet"])?;
+        Ok(self)
+    }
+
+    /// Start a merge with a conflict in `path`.
+    ///
+    /// This commits one version of `path` to a new branch named
+    /// [`CONFLICT_BRANCH`], commits another version to the current branch, and
+    /// then merges [`CONFLICT_BRANCH`], leaving the repository in the `Merge`
+    /// state with `path` conflicted.
+    ///
+    /// # Errors
+    ///
+    /// This will return [`io::Error`] if any of the `git` commands failed, or
+    /// if the merge did not stop with a conflict.
+    pub fn conflict(self, path: &str) -> io::Result<Self> {
+        let repo = self
+            .write(path, "base\n")?
+            .commit_all("conflict base")?
+            .branch(CONFLICT_BRANCH)?
+            .write(path, "theirs\n")?
+            .commit_all("conflict theirs")?
+            .checkout("-")?
+            .write(path, "ours\n")?
+            .commit_all("conflict ours")?;
+        let output = repo.command(["merge", CONFLICT_BRANCH]).output()?;
+        if output.status.success() {
+            return Err(io::Error::other("merge did not conflict"));
+        }
+        Ok(repo)
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,338 @@
+//! Limit how long summarizing can take.
+//!
+//! When the timeout fires, the sections of the summary that were completed
+//! (see [`ShellWriter::flush()`]) are output, followed by `repo_error`. If
+//! nothing was completed, `repo_state=Error` is output as well. See
+//! [`write_timeout()`].
+
+use git_status_vars::{
+    vars, Condition, Dialect, Error, ErrorInfo, ShellWriter,
+};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Environment variable that marks a child started by [`Strategy::Process`].
+pub const CHILD_ENV: &str = "GIT_STATUS_VARS_TIMEOUT_CHILD";
+
+/// Environment variable that tells a child started by [`Strategy::Process`] to
+/// only summarize the repository at this index.
+pub const CHILD_REPOSITORY_ENV: &str = "GIT_STATUS_VARS_TIMEOUT_REPOSITORY";
+
+/// Get the index of the repository a child should summarize, if any. See
+/// [`CHILD_REPOSITORY_ENV`].
+pub fn child_repository() -> Option<usize> {
+    std::env::var(CHILD_REPOSITORY_ENV).ok()?.parse().ok()
+}
+
+/// Where the output of a summary goes.
+#[derive(Clone, Debug, Default)]
+pub struct Target {
+    /// The prefix for each line, e.g. `"local "`.
+    pub prefix: String,
+
+    /// The group for the variables, e.g. `"repo1"`, if any.
+    pub group: Option<String>,
+
+    /// The shell syntax to output.
+    pub dialect: Dialect,
+}
+
+impl Target {
+    /// Create a [`ShellWriter`] for this target.
+    pub fn writer<W: Write>(&self, writer: W) -> ShellWriter<W> {
+        let out =
+            ShellWriter::new(writer, &self.prefix).with_dialect(self.dialect);
+        match &self.group {
+            Some(group) => out.group(group),
+            None => out,
+        }
+    }
+}
+
+/// How to enforce the timeout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Strategy {
+    /// Summarize in a worker thread while the main thread waits.
+    #[default]
+    Thread,
+
+    /// Summarize in a child process, which is killed on timeout.
+    Process,
+}
+
+/// Parse a duration like `500ms`, `2s`, or `250` (milliseconds).
+///
+/// # Errors
+///
+/// Returns an error message if the input isn’t a valid duration.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let (number, unit) = input
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((input, ""), |i| input.split_at(i));
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration {input:?}"))?;
+    match unit {
+        "" | "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        _ => Err(format!("invalid unit {unit:?} (expected ms or s)")),
+    }
+}
+
+/// Output the partial summary and the variables that indicate that summarizing
+/// timed out.
+pub fn write_timeout(target: &Target, timeout: Duration, partial: &[u8]) {
+    io::stdout().write_all(partial).unwrap();
+    let out = target.writer(io::stdout());
+    if partial.is_empty() {
+        out.write_var(vars::REPO_STATE, "Error");
+    }
+    ErrorInfo::from(Error::Timeout(timeout))
+        .write_as(&out, vars::REPO_ERROR)
+        .unwrap();
+}
+
+/// A buffer that only makes written output visible once it’s flushed.
+///
+/// This ensures that a partial summary never contains part of a section.
+#[derive(Debug, Default)]
+pub struct StagedBuffer {
+    /// Output that has been written but not flushed.
+    pending: Vec<u8>,
+
+    /// Output that has been flushed.
+    committed: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Write for StagedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.committed.lock().unwrap().append(&mut self.pending);
+        Ok(())
+    }
+}
+
+/// The byte that a child started by [`Strategy::Process`] writes after each
+/// complete section. See [`SectionWriter`].
+pub const SECTION_END: u8 = 0;
+
+/// A writer for a child started by [`Strategy::Process`] that only writes its
+/// output when it’s flushed, i.e. when a section is complete, followed by
+/// [`SECTION_END`].
+///
+/// Writing whole sections isn’t enough on its own: a pipe doesn’t preserve
+/// the boundaries between writes, so when the child is killed the parent may
+/// have read only part of a section. The parent uses [`complete_sections()`]
+/// to drop it.
+#[derive(Debug)]
+pub struct SectionWriter<W: Write> {
+    /// Where complete sections are written.
+    inner: W,
+
+    /// Output that has been written but not flushed.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> SectionWriter<W> {
+    /// Create a new `SectionWriter` that writes complete sections to `inner`.
+    pub const fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for SectionWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.push(SECTION_END);
+            self.inner.write_all(&self.pending)?;
+            self.pending.clear();
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for SectionWriter<W> {
+    /// Write whatever is left when the child finishes.
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Get the output of the complete sections from the output of a child started
+/// by [`Strategy::Process`], without the [`SECTION_END`] bytes.
+pub fn complete_sections(output: &[u8]) -> Vec<u8> {
+    let end = output
+        .iter()
+        .rposition(|&byte| byte == SECTION_END)
+        .map_or(0, |i| i.saturating_add(1));
+    output[..end]
+        .iter()
+        .copied()
+        .filter(|&byte| byte != SECTION_END)
+        .collect()
+}
+
+/// Run `summarize` in a worker thread, and wait up to `timeout` for it.
+///
+/// `summarize` is passed a [`ShellWriter`] that writes to a [`StagedBuffer`].
+/// If it finishes in time, the buffer is written to stdout. Otherwise, the
+/// flushed part of the buffer is passed to [`write_timeout()`].
+///
+/// The worker thread is not stopped on timeout; the process is expected to
+/// exit soon afterward.
+pub fn run_in_thread<F>(
+    target: &Target,
+    timeout: Duration,
+    summarize: F,
+) -> Condition
+where
+    F: FnOnce(&ShellWriter<StagedBuffer>) -> Condition + Send + 'static,
+{
+    let buffer = StagedBuffer::default();
+    let committed = Arc::clone(&buffer.committed);
+    let (sender, receiver) = mpsc::channel();
+    let worker_target = target.clone();
+    thread::spawn(move || {
+        let out = worker_target.writer(buffer);
+        let condition = summarize(&out);
+        out.flush();
+        // The receiver is gone if we timed out.
+        let _ = sender.send(condition);
+    });
+
+    let result = receiver.recv_timeout(timeout);
+    let committed = committed.lock().unwrap();
+    if let Ok(condition) = result {
+        io::stdout().write_all(&committed).unwrap();
+        condition
+    } else {
+        write_timeout(target, timeout, &committed);
+        Condition::Error
+    }
+}
+
+/// Run this executable again in a child process, and wait up to `timeout`
+/// for it.
+///
+/// The child is passed the same arguments and [`CHILD_ENV`] so that it doesn’t
+/// apply the timeout again. It reports its [`Condition`] in its exit code, and
+/// writes its output with a [`SectionWriter`] so that only complete sections
+/// are used.
+///
+/// If `stdin` is passed, it is written to the child’s stdin. Otherwise, the
+/// child inherits stdin.
+///
+/// If `repository` is passed, the child only summarizes the repository at
+/// that index (see [`CHILD_REPOSITORY_ENV`]).
+///
+/// If the child finishes in time, its output is written to stdout. Otherwise,
+/// it is killed and the sections it completed are passed to
+/// [`write_timeout()`].
+pub fn run_in_process(
+    target: &Target,
+    timeout: Duration,
+    stdin: Option<&[u8]>,
+    repository: Option<usize>,
+) -> Condition {
+    let out = target.writer(io::stdout());
+    let child = std::env::current_exe().and_then(|executable| {
+        let mut command = Command::new(executable);
+        if let Some(index) = repository {
+            command.env(CHILD_REPOSITORY_ENV, index.to_string());
+        }
+        let mut child = command
+            .args(std::env::args_os().skip(1))
+            .env(CHILD_ENV, "1")
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            })
+            .stdout(Stdio::piped())
+            .spawn()?;
+        // The child reads all of stdin before it outputs anything.
+        if let (Some(input), Some(mut child_stdin)) =
+            (stdin, child.stdin.take())
+        {
+            if let Err(error) = child_stdin.write_all(input) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error);
+            }
+        }
+        Ok(child)
+    });
+    let mut child = match child {
+        Ok(child) => child,
+        Err(error) => {
+            out.write_var(vars::REPO_STATE, "Error");
+            ErrorInfo::from(Error::Io(error))
+                .write_as(&out, vars::REPO_ERROR)
+                .unwrap();
+            return Condition::Error;
+        }
+    };
+
+    let mut stdout = child.stdout.take().expect("stdout should be piped");
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let reader_output = Arc::clone(&output);
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut chunk = [0; 4096];
+        let result = loop {
+            match stdout.read(&mut chunk) {
+                Ok(0) => break Ok(()),
+                Ok(length) => reader_output
+                    .lock()
+                    .unwrap()
+                    .extend_from_slice(&chunk[..length]),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error),
+            }
+        };
+        let _ = sender.send(result);
+    });
+
+    let result = receiver.recv_timeout(timeout);
+    let output = output.lock().unwrap();
+    match result {
+        Ok(Ok(())) => {
+            io::stdout().write_all(&complete_sections(&output)).unwrap();
+            child
+                .wait()
+                .ok()
+                .and_then(|status| status.code())
+                .and_then(Condition::from_exit_code)
+                .unwrap_or(Condition::Error)
+        }
+        Ok(Err(error)) => {
+            let _ = child.kill();
+            out.write_var(vars::REPO_STATE, "Error");
+            ErrorInfo::from(Error::Io(error))
+                .write_as(&out, vars::REPO_ERROR)
+                .unwrap();
+            Condition::Error
+        }
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            write_timeout(target, timeout, &complete_sections(&output));
+            Condition::Error
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Report how long parts of a summary take.
+//!
+//! This is a minimal version of the spans in the `tracing` crate. Nothing is
+//! recorded unless [`enable()`] has been called, in which case each [`Span`]
+//! writes its name and how long it was open to stderr when it’s dropped.
+//! Nested spans are indented.
+//!
+//! ```rust
+//! use git_status_vars::trace;
+//! let _span = trace::span("outer");
+//! {
+//!     let _span = trace::span("inner");
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// Whether spans should be reported.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// How many spans are open in the current thread.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Report spans to stderr from now on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Check if spans are reported.
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Open a span. It is reported when it’s dropped.
+#[must_use]
+pub fn span(name: &'static str) -> Span {
+    Span::new(name)
+}
+
+/// A part of a summary being timed. See [`span()`].
+#[derive(Debug)]
+pub struct Span {
+    /// The name of the span, and when it was opened, if spans are reported.
+    inner: Option<(&'static str, Instant)>,
+}
+
+impl Span {
+    /// Open a span. See [`span()`].
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        if !is_enabled() {
+            return Self { inner: None };
+        }
+        DEPTH.with(|depth| depth.set(depth.get().saturating_add(1)));
+        Self {
+            inner: Some((name, Instant::now())),
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some((name, start)) = self.inner else {
+            return;
+        };
+        let elapsed = start.elapsed();
+        let depth = DEPTH.with(|depth| {
+            let open = depth.get().saturating_sub(1);
+            depth.set(open);
+            open
+        });
+        // Ignore errors; tracing shouldn’t cause a failure.
+        let _ = writeln!(
+            io::stderr(),
+            "trace: {:indent$}{name} {}us",
+            "",
+            elapsed.as_micros(),
+            indent = depth.saturating_mul(2),
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! The value of a variable.
+
+use std::fmt;
+
+/// The value of a variable in the output.
+///
+/// The shell output renders every value as a string with [`fmt::Display`],
+/// but other formats can keep the type, e.g. to output real numbers and
+/// booleans in JSON.
+///
+/// ```rust
+/// use git_status_vars::Value;
+/// assert_eq!(Value::from(3_usize), Value::Int(3));
+/// assert_eq!(Value::from(None::<usize>), Value::Null);
+/// assert_eq!(Value::from(vec!["a", "b"]).to_string(), "a b");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    /// A string, e.g. a reference name.
+    Str(String),
+
+    /// An integer, e.g. a count of files.
+    Int(i64),
+
+    /// A boolean, e.g. whether the repository is bare.
+    Bool(bool),
+
+    /// No value, e.g. `head_ahead` when there is no upstream. This is
+    /// rendered as an empty string in the shell output.
+    Null,
+
+    /// A list of values. This is rendered as the values separated by spaces
+    /// in the shell output.
+    List(Vec<Self>),
+}
+
+impl Value {
+    /// Get the string, if this is a [`Value::Str`].
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the integer, if this is a [`Value::Int`].
+    #[must_use]
+    pub const fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Get the boolean, if this is a [`Value::Bool`].
+    #[must_use]
+    pub const fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Check if this is [`Value::Null`].
+    #[must_use]
+    pub const fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Str(value) => f.write_str(value),
+            Self::Int(value) => value.fmt(f),
+            Self::Bool(value) => value.fmt(f),
+            Self::Null => Ok(()),
+            Self::List(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    value.fmt(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_owned())
+    }
+}
+
+impl From<&String> for Value {
+    fn from(value: &String) -> Self {
+        Self::Str(value.clone())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<usize> for Value {
+    /// Counts are `usize`. They can’t realistically exceed [`i64::MAX`], but
+    /// if they do they are clamped to it.
+    fn from(value: usize) -> Self {
+        Self::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<u64> for Value {
+    /// Clamped to [`i64::MAX`] like `usize`.
+    fn from(value: u64) -> Self {
+        Self::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl<T: Into<Self>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl<T: Into<Self>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Self::List(values.into_iter().map(Into::into).collect())
+    }
+}
@@ -0,0 +1,307 @@
+//! The names of the output variables, for use instead of string literals.
+//!
+//! These are the full names of the variables for a single repository, as
+//! returned by [`RepoSummary::iter_vars()`][crate::RepoSummary::iter_vars].
+//! See [`schema::VARS`][crate::schema::VARS] for what each one means.
+//!
+//! ```no_run
+//! use git_status_vars::{summarize_path, vars, SummarizeOptions};
+//! use std::path::Path;
+//!
+//! let options = SummarizeOptions::default();
+//! let summary = summarize_path(Path::new("."), &options).unwrap();
+//! for (name, value) in summary.iter_vars() {
+//!     if name == vars::UNSTAGED_COUNT {
+//!         println!("{value} unstaged changes");
+//!     }
+//! }
+//! ```
+
+/// `schema_version`: the version of the set of variables.
+pub const SCHEMA_VERSION: &str = "schema_version";
+
+/// `repo_state`: the state of the repository, e.g. `Clean`.
+pub const REPO_STATE: &str = "repo_state";
+
+/// `ps1_state`: the state as `__git_ps1` shows it, e.g. `|MERGING`, or empty
+/// if the repository is clean.
+///
+/// This includes the leading `|`, unlike
+/// [`ps1_state()`][crate::git2_interop::ps1_state].
+pub const PS1_STATE: &str = "ps1_state";
+
+/// `repo_workdir`: the working tree, if there is one.
+pub const REPO_WORKDIR: &str = "repo_workdir";
+
+/// `repo_workdir_raw`: the working tree escaped with
+/// [`escape_bytes()`][crate::escape_bytes], if it isn’t valid UTF-8.
+pub const REPO_WORKDIR_RAW: &str = "repo_workdir_raw";
+
+/// `repo_workdir_logical`: the working tree as it was reached, without
+/// resolving symlinks.
+pub const REPO_WORKDIR_LOGICAL: &str = "repo_workdir_logical";
+
+/// `repo_workdir_physical`: the working tree with symlinks resolved.
+pub const REPO_WORKDIR_PHYSICAL: &str = "repo_workdir_physical";
+
+/// `repo_empty`: whether the repository is empty.
+pub const REPO_EMPTY: &str = "repo_empty";
+
+/// `repo_bare`: whether the repository is bare.
+pub const REPO_BARE: &str = "repo_bare";
+
+/// `fetch_age_seconds`: how long ago the repository was last fetched, or empty
+/// if it has never been fetched.
+pub const FETCH_AGE_SECONDS: &str = "fetch_age_seconds";
+
+/// `repo_error`: the error that prevented summarizing the repository.
+pub const REPO_ERROR: &str = "repo_error";
+
+/// `repo_error_code`: the code of `repo_error`, e.g. `not_found`.
+pub const REPO_ERROR_CODE: &str = "repo_error_code";
+
+/// `repo_error_class`: the class of `repo_error`, e.g. `reference`.
+pub const REPO_ERROR_CLASS: &str = "repo_error_class";
+
+/// `fetch_error`: the error fetching the remote of the upstream.
+pub const FETCH_ERROR: &str = "fetch_error";
+
+/// `fetch_error_code`: the code of `fetch_error`, e.g. `auth`.
+pub const FETCH_ERROR_CODE: &str = "fetch_error_code";
+
+/// `fetch_error_class`: the class of `fetch_error`, e.g. `net`.
+pub const FETCH_ERROR_CLASS: &str = "fetch_error_class";
+
+/// `fetch_timeout`: whether the fetch section ran out of time.
+pub const FETCH_TIMEOUT: &str = "fetch_timeout";
+
+/// `head_ref_length`: the number of references from `HEAD` to a commit.
+pub const HEAD_REF_LENGTH: &str = "head_ref_length";
+
+/// `head_ref{n}_name`: the full name of a reference. Use with [`nth()`].
+pub const HEAD_REF_NAME: &str = "head_ref{n}_name";
+
+/// `head_ref{n}_name_raw`: the full name of a reference escaped with
+/// [`escape_bytes()`][crate::escape_bytes], if it isn’t valid UTF-8. Use with
+/// [`nth()`].
+pub const HEAD_REF_NAME_RAW: &str = "head_ref{n}_name_raw";
+
+/// `head_ref{n}_short`: the short name of a reference. Use with [`nth()`].
+pub const HEAD_REF_SHORT: &str = "head_ref{n}_short";
+
+/// `head_ref{n}_kind`: the kind of a reference. Use with [`nth()`].
+pub const HEAD_REF_KIND: &str = "head_ref{n}_kind";
+
+/// `head_ref{n}_error`: the error resolving a reference. Use with [`nth()`].
+pub const HEAD_REF_ERROR: &str = "head_ref{n}_error";
+
+/// `head_ref{n}_error_code`: the code of `head_ref{n}_error`, e.g. `not_found`.
+/// Use with [`nth()`].
+pub const HEAD_REF_ERROR_CODE: &str = "head_ref{n}_error_code";
+
+/// `head_ref{n}_error_class`: the class of `head_ref{n}_error`, e.g.
+/// `reference`. Use with [`nth()`].
+pub const HEAD_REF_ERROR_CLASS: &str = "head_ref{n}_error_class";
+
+/// `head_hash`: the commit `HEAD` points to.
+pub const HEAD_HASH: &str = "head_hash";
+
+/// `head_unborn`: whether `HEAD` points to a branch with no commits yet.
+pub const HEAD_UNBORN: &str = "head_unborn";
+
+/// `head_error`: the error that prevented computing the head section.
+pub const HEAD_ERROR: &str = "head_error";
+
+/// `head_error_code`: the code of `head_error`, e.g. `not_found`.
+pub const HEAD_ERROR_CODE: &str = "head_error_code";
+
+/// `head_error_class`: the class of `head_error`, e.g. `reference`.
+pub const HEAD_ERROR_CLASS: &str = "head_error_class";
+
+/// `head_timeout`: whether the head section ran out of time.
+pub const HEAD_TIMEOUT: &str = "head_timeout";
+
+/// `head_ahead`: how many commits `HEAD` is ahead of its upstream.
+pub const HEAD_AHEAD: &str = "head_ahead";
+
+/// `head_behind`: how many commits `HEAD` is behind its upstream.
+pub const HEAD_BEHIND: &str = "head_behind";
+
+/// `head_ahead_exact`: whether `head_ahead` and `head_behind` are exact, i.e.
+/// the repository isn’t shallow and has no grafts or replace references.
+pub const HEAD_AHEAD_EXACT: &str = "head_ahead_exact";
+
+/// `head_upstream_error`: the error comparing `HEAD` with its upstream.
+pub const HEAD_UPSTREAM_ERROR: &str = "head_upstream_error";
+
+/// `head_upstream_error_code`: the code of `head_upstream_error`, e.g.
+/// `not_found`.
+pub const HEAD_UPSTREAM_ERROR_CODE: &str = "head_upstream_error_code";
+
+/// `head_upstream_error_class`: the class of `head_upstream_error`, e.g.
+/// `reference`.
+pub const HEAD_UPSTREAM_ERROR_CLASS: &str = "head_upstream_error_class";
+
+/// `upstream_error`: the error that prevented computing the upstream section.
+pub const UPSTREAM_ERROR: &str = "upstream_error";
+
+/// `upstream_error_code`: the code of `upstream_error`, e.g. `not_found`.
+pub const UPSTREAM_ERROR_CODE: &str = "upstream_error_code";
+
+/// `upstream_error_class`: the class of `upstream_error`, e.g. `reference`.
+pub const UPSTREAM_ERROR_CLASS: &str = "upstream_error_class";
+
+/// `upstream_timeout`: whether the upstream section ran out of time.
+pub const UPSTREAM_TIMEOUT: &str = "upstream_timeout";
+
+/// `untracked_count`: the number of untracked files.
+pub const UNTRACKED_COUNT: &str = "untracked_count";
+
+/// `unstaged_count`: the number of files with unstaged changes.
+pub const UNSTAGED_COUNT: &str = "unstaged_count";
+
+/// `staged_count`: the number of files with staged changes.
+pub const STAGED_COUNT: &str = "staged_count";
+
+/// `conflicted_count`: the number of conflicted files.
+pub const CONFLICTED_COUNT: &str = "conflicted_count";
+
+/// `ignored_count`: the number of ignored files.
+pub const IGNORED_COUNT: &str = "ignored_count";
+
+/// `counts_truncated`: whether counting stopped early.
+pub const COUNTS_TRUNCATED: &str = "counts_truncated";
+
+/// `untracked_truncated`: whether untracked directories were counted as one
+/// file each.
+pub const UNTRACKED_TRUNCATED: &str = "untracked_truncated";
+
+/// `repo_locked`: whether another process had the index locked, so it
+/// couldn’t be updated.
+pub const REPO_LOCKED: &str = "repo_locked";
+
+/// `counts_error`: the error that prevented counting changes.
+pub const COUNTS_ERROR: &str = "counts_error";
+
+/// `counts_error_code`: the code of `counts_error`, e.g. `not_found`.
+pub const COUNTS_ERROR_CODE: &str = "counts_error_code";
+
+/// `counts_error_class`: the class of `counts_error`, e.g. `reference`.
+pub const COUNTS_ERROR_CLASS: &str = "counts_error_class";
+
+/// `counts_timeout`: whether the counts section ran out of time.
+pub const COUNTS_TIMEOUT: &str = "counts_timeout";
+
+/// `counts_skipped`: whether counting was skipped.
+pub const COUNTS_SKIPPED: &str = "counts_skipped";
+
+/// `stash_count`: the number of stashes.
+pub const STASH_COUNT: &str = "stash_count";
+
+/// `stash_error`: the error that prevented counting stashes.
+pub const STASH_ERROR: &str = "stash_error";
+
+/// `stash_error_code`: the code of `stash_error`, e.g. `not_found`.
+pub const STASH_ERROR_CODE: &str = "stash_error_code";
+
+/// `stash_error_class`: the class of `stash_error`, e.g. `reference`.
+pub const STASH_ERROR_CLASS: &str = "stash_error_class";
+
+/// `stash_timeout`: whether the stash section ran out of time.
+pub const STASH_TIMEOUT: &str = "stash_timeout";
+
+/// `superproject_workdir`: the working tree of the superproject, if this is a
+/// submodule.
+pub const SUPERPROJECT_WORKDIR: &str = "superproject_workdir";
+
+/// `superproject_path`: the path of this submodule within the superproject.
+pub const SUPERPROJECT_PATH: &str = "superproject_path";
+
+/// `superproject_drifted`: whether the superproject would show this submodule
+/// as changed.
+pub const SUPERPROJECT_DRIFTED: &str = "superproject_drifted";
+
+/// `superproject_error`: the error that prevented finding the superproject.
+pub const SUPERPROJECT_ERROR: &str = "superproject_error";
+
+/// `superproject_error_code`: the code of `superproject_error`, e.g.
+/// `not_found`.
+pub const SUPERPROJECT_ERROR_CODE: &str = "superproject_error_code";
+
+/// `superproject_error_class`: the class of `superproject_error`, e.g.
+/// `submodule`.
+pub const SUPERPROJECT_ERROR_CLASS: &str = "superproject_error_class";
+
+/// `superproject_timeout`: whether the superproject section ran out of time.
+pub const SUPERPROJECT_TIMEOUT: &str = "superproject_timeout";
+
+/// `remote_tip_hash`: the commit the upstream branch points to on the remote.
+pub const REMOTE_TIP_HASH: &str = "remote_tip_hash";
+
+/// `head_behind_remote_tip`: how many commits `HEAD` is behind the upstream
+/// branch on the remote.
+pub const HEAD_BEHIND_REMOTE_TIP: &str = "head_behind_remote_tip";
+
+/// `remote_error`: the error checking the upstream branch on the remote.
+pub const REMOTE_ERROR: &str = "remote_error";
+
+/// `remote_error_code`: the code of `remote_error`, e.g. `not_found`.
+pub const REMOTE_ERROR_CODE: &str = "remote_error_code";
+
+/// `remote_error_class`: the class of `remote_error`, e.g. `net`.
+pub const REMOTE_ERROR_CLASS: &str = "remote_error_class";
+
+/// `remote_timeout`: whether the remote section ran out of time.
+pub const REMOTE_TIMEOUT: &str = "remote_timeout";
+
+/// `color_hint`: the color to show the repository in, e.g. `yellow` if it’s
+/// dirty.
+pub const COLOR_HINT: &str = "color_hint";
+
+/// `color_ansi`: the ANSI escape sequence for `color_hint`.
+pub const COLOR_ANSI: &str = "color_ansi";
+
+/// `glyphs`: a compact summary of the counts, e.g. `●2 ✚1 ↑2`.
+pub const GLYPHS: &str = "glyphs";
+
+/// `timing_fetch_us`: microseconds spent on the fetch section.
+pub const TIMING_FETCH_US: &str = "timing_fetch_us";
+
+/// `timing_head_us`: microseconds spent on the head section.
+pub const TIMING_HEAD_US: &str = "timing_head_us";
+
+/// `timing_upstream_us`: microseconds spent on the upstream section.
+pub const TIMING_UPSTREAM_US: &str = "timing_upstream_us";
+
+/// `timing_counts_us`: microseconds spent on the counts section.
+pub const TIMING_COUNTS_US: &str = "timing_counts_us";
+
+/// `timing_stash_us`: microseconds spent on the stash section.
+pub const TIMING_STASH_US: &str = "timing_stash_us";
+
+/// `timing_superproject_us`: microseconds spent on the superproject section.
+pub const TIMING_SUPERPROJECT_US: &str = "timing_superproject_us";
+
+/// `timing_remote_us`: microseconds spent on the remote section.
+pub const TIMING_REMOTE_US: &str = "timing_remote_us";
+
+/// `repo_count`: the number of repositories, when summarizing more than one.
+pub const REPO_COUNT: &str = "repo_count";
+
+/// `repo_error_count`: the number of repositories that had errors or weren’t
+/// found, when summarizing more than one.
+pub const REPO_ERROR_COUNT: &str = "repo_error_count";
+
+/// Get the name of a `head_ref{n}_*` variable for reference `n`, counting
+/// from 1.
+///
+/// ```rust
+/// use git_status_vars::vars::{nth, HEAD_REF_KIND};
+///
+/// assert_eq!(nth(HEAD_REF_KIND, 1), "head_ref1_kind");
+/// ```
+#[must_use]
+#[allow(clippy::literal_string_with_formatting_args)] // Not a format string.
+pub fn nth(name: &str, n: usize) -> String {
+    name.replace("{n}", &n.to_string())
+}
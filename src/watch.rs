@@ -0,0 +1,123 @@
+//! Output summaries again and again until killed.
+//!
+//! With `watch`, the repository is summarized every `--interval`. Each summary
+//! is the same as the output of `summary`, followed by an empty line, so that a
+//! status bar or editor can read one summary at a time from a pipe.
+//!
+//! With `--debounce`, a summary that differs from the one before it is held
+//! back until the variables have stayed the same for that long, so that a
+//! burst of changes (e.g. a build) produces one summary once it’s over. While
+//! a summary is held back, the repository is checked every `--debounce` if
+//! that’s shorter than `--interval`, so that it’s output soon after it
+//! settles.
+//!
+//! With `--changed-only`, a summary is only output if it differs from the last
+//! one that was output, so the stream can be used as a feed of changes.
+//!
+//! Summaries are compared without [`VOLATILE_VARS`].
+//!
+//! With `--fsmonitor`, each summary asks the `core.fsmonitor` hook (e.g.
+//! `query-watchman`) or git’s builtin fsmonitor daemon (`core.fsmonitor=true`)
+//! what changed since the last one, so a watcher that is already running is
+//! used instead of checking every file. The daemon is only supported on Unix.
+//!
+//! This polls; it doesn’t watch the file system. `--debounce` only
+//! approximates coalescing file system events, since changes are only noticed
+//! when the repository is checked.
+
+use crate::params::{Params, WatchParams};
+use crate::timeout;
+use git_status_vars::{schema, vars};
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Instant;
+
+/// Variables that change without the repository changing, and so are ignored
+/// when comparing summaries.
+const VOLATILE_VARS: [&str; 1] = [vars::FETCH_AGE_SECONDS];
+
+/// Summarize the repository every `--interval` (or more often while a change
+/// is held back by `--debounce`) until killed or stdout is closed.
+pub fn watch(watch: &WatchParams) -> ExitCode {
+    let params = &watch.params;
+    let target = timeout::Target {
+        prefix: params.prefix.clone().unwrap_or_default(),
+        group: None,
+        dialect: params.dialect,
+    };
+    let volatile = volatile_lines(&target);
+    let mut stdout = io::stdout();
+    let mut previous: Option<Vec<u8>> = None;
+    let mut emitted: Option<Vec<u8>> = None;
+    // When the output last changed, or `None` if it hasn’t since we started.
+    let mut changed_at: Option<Instant> = None;
+    loop {
+        let output = render(params, &target);
+        let key = comparable(&output, &volatile);
+        if previous.as_ref().is_some_and(|previous| *previous != key) {
+            changed_at = Some(Instant::now());
+        }
+        let settled = changed_at
+            .map_or(true, |changed_at| changed_at.elapsed() >= watch.debounce);
+        let repeated = watch.changed_only && emitted.as_ref() == Some(&key);
+        if settled && !repeated {
+            if stdout
+                .write_all(&output)
+                .and_then(|()| stdout.flush())
+                .is_err()
+            {
+                // Whoever was reading has gone away.
+                return ExitCode::SUCCESS;
+            }
+            emitted = Some(key.clone());
+        }
+        previous = Some(key);
+        if settled {
+            thread::sleep(watch.interval);
+        } else {
+            thread::sleep(watch.interval.min(watch.debounce));
+        }
+    }
+}
+
+/// Summarize the repository in `params` and return the output, followed by an
+/// empty line.
+fn render(params: &Params, target: &timeout::Target) -> Vec<u8> {
+    let mut output = vec![];
+    {
+        let out = target.writer(&mut output);
+        out.write_value(
+            vars::SCHEMA_VERSION,
+            u64::from(schema::SCHEMA_VERSION),
+        );
+        crate::summarize(params, &out);
+        out.write_blank_line();
+    }
+    output
+}
+
+/// Get the start of the lines that set [`VOLATILE_VARS`] for `target`, e.g.
+/// `fetch_age_seconds=`, up to where the value starts.
+fn volatile_lines(target: &timeout::Target) -> Vec<Vec<u8>> {
+    let value = format!("{}\n", target.dialect.quote(""));
+    VOLATILE_VARS
+        .iter()
+        .map(|var| {
+            let mut line = vec![];
+            target.writer(&mut line).write_var(var, "");
+            line.truncate(line.len().saturating_sub(value.len()));
+            line
+        })
+        .collect()
+}
+
+/// Get the lines of `output` that don’t start with one of `volatile`.
+fn comparable(output: &[u8], volatile: &[Vec<u8>]) -> Vec<u8> {
+    output
+        .split_inclusive(|&byte| byte == b'\n')
+        .filter(|line| !volatile.iter().any(|start| line.starts_with(start)))
+        .flatten()
+        .copied()
+        .collect()
+}
@@ -0,0 +1,94 @@
+//! Information about linked worktrees.
+
+use crate::{trace, Error};
+use git2::{Repository, WorktreeLockStatus};
+use std::path::PathBuf;
+
+/// A linked worktree. See [`worktree_info()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorktreeSummary {
+    /// The name of the worktree, e.g. for `git worktree lock`.
+    pub name: String,
+
+    /// The path of the worktree’s working tree.
+    pub path: PathBuf,
+
+    /// The branch checked out in the worktree (e.g. `"main"`), or `None` if
+    /// `HEAD` is detached or the worktree could not be opened.
+    pub branch: Option<String>,
+
+    /// Whether the worktree is locked.
+    pub locked: bool,
+
+    /// The reason the worktree was locked, if one was given.
+    pub lock_reason: Option<String>,
+
+    /// Whether `git worktree prune` would remove the worktree, e.g. because its
+    /// working tree was deleted.
+    pub prunable: bool,
+}
+
+/// Get information about the linked worktrees of a repository.
+///
+/// Worktrees are sorted by name, and the main worktree isn’t included. This
+/// works the same whether `repository` is the main worktree, a linked
+/// worktree, or bare.
+///
+/// ```no_run
+/// use git_status_vars::git2_interop::worktree_info;
+/// use git2::Repository;
+///
+/// let repository = Repository::open_from_env().unwrap();
+/// for worktree in worktree_info(&repository).unwrap() {
+///     let branch = worktree.branch.as_deref().unwrap_or("(detached)");
+///     println!("{} {branch}", worktree.path.display());
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This will return an [`Error`] if the worktrees could not be listed or
+/// one of them could not be read.
+pub fn worktree_info(
+    repository: &Repository,
+) -> Result<Vec<WorktreeSummary>, Error> {
+    let _span = trace::span("worktree_info");
+    let names = repository.worktrees()?;
+    let mut names: Vec<&str> = names.iter().flatten().collect();
+    names.sort_unstable();
+    names
+        .into_iter()
+        .map(|name| {
+            let worktree = repository.find_worktree(name)?;
+            let lock_reason = match worktree.is_locked()? {
+                // git writes the reason with a trailing newline.
+                WorktreeLockStatus::Locked(reason) => Some(
+                    reason
+                        .map(|reason| reason.trim_end_matches('\n').to_owned()),
+                ),
+                WorktreeLockStatus::Unlocked => None,
+            };
+            Ok(WorktreeSummary {
+                name: name.to_owned(),
+                path: worktree.path().to_path_buf(),
+                branch: worktree_branch(&worktree),
+                locked: lock_reason.is_some(),
+                lock_reason: lock_reason.flatten(),
+                prunable: worktree.is_prunable(None)?,
+            })
+        })
+        .collect()
+}
+
+/// Get the branch checked out in a worktree, if any.
+fn worktree_branch(worktree: &git2::Worktree) -> Option<String> {
+    let repository = Repository::open_from_worktree(worktree).ok()?;
+    let head = repository.find_reference("HEAD").ok()?;
+    let target = head.symbolic_target()?;
+    Some(
+        target
+            .strip_prefix("refs/heads/")
+            .unwrap_or(target)
+            .to_owned(),
+    )
+}
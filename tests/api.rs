@@ -0,0 +1,884 @@
+//! Test the library API.
+
+use git_status_vars::format::{OutputFormat, ShellFormat};
+use git_status_vars::git2_interop::summarize_repository;
+use git_status_vars::git2_interop::superproject_info;
+use git_status_vars::git2_interop::worktree_info;
+use git_status_vars::git2_interop::{ahead_behind, upstream_difference_for};
+use git_status_vars::git2_interop::{count_changes, count_changes_with};
+use git_status_vars::git2_interop::{
+    count_stashes, stash_info, submodule_info,
+};
+use git_status_vars::git2_interop::{status_options, summarize, with_config};
+use git_status_vars::schema::{self, SCHEMA_VERSION};
+use git_status_vars::{collect_vars, ChangeCounters, ChangeKind, Value};
+use git_status_vars::{summarize_path, summarize_paths};
+use git_status_vars::{vars, CancelToken, Error, FmtWriter, SummarizeOptions};
+use git_status_vars::{Dialect, ShellWriter, UntrackedFiles};
+use git_status_vars::{ErrorInfo, ObjectId, ERROR_CLASSES, ERROR_CODES};
+use std::time::Duration;
+use target_test_dir::with_test_dir;
+
+// We don’t use everything in helpers.
+#[allow(dead_code)]
+mod helpers;
+
+#[test]
+#[with_test_dir]
+fn iter_vars() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    std::fs::write(root.join("repo").join("c"), "1c").unwrap();
+
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    let summary = summarize(&repository, &SummarizeOptions::default()).unwrap();
+    let vars: Vec<_> = summary.iter_vars().collect();
+
+    let get = |name: &str| {
+        vars.iter()
+            .find(|(var, _)| var == name)
+            .map(|(_, value)| value.clone())
+    };
+    assert_eq!(get(vars::REPO_STATE), Some(Value::from("Clean")));
+    assert_eq!(get(vars::REPO_BARE), Some(Value::Bool(false)));
+    assert_eq!(
+        get(&vars::nth(vars::HEAD_REF_SHORT, 1)),
+        Some(Value::from("main"))
+    );
+    assert_eq!(get(vars::HEAD_AHEAD), Some(Value::Null));
+    assert_eq!(get(vars::UNTRACKED_COUNT), Some(Value::Int(1)));
+    assert_eq!(get(vars::IGNORED_COUNT), None);
+
+    // The order and rendered values match the shell output.
+    let expected = ShellFormat::new("").render_to_string(&summary).unwrap();
+    let actual: Vec<_> = vars
+        .iter()
+        .map(|(var, value)| {
+            format!("{var}={}", git_status_vars::shell_quote(value))
+        })
+        .collect();
+    assert_eq!(actual, expected.lines().collect::<Vec<_>>());
+}
+
+#[test]
+#[with_test_dir]
+fn schema_vars() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    let options = SummarizeOptions {
+        include_ignored: true,
+        ..SummarizeOptions::default()
+    };
+    let summary = summarize(&repository, &options).unwrap();
+    for (name, _) in summary.iter_vars() {
+        assert!(schema::find_var(&name).is_some(), "{name} not in schema");
+    }
+}
+
+#[test]
+fn schema_names() {
+    // If this fails, update this, and make sure SCHEMA_VERSION is incremented
+    // once for the next release.
+    let names: Vec<_> = schema::VARS.iter().map(|var| var.name).collect();
+    assert_eq!(
+        (SCHEMA_VERSION, names),
+        (
+            1,
+            vec![
+                "schema_version",
+                "repo_state",
+                "ps1_state",
+                "repo_workdir",
+                "repo_workdir_raw",
+                "repo_workdir_logical",
+                "repo_workdir_physical",
+                "repo_empty",
+                "repo_bare",
+                "fetch_age_seconds",
+                "repo_error",
+                "repo_error_code",
+                "repo_error_class",
+                "fetch_error",
+                "fetch_error_code",
+                "fetch_error_class",
+                "fetch_timeout",
+                "head_ref_length",
+                "head_ref{n}_name",
+                "head_ref{n}_name_raw",
+                "head_ref{n}_short",
+                "head_ref{n}_kind",
+                "head_ref{n}_error",
+                "head_ref{n}_error_code",
+                "head_ref{n}_error_class",
+                "head_hash",
+                "head_unborn",
+                "head_error",
+                "head_error_code",
+                "head_error_class",
+                "head_timeout",
+                "head_ahead",
+                "head_behind",
+                "head_ahead_exact",
+                "head_upstream_error",
+                "head_upstream_error_code",
+                "head_upstream_error_class",
+                "upstream_error",
+                "upstream_error_code",
+                "upstream_error_class",
+                "upstream_timeout",
+                "untracked_count",
+                "unstaged_count",
+                "staged_count",
+                "conflicted_count",
+                "ignored_count",
+                "counts_truncated",
+                "untracked_truncated",
+                "repo_locked",
+                "counts_error",
+                "counts_error_code",
+                "counts_error_class",
+                "counts_timeout",
+                "counts_skipped",
+                "stash_count",
+                "stash_error",
+                "stash_error_code",
+                "stash_error_class",
+                "stash_timeout",
+                "superproject_workdir",
+                "superproject_path",
+                "superproject_drifted",
+                "superproject_error",
+                "superproject_error_code",
+                "superproject_error_class",
+                "superproject_timeout",
+                "remote_tip_hash",
+                "head_behind_remote_tip",
+                "remote_error",
+                "remote_error_code",
+                "remote_error_class",
+                "remote_timeout",
+                "color_hint",
+                "color_ansi",
+                "glyphs",
+                "timing_fetch_us",
+                "timing_head_us",
+                "timing_upstream_us",
+                "timing_counts_us",
+                "timing_stash_us",
+                "timing_superproject_us",
+                "timing_remote_us",
+            ]
+        )
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn cancel() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    let token = CancelToken::new();
+    let options = SummarizeOptions::default().with_cancel(token.clone());
+    summarize(&repository, &options).unwrap();
+
+    token.cancel();
+    assert!(matches!(
+        summarize(&repository, &options),
+        Err(Error::Cancelled)
+    ));
+
+    // A deadline in the past cancels immediately.
+    let options = SummarizeOptions::default()
+        .with_cancel(CancelToken::new().with_timeout(Duration::ZERO));
+    let mut output = String::new();
+    summarize_repository(
+        &ShellWriter::new(FmtWriter::new(&mut output), ""),
+        git2::Repository::open(root.join("repo")),
+        &options,
+    );
+    assert_eq!(
+        output,
+        "repo_state=Error\nrepo_error=Cancelled\nrepo_error_code=cancelled\n\
+        repo_error_class=summary\n"
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn cache_with_cancel_token() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    // Each call gets a new token with a different deadline.
+    let summarize = || {
+        let mut options = SummarizeOptions::default().with_cancel(
+            CancelToken::new().with_timeout(Duration::from_secs(60)),
+        );
+        options.cache = true;
+        let mut output = String::new();
+        summarize_repository(
+            &ShellWriter::new(FmtWriter::new(&mut output), ""),
+            git2::Repository::open(root.join("repo")),
+            &options,
+        );
+        output
+    };
+    let cache = root.join("repo/.git/git-status-vars-cache");
+    let modified = || std::fs::metadata(&cache).unwrap().modified().unwrap();
+
+    let output = summarize();
+    assert!(output.starts_with("repo_state=Clean\n"), "{output}");
+    let first = modified();
+
+    // The token isn’t part of the key, so the cache is used, not rewritten.
+    assert_eq!(summarize(), output);
+    assert_eq!(modified(), first);
+    assert!(!root.join("repo/.git/logs/refs/stash").exists());
+}
+
+#[test]
+#[with_test_dir]
+fn fetch_cancelled() {
+    use git_status_vars::git2_interop::{fetch_upstream, head_info};
+
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+
+    // A remote that never answers.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("git://{}/upstream", listener.local_addr().unwrap());
+    helpers::git(&root, "clone", ["remote", "set-url", "origin", &url])
+        .unwrap();
+
+    let repository = git2::Repository::open(root.join("clone")).unwrap();
+    let head = head_info(&repository);
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert!(matches!(
+        fetch_upstream(&repository, &head, None, Some(&cancel)),
+        Err(Error::Cancelled)
+    ));
+
+    // It gave up before connecting.
+    assert_eq!(
+        listener.accept().unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+}
+
+#[test]
+fn error_info() {
+    let error = git2::Error::new(
+        git2::ErrorCode::UnbornBranch,
+        git2::ErrorClass::FetchHead,
+        "no commits",
+    );
+    let info = ErrorInfo::from(&error);
+    assert_eq!(
+        info,
+        ErrorInfo {
+            message: "no commits".to_owned(),
+            code: "unborn_branch",
+            class: "fetch_head",
+        }
+    );
+    assert!(ERROR_CODES.contains(&info.code));
+    assert!(ERROR_CLASSES.contains(&info.class));
+    assert!(!info.is_empty());
+    assert!(ErrorInfo::default().is_empty());
+
+    let error = Error::InvalidOptions("bad".to_owned());
+    assert_eq!(
+        (error.code(), error.class()),
+        ("invalid_options", "options")
+    );
+    assert!(ERROR_CODES.contains(&error.code()));
+    assert!(ERROR_CLASSES.contains(&error.class()));
+
+    let error = Error::from(git2::Error::new(
+        git2::ErrorCode::UnbornBranch,
+        git2::ErrorClass::FetchHead,
+        "no commits",
+    ));
+    let git = error.git().unwrap();
+    assert_eq!(
+        (git.message(), git.code(), git.class()),
+        ("no commits", "unborn_branch", "fetch_head")
+    );
+    assert_eq!(
+        error.to_string(),
+        "no commits; class=fetch_head; code=unborn_branch"
+    );
+    assert_eq!(ErrorInfo::from(&error), info);
+}
+
+/// Values that need care when quoted, and how each dialect quotes them: sh,
+/// fish, PowerShell, and csh.
+const TRICKY_VALUES: &[(&str, [&str; 4])] = &[
+    ("", ["''", "''", "''", "''"]),
+    ("main", ["main", "main", "'main'", "main"]),
+    ("a b", ["'a b'", "'a b'", "'a b'", "'a b'"]),
+    ("it's", [r"'it'\''s'", r"'it\'s'", "'it''s'", r"'it'\''s'"]),
+    (r#""x""#, [r#"'"x"'"#, r#"'"x"'"#, r#"'"x"'"#, r#"'"x"'"#]),
+    ("$HOME", ["'$HOME'", "'$HOME'", "'$HOME'", "'$HOME'"]),
+    ("`id`", ["'`id`'", "'`id`'", "'`id`'", "'`id`'"]),
+    (r"a\b", [r"'a\b'", r"'a\\b'", r"'a\b'", r"'a\b'"]),
+    ("a\nb", ["'a\nb'", "'a\nb'", "'a\nb'", "'a\\\nb'"]),
+    ("wow!", ["wow!", "'wow!'", "'wow!'", r"'wow\!'"]),
+    ("café ☃", ["'café ☃'", "'café ☃'", "'café ☃'", "'café ☃'"]),
+    ("it’s", ["it’s", "'it’s'", "'it’’s'", "'it’s'"]),
+];
+
+#[test]
+fn dialect_quote() {
+    for (value, expected) in TRICKY_VALUES {
+        for (dialect, expected) in Dialect::ALL.into_iter().zip(expected) {
+            assert_eq!(
+                dialect.quote(value),
+                *expected,
+                "{dialect} quoting {value:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn dialect_sh_round_trip() {
+    for (value, _) in TRICKY_VALUES {
+        let mut buffer: Vec<u8> = vec![];
+        ShellWriter::new(&mut buffer, "").write_var("var", value);
+        let script =
+            format!("{}printf %s \"$var\"", String::from_utf8(buffer).unwrap());
+        let output = std::process::Command::new("sh")
+            .args(["-c", &script])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), *value);
+    }
+}
+
+#[test]
+fn dialect_writer() {
+    let mut buffer: Vec<u8> = vec![];
+    let out = ShellWriter::new(&mut buffer, "")
+        .with_unset_missing(true)
+        .group("repo");
+    for dialect in Dialect::ALL {
+        let out = out.with_dialect(dialect);
+        assert_eq!(out.dialect(), dialect);
+        out.write_var("state", "it's");
+        out.write_missing("error");
+    }
+    drop(out);
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        r"repo_state='it'\''s'
+unset repo_error
+set repo_state 'it\'s'
+set -e repo_error
+$repo_state = 'it''s'
+Remove-Variable repo_error -ErrorAction SilentlyContinue
+set repo_state = 'it'\''s'
+unset repo_error
+"
+    );
+
+    // PowerShell has booleans and numbers.
+    let mut buffer: Vec<u8> = vec![];
+    let out = ShellWriter::new(&mut buffer, "");
+    for dialect in Dialect::ALL {
+        let out = out.with_dialect(dialect);
+        out.write_value("bare", true);
+        out.write_value("count", 3_usize);
+        out.write_value("ahead", None::<usize>);
+    }
+    drop(out);
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        r"bare=true
+count=3
+ahead=''
+set bare true
+set count 3
+set ahead ''
+$bare = $true
+$count = 3
+$ahead = ''
+set bare = true
+set count = 3
+set ahead = ''
+"
+    );
+
+    assert_eq!("fish".parse(), Ok(Dialect::Fish));
+    assert_eq!(
+        "bash".parse::<Dialect>(),
+        Err("expected one of: sh, fish, powershell, csh".to_owned())
+    );
+}
+
+#[test]
+fn clone_writer_without_clone() {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    /// A writer that isn’t `Clone`, like `io::Stdout`.
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn assert_clone<T: Clone>(_: &T) {}
+
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let out = ShellWriter::new(Shared(Arc::clone(&buffer)), "");
+    std::thread::scope(|scope| {
+        for n in 1..=2 {
+            let out = out.clone();
+            scope.spawn(move || out.write_var(format!("n{n}"), n));
+        }
+    });
+    drop(out);
+
+    let buffer = buffer.lock().unwrap().clone();
+    let mut lines: Vec<_> = buffer.split(|c| *c == b'\n').collect();
+    lines.sort_unstable();
+    assert_eq!(lines, [&b""[..], b"n1=1", b"n2=2"]);
+
+    // The same goes for `io::Stdout`.
+    assert_clone(&ShellWriter::default());
+}
+
+#[test]
+#[with_test_dir]
+fn count_changes_with_status_options() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    std::fs::write(root.join("repo").join("a"), "2a").unwrap();
+    std::fs::write(root.join("repo").join("b"), "2b").unwrap();
+    std::fs::create_dir(root.join("repo").join("dir")).unwrap();
+    std::fs::write(root.join("repo").join("dir").join("c"), "1c").unwrap();
+    std::fs::write(root.join("repo").join("dir").join("d"), "1d").unwrap();
+
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    let options = SummarizeOptions::default();
+    let changes = count_changes(&repository, &options).unwrap();
+    assert_eq!((changes.untracked(), changes.unstaged()), (1, 2));
+
+    let mut pathspec_options = status_options(&options);
+    pathspec_options.pathspec("a").pathspec("dir");
+    pathspec_options.recurse_untracked_dirs(true);
+    let changes =
+        count_changes_with(&repository, &options, &mut pathspec_options)
+            .unwrap();
+    assert_eq!((changes.untracked(), changes.unstaged()), (2, 1));
+}
+
+#[test]
+#[with_test_dir]
+fn untracked_files_with_config() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    let untracked_files = |options: SummarizeOptions| {
+        with_config(options, &repository).untracked_files
+    };
+    assert_eq!(untracked_files(SummarizeOptions::default()), None);
+
+    let mut config = repository.config().unwrap();
+    for (value, expected) in [
+        ("no", UntrackedFiles::No),
+        ("normal", UntrackedFiles::Normal),
+        ("all", UntrackedFiles::All),
+        ("false", UntrackedFiles::No),
+        ("yes", UntrackedFiles::Normal),
+    ] {
+        config.set_str("status.showUntrackedFiles", value).unwrap();
+        assert_eq!(
+            untracked_files(SummarizeOptions::default()),
+            Some(expected),
+            "status.showUntrackedFiles={value}"
+        );
+    }
+
+    // Explicit options aren’t replaced.
+    assert_eq!(
+        untracked_files(
+            SummarizeOptions::default()
+                .with_untracked_files(UntrackedFiles::Normal)
+        ),
+        Some(UntrackedFiles::Normal),
+    );
+
+    config
+        .set_str("status.showUntrackedFiles", "bogus")
+        .unwrap();
+    assert_eq!(untracked_files(SummarizeOptions::default()), None);
+}
+
+#[test]
+fn change_counters() {
+    let changes = ChangeCounters::from([1, 2, 3, 4]);
+    assert_eq!(changes.get(ChangeKind::Staged), Some(3));
+    assert_eq!(changes.ignored(), None);
+
+    // Counts are kept in output order.
+    let kinds: Vec<_> = changes.counts.keys().copied().collect();
+    assert_eq!(kinds, ChangeKind::ALL[..4]);
+    let names: Vec<_> = collect_vars(&changes)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            vars::UNTRACKED_COUNT,
+            vars::UNSTAGED_COUNT,
+            vars::STAGED_COUNT,
+            vars::CONFLICTED_COUNT,
+        ]
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn head_accessors() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    let options = SummarizeOptions::default();
+
+    // Unborn branch.
+    let head = summarize(&repository, &options).unwrap().head.unwrap();
+    assert_eq!(head.branch_name(), Some("main"));
+    assert!(!head.is_detached());
+    assert!(head.is_unborn());
+    assert_eq!(head.trail[1].error, ErrorInfo::default());
+    assert_eq!(head.short_hash(8), "");
+
+    helpers::make_commit(&root, "repo", 1);
+    let head = summarize(&repository, &options).unwrap().head.unwrap();
+    assert_eq!(head.branch_name(), Some("main"));
+    assert!(!head.is_detached());
+    assert!(!head.is_unborn());
+    assert_eq!(head.short_hash(8), &head.hash[..8]);
+    assert_eq!(head.short_hash(100), head.hash);
+
+    helpers::git(&root, "repo", ["checkout", "--detach"]).unwrap();
+    let head = summarize(&repository, &options).unwrap().head.unwrap();
+    assert_eq!(head.branch_name(), None);
+    assert!(head.is_detached());
+}
+
+#[test]
+#[with_test_dir]
+fn ahead_behind_branches() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["checkout", "-b", "feature"]).unwrap();
+    helpers::make_commit(&root, "repo", 2);
+    helpers::make_commit(&root, "repo", 3);
+    helpers::git(&root, "repo", ["checkout", "main"]).unwrap();
+    helpers::make_commit(&root, "repo", 4);
+    helpers::git(
+        &root,
+        "repo",
+        ["branch", "--set-upstream-to", "main", "feature"],
+    )
+    .unwrap();
+
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    assert_eq!(
+        ahead_behind(&repository, "feature", "main").unwrap(),
+        (2, 1)
+    );
+    assert_eq!(
+        ahead_behind(&repository, "main", "feature~2").unwrap(),
+        (1, 0)
+    );
+    assert!(matches!(
+        ahead_behind(&repository, "main", "missing"),
+        Err(Error::Git(_))
+    ));
+
+    assert_eq!(
+        upstream_difference_for(&repository, "feature").unwrap(),
+        Some((2, 1))
+    );
+    assert_eq!(
+        upstream_difference_for(&repository, "refs/heads/feature").unwrap(),
+        Some((2, 1))
+    );
+    // main has no upstream.
+    assert!(matches!(
+        upstream_difference_for(&repository, "main"),
+        Err(Error::Git(error)) if error.code() == "not_found"
+    ));
+}
+
+#[test]
+#[with_test_dir]
+fn stashes() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    assert_eq!(stash_info(&repository).unwrap().count(), 0);
+
+    std::fs::write(root.join("repo").join("a"), "2a").unwrap();
+    helpers::git(&root, "repo", ["stash"]).unwrap();
+    helpers::git(&root, "repo", ["checkout", "--detach"]).unwrap();
+    std::fs::write(root.join("repo").join("a"), "3a").unwrap();
+    helpers::git(&root, "repo", ["stash", "push", "-m", "my: message"])
+        .unwrap();
+
+    let stashes = stash_info(&repository).unwrap();
+    assert_eq!(stashes.count(), 2);
+    assert_eq!(stashes.entries[0].message, "On (no branch): my: message");
+    assert_eq!(stashes.entries[0].branch.as_deref(), Some("(no branch)"));
+    assert!(stashes.entries[1].message.starts_with("WIP on main: "));
+    assert_eq!(stashes.entries[1].branch.as_deref(), Some("main"));
+    assert!(stashes.entries[1].time > 0);
+    let newest = repository.revparse_single("stash@{0}").unwrap().id();
+    assert_eq!(stashes.entries[0].id, ObjectId::from(newest));
+    assert_eq!(stashes.entries[0].id.to_string(), newest.to_string());
+    assert_eq!(count_stashes(&repository).unwrap(), 2);
+}
+
+#[test]
+#[with_test_dir]
+fn submodules() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "sub");
+    helpers::make_commit(&root, "sub", 1);
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let add = ["-c", "protocol.file.allow=always", "submodule", "add"];
+    helpers::git(&root, "repo", add.into_iter().chain(["../sub", "sub"]))
+        .unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "add sub"]).unwrap();
+
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    let submodules = submodule_info(&repository).unwrap();
+    assert_eq!(submodules.len(), 1);
+    let submodule = &submodules[0];
+    assert_eq!(submodule.name, "sub");
+    assert_eq!(submodule.path, std::path::Path::new("sub"));
+    assert!(submodule.initialized);
+    assert!(!submodule.dirty);
+    assert!(!submodule.drifted());
+
+    helpers::make_commit(&root, "repo/sub", 2);
+    std::fs::write(root.join("repo/sub/c"), "untracked").unwrap();
+    let submodule = submodule_info(&repository).unwrap().remove(0);
+    assert!(submodule.dirty);
+    assert!(submodule.drifted());
+    assert_ne!(submodule.head_id, submodule.recorded_id);
+}
+
+#[test]
+#[with_test_dir]
+fn superproject() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "sub");
+    helpers::make_commit(&root, "sub", 1);
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let add = ["-c", "protocol.file.allow=always", "submodule", "add"];
+    helpers::git(&root, "repo", add.into_iter().chain(["../sub", "sub"]))
+        .unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "add sub"]).unwrap();
+
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    assert_eq!(superproject_info(&repository).unwrap(), None);
+
+    let repository = git2::Repository::open(root.join("repo/sub")).unwrap();
+    let superproject = superproject_info(&repository).unwrap().unwrap();
+    assert_eq!(
+        superproject.workdir,
+        root.join("repo/").canonicalize().unwrap().join("")
+    );
+    assert_eq!(superproject.path, std::path::Path::new("sub"));
+    assert!(!superproject.drifted());
+
+    helpers::make_commit(&root, "repo/sub", 2);
+    let superproject = superproject_info(&repository).unwrap().unwrap();
+    assert!(superproject.drifted());
+    assert_ne!(superproject.head_id, superproject.recorded_id);
+
+    // Staging the new commit still differs from `HEAD`.
+    helpers::git(&root, "repo", ["add", "sub"]).unwrap();
+    let superproject = superproject_info(&repository).unwrap().unwrap();
+    assert!(superproject.drifted());
+    assert_eq!(superproject.head_id, superproject.index_id);
+
+    // A repository that isn’t a submodule.
+    helpers::git_init(&root, "repo/nested");
+    let repository = git2::Repository::open(root.join("repo/nested")).unwrap();
+    assert_eq!(superproject_info(&repository).unwrap(), None);
+}
+
+#[test]
+#[with_test_dir]
+fn worktrees() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let repository = git2::Repository::open(root.join("repo")).unwrap();
+    assert_eq!(worktree_info(&repository).unwrap(), []);
+
+    helpers::git(&root, "repo", ["worktree", "add", "../wt1"]).unwrap();
+    helpers::git(&root, "repo", ["worktree", "add", "--detach", "../wt2"])
+        .unwrap();
+    helpers::git(
+        &root,
+        "repo",
+        ["worktree", "lock", "--reason", "usb", "wt2"],
+    )
+    .unwrap();
+    helpers::git(&root, "repo", ["worktree", "add", "../wt3"]).unwrap();
+    std::fs::remove_dir_all(root.join("wt3")).unwrap();
+
+    let worktrees = worktree_info(&repository).unwrap();
+    let summary: Vec<_> = worktrees
+        .iter()
+        .map(|worktree| {
+            (
+                worktree.name.as_str(),
+                worktree.branch.as_deref(),
+                worktree.lock_reason.as_deref(),
+                worktree.locked,
+                worktree.prunable,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("wt1", Some("wt1"), None, false, false),
+            ("wt2", None, Some("usb"), true, false),
+            ("wt3", None, None, false, true),
+        ]
+    );
+    assert!(worktrees[0].path.ends_with("wt1"));
+}
+
+#[test]
+#[with_test_dir]
+fn summarize_many() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    let mut paths = vec![];
+    for n in 1..=5 {
+        let name = format!("repo{n}");
+        helpers::git_init(&root, &name);
+        helpers::make_commit(&root, &name, n);
+        for i in 0..n {
+            std::fs::write(root.join(&name).join(format!("new{i}")), "")
+                .unwrap();
+        }
+        paths.push(root.join(name));
+    }
+    paths.insert(2, root.join("missing"));
+
+    let untracked = |results: Vec<Result<_, Error>>| -> Vec<Option<usize>> {
+        results
+            .into_iter()
+            .map(|result: Result<git_status_vars::RepoSummary, _>| {
+                result
+                    .ok()
+                    .map(|summary| summary.changes.unwrap().untracked())
+            })
+            .collect()
+    };
+    let expected = [Some(1), Some(2), None, Some(3), Some(4), Some(5)];
+    let options = SummarizeOptions::default();
+    assert_eq!(untracked(summarize_paths(&paths, &options)), expected);
+    let options = SummarizeOptions {
+        parallel: true,
+        ..options
+    };
+    assert_eq!(untracked(summarize_paths(&paths, &options)), expected);
+}
+
+#[test]
+#[with_test_dir]
+fn summarize_one_path() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    std::fs::create_dir(root.join("repo").join("dir")).unwrap();
+    std::fs::write(root.join("repo").join("dir").join("c"), "1c").unwrap();
+
+    let options = SummarizeOptions::default();
+    let summary =
+        summarize_path(&root.join("repo").join("dir"), &options).unwrap();
+    assert_eq!(summary.changes.unwrap().untracked(), 1);
+
+    // A missing path is an error, as it is for `repo_state`, rather than a
+    // missing repository.
+    let error = summarize_path(&root.join("missing"), &options).unwrap_err();
+    assert!(!error.is_not_found(), "{error:?}");
+
+    let error = Error::from(git2::Error::new(
+        git2::ErrorCode::NotFound,
+        git2::ErrorClass::Repository,
+        "could not find repository",
+    ));
+    assert!(error.is_not_found(), "{error:?}");
+    let error = Error::from(git2::Error::new(
+        git2::ErrorCode::NotFound,
+        git2::ErrorClass::Object,
+        "object not found",
+    ));
+    assert!(!error.is_not_found(), "{error:?}");
+}
@@ -0,0 +1,96 @@
+//! Test `#[derive(ShellVars)]`.
+
+#![cfg(feature = "derive")]
+
+use git_status_vars::{ShellVars, ShellWriter};
+use pretty_assertions::assert_str_eq;
+
+// We don’t use everything in helpers.
+#[allow(dead_code)]
+mod helpers;
+
+/// Nested in [`Prompt`].
+#[derive(ShellVars)]
+struct Battery {
+    /// Written as `battery_level`.
+    level: u8,
+}
+
+/// Exercises every attribute.
+#[derive(ShellVars)]
+struct Prompt<'a> {
+    /// Plain field.
+    name: &'a str,
+
+    /// Renamed field.
+    #[shell_vars(rename = "count")]
+    number_of_things: usize,
+
+    /// Missing when `None`.
+    upstream: Option<String>,
+
+    /// Present when `Some`.
+    hash: Option<&'a str>,
+
+    /// Written with `Debug`.
+    #[shell_vars(debug)]
+    error: Option<&'a str>,
+
+    /// Written as a group.
+    #[shell_vars(group)]
+    battery: Battery,
+
+    /// Not written.
+    #[shell_vars(skip)]
+    #[allow(dead_code)]
+    secret: &'a str,
+}
+
+/// Render `vars` with `ShellWriter`.
+fn render<V: ShellVars>(vars: &V, unset_missing: bool) -> String {
+    let mut buffer = Vec::new();
+    ShellWriter::new(&mut buffer, "")
+        .with_unset_missing(unset_missing)
+        .write_vars(vars);
+    String::from_utf8(buffer).unwrap()
+}
+
+#[test]
+fn derive_shell_vars() {
+    let prompt = Prompt {
+        name: "it's",
+        number_of_things: 3,
+        upstream: None,
+        hash: Some("abc"),
+        error: Some("bad"),
+        battery: Battery { level: 90 },
+        secret: "hidden",
+    };
+
+    assert_str_eq!(
+        render(&prompt, false),
+        helpers::strip_indent(
+            r#"
+            name='it'\''s'
+            count=3
+            hash=abc
+            error='"bad"'
+            battery_level=90
+            "#
+        ),
+    );
+
+    assert_str_eq!(
+        render(&prompt, true),
+        helpers::strip_indent(
+            r#"
+            name='it'\''s'
+            count=3
+            unset upstream
+            hash=abc
+            error='"bad"'
+            battery_level=90
+            "#
+        ),
+    );
+}
@@ -3,9 +3,13 @@ use bstr::{BString, ByteSlice};
 use duct::cmd;
 use pretty_assertions::assert_str_eq;
 use regex::Regex;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
 use std::fs;
+use std::io::{BufRead, BufReader};
 use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
 
 /// Run the crate binary and return its output if successful.
 pub fn git_status_vars<I, S>(root: &Path, args: I) -> BString
@@ -19,6 +23,8 @@ where
         .env("HOME", root)
         .env("GIT_CONFIG_GLOBAL", root.join(".gitconfig"))
         .env("GIT_CONFIG_SYSTEM", "/dev/null")
+        // The test directory is inside this repository, so don’t look above it.
+        .env("GIT_CEILING_DIRECTORIES", root.parent().unwrap())
         .stderr_to_stdout()
         .stdout_capture()
         .run()
@@ -27,6 +33,127 @@ where
         .into()
 }
 
+/// Run the crate binary with `input` on stdin and return its output if
+/// successful.
+pub fn git_status_vars_stdin<I, S>(
+    root: &Path,
+    args: I,
+    input: &[u8],
+) -> BString
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    let executable = cargo_bin(env!("CARGO_PKG_NAME"));
+    cmd(executable, args)
+        .dir(root)
+        .env("HOME", root)
+        .env("GIT_CONFIG_GLOBAL", root.join(".gitconfig"))
+        .env("GIT_CONFIG_SYSTEM", "/dev/null")
+        .env("GIT_CEILING_DIRECTORIES", root.parent().unwrap())
+        .stdin_bytes(input)
+        .stderr_to_stdout()
+        .stdout_capture()
+        .run()
+        .unwrap()
+        .stdout
+        .into()
+}
+
+/// Run the crate binary in `dir` (relative to `root`) with extra environment
+/// variables and return its output if successful.
+pub fn git_status_vars_env<I, S>(
+    root: &Path,
+    dir: &str,
+    args: I,
+    vars: &[(&str, &str)],
+) -> BString
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    let executable = cargo_bin(env!("CARGO_PKG_NAME"));
+    let mut expression = cmd(executable, args)
+        .dir(root.join(dir))
+        .env("HOME", root)
+        .env("GIT_CONFIG_GLOBAL", root.join(".gitconfig"))
+        .env("GIT_CONFIG_SYSTEM", "/dev/null")
+        .env("GIT_CEILING_DIRECTORIES", root.parent().unwrap());
+    for (name, value) in vars {
+        expression = expression.env(name, value);
+    }
+    expression
+        .stderr_to_stdout()
+        .stdout_capture()
+        .run()
+        .unwrap()
+        .stdout
+        .into()
+}
+
+/// Run the crate binary and return its exit code.
+pub fn git_status_vars_exit_code<I, S>(root: &Path, args: I) -> Option<i32>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    let executable = cargo_bin(env!("CARGO_PKG_NAME"));
+    cmd(executable, args)
+        .dir(root)
+        .env("HOME", root)
+        .env("GIT_CONFIG_GLOBAL", root.join(".gitconfig"))
+        .env("GIT_CONFIG_SYSTEM", "/dev/null")
+        .env("GIT_CEILING_DIRECTORIES", root.parent().unwrap())
+        .stdout_null()
+        .stderr_null()
+        .unchecked()
+        .run()
+        .unwrap()
+        .status
+        .code()
+}
+
+/// Start the crate binary for `watch`, and send each summary it outputs (up to
+/// an empty line) to the returned receiver.
+///
+/// The caller must kill the child.
+pub fn git_status_vars_summaries<I, S>(
+    root: &Path,
+    args: I,
+) -> (Child, mpsc::Receiver<String>)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut child = Command::new(cargo_bin(env!("CARGO_PKG_NAME")))
+        .args(args)
+        .current_dir(root)
+        .env("HOME", root)
+        .env("GIT_CONFIG_GLOBAL", root.join(".gitconfig"))
+        .env("GIT_CONFIG_SYSTEM", "/dev/null")
+        .env("GIT_CEILING_DIRECTORIES", root.parent().unwrap())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut summary = String::new();
+        for line in stdout.lines() {
+            let line = line.unwrap();
+            if line.is_empty() {
+                if sender.send(std::mem::take(&mut summary)).is_err() {
+                    return;
+                }
+            } else {
+                summary.push_str(&line);
+                summary.push('\n');
+            }
+        }
+    });
+    (child, receiver)
+}
+
 /// Set up a call to `git` in the `repo` directory.
 fn run_git<I, S>(root: &Path, repo: &str, args: I) -> duct::Expression
 where
@@ -52,7 +179,7 @@ where
     let shell_args =
         shell_words::join(args.iter().map(|arg| arg.to_string_lossy()));
 
-    println!("`git {shell_args}` in {:?}", root.join(repo));
+    println!("`git {shell_args}` in {}", root.join(repo).display());
     let output = run_git(root, repo, args).run()?;
     print!("{}", output.stdout.as_bstr());
     Ok(())
@@ -97,6 +224,93 @@ pub fn make_commit(root: &Path, repo: &str, n: u8) {
     git(root, repo, ["commit", "-m", &format!("commit {n}")]).unwrap();
 }
 
+/// Add a table of references to the reftable stack in `repo`.
+///
+/// `refs` must be sorted by name. Values are formatted like loose reference
+/// files: `ref: refs/heads/main` for a symbolic reference, an object ID in hex
+/// for a direct reference, or `""` to delete the reference.
+///
+/// This only writes what `git-status-vars` reads: a single unaligned ref block
+/// with no logs, indexes, or checksum.
+pub fn add_reftable(
+    root: &Path,
+    repo: &str,
+    table: &str,
+    refs: &[(&str, &str)],
+) {
+    // Lengths are small, so none of this can overflow.
+    #![allow(clippy::arithmetic_side_effects, clippy::cast_possible_truncation)]
+
+    /// Encode a variable length integer like git does.
+    fn varint(out: &mut Vec<u8>, mut value: usize) {
+        let mut bytes = vec![(value & 0x7f) as u8];
+        while value >= 0x80 {
+            value = (value >> 7) - 1;
+            bytes.push(0x80 | (value & 0x7f) as u8);
+        }
+        out.extend(bytes.iter().rev());
+    }
+
+    let mut header = b"REFT\x01".to_vec();
+    header.extend([0, 0x10, 0]); // Block size 4096.
+    header.extend(1u64.to_be_bytes()); // Minimum update index.
+    header.extend(1u64.to_be_bytes()); // Maximum update index.
+
+    let mut records = vec![];
+    let mut previous = "";
+    for (name, value) in refs {
+        let prefix = name
+            .bytes()
+            .zip(previous.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = &name.as_bytes()[prefix..];
+        let (value_type, value) = if value.is_empty() {
+            (0, vec![])
+        } else if let Some(target) = value.strip_prefix("ref: ") {
+            let mut bytes = vec![];
+            varint(&mut bytes, target.len());
+            bytes.extend(target.bytes());
+            (3, bytes)
+        } else {
+            let bytes = (0..value.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+                .collect();
+            (1, bytes)
+        };
+        varint(&mut records, prefix);
+        varint(&mut records, suffix.len() << 3 | value_type);
+        records.extend(suffix);
+        varint(&mut records, 0); // Update index delta.
+        records.extend(value);
+        previous = name;
+    }
+
+    // Block type, length, records, one restart at the first record, and the
+    // number of restarts.
+    let restart = header.len() + 4;
+    let block_len = restart + records.len() + 3 + 2;
+    let mut data = header.clone();
+    data.push(b'r');
+    data.extend(&(block_len as u32).to_be_bytes()[1..]);
+    data.extend(records);
+    data.extend(&(restart as u32).to_be_bytes()[1..]);
+    data.extend(1u16.to_be_bytes());
+
+    // Footer: the header, five offsets, and a CRC-32 that isn’t checked.
+    data.extend(header);
+    data.extend([0; 5 * 8 + 4]);
+
+    let directory = root.join(repo).join(".git/reftable");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join(table), data).unwrap();
+    let list =
+        fs::read_to_string(directory.join("tables.list")).unwrap_or_default();
+    fs::write(directory.join("tables.list"), format!("{list}{table}\n"))
+        .unwrap();
+}
+
 /// Check the output of git-status-vars against a string.
 ///
 /// This produces an easy to read diff when necessary.
@@ -109,7 +323,8 @@ pub fn make_commit(root: &Path, repo: &str, n: u8) {
 /// Also, it will replace the string `@REPO@` with the repo path (`repo`).
 ///
 /// Because git hashes are not the same from run to run, it will replace any
-/// output matching `_hash=[0-9a-f]{40}` with `_hash=@HASH@`.
+/// SHA-1 or SHA-256 hash in output like `_hash=[0-9a-f]{40}` with
+/// `_hash=@HASH@`.
 ///
 /// ```no_run
 /// assert_git_status_vars(
@@ -132,8 +347,21 @@ pub fn make_commit(root: &Path, repo: &str, n: u8) {
 /// );
 /// ```
 pub fn assert_git_status_vars(root: &Path, repo: &str, expected: &str) {
-    let re = Regex::new(r"_hash=[0-9a-f]{40}").unwrap();
-    let output = git_status_vars(root, [repo]);
+    assert_git_status_vars_args(root, repo, &[], expected);
+}
+
+/// Check the output of git-status-vars with extra arguments against a string.
+///
+/// The arguments are passed before `repo`. See [`assert_git_status_vars()`]
+/// for the format of `expected`.
+pub fn assert_git_status_vars_args(
+    root: &Path,
+    repo: &str,
+    args: &[&str],
+    expected: &str,
+) {
+    let re = Regex::new(r"_hash=[0-9a-f]{40}(?:[0-9a-f]{24})?\b").unwrap();
+    let output = git_status_vars(root, args.iter().copied().chain([repo]));
     let output = output.to_str_lossy();
     let output = re.replace_all(&output, "_hash=@HASH@");
 
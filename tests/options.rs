@@ -0,0 +1,3551 @@
+//! Test command line options.
+
+#![cfg(feature = "cli")]
+
+use pretty_assertions::assert_str_eq;
+use regex::Regex;
+use std::fs;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+use target_test_dir::with_test_dir;
+
+// We don’t use everything in helpers.
+#[allow(dead_code)]
+mod helpers;
+
+#[test]
+#[with_test_dir]
+fn cache() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ";
+
+    // First run fills the cache, second run reads it.
+    helpers::assert_git_status_vars_args(&root, "repo", &["--cache"], expected);
+    assert!(root.join("repo/.git/git-status-vars-cache").exists());
+    assert!(root.join("repo/.git/git-status-vars-no-upstream").exists());
+    helpers::assert_git_status_vars_args(&root, "repo", &["--cache"], expected);
+
+    // Configuring an upstream invalidates the record of branches without one.
+    helpers::git(&root, "repo", ["config", "branch.main.remote", "."]).unwrap();
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "branch.main.merge", "refs/heads/main"],
+    )
+    .unwrap();
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--cache"],
+        &expected
+            .replace("head_ahead=''", "head_ahead=0")
+            .replace("head_behind=''", "head_behind=0")
+            .replace("head_ahead_exact=''", "head_ahead_exact=true"),
+    );
+    helpers::git(&root, "repo", ["config", "--unset", "branch.main.remote"])
+        .unwrap();
+    helpers::git(&root, "repo", ["config", "--unset", "branch.main.merge"])
+        .unwrap();
+    helpers::assert_git_status_vars_args(&root, "repo", &["--cache"], expected);
+
+    // Changing a tracked file invalidates the cache.
+    fs::write(root.join("repo").join("a"), "changed").unwrap();
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--cache"],
+        &expected
+            .replace("unstaged_count=0", "unstaged_count=1")
+            .replace("color_hint=green", "color_hint=yellow"),
+    );
+
+    // Staging the change invalidates the cache, even if the size and
+    // modification time of the index stay the same.
+    #[cfg(unix)]
+    {
+        let index = root.join("repo/.git/index");
+        let saved = root.join("index-mtime");
+        duct::cmd!("touch", "-r", &index, &saved).run().unwrap();
+        helpers::git(&root, "repo", ["add", "a"]).unwrap();
+        duct::cmd!("touch", "-r", &saved, &index).run().unwrap();
+        helpers::assert_git_status_vars_args(
+            &root,
+            "repo",
+            &["--cache"],
+            &expected
+                .replace(" staged_count=0", " staged_count=1")
+                .replace("color_hint=green", "color_hint=yellow"),
+        );
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn cache_corrupt_index() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/.git/index"), "garbage").unwrap();
+
+    // The cache key can’t be calculated, so the repository is summarized as
+    // usual, with the error in the counts section.
+    let expected = helpers::git_status_vars(&root, ["repo"]).to_string();
+    assert!(expected.contains("\ncounts_error="), "{expected}");
+    let output = helpers::git_status_vars(&root, ["--cache", "repo"]);
+    assert_str_eq!(expected, output.to_string());
+    assert!(!root.join("repo/.git/git-status-vars-cache").exists());
+}
+
+#[test]
+#[with_test_dir]
+fn cache_invalidated() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    fs::create_dir(root.join("repo/dir")).unwrap();
+    fs::write(root.join("repo/dir/c"), "c").unwrap();
+    helpers::git(&root, "repo", ["add", "dir/c"]).unwrap();
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["branch", "upstream"]).unwrap();
+    helpers::git(&root, "repo", ["branch", "--set-upstream-to", "upstream"])
+        .unwrap();
+
+    let summary = || {
+        let output =
+            helpers::git_status_vars(&root, ["--cache", "repo"]).to_string();
+        output
+            .lines()
+            .filter(|line| {
+                [
+                    "head_ref1_short=",
+                    "head_ahead=",
+                    "head_behind=",
+                    "untracked_count=",
+                    "unstaged_count=",
+                    "staged_count=",
+                ]
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let clean = "head_ref1_short=main head_ahead=0 head_behind=0 \
+        untracked_count=0 unstaged_count=0 staged_count=0";
+    assert_eq!(summary(), clean);
+    assert_eq!(summary(), clean);
+
+    // Changing a tracked file in a subdirectory.
+    fs::write(root.join("repo/dir/c"), "changed").unwrap();
+    assert_eq!(
+        summary(),
+        clean.replace("unstaged_count=0", "unstaged_count=1")
+    );
+
+    // Staging it.
+    helpers::git(&root, "repo", ["add", "dir/c"]).unwrap();
+    assert_eq!(
+        summary(),
+        clean.replace(" staged_count=0", " staged_count=1")
+    );
+
+    // Committing it.
+    helpers::git(&root, "repo", ["commit", "-m", "change c"]).unwrap();
+    assert_eq!(summary(), clean.replace("head_ahead=0", "head_ahead=1"));
+
+    // Moving the upstream.
+    helpers::git(&root, "repo", ["branch", "-f", "upstream", "main"]).unwrap();
+    assert_eq!(summary(), clean);
+
+    // Deleting a tracked file.
+    fs::remove_file(root.join("repo/dir/c")).unwrap();
+    assert_eq!(
+        summary(),
+        clean.replace("unstaged_count=0", "unstaged_count=1")
+    );
+    helpers::git(&root, "repo", ["checkout", "dir/c"]).unwrap();
+    assert_eq!(summary(), clean);
+
+    // Adding an untracked file in a subdirectory.
+    fs::write(root.join("repo/dir/untracked"), "").unwrap();
+    assert_eq!(
+        summary(),
+        clean.replace("untracked_count=0", "untracked_count=1")
+    );
+    fs::remove_file(root.join("repo/dir/untracked")).unwrap();
+    assert_eq!(summary(), clean);
+
+    // Switching to another branch.
+    helpers::git(&root, "repo", ["switch", "-c", "other"]).unwrap();
+    assert_eq!(
+        summary(),
+        clean
+            .replace("main", "other")
+            .replace("head_ahead=0", "head_ahead=''")
+            .replace("head_behind=0", "head_behind=''")
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn cache_untracked() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let untracked = |count| {
+        let output = helpers::git_status_vars(
+            &root,
+            [
+                "--cache",
+                "--untracked-files",
+                "all",
+                "--only",
+                "counts",
+                "repo",
+            ],
+        )
+        .to_string();
+        assert!(
+            output.contains(&format!("\nuntracked_count={count}\n")),
+            "expected untracked_count={count} in:\n{output}"
+        );
+    };
+
+    // Adding a file to an existing untracked directory invalidates the cache.
+    fs::create_dir(root.join("repo/untracked")).unwrap();
+    fs::write(root.join("repo/untracked/one"), "").unwrap();
+    untracked(1);
+    fs::write(root.join("repo/untracked/two"), "").unwrap();
+    untracked(2);
+
+    // Changing the files that list ignored files invalidates the cache.
+    fs::write(root.join("repo/.git/info/exclude"), "untracked/\n").unwrap();
+    untracked(0);
+    fs::remove_file(root.join("repo/.git/info/exclude")).unwrap();
+    fs::write(root.join("repo/.gitignore"), "untracked/\n").unwrap();
+    untracked(1);
+    fs::write(root.join("repo/.gitignore"), "untracked/\n.gitignore\n")
+        .unwrap();
+    untracked(0);
+    fs::remove_file(root.join("repo/.gitignore")).unwrap();
+    let excludes = root.join("excludes");
+    fs::write(&excludes, "untracked/\n").unwrap();
+    helpers::git(
+        &root,
+        "repo",
+        [
+            "config",
+            "--global",
+            "core.excludesFile",
+            excludes.to_str().unwrap(),
+        ],
+    )
+    .unwrap();
+    untracked(0);
+    fs::write(&excludes, "").unwrap();
+    untracked(2);
+}
+
+#[test]
+#[with_test_dir]
+fn cache_state() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::make_commit(&root, "repo", 2);
+    helpers::make_commit(&root, "repo", 3);
+    helpers::git(
+        &root,
+        "repo",
+        ["format-patch", "-o", "../patches", "HEAD~2"],
+    )
+    .unwrap();
+    helpers::git(&root, "repo", ["reset", "--hard", "HEAD~2"]).unwrap();
+    helpers::make_commit(&root, "repo", 4);
+
+    let state = || {
+        let output =
+            helpers::git_status_vars(&root, ["--cache", "repo"]).to_string();
+        output
+            .lines()
+            .filter(|line| {
+                line.starts_with("repo_state=")
+                    || line.starts_with("ps1_state=")
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    assert_eq!(state(), "repo_state=Clean ps1_state=''");
+
+    // Starting an operation invalidates the cache, even if it doesn’t change
+    // the index or the working tree.
+    helpers::git(
+        &root,
+        "repo",
+        [
+            "am",
+            "../patches/0001-commit-2.patch",
+            "../patches/0002-commit-3.patch",
+        ],
+    )
+    .expect_err("am should fail");
+    assert_eq!(state(), "repo_state=ApplyMailbox ps1_state='|AM 1/2'");
+
+    // Progress is written in place, without changing the directory, and it
+    // invalidates the cache too.
+    fs::write(root.join("repo/.git/rebase-apply/next"), "2\n").unwrap();
+    assert_eq!(state(), "repo_state=ApplyMailbox ps1_state='|AM 2/2'");
+
+    // Finishing the operation invalidates the cache.
+    helpers::git(&root, "repo", ["am", "--abort"]).unwrap();
+    assert_eq!(state(), "repo_state=Clean ps1_state=''");
+}
+
+#[test]
+#[with_test_dir]
+fn cache_linked_worktree() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["worktree", "add", "-b", "branch", "../wt"])
+        .unwrap();
+
+    let stash_count = || {
+        let output = helpers::git_status_vars(
+            &root,
+            ["--cache", "--only", "stash", "wt"],
+        )
+        .to_string();
+        output
+            .lines()
+            .find(|line| line.starts_with("stash_count="))
+            .unwrap()
+            .to_owned()
+    };
+    assert_eq!(stash_count(), "stash_count=0");
+
+    // Stashing in another worktree invalidates the cache.
+    fs::write(root.join("repo").join("a"), "stashed").unwrap();
+    helpers::git(&root, "repo", ["stash"]).unwrap();
+    assert_eq!(stash_count(), "stash_count=1");
+}
+
+#[test]
+#[with_test_dir]
+fn only() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo").join("untracked"), "").unwrap();
+
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "head,counts"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        untracked_count=1
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=yellow
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn exclude() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--exclude", "stash,upstream", "--exclude", "head"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=green
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn no_sections() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--no-status", "--no-stash", "--no-upstream"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        color_hint=green
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn exit_code() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+    let exit_code = |repo: &str, extra: &[&str]| {
+        let mut args = vec!["--exit-code"];
+        args.extend(extra);
+        args.push(repo);
+        helpers::git_status_vars_exit_code(&root, args)
+    };
+
+    assert_eq!(exit_code(".", &[]), Some(3));
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    assert_eq!(exit_code("repo", &[]), Some(0));
+
+    fs::write(root.join("repo").join("untracked"), "").unwrap();
+    assert_eq!(exit_code("repo", &[]), Some(1));
+    assert_eq!(exit_code("repo", &["--cache"]), Some(1));
+    assert_eq!(exit_code("repo", &["--cache"]), Some(1));
+    assert_eq!(exit_code("repo", &["--no-status"]), Some(0));
+
+    helpers::git(&root, "repo", ["switch", "-c", "branch"]).unwrap();
+    helpers::make_commit(&root, "repo", 2);
+    helpers::git(&root, "repo", ["switch", "main"]).unwrap();
+    helpers::make_commit(&root, "repo", 3);
+    helpers::git(&root, "repo", ["merge", "branch"]).unwrap_err();
+    assert_eq!(exit_code("repo", &[]), Some(2));
+
+    // With multiple repositories, the worst condition is used.
+    assert_eq!(exit_code("repo", &["."]), Some(3));
+    assert_eq!(exit_code("repo", &["nonexistent", "."]), Some(4));
+
+    // Repositories that weren’t found count as errors, as do broken ones.
+    helpers::git_init(&root, "broken");
+    fs::write(root.join("broken/.git/HEAD"), "garbage\n").unwrap();
+    assert_eq!(exit_code("repo", &["broken"]), Some(4));
+    let output = helpers::git_status_vars(
+        &root,
+        ["repo", "nonexistent", ".", "broken", "repo"],
+    );
+    assert!(output.ends_with(b"\nrepo_error_count=3\n"), "{output}");
+
+    // Without --exit-code, the exit code is always 0.
+    assert_eq!(helpers::git_status_vars_exit_code(&root, ["repo"]), Some(0));
+}
+
+#[test]
+#[with_test_dir]
+fn timeout() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=green
+        ";
+
+    for strategy in ["thread", "process"] {
+        let args = ["--only", "counts", "--timeout-strategy", strategy];
+        helpers::assert_git_status_vars_args(
+            &root,
+            "repo",
+            &[&args[..], &["--timeout", "10s"]].concat(),
+            expected,
+        );
+
+        // With multiple repositories, the timeout applies to each one. Only
+        // complete sections should be output before the timeout.
+        let mut args = args.to_vec();
+        args.extend(["--timeout", "1ms"]);
+        args.extend(["repo"; 100]);
+        let output = helpers::git_status_vars(&root, args).to_string();
+        // No line is cut off, even if a child process was killed mid-write.
+        assert!(!output.contains('\0'), "{output}");
+        assert!(shell_words::split(&output).is_ok(), "{output}");
+        let mut timed_out = 0;
+        for i in 1..=100 {
+            let group = format!("repo{i}_");
+            let lines: Vec<&str> = output
+                .lines()
+                .filter(|line| line.starts_with(&group))
+                .collect();
+            let counts =
+                lines.iter().filter(|l| l.contains("ed_count=")).count();
+            if lines.contains(
+                &format!("{group}repo_error='Timed out after 1ms'").as_str(),
+            ) {
+                timed_out += 1;
+                assert!(counts == 0 || counts == 4, "{output}");
+            } else {
+                assert_eq!(counts, 4, "{output}");
+            }
+        }
+        assert!(
+            output.ends_with(&format!("\nrepo_error_count={timed_out}\n")),
+            "{output}"
+        );
+        if strategy == "process" {
+            // Starting a process takes longer than 1ms.
+            assert!(timed_out > 0, "{output}");
+        }
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn group_by_name() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "my-repo");
+    helpers::git_init(&root, "other");
+    helpers::git_init(&root, "other/my-repo");
+
+    let output = helpers::git_status_vars(
+        &root,
+        [
+            "--group-by",
+            "name",
+            "--only",
+            "counts",
+            "my-repo",
+            "other",
+            "other/my-repo",
+        ],
+    );
+    let expected = helpers::strip_indent(
+        "
+        schema_version=1
+        repo_count=3
+
+        my_repo_path=my-repo
+        my_repo_repo_state=Clean
+        my_repo_ps1_state=''
+        my_repo_repo_workdir=@ROOT@/my-repo/
+        my_repo_repo_empty=true
+        my_repo_repo_bare=false
+        my_repo_fetch_age_seconds=''
+        my_repo_untracked_count=0
+        my_repo_unstaged_count=0
+        my_repo_staged_count=0
+        my_repo_conflicted_count=0
+        my_repo_untracked_truncated=false
+        my_repo_color_hint=green
+
+        other_path=other
+        other_repo_state=Clean
+        other_ps1_state=''
+        other_repo_workdir=@ROOT@/other/
+        other_repo_empty=true
+        other_repo_bare=false
+        other_fetch_age_seconds=''
+        other_untracked_count=0
+        other_unstaged_count=0
+        other_staged_count=0
+        other_conflicted_count=0
+        other_untracked_truncated=false
+        other_color_hint=green
+
+        my_repo_2_path=other/my-repo
+        my_repo_2_repo_state=Clean
+        my_repo_2_ps1_state=''
+        my_repo_2_repo_workdir=@ROOT@/other/my-repo/
+        my_repo_2_repo_empty=true
+        my_repo_2_repo_bare=false
+        my_repo_2_fetch_age_seconds=''
+        my_repo_2_untracked_count=0
+        my_repo_2_unstaged_count=0
+        my_repo_2_staged_count=0
+        my_repo_2_conflicted_count=0
+        my_repo_2_untracked_truncated=false
+        my_repo_2_color_hint=green
+
+        repo_error_count=0
+        ",
+    )
+    .replace("@ROOT@", &root.display().to_string());
+    assert_eq!(output, expected);
+}
+
+#[test]
+#[with_test_dir]
+fn timing() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let output = helpers::git_status_vars(
+        &root,
+        ["--timing", "--cache", "--exclude", "upstream", "repo"],
+    )
+    .to_string();
+    let re = Regex::new(
+        r"\nstash_count=0\ncolor_hint=green\ntiming_head_us=\d+\ntiming_counts_us=\d+\ntiming_stash_us=\d+\n$",
+    )
+    .unwrap();
+    assert!(re.is_match(&output), "{output}");
+
+    // Timing disables the cache.
+    assert!(!root.join("repo/.git/git-status-vars-cache").exists());
+}
+
+#[test]
+#[with_test_dir]
+fn bench() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let output = helpers::git_status_vars(
+        &root,
+        ["--bench", "3", "--only", "head,counts", "repo"],
+    )
+    .to_string();
+    let re = Regex::new(&helpers::strip_indent(
+        r"
+        ^bench_runs=3
+        bench_head_min_us=\d+
+        bench_head_median_us=\d+
+        bench_head_max_us=\d+
+        bench_counts_min_us=\d+
+        bench_counts_median_us=\d+
+        bench_counts_max_us=\d+
+        bench_total_min_us=\d+
+        bench_total_median_us=\d+
+        bench_total_max_us=\d+
+        $",
+    ))
+    .unwrap();
+    assert!(re.is_match(&output), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn include_submodules() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "sub");
+    helpers::make_commit(&root, "sub", 1);
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(
+        &root,
+        "repo",
+        [
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "../sub",
+        ],
+    )
+    .unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "add submodule"]).unwrap();
+    helpers::make_commit(&root, "repo/sub", 2);
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=green
+        ";
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts"],
+        expected,
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--include-submodules"],
+        &expected
+            .replace("unstaged_count=0", "unstaged_count=1")
+            .replace("color_hint=green", "color_hint=yellow"),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn cache_include_submodules() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "sub");
+    helpers::make_commit(&root, "sub", 1);
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let add = ["-c", "protocol.file.allow=always", "submodule", "add"];
+    helpers::git(&root, "repo", add.into_iter().chain(["../sub", "sub"]))
+        .unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "add sub"]).unwrap();
+
+    let args = ["--cache", "--include-submodules", "repo"];
+    let unstaged_count = || {
+        helpers::git_status_vars(&root, args)
+            .to_string()
+            .lines()
+            .find(|line| line.starts_with("unstaged_count="))
+            .unwrap()
+            .to_owned()
+    };
+    assert_eq!(unstaged_count(), "unstaged_count=0");
+
+    // Committing in the submodule doesn’t change the superproject’s index or
+    // any of its tracked files.
+    helpers::make_commit(&root, "repo/sub", 2);
+    assert_eq!(unstaged_count(), "unstaged_count=1");
+}
+
+#[test]
+#[with_test_dir]
+fn include_ignored() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    fs::write(root.join("repo").join(".gitignore"), "*.log\n").unwrap();
+    fs::write(root.join("repo").join("a.log"), "").unwrap();
+    fs::write(root.join("repo").join("b.log"), "").unwrap();
+
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--include-ignored"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=true
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=1
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        ignored_count=2
+        untracked_truncated=false
+        color_hint=yellow
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn bare() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, ".", ["clone", "--bare", "repo", "bare.git"]).unwrap();
+    helpers::git(&root, "bare.git", ["worktree", "add", "../wt"]).unwrap();
+    fs::write(root.join("wt").join("untracked"), "").unwrap();
+
+    helpers::assert_git_status_vars_args(
+        &root,
+        "bare.git",
+        &["--only", "counts", "--allow-bare"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=''
+        repo_empty=false
+        repo_bare=true
+        fetch_age_seconds=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        color_hint=green
+        ",
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "bare.git",
+        &["--only", "counts", "--require-worktree"],
+        r"
+        schema_version=1
+        repo_state=Error
+        repo_error='bare repository has no working tree'
+        repo_error_code=invalid_options
+        repo_error_class=options
+        ",
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "bare.git",
+        &["--only", "counts", "--worktree", "wt"],
+        &format!(
+            r"
+            schema_version=1
+            repo_state=Clean
+            ps1_state=''
+            repo_workdir={}/
+            repo_empty=false
+            repo_bare=false
+            fetch_age_seconds=''
+            untracked_count=1
+            unstaged_count=0
+            staged_count=0
+            conflicted_count=0
+            untracked_truncated=false
+            color_hint=yellow
+            ",
+            root.join("wt").display()
+        ),
+    );
+
+    // Repositories that aren’t bare are unaffected.
+    helpers::assert_git_status_vars_args(
+        &root,
+        "wt",
+        &["--only", "counts", "--require-worktree"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=1
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=yellow
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn ceiling() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "outer");
+    fs::create_dir(root.join("outer").join("inner")).unwrap();
+
+    // A subdirectory of a repository finds the repository.
+    let output = helpers::git_status_vars(&root, ["outer/inner"]);
+    assert!(
+        output.starts_with(
+            format!(
+                "schema_version=1\nrepo_state=Clean\nps1_state=''\nrepo_workdir={}/\n",
+                root.join("outer").display()
+            )
+            .as_bytes()
+        ),
+        "{output}"
+    );
+
+    let ceiling = root.join("outer");
+    assert_eq!(
+        "schema_version=1\nrepo_state=NotFound\n",
+        helpers::git_status_vars(
+            &root,
+            [
+                "--ceiling".as_ref(),
+                ceiling.as_os_str(),
+                "outer/inner".as_ref()
+            ],
+        )
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn no_search() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "outer");
+    fs::create_dir(root.join("outer").join("inner")).unwrap();
+
+    assert_eq!(
+        "schema_version=1\nrepo_state=NotFound\n",
+        helpers::git_status_vars(&root, ["--no-search", "outer/inner"])
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "outer",
+        &["--no-search", "--only", "counts"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=true
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=green
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn git_env() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::create_dir(root.join("other")).unwrap();
+    fs::write(root.join("other").join("a"), "changed").unwrap();
+
+    let workdir = |dir, args: &[&str], vars: &[(&str, &str)]| {
+        let output =
+            helpers::git_status_vars_env(&root, dir, args, vars).to_string();
+        let prefix = format!("{}/", root.display());
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix("repo_workdir="))
+            .map(|workdir| workdir.replace(&prefix, ""))
+            .unwrap_or(output)
+    };
+
+    // Relative paths are relative to the current directory, like `git`.
+    let other = [("GIT_WORK_TREE", "../other")];
+    assert_eq!(workdir("repo", &[], &other), "other/");
+    let other = [("GIT_DIR", "../repo/.git"), ("GIT_WORK_TREE", ".")];
+    assert_eq!(workdir("other", &[], &other), "other/");
+
+    // With a path, they are relative to the path, like `git -C`.
+    let other = [("GIT_WORK_TREE", "../other")];
+    assert_eq!(workdir(".", &["repo"], &other), "other/");
+    assert_eq!(workdir(".", &["--no-search", "repo"], &other), "other/");
+    let other = [("GIT_DIR", "../repo/.git"), ("GIT_WORK_TREE", ".")];
+    assert_eq!(workdir(".", &["other"], &other), "other/");
+    assert!(helpers::git_status_vars_env(&root, ".", ["other"], &other)
+        .to_string()
+        .contains("\nunstaged_count=2\n"),);
+    assert!(helpers::git_status_vars_env(
+        &root,
+        ".",
+        ["--head-only", "other"],
+        &other
+    )
+    .to_string()
+    .contains("\nhead_ref1_name=refs/heads/main\n"),);
+
+    // `--no-git-env` ignores them.
+    assert_eq!(workdir(".", &["--no-git-env", "repo"], &other), "repo/");
+    assert_eq!(workdir("repo", &["--no-git-env"], &other), "repo/");
+}
+
+#[test]
+#[with_test_dir]
+fn upstream_ref() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["branch", "old"]).unwrap();
+    helpers::make_commit(&root, "repo", 2);
+    helpers::make_commit(&root, "repo", 3);
+
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "upstream", "--upstream-ref", "old"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ahead=2
+        head_behind=0
+        head_ahead_exact=true
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
+        color_hint=green
+        ",
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "upstream", "--upstream-ref", "nonexistent"],
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error='revspec '\''nonexistent'\'' not found'
+        head_upstream_error_code=not_found
+        head_upstream_error_class=reference
+        color_hint=green
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn unset_missing() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    assert_eq!(
+        helpers::git_status_vars(&root, ["--unset-missing", "."]),
+        r"schema_version=1
+repo_state=NotFound
+unset ps1_state
+unset repo_workdir
+unset repo_workdir_raw
+unset repo_workdir_logical
+unset repo_workdir_physical
+unset repo_empty
+unset repo_bare
+unset fetch_age_seconds
+unset repo_error
+unset repo_error_code
+unset repo_error_class
+unset fetch_error
+unset fetch_error_code
+unset fetch_error_class
+unset head_ref_length
+unset head_hash
+unset head_unborn
+unset head_error
+unset head_error_code
+unset head_error_class
+unset head_ahead
+unset head_behind
+unset head_ahead_exact
+unset head_upstream_error
+unset head_upstream_error_code
+unset head_upstream_error_class
+unset upstream_error
+unset upstream_error_code
+unset upstream_error_class
+unset untracked_count
+unset unstaged_count
+unset staged_count
+unset conflicted_count
+unset ignored_count
+unset counts_truncated
+unset untracked_truncated
+unset repo_locked
+unset counts_error
+unset counts_error_code
+unset counts_error_class
+unset stash_count
+unset stash_error
+unset stash_error_code
+unset stash_error_class
+unset superproject_workdir
+unset superproject_path
+unset superproject_drifted
+unset superproject_error
+unset superproject_error_code
+unset superproject_error_class
+unset remote_tip_hash
+unset head_behind_remote_tip
+unset remote_error
+unset remote_error_code
+unset remote_error_class
+unset color_hint
+unset color_ansi
+unset glyphs
+"
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn unset_missing_sections() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        unset ps1_state
+        repo_workdir=@REPO@/
+        unset repo_workdir_raw
+        unset repo_workdir_logical
+        unset repo_workdir_physical
+        repo_empty=false
+        repo_bare=false
+        unset fetch_age_seconds
+        unset repo_error
+        unset repo_error_code
+        unset repo_error_class
+        unset fetch_error
+        unset fetch_error_code
+        unset fetch_error_class
+        unset head_ref_length
+        unset head_hash
+        unset head_unborn
+        unset head_error
+        unset head_error_code
+        unset head_error_class
+        unset stash_count
+        unset stash_error
+        unset stash_error_code
+        unset stash_error_class
+        unset superproject_workdir
+        unset superproject_path
+        unset superproject_drifted
+        unset superproject_error
+        unset superproject_error_code
+        unset superproject_error_class
+        unset remote_tip_hash
+        unset head_behind_remote_tip
+        unset remote_error
+        unset remote_error_code
+        unset remote_error_class
+        unset head_ahead
+        unset head_behind
+        unset head_ahead_exact
+        unset head_upstream_error
+        unset head_upstream_error_code
+        unset head_upstream_error_class
+        unset upstream_error
+        unset upstream_error_code
+        unset upstream_error_class
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        unset ignored_count
+        unset counts_truncated
+        untracked_truncated=false
+        unset repo_locked
+        unset counts_error
+        unset counts_error_code
+        unset counts_error_class
+        color_hint=green
+        unset color_ansi
+        unset glyphs
+        ";
+    let args = ["--unset-missing", "--only", "upstream,counts"];
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+
+    // The cache replays `unset` lines.
+    let args = ["--cache", "--unset-missing", "--only", "upstream,counts"];
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+}
+
+#[test]
+#[with_test_dir]
+fn stdin() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::git_init(&root, "new\nline");
+
+    let expected = helpers::strip_indent(&format!(
+        r"
+        schema_version=1
+        repo_count=3
+
+        repo1_path=repo
+        repo1_repo_state=Clean
+        repo1_ps1_state=''
+        repo1_repo_workdir={root}/repo/
+        repo1_repo_empty=true
+        repo1_repo_bare=false
+        repo1_fetch_age_seconds=''
+        repo1_stash_count=0
+        repo1_color_hint=green
+
+        repo2_path='new
+        line'
+        repo2_repo_state=Clean
+        repo2_ps1_state=''
+        repo2_repo_workdir='{root}/new
+        line/'
+        repo2_repo_empty=true
+        repo2_repo_bare=false
+        repo2_fetch_age_seconds=''
+        repo2_stash_count=0
+        repo2_color_hint=green
+
+        repo3_path=.
+        repo3_repo_state=NotFound
+
+        repo_error_count=1
+        ",
+        root = root.display()
+    ));
+
+    let output = helpers::git_status_vars_stdin(
+        &root,
+        ["--stdin0", "--only", "stash", "repo"],
+        b"new\nline\0.\0",
+    );
+    assert_str_eq!(expected, output.to_string());
+
+    let output = helpers::git_status_vars_stdin(
+        &root,
+        [
+            "--stdin0",
+            "--only",
+            "stash",
+            "--timeout",
+            "10s",
+            "--timeout-strategy",
+            "process",
+        ],
+        b"repo\0new\nline\0.",
+    );
+    assert_str_eq!(expected, output.to_string());
+
+    // With --stdin, the newline splits the path.
+    let output = helpers::git_status_vars_stdin(
+        &root,
+        ["--stdin", "--only", "stash"],
+        b"repo\n\n.\n",
+    );
+    assert!(
+        output.starts_with(b"schema_version=1\nrepo_count=2\n"),
+        "{output}"
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn subcommands() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    assert_eq!(
+        helpers::git_status_vars(&root, ["summary", "--only", "stash", "repo"]),
+        helpers::git_status_vars(&root, ["--only", "stash", "repo"]),
+    );
+
+    let output = helpers::git_status_vars(&root, ["init", "zsh"]);
+    assert!(
+        output.ends_with(b"add-zsh-hook precmd git_status_vars_update\n"),
+        "{output}"
+    );
+
+    let output =
+        helpers::git_status_vars(&root, ["doctor", "repo"]).to_string();
+    let re = Regex::new(
+        r"^version=[0-9.]+\nlibgit2_version=[0-9.]+\nrepo_git_dir=\S+/repo/\.git/\ntiming_head_us=\d+\ntiming_upstream_us=\d+\ntiming_counts_us=\d+\ntiming_stash_us=\d+\n$",
+    )
+    .unwrap();
+    assert!(re.is_match(&output), "{output}");
+
+    let output = helpers::git_status_vars(&root, ["schema"]).to_string();
+    assert!(output.starts_with("{\n"), "{output}");
+    assert!(output.ends_with("\n}\n"), "{output}");
+    assert!(output.contains(r#""schema_version": {"#), "{output}");
+    assert!(output.contains(r#""^head_ref[0-9]+_name$": {"#), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn subcommand_named_repository() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "init");
+    helpers::make_commit(&root, "init", 1);
+
+    let summarize =
+        |args: &[&str]| helpers::git_status_vars(&root, args).to_string();
+    let expected = summarize(&["./init"]);
+    assert!(expected.contains("\nhead_ref1_short=main\n"), "{expected}");
+
+    // A path named like a subcommand is summarized, like it was before there
+    // were subcommands.
+    assert_eq!(summarize(&["init"]), expected);
+    assert_eq!(summarize(&["--", "init"]), expected);
+    assert_eq!(summarize(&["summary", "init"]), expected);
+    let expected = summarize(&["--only", "head", "./init"]);
+    assert_eq!(summarize(&["init", "--only", "head"]), expected);
+    assert_eq!(summarize(&["--only", "head", "init"]), expected);
+    assert_eq!(summarize(&["init", "--only=head"]), expected);
+
+    // Arguments only the subcommand accepts select the subcommand.
+    let output = summarize(&["init", "zsh"]);
+    assert!(output.contains("git_status_vars_update"), "{output}");
+
+    // Without a path named like it, the subcommand is used.
+    fs::remove_dir_all(root.join("init")).unwrap();
+    assert_eq!(
+        helpers::git_status_vars_exit_code(&root, ["init"]),
+        Some(2),
+        "init requires a shell",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn version() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    let output = helpers::git_status_vars(&root, ["--version"]).to_string();
+    let re = Regex::new(
+        r"^git-status-vars [0-9.]+\nlibgit2 [0-9.]+ \([a-z, ]*\)\noutput formats: sh, fish, powershell, csh\n$",
+    )
+    .unwrap();
+    assert!(re.is_match(&output), "{output}");
+
+    let output = helpers::git_status_vars(&root, ["-V"]).to_string();
+    assert!(!output.contains("libgit2"), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn max_entries() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    for name in ["a", "b", "c"] {
+        fs::write(root.join("repo").join(name), "").unwrap();
+    }
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=true
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=2+
+        unstaged_count=0+
+        staged_count=0+
+        conflicted_count=0+
+        counts_truncated=true
+        untracked_truncated=false
+        color_hint=yellow
+        ";
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--max-entries", "2"],
+        expected,
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--max-entries", "3"],
+        &expected
+            .replace("2+", "3")
+            .replace("0+", "0")
+            .replace("truncated=true", "truncated=false"),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn count_limit() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    for name in ["a", "b", "c"] {
+        fs::write(root.join("repo").join(name), "").unwrap();
+    }
+    helpers::git(&root, "repo", ["add", "a"]).unwrap();
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=true
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=1+
+        unstaged_count=0
+        staged_count=1
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=yellow
+        ";
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--count-limit", "1"],
+        expected,
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--count-limit", "2"],
+        &expected.replace("1+", "2"),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn count_limit_stops_early() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/d"), "").unwrap();
+    helpers::git(&root, "repo", ["add", "d"]).unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "d"]).unwrap();
+    helpers::git(&root, "repo", ["switch", "-c", "branch"]).unwrap();
+    helpers::make_commit(&root, "repo", 2);
+    helpers::git(&root, "repo", ["switch", "main"]).unwrap();
+    helpers::make_commit(&root, "repo", 3);
+    helpers::git(&root, "repo", ["merge", "branch"]).unwrap_err();
+
+    // Status entries are sorted by path: a and b are conflicted, c is staged, d
+    // is unstaged, e is untracked, and so is the directory z.
+    fs::write(root.join("repo/c"), "").unwrap();
+    helpers::git(&root, "repo", ["add", "c"]).unwrap();
+    fs::write(root.join("repo/d"), "d").unwrap();
+    fs::write(root.join("repo/e"), "").unwrap();
+    fs::create_dir(root.join("repo/z")).unwrap();
+    fs::write(root.join("repo/z/file"), "").unwrap();
+
+    let counts = |args: &[&str]| {
+        let mut args = [&["--only", "counts"], args].concat();
+        args.push("repo");
+        helpers::git_status_vars(&root, args).to_string()
+    };
+
+    let output = counts(&[]);
+    assert!(output.contains("\nuntracked_count=2\n"), "{output}");
+    assert!(output.contains("\nuntracked_truncated=true\n"), "{output}");
+
+    // Every count reaches the limit by e, so z is never examined.
+    let output = counts(&["--count-limit", "0"]);
+    for kind in ["untracked", "unstaged", "staged", "conflicted"] {
+        assert!(output.contains(&format!("\n{kind}_count=0+\n")), "{output}");
+    }
+    assert!(output.contains("\nuntracked_truncated=false\n"), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn status_options() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["mv", "a", "c"]).unwrap();
+    fs::rename(root.join("repo/b"), root.join("repo/d")).unwrap();
+    fs::write(root.join("repo/e"), "e").unwrap();
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=2
+        unstaged_count=1
+        staged_count=1
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=yellow
+        ";
+    // Like `git status`, the staged rename is one change, but the deleted and
+    // untracked files aren’t paired up.
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts"],
+        expected,
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--no-untracked"],
+        &expected
+            .replace("untracked_count=2", "untracked_count=0")
+            .replace("\n        untracked_truncated=false", ""),
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--no-detect-renames"],
+        &expected.replace(" staged_count=1", " staged_count=2"),
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &[
+            "--only",
+            "counts",
+            "--no-detect-renames",
+            "--detect-renames",
+        ],
+        expected,
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn renames_config() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["mv", "a", "c"]).unwrap();
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=2
+        conflicted_count=0
+        untracked_truncated=false
+        color_hint=yellow
+        ";
+    let renamed = expected.replace(" staged_count=2", " staged_count=1");
+    let args = ["--only", "counts"];
+
+    helpers::git(&root, "repo", ["config", "diff.renames", "false"]).unwrap();
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--detect-renames"],
+        &renamed,
+    );
+
+    // status.renames takes precedence over diff.renames.
+    helpers::git(&root, "repo", ["config", "status.renames", "copies"])
+        .unwrap();
+    helpers::assert_git_status_vars_args(&root, "repo", &args, &renamed);
+    helpers::git(&root, "repo", ["config", "status.renames", "no"]).unwrap();
+    helpers::git(&root, "repo", ["config", "diff.renames", "true"]).unwrap();
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+
+    // Invalid values are ignored.
+    helpers::git(&root, "repo", ["config", "status.renames", "bogus"]).unwrap();
+    helpers::assert_git_status_vars_args(&root, "repo", &args, &renamed);
+}
+
+#[test]
+#[with_test_dir]
+fn untracked_files() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::create_dir_all(root.join("repo/dir/sub")).unwrap();
+    for path in ["c", "dir/d", "dir/sub/e", "dir/sub/f"] {
+        fs::write(root.join("repo").join(path), "").unwrap();
+    }
+
+    // Like `git status`, the untracked directory is counted once.
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=2
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=true
+        color_hint=yellow
+        ";
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts"],
+        expected,
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--untracked-files", "all"],
+        &expected
+            .replace("untracked_count=2", "untracked_count=4")
+            .replace("untracked_truncated=true", "untracked_truncated=false"),
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--untracked-files", "no"],
+        &expected
+            .replace("untracked_count=2", "untracked_count=0")
+            .replace("\n        untracked_truncated=true", "")
+            .replace("color_hint=yellow", "color_hint=green"),
+    );
+    assert_eq!(
+        helpers::git_status_vars_exit_code(
+            &root,
+            ["--untracked-files", "all", "--no-untracked", "repo"]
+        ),
+        Some(2),
+    );
+    assert_eq!(
+        helpers::git_status_vars_exit_code(
+            &root,
+            ["--untracked-files", "some", "repo"]
+        ),
+        Some(2),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn untracked_files_config() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::create_dir_all(root.join("repo/dir")).unwrap();
+    for path in ["c", "dir/d", "dir/e"] {
+        fs::write(root.join("repo").join(path), "").unwrap();
+    }
+
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        color_hint=green
+        ";
+    let args = ["--only", "counts"];
+    let with_untracked = |count, truncated| {
+        expected
+            .replace("untracked_count=0", &format!("untracked_count={count}"))
+            .replace(
+                "conflicted_count=0",
+                &format!(
+                    "conflicted_count=0\n        untracked_truncated={truncated}"
+                ),
+            )
+            .replace("color_hint=green", "color_hint=yellow")
+    };
+
+    helpers::git(&root, "repo", ["config", "status.showUntrackedFiles", "no"])
+        .unwrap();
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--untracked-files", "normal"],
+        &with_untracked(2, true),
+    );
+
+    // Like git, boolean values are accepted.
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "status.showUntrackedFiles", "false"],
+    )
+    .unwrap();
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "status.showUntrackedFiles", "all"],
+    )
+    .unwrap();
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &args,
+        &with_untracked(3, false),
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--no-untracked"],
+        expected,
+    );
+
+    // The configuration is part of the cache key.
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--cache"],
+        &with_untracked(3, false),
+    );
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "status.showUntrackedFiles", "normal"],
+    )
+    .unwrap();
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--only", "counts", "--cache"],
+        &with_untracked(2, true),
+    );
+
+    // Invalid values are ignored.
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "status.showUntrackedFiles", "bogus"],
+    )
+    .unwrap();
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &args,
+        &with_untracked(2, true),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn skip_counts_above() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/c"), "").unwrap();
+
+    // The index has two entries.
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        unset ps1_state
+        repo_workdir=@REPO@/
+        unset repo_workdir_raw
+        unset repo_workdir_logical
+        unset repo_workdir_physical
+        repo_empty=false
+        repo_bare=false
+        unset fetch_age_seconds
+        unset repo_error
+        unset repo_error_code
+        unset repo_error_class
+        unset fetch_error
+        unset fetch_error_code
+        unset fetch_error_class
+        unset head_ref_length
+        unset head_hash
+        unset head_unborn
+        unset head_error
+        unset head_error_code
+        unset head_error_class
+        unset head_ahead
+        unset head_behind
+        unset head_ahead_exact
+        unset head_upstream_error
+        unset head_upstream_error_code
+        unset head_upstream_error_class
+        unset upstream_error
+        unset upstream_error_code
+        unset upstream_error_class
+        unset stash_count
+        unset stash_error
+        unset stash_error_code
+        unset stash_error_class
+        unset superproject_workdir
+        unset superproject_path
+        unset superproject_drifted
+        unset superproject_error
+        unset superproject_error_code
+        unset superproject_error_class
+        unset remote_tip_hash
+        unset head_behind_remote_tip
+        unset remote_error
+        unset remote_error_code
+        unset remote_error_class
+        unset untracked_count
+        unset unstaged_count
+        unset staged_count
+        unset conflicted_count
+        unset ignored_count
+        unset counts_truncated
+        unset untracked_truncated
+        unset repo_locked
+        unset counts_error
+        unset counts_error_code
+        unset counts_error_class
+        counts_skipped=true
+        color_hint=green
+        unset color_ansi
+        unset glyphs
+        ";
+    let args = ["--unset-missing", "--only", "counts"];
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &[&args[..], &["--skip-counts-above", "1"]].concat(),
+        expected,
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &[&args[..], &["--skip-counts-above", "2"]].concat(),
+        &expected
+            .replace("unset untracked_count", "untracked_count=1")
+            .replace("unset unstaged_count", "unstaged_count=0")
+            .replace("unset staged_count", "staged_count=0")
+            .replace("unset conflicted_count", "conflicted_count=0")
+            .replace("unset untracked_truncated", "untracked_truncated=false")
+            .replace("counts_skipped=true", "unset counts_skipped")
+            .replace("color_hint=green", "color_hint=yellow"),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+#[with_test_dir]
+fn resolve_symlinks() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    fs::create_dir(root.join("real")).unwrap();
+    helpers::git_init(&root, "real/repo");
+    fs::create_dir(root.join("real/repo/sub")).unwrap();
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+
+    let workdir = |args: &[&str]| {
+        helpers::git_status_vars(&root, args)
+            .to_string()
+            .lines()
+            .find_map(|line| line.strip_prefix("repo_workdir="))
+            .map(str::to_owned)
+    };
+    let real = format!("{}/", root.join("real/repo").display());
+    let link = format!("{}/", root.join("link/repo").display());
+
+    assert_eq!(workdir(&["link/repo"]), Some(real.clone()));
+    assert_eq!(
+        workdir(&["--no-resolve-symlinks", "--resolve-symlinks", "link/repo"]),
+        Some(real)
+    );
+    assert_eq!(
+        workdir(&["--no-resolve-symlinks", "link/repo"]),
+        Some(link.clone())
+    );
+    assert_eq!(
+        workdir(&["--no-resolve-symlinks", "link/repo/sub/."]),
+        Some(link.clone())
+    );
+    assert_eq!(
+        workdir(&["--no-resolve-symlinks", "link/repo/sub/../../repo"]),
+        Some(link)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+#[with_test_dir]
+fn both_workdirs() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    fs::create_dir(root.join("real")).unwrap();
+    helpers::git_init(&root, "real/repo");
+    std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+
+    let workdirs = |args: &[&str]| {
+        helpers::git_status_vars(&root, args)
+            .to_string()
+            .lines()
+            .filter(|line| line.contains("repo_workdir"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let real = format!("{}/", root.join("real/repo").display());
+    let link = format!("{}/", root.join("link/repo").display());
+
+    assert_eq!(
+        workdirs(&["--both-workdirs", "link/repo"]),
+        format!(
+            "repo_workdir={link}\n\
+            repo_workdir_logical={link}\n\
+            repo_workdir_physical={real}"
+        )
+    );
+    assert_eq!(
+        workdirs(&["--both-workdirs", "real/repo"]),
+        format!(
+            "repo_workdir={real}\n\
+            repo_workdir_logical={real}\n\
+            repo_workdir_physical={real}"
+        )
+    );
+    assert_eq!(
+        workdirs(&["--unset-missing", "link/repo"]),
+        format!(
+            "repo_workdir={real}\n\
+            unset repo_workdir_raw\n\
+            unset repo_workdir_logical\n\
+            unset repo_workdir_physical"
+        )
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn superproject() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "sub");
+    helpers::make_commit(&root, "sub", 1);
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let add = ["-c", "protocol.file.allow=always", "submodule", "add"];
+    helpers::git(&root, "repo", add.into_iter().chain(["../sub", "sub"]))
+        .unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "add sub"]).unwrap();
+
+    let superproject = |repo: &str| {
+        helpers::git_status_vars(&root, ["--only", "superproject", repo])
+            .to_string()
+            .lines()
+            .filter(|line| line.starts_with("superproject_"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let workdir = root.join("repo").canonicalize().unwrap();
+    let workdir = workdir.display();
+
+    assert_eq!(
+        superproject("repo/sub"),
+        format!(
+            "superproject_workdir={workdir}/\n\
+            superproject_path=sub\n\
+            superproject_drifted=false"
+        ),
+    );
+    assert!(
+        helpers::git_status_vars(&root, ["--superproject", "repo/sub"])
+            .to_string()
+            .contains("\nstash_count=0\nsuperproject_workdir=")
+    );
+
+    helpers::make_commit(&root, "repo/sub", 2);
+    assert!(superproject("repo/sub").ends_with("superproject_drifted=true"));
+
+    assert_eq!(
+        superproject("repo"),
+        "superproject_workdir=''\n\
+        superproject_path=''\n\
+        superproject_drifted=''",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn cache_superproject() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "sub");
+    helpers::make_commit(&root, "sub", 1);
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let add = ["-c", "protocol.file.allow=always", "submodule", "add"];
+    helpers::git(&root, "repo", add.into_iter().chain(["../sub", "sub"]))
+        .unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "add sub"]).unwrap();
+    helpers::make_commit(&root, "repo/sub", 2);
+
+    let drifted = || {
+        helpers::git_status_vars(
+            &root,
+            ["--cache", "--only", "superproject", "repo/sub"],
+        )
+        .to_string()
+        .lines()
+        .find(|line| line.starts_with("superproject_drifted="))
+        .unwrap()
+        .to_owned()
+    };
+    assert_eq!(drifted(), "superproject_drifted=true");
+
+    // Committing the gitlink changes only the superproject.
+    helpers::git(&root, "repo", ["add", "sub"]).unwrap();
+    helpers::git(&root, "repo", ["commit", "-m", "update sub"]).unwrap();
+    assert_eq!(drifted(), "superproject_drifted=false");
+}
+
+#[cfg(unix)]
+#[test]
+#[with_test_dir]
+fn update_index() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    let index_path = root.join("repo/.git/index");
+
+    // Change the stat information without changing the contents.
+    duct::cmd!("touch", "-d", "2001-01-01", root.join("repo/a"))
+        .run()
+        .unwrap();
+    let index = fs::read(&index_path).unwrap();
+
+    let args = ["--only", "counts"];
+    let output = helpers::git_status_vars(&root, args.iter().chain(&["repo"]));
+    assert!(
+        output.ends_with(
+            b"unstaged_count=0\nstaged_count=0\nconflicted_count=0\n\
+            untracked_truncated=false\ncolor_hint=green\n"
+        ),
+        "{output}"
+    );
+    assert_eq!(index, fs::read(&index_path).unwrap());
+
+    let args = ["--only", "counts", "--update-index", "repo"];
+    let output = helpers::git_status_vars(&root, args);
+    assert!(
+        output.ends_with(
+            b"conflicted_count=0\nuntracked_truncated=false\nrepo_locked=false\n\
+            color_hint=green\n"
+        ),
+        "{output}"
+    );
+    assert_ne!(index, fs::read(&index_path).unwrap());
+
+    // Rebuild the index and then modify a file while another process has the
+    // index locked, so it needs to be updated but can’t be.
+    fs::remove_file(&index_path).unwrap();
+    helpers::git(&root, "repo", ["reset", "-q"]).unwrap();
+    let index = fs::read(&index_path).unwrap();
+    fs::write(root.join("repo/.git/index.lock"), "").unwrap();
+    duct::cmd!("touch", root.join("repo/a")).run().unwrap();
+    let output = helpers::git_status_vars(&root, args);
+    assert!(
+        output.ends_with(
+            b"conflicted_count=0\nuntracked_truncated=false\nrepo_locked=true\n\
+            color_hint=green\n"
+        ),
+        "{output}"
+    );
+    assert_eq!(index, fs::read(&index_path).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+#[with_test_dir]
+fn fsmonitor() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    // A fake hook that reports the paths in the file `changed`.
+    let changed = root.join("changed");
+    let hook = root.join("hook");
+    fs::write(
+        &hook,
+        format!("#!/bin/sh\nprintf 'token\\0'\ncat {changed:?}\n"),
+    )
+    .unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(&changed, "").unwrap();
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "core.fsmonitor", hook.to_str().unwrap()],
+    )
+    .unwrap();
+
+    let args = ["--only", "counts", "--fsmonitor", "repo"];
+    let counts = |untracked, unstaged| {
+        format!(
+            "untracked_count={untracked}\nunstaged_count={unstaged}\n\
+                staged_count=0\nconflicted_count=0\nuntracked_truncated=false\n\
+                color_hint=yellow\n"
+        )
+    };
+
+    // Nothing saved yet, so everything is checked.
+    fs::write(root.join("repo/a"), "changed").unwrap();
+    let output = helpers::git_status_vars(&root, args);
+    assert!(output.ends_with(counts(0, 1).as_bytes()), "{output}");
+
+    // The hook doesn’t report any changes, so none are found.
+    fs::write(root.join("repo/b"), "changed").unwrap();
+    fs::write(root.join("repo/c"), "new").unwrap();
+    let output = helpers::git_status_vars(&root, args);
+    assert!(output.ends_with(counts(0, 1).as_bytes()), "{output}");
+
+    // Only the tracked file reported by the hook is checked.
+    fs::write(&changed, "b\0").unwrap();
+    let output = helpers::git_status_vars(&root, args);
+    assert!(output.ends_with(counts(0, 2).as_bytes()), "{output}");
+
+    // The hook reports that everything changed.
+    fs::write(&changed, "/\0").unwrap();
+    let output = helpers::git_status_vars(&root, args);
+    assert!(output.ends_with(counts(1, 2).as_bytes()), "{output}");
+
+    // Without --fsmonitor, the hook isn’t used.
+    fs::write(&changed, "").unwrap();
+    fs::write(root.join("repo/a"), "1a").unwrap();
+    let output = helpers::git_status_vars(&root, ["--only", "counts", "repo"]);
+    assert!(output.ends_with(counts(1, 1).as_bytes()), "{output}");
+}
+
+#[cfg(unix)]
+#[test]
+#[with_test_dir]
+fn fsmonitor_path() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    // A fake hook on `$PATH` that never reports any changes.
+    let bin = root.join("bin");
+    fs::create_dir(&bin).unwrap();
+    let hook = bin.join("query-hook");
+    fs::write(&hook, "#!/bin/sh\nprintf 'token\\0'\n").unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    helpers::git(&root, "repo", ["config", "core.fsmonitor", "query-hook"])
+        .unwrap();
+
+    let path = format!(
+        "{}:{}",
+        bin.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let unstaged = || {
+        let output = helpers::git_status_vars_env(
+            &root,
+            ".",
+            ["--only", "counts", "--fsmonitor", "repo"],
+            &[("PATH", &path)],
+        )
+        .to_string();
+        output
+            .lines()
+            .find(|line| line.starts_with("unstaged_count="))
+            .unwrap_or(&output)
+            .to_owned()
+    };
+
+    // Nothing saved yet, so everything is checked.
+    fs::write(root.join("repo/a"), "changed").unwrap();
+    assert_eq!(unstaged(), "unstaged_count=1");
+
+    // The hook is found on `$PATH` and doesn’t report the change.
+    fs::write(root.join("repo/b"), "changed").unwrap();
+    assert_eq!(unstaged(), "unstaged_count=1");
+}
+
+#[test]
+#[with_test_dir]
+fn parallel() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/a"), "changed").unwrap();
+    fs::write(root.join("repo/c"), "new").unwrap();
+    helpers::git(&root, "repo", ["add", "c"]).unwrap();
+    fs::write(root.join("repo/d"), "new").unwrap();
+
+    assert_str_eq!(
+        helpers::git_status_vars(&root, ["repo"]).to_string(),
+        helpers::git_status_vars(&root, ["--parallel", "repo"]).to_string(),
+    );
+
+    let output =
+        helpers::git_status_vars(&root, ["--parallel", "--timing", "repo"]);
+    assert!(
+        Regex::new(r"(?m)^timing_counts_us=\d+$")
+            .unwrap()
+            .is_match(&output.to_string()),
+        "{output}"
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn budget() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/c"), "new").unwrap();
+
+    // A section in another thread can’t finish in 0ms.
+    let expected = r"
+        schema_version=1
+        repo_state=Clean
+        unset ps1_state
+        repo_workdir=@REPO@/
+        unset repo_workdir_raw
+        unset repo_workdir_logical
+        unset repo_workdir_physical
+        repo_empty=false
+        repo_bare=false
+        unset fetch_age_seconds
+        unset repo_error
+        unset repo_error_code
+        unset repo_error_class
+        unset fetch_error
+        unset fetch_error_code
+        unset fetch_error_class
+        unset head_ahead
+        unset head_behind
+        unset head_ahead_exact
+        unset head_upstream_error
+        unset head_upstream_error_code
+        unset head_upstream_error_class
+        unset upstream_error
+        unset upstream_error_code
+        unset upstream_error_class
+        unset superproject_workdir
+        unset superproject_path
+        unset superproject_drifted
+        unset superproject_error
+        unset superproject_error_code
+        unset superproject_error_class
+        unset remote_tip_hash
+        unset head_behind_remote_tip
+        unset remote_error
+        unset remote_error_code
+        unset remote_error_class
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        unset head_ref1_error
+        unset head_ref1_error_code
+        unset head_ref1_error_class
+        head_hash=@HASH@
+        head_unborn=false
+        unset head_error
+        unset head_error_code
+        unset head_error_class
+        unset head_timeout
+        unset counts_error
+        unset counts_error_code
+        unset counts_error_class
+        counts_timeout=true
+        stash_count=0
+        unset stash_error
+        unset stash_error_code
+        unset stash_error_class
+        color_hint=green
+        unset color_ansi
+        unset glyphs
+        ";
+    let args = [
+        "--unset-missing",
+        "--no-upstream",
+        "--budget",
+        "head=60s,counts=0ms",
+    ];
+    helpers::assert_git_status_vars_args(&root, "repo", &args, expected);
+}
+
+#[test]
+#[with_test_dir]
+fn trace() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    // stderr is mixed into the output.
+    let output = helpers::git_status_vars(&root, ["--trace", "repo"]);
+    let output = output.to_string();
+    for span in ["open", "  head_info", "    statuses", "  count_changes"] {
+        let re = Regex::new(&format!(r"(?m)^trace: {span} \d+us$")).unwrap();
+        assert!(re.is_match(&output), "{span:?} missing: {output}");
+    }
+    assert!(output.contains("\nrepo_state=Clean\n"), "{output}");
+
+    let output = helpers::git_status_vars(&root, ["repo"]);
+    assert!(!output.to_string().contains("trace:"), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn head_only() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::git_init(&root, "unborn");
+    helpers::make_commit(&root, "repo", 1);
+
+    // The head variables should match a normal summary.
+    let head_vars = |args: &[&str]| {
+        let output = helpers::git_status_vars(&root, args).to_string();
+        output
+            .lines()
+            .filter(|line| line.starts_with("head_"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let assert_same = |repo: &str| {
+        assert_str_eq!(
+            head_vars(&["--only", "head", repo]),
+            head_vars(&["--head-only", repo]),
+        );
+    };
+
+    assert_same("repo");
+    assert_same("repo/.git");
+    assert_same("unborn");
+    helpers::git(&root, "repo", ["pack-refs", "--all"]).unwrap();
+    assert_same("repo");
+    helpers::git(&root, "repo", ["checkout", "--detach"]).unwrap();
+    assert_same("repo");
+
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--unset-missing", "--head-only"],
+        r"
+        schema_version=1
+        repo_state=Unknown
+        unset repo_workdir
+        unset repo_workdir_raw
+        unset repo_workdir_logical
+        unset repo_workdir_physical
+        unset repo_empty
+        unset repo_bare
+        unset fetch_age_seconds
+        unset repo_error
+        unset repo_error_code
+        unset repo_error_class
+        head_ref_length=0
+        head_hash=@HASH@
+        head_unborn=false
+        unset head_error
+        unset head_error_code
+        unset head_error_class
+        unset fetch_error
+        unset fetch_error_code
+        unset fetch_error_class
+        unset head_ahead
+        unset head_behind
+        unset head_ahead_exact
+        unset head_upstream_error
+        unset head_upstream_error_code
+        unset head_upstream_error_class
+        unset upstream_error
+        unset upstream_error_code
+        unset upstream_error_class
+        unset untracked_count
+        unset unstaged_count
+        unset staged_count
+        unset conflicted_count
+        unset ignored_count
+        unset counts_truncated
+        unset untracked_truncated
+        unset repo_locked
+        unset counts_error
+        unset counts_error_code
+        unset counts_error_class
+        unset stash_count
+        unset stash_error
+        unset stash_error_code
+        unset stash_error_class
+        unset superproject_workdir
+        unset superproject_path
+        unset superproject_drifted
+        unset superproject_error
+        unset superproject_error_code
+        unset superproject_error_class
+        unset remote_tip_hash
+        unset head_behind_remote_tip
+        unset remote_error
+        unset remote_error_code
+        unset remote_error_class
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn dialect() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["checkout", "-q", "-b", "it's"]).unwrap();
+
+    let output = helpers::git_status_vars(
+        &root,
+        ["--dialect", "fish", "--unset-missing", "repo"],
+    )
+    .to_string();
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines[0], "set schema_version 1");
+    assert!(lines.contains(&"set head_ref1_short 'it\\'s'"), "{output}");
+    assert!(lines.contains(&"set -e repo_workdir_raw"), "{output}");
+
+    // PowerShell has booleans and numbers, so they aren’t strings. The cache
+    // keeps the types.
+    for args in [&[][..], &["--cache"], &["--cache"]] {
+        let args = [&["--dialect", "powershell"], args, &["repo"]].concat();
+        let output = helpers::git_status_vars(&root, args).to_string();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "$schema_version = 1");
+        for line in [
+            "$head_ref1_short = 'it''s'",
+            "$repo_state = 'Clean'",
+            "$repo_empty = $false",
+            "$head_unborn = $false",
+            "$head_ahead = ''",
+            "$untracked_count = 0",
+            "$untracked_truncated = $false",
+            "$stash_count = 0",
+        ] {
+            assert!(lines.contains(&line), "{line}\n{output}");
+        }
+    }
+
+    // Prefixes like `local ` only work in sh.
+    for dialect in ["fish", "powershell", "csh"] {
+        assert_eq!(
+            helpers::git_status_vars_exit_code(
+                &root,
+                ["--dialect", dialect, "-p", "local ", "repo"],
+            ),
+            Some(2),
+        );
+    }
+
+    assert_eq!(
+        helpers::git_status_vars_exit_code(&root, ["--dialect", "bash"]),
+        Some(2),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn path_style() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    // Paths are only changed on Windows.
+    assert_eq!(
+        helpers::git_status_vars(&root, ["--path-style", "slash", "repo"]),
+        helpers::git_status_vars(&root, ["repo"]),
+    );
+    assert_eq!(
+        helpers::git_status_vars_exit_code(&root, ["--path-style", "dos"]),
+        Some(2),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn prompt() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    helpers::make_commit(&root, "upstream", 2);
+    helpers::make_commit(&root, "clone", 3);
+    helpers::git(&root, "clone", ["fetch"]).unwrap();
+
+    let prompt = |args: &[&str]| {
+        helpers::git_status_vars(
+            &root,
+            std::iter::once(&"--prompt").chain(args).chain(&["clone"]),
+        )
+        .to_string()
+    };
+    let branch = prompt(&[]);
+    let branch = branch.split(' ').next().unwrap();
+    assert_eq!(prompt(&[]), format!("{branch} ↑1 ↓1\n"));
+
+    fs::write(root.join("clone/a"), "changed").unwrap();
+    fs::write(root.join("clone/c"), "new").unwrap();
+    fs::write(root.join("clone/d"), "new").unwrap();
+    helpers::git(&root, "clone", ["add", "d"]).unwrap();
+    assert_eq!(prompt(&[]), format!("{branch} ↑1 ↓1 +1 !1 ?1\n"));
+    assert_eq!(
+        prompt(&["--prompt-symbols", "ahead=>,untracked=…", "--no-upstream"]),
+        format!("{branch} +1 !1 …1\n"),
+    );
+
+    helpers::git(&root, "clone", ["stash", "-u"]).unwrap();
+    helpers::git(&root, "clone", ["merge", "@{upstream}"])
+        .expect_err("merge should conflict");
+    assert_eq!(
+        prompt(&["--prompt-symbols", "state=/"]),
+        format!("{branch}/MERGING ↑1 ↓1 =2 $1\n"),
+    );
+    assert_eq!(
+        helpers::git_status_vars_exit_code(
+            &root,
+            ["--prompt", "--exit-code", "clone"]
+        ),
+        Some(2),
+    );
+    assert_eq!(
+        helpers::git_status_vars(&root, ["--prompt", "not-a-repo"]),
+        "\n",
+    );
+    assert_eq!(
+        helpers::git_status_vars_exit_code(
+            &root,
+            ["--prompt-symbols", "bogus=x", "--prompt", "clone"]
+        ),
+        Some(2),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn color_hint() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let colors = |args: &[&str]| {
+        let output = helpers::git_status_vars(
+            &root,
+            args.iter().chain(&["--only", "counts", "repo"]),
+        )
+        .to_string();
+        output
+            .lines()
+            .filter(|line| {
+                line.contains(" color_") || line.starts_with("color_")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(colors(&[]), "color_hint=green");
+    assert_eq!(
+        colors(&["--color-ansi"]),
+        "color_hint=green\ncolor_ansi='\x1b[32m'",
+    );
+    assert_eq!(
+        colors(&["--unset-missing"]),
+        "color_hint=green\nunset color_ansi",
+    );
+
+    fs::write(root.join("repo/c"), "new").unwrap();
+    assert_eq!(
+        colors(&["--color-ansi"]),
+        "color_hint=yellow\ncolor_ansi='\x1b[33m'",
+    );
+
+    helpers::git(&root, "repo", ["checkout", "-q", "-b", "other"]).unwrap();
+    helpers::make_commit(&root, "repo", 2);
+    helpers::git(&root, "repo", ["checkout", "-q", "-"]).unwrap();
+    helpers::make_commit(&root, "repo", 3);
+    helpers::git(&root, "repo", ["merge", "other"])
+        .expect_err("merge should conflict");
+    assert_eq!(
+        colors(&["--color-ansi"]),
+        "color_hint=red\ncolor_ansi='\x1b[31m'",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn glyphs() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    helpers::make_commit(&root, "clone", 2);
+
+    let glyphs = |args: &[&str]| {
+        let output =
+            helpers::git_status_vars(&root, args.iter().chain(&["clone"]))
+                .to_string();
+        output
+            .lines()
+            .filter(|line| {
+                line.starts_with("glyphs=") || line.ends_with(" glyphs")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_eq!(glyphs(&[]), "");
+    assert_eq!(glyphs(&["--unset-missing"]), "unset glyphs");
+    assert_eq!(glyphs(&["--glyphs", "unicode"]), "glyphs=↑1");
+
+    fs::write(root.join("clone/c"), "new").unwrap();
+    helpers::git(&root, "clone", ["stash", "-u"]).unwrap();
+    fs::write(root.join("clone/a"), "changed").unwrap();
+    fs::write(root.join("clone/c"), "new").unwrap();
+    fs::write(root.join("clone/d"), "new").unwrap();
+    fs::write(root.join("clone/e"), "new").unwrap();
+    helpers::git(&root, "clone", ["add", "e"]).unwrap();
+    assert_eq!(glyphs(&["--glyphs", "unicode"]), "glyphs='●1 ✚1 …2 ⚑1 ↑1'");
+    assert_eq!(glyphs(&["--glyphs", "ascii"]), "glyphs='+1 *1 ?2 $1 >1'");
+    assert_eq!(
+        glyphs(&["--glyphs", "ascii", "--no-upstream", "--no-stash"]),
+        "glyphs='+1 *1 ?2'",
+    );
+    assert_eq!(
+        glyphs(&["--glyphs", "ascii", "--count-limit", "1"]),
+        "glyphs='+1 *1 ?1+ $1 >1'",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn vcs_info() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let vcs_info = |args: &[&str]| {
+        helpers::git_status_vars(
+            &root,
+            std::iter::once(&"--vcs-info").chain(args).chain(&["repo"]),
+        )
+        .to_string()
+    };
+    let output = vcs_info(&[]);
+    let hash = output
+        .lines()
+        .find_map(|line| line.strip_prefix("vcs_info_revision="))
+        .unwrap()
+        .to_owned();
+    assert_eq!(
+        output,
+        format!(
+            "vcs_info_msg_0_=' (git)-[main]-'\n\
+            vcs_info_branch=main\n\
+            vcs_info_action=''\n\
+            vcs_info_unstaged=''\n\
+            vcs_info_staged=''\n\
+            vcs_info_revision={hash}\n"
+        ),
+    );
+
+    fs::write(root.join("repo/a"), "changed").unwrap();
+    fs::write(root.join("repo/c"), "new").unwrap();
+    helpers::git(&root, "repo", ["add", "c"]).unwrap();
+    assert!(vcs_info(&[]).starts_with("vcs_info_msg_0_=' (git)-[main]US-'\n"));
+    assert!(vcs_info(&[
+        "--vcs-info-formats",
+        "%F{green}%b%f%u%c %%",
+        "--vcs-info-formats",
+        "%r@%3.3i|%-5x",
+        "--vcs-info-unstagedstr",
+        "*",
+        "--vcs-info-stagedstr",
+        "+",
+    ])
+    .starts_with(&format!(
+        "vcs_info_msg_0_='%F{{green}}main%f*+ %%'\n\
+        vcs_info_msg_1_='repo@{}|%-5x'\n",
+        &hash[..3],
+    )));
+
+    helpers::git(&root, "repo", ["stash"]).unwrap();
+    helpers::git(&root, "repo", ["checkout", "-q", "-b", "other"]).unwrap();
+    helpers::make_commit(&root, "repo", 2);
+    helpers::git(&root, "repo", ["checkout", "-q", "-"]).unwrap();
+    helpers::make_commit(&root, "repo", 3);
+    helpers::git(&root, "repo", ["merge", "other"])
+        .expect_err("merge should conflict");
+    assert!(vcs_info(&["--vcs-info-formats", "unused"])
+        .starts_with("vcs_info_msg_0_=' (git)-[main|merge]US-'\n"));
+
+    assert_eq!(
+        helpers::git_status_vars(&root, ["--vcs-info", "."]),
+        "vcs_info_msg_0_=''\n\
+        vcs_info_branch=''\n\
+        vcs_info_action=''\n\
+        vcs_info_unstaged=''\n\
+        vcs_info_staged=''\n\
+        vcs_info_revision=''\n",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn p10k() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    helpers::make_commit(&root, "clone", 2);
+    fs::write(root.join("clone/a"), "changed").unwrap();
+    fs::write(root.join("clone/c"), "new").unwrap();
+
+    let p10k = |args: &[&str]| {
+        let output = helpers::git_status_vars(
+            &root,
+            std::iter::once(&"--p10k").chain(args).chain(&["clone"]),
+        )
+        .to_string();
+        let re = Regex::new(r"=[0-9a-f]{40}\n").unwrap();
+        re.replace(&output, "=@HASH@\n").into_owned()
+    };
+    assert_eq!(
+        p10k(&[]),
+        format!(
+            "VCS_STATUS_RESULT=ok-sync\n\
+            VCS_STATUS_WORKDIR={}\n\
+            VCS_STATUS_COMMIT=@HASH@\n\
+            VCS_STATUS_LOCAL_BRANCH=main\n\
+            VCS_STATUS_REMOTE_NAME=''\n\
+            VCS_STATUS_REMOTE_BRANCH=''\n\
+            VCS_STATUS_REMOTE_URL=''\n\
+            VCS_STATUS_ACTION=''\n\
+            VCS_STATUS_HAS_STAGED=0\n\
+            VCS_STATUS_HAS_UNSTAGED=1\n\
+            VCS_STATUS_HAS_CONFLICTED=0\n\
+            VCS_STATUS_HAS_UNTRACKED=1\n\
+            VCS_STATUS_NUM_STAGED=0\n\
+            VCS_STATUS_NUM_UNSTAGED=1\n\
+            VCS_STATUS_NUM_CONFLICTED=0\n\
+            VCS_STATUS_NUM_UNTRACKED=1\n\
+            VCS_STATUS_COMMITS_AHEAD=1\n\
+            VCS_STATUS_COMMITS_BEHIND=0\n\
+            VCS_STATUS_PUSH_COMMITS_AHEAD=0\n\
+            VCS_STATUS_PUSH_COMMITS_BEHIND=0\n\
+            VCS_STATUS_STASHES=0\n\
+            VCS_STATUS_TAG=''\n",
+            root.join("clone").display(),
+        ),
+    );
+
+    // Counts that weren’t computed are unknown.
+    let output = p10k(&["--skip-counts-above", "1"]);
+    assert!(output.contains(
+        "VCS_STATUS_HAS_STAGED=-1\n\
+        VCS_STATUS_HAS_UNSTAGED=-1\n\
+        VCS_STATUS_HAS_CONFLICTED=-1\n\
+        VCS_STATUS_HAS_UNTRACKED=-1\n\
+        VCS_STATUS_NUM_STAGED=0\n"
+    ));
+
+    let output = helpers::git_status_vars(&root, ["--p10k", "."]);
+    assert!(output.starts_with(
+        b"VCS_STATUS_RESULT=norepo-sync\nunset VCS_STATUS_WORKDIR\n"
+    ));
+}
+
+#[test]
+#[with_test_dir]
+fn two_phase() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/c"), "new").unwrap();
+
+    let output =
+        helpers::git_status_vars(&root, ["--two-phase", "repo"]).to_string();
+    let (first, second) = output.split_once("\n\n").unwrap();
+    assert!(first.starts_with("schema_version=1\nrepo_state=Clean\n"));
+    assert!(first.ends_with("head_unborn=false"), "{first}");
+    assert!(second.contains("untracked_count=1\n"), "{second}");
+    assert!(second.ends_with("color_hint=yellow\n"), "{second}");
+
+    // The phases together are the usual output.
+    assert_eq!(
+        format!("{first}\n{second}"),
+        helpers::git_status_vars(&root, ["repo"]).to_string(),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn serve() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::process::{Command, Stdio};
+
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::create_dir(root.join("norepo")).unwrap();
+
+    let mut child =
+        Command::new(assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME")))
+            .args(["serve", "--http", "127.0.0.1:0"])
+            .current_dir(&root)
+            .env("HOME", &root)
+            .env("GIT_CONFIG_GLOBAL", root.join(".gitconfig"))
+            .env("GIT_CONFIG_SYSTEM", "/dev/null")
+            .env("GIT_CEILING_DIRECTORIES", root.parent().unwrap())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stderr.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let address = line.trim().strip_prefix("listening on http://").unwrap();
+
+    let get = |target: &str| {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {target} HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = get("/summary?path=repo");
+    let result = std::panic::catch_unwind(|| {
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        assert!(
+            body.starts_with(
+                r#"{"schema_version": 1, "repo_state": "Clean", "#
+            ),
+            "{body}"
+        );
+        assert!(body.contains(r#""head_ref1_short": "main""#), "{body}");
+        assert!(body.ends_with("}\n"), "{body}");
+
+        let response = get("/summary?path=norepo");
+        assert!(
+            response.contains(r#""repo_state": "NotFound""#),
+            "{response}"
+        );
+
+        // A missing path is an error, as it is with the shell output.
+        let response = get("/summary?path=%2Fnonexistent");
+        assert!(response.contains(r#""repo_state": "Error""#), "{response}");
+
+        assert!(get("/summary").starts_with("HTTP/1.1 400 "));
+        assert!(get("/other?path=repo").starts_with("HTTP/1.1 404 "));
+
+        // Lines are only read up to a limit.
+        let long = format!("/summary?path={}", "a".repeat(10_000));
+        assert!(get(&long).starts_with("HTTP/1.1 414 "));
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn serve_refused() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    let exit_code = |args: &[&str]| {
+        helpers::git_status_vars_exit_code(
+            &root,
+            [&["serve", "--http"], args].concat(),
+        )
+    };
+
+    // There’s no authentication, so only loopback addresses are allowed by
+    // default.
+    assert_eq!(exit_code(&["0.0.0.0:0"]), Some(2));
+    assert_eq!(exit_code(&["[::]:0"]), Some(2));
+
+    // Options that write to the repository or use the network.
+    assert_eq!(exit_code(&["127.0.0.1:0", "--update-index"]), Some(2));
+    assert_eq!(exit_code(&["127.0.0.1:0", "--cache"]), Some(2));
+    assert_eq!(exit_code(&["127.0.0.1:0", "--fetch"]), Some(2));
+    assert_eq!(exit_code(&["127.0.0.1:0", "--online"]), Some(2));
+
+    // Options that compute sections in other threads.
+    assert_eq!(exit_code(&["127.0.0.1:0", "--budget", "head=1s"]), Some(2));
+    assert_eq!(exit_code(&["127.0.0.1:0", "--parallel"]), Some(2));
+
+    // Options that change how the output is produced.
+    for option in [
+        "--prompt",
+        "--vcs-info",
+        "--p10k",
+        "--head-only",
+        "--two-phase",
+        "--stdin",
+        "--stdin0",
+        "--exit-code",
+    ] {
+        assert_eq!(exit_code(&["127.0.0.1:0", option]), Some(2), "{option}");
+    }
+    assert_eq!(exit_code(&["127.0.0.1:0", "--bench", "3"]), Some(2));
+
+    // Options that only affect shell output; serve always outputs JSON.
+    assert_eq!(exit_code(&["127.0.0.1:0", "--prefix", "g_"]), Some(2));
+    assert_eq!(exit_code(&["127.0.0.1:0", "--dialect", "fish"]), Some(2));
+    assert_eq!(exit_code(&["127.0.0.1:0", "--unset-missing"]), Some(2));
+}
+
+#[test]
+#[with_test_dir]
+fn watch() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let (mut child, summaries) = helpers::git_status_vars_summaries(
+        &root,
+        ["watch", "--interval", "50ms", "repo"],
+    );
+    let result = std::panic::catch_unwind(|| {
+        let next = || summaries.recv_timeout(Duration::from_secs(10)).unwrap();
+        let expected = helpers::git_status_vars(&root, ["repo"]).to_string();
+        assert_str_eq!(next(), expected);
+        assert_str_eq!(next(), expected);
+
+        // Changes show up in a later summary.
+        fs::write(root.join("repo/untracked"), "").unwrap();
+        while next().contains("\nuntracked_count=0\n") {}
+        assert!(next().contains("\nuntracked_count=1\n"));
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn watch_debounce() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let (mut child, summaries) = helpers::git_status_vars_summaries(
+        &root,
+        ["watch", "--interval", "20ms", "--debounce", "3600s", "repo"],
+    );
+    let result = std::panic::catch_unwind(|| {
+        // Nothing has changed yet, so summaries aren’t held back.
+        let next = || summaries.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(next().contains("\nuntracked_count=0\n"));
+        assert!(next().contains("\nuntracked_count=0\n"));
+
+        // Once something changes, nothing is output until an hour passes.
+        fs::write(root.join("repo/untracked"), "").unwrap();
+        while let Ok(summary) =
+            summaries.recv_timeout(Duration::from_millis(500))
+        {
+            assert!(summary.contains("\nuntracked_count=0\n"), "{summary}");
+        }
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn watch_debounce_shorter() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let (mut child, summaries) = helpers::git_status_vars_summaries(
+        &root,
+        [
+            "watch",
+            "--interval",
+            "2s",
+            "--debounce",
+            "50ms",
+            "--changed-only",
+            "repo",
+        ],
+    );
+    let result = std::panic::catch_unwind(|| {
+        let next = || summaries.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(next().contains("\nuntracked_count=0\n"));
+
+        // The change is noticed at the next interval, then output once it has
+        // settled for the debounce, rather than at the interval after that.
+        let start = std::time::Instant::now();
+        fs::write(root.join("repo/untracked"), "").unwrap();
+        assert!(next().contains("\nuntracked_count=1\n"));
+        let elapsed = start.elapsed();
+        assert!(elapsed < Duration::from_millis(3500), "{elapsed:?}");
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn watch_changed_only() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/.git/FETCH_HEAD"), "").unwrap();
+
+    let (mut child, summaries) = helpers::git_status_vars_summaries(
+        &root,
+        ["watch", "--interval", "20ms", "--changed-only", "repo"],
+    );
+    let result = std::panic::catch_unwind(|| {
+        let next = |timeout| summaries.recv_timeout(timeout);
+        let summary = next(Duration::from_secs(10)).unwrap();
+        assert!(summary.contains("\nuntracked_count=0\n"), "{summary}");
+
+        // Nothing has changed, so nothing else is output. The fetch age goes
+        // up without the repository changing, so it’s ignored.
+        assert_eq!(
+            next(Duration::from_millis(1500)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        fs::write(root.join("repo/untracked"), "").unwrap();
+        let summary = next(Duration::from_secs(10)).unwrap();
+        assert!(summary.contains("\nuntracked_count=1\n"), "{summary}");
+        assert_eq!(
+            next(Duration::from_millis(500)),
+            Err(RecvTimeoutError::Timeout)
+        );
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+#[with_test_dir]
+fn watch_fsmonitor() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    // A fake hook that reports the paths in the file `changed`.
+    let changed = root.join("changed");
+    let hook = root.join("hook");
+    fs::write(
+        &hook,
+        format!("#!/bin/sh\nprintf 'token\\0'\ncat {changed:?}\n"),
+    )
+    .unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(&changed, "").unwrap();
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "core.fsmonitor", hook.to_str().unwrap()],
+    )
+    .unwrap();
+
+    let (mut child, summaries) = helpers::git_status_vars_summaries(
+        &root,
+        [
+            "watch",
+            "--interval",
+            "20ms",
+            "--changed-only",
+            "--fsmonitor",
+            "repo",
+        ],
+    );
+    let result = std::panic::catch_unwind(|| {
+        let next = |timeout| summaries.recv_timeout(timeout);
+        let summary = next(Duration::from_secs(10)).unwrap();
+        assert!(summary.contains("\nunstaged_count=0\n"), "{summary}");
+
+        // The hook doesn’t report the change, so it isn’t noticed.
+        fs::write(root.join("repo/a"), "changed").unwrap();
+        assert_eq!(
+            next(Duration::from_millis(500)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        fs::write(&changed, "a\0").unwrap();
+        let summary = next(Duration::from_secs(10)).unwrap();
+        assert!(summary.contains("\nunstaged_count=1\n"), "{summary}");
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+#[with_test_dir]
+fn watch_fsmonitor_daemon() {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["config", "core.fsmonitor", "true"]).unwrap();
+
+    // A fake daemon that reports the paths in `changed` and records the tokens
+    // it was sent. Each answer has a new token.
+    let changed = Arc::new(Mutex::new(String::new()));
+    let tokens = Arc::new(Mutex::new(Vec::new()));
+    let listener =
+        UnixListener::bind(root.join("repo/.git/fsmonitor--daemon.ipc"))
+            .unwrap();
+    {
+        let changed = Arc::clone(&changed);
+        let tokens = Arc::clone(&tokens);
+        std::thread::spawn(move || {
+            for (n, stream) in listener.incoming().enumerate() {
+                let mut stream = stream.unwrap();
+                let mut request = vec![];
+                loop {
+                    let mut length = [0; 4];
+                    stream.read_exact(&mut length).unwrap();
+                    let length = std::str::from_utf8(&length).unwrap();
+                    let length = usize::from_str_radix(length, 16).unwrap();
+                    if length == 0 {
+                        break;
+                    }
+                    let mut payload = vec![0; length - 4];
+                    stream.read_exact(&mut payload).unwrap();
+                    request.extend(payload);
+                }
+                tokens
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8(request).unwrap());
+
+                let answer = format!("token{n}\0{}", changed.lock().unwrap());
+                write!(stream, "{:04x}{answer}0000", answer.len() + 4).unwrap();
+            }
+        });
+    }
+
+    let (mut child, summaries) = helpers::git_status_vars_summaries(
+        &root,
+        [
+            "watch",
+            "--interval",
+            "20ms",
+            "--changed-only",
+            "--fsmonitor",
+            "repo",
+        ],
+    );
+    let result = std::panic::catch_unwind(|| {
+        let next = |timeout| summaries.recv_timeout(timeout);
+        let summary = next(Duration::from_secs(10)).unwrap();
+        assert!(summary.contains("\nunstaged_count=0\n"), "{summary}");
+
+        // The daemon doesn’t report the change, so it isn’t noticed.
+        fs::write(root.join("repo/a"), "changed").unwrap();
+        assert_eq!(
+            next(Duration::from_millis(500)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        *changed.lock().unwrap() = "a\0".to_owned();
+        let summary = next(Duration::from_secs(10)).unwrap();
+        assert!(summary.contains("\nunstaged_count=1\n"), "{summary}");
+
+        // The first query has no token, and each one after that passes the
+        // token from the answer before.
+        let tokens = tokens.lock().unwrap().clone();
+        assert_eq!(tokens[..3], ["builtin:fake", "token0", "token1"]);
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+    if let Err(panic) = result {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn watch_refused() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    let exit_code = |args: &[&str]| {
+        helpers::git_status_vars_exit_code(&root, [&["watch"], args].concat())
+    };
+    assert_eq!(exit_code(&["a", "b"]), Some(2));
+    assert_eq!(exit_code(&["--prompt"]), Some(2));
+    assert_eq!(exit_code(&["--two-phase"]), Some(2));
+    assert_eq!(exit_code(&["--timeout", "1s"]), Some(2));
+    assert_eq!(exit_code(&["--interval", "soon"]), Some(2));
+}
+
+#[test]
+#[with_test_dir]
+fn fetch_age() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+
+    let output =
+        helpers::git_status_vars(&root, ["--cache", "repo"]).to_string();
+    assert!(output.contains("\nfetch_age_seconds=''\n"), "{output}");
+
+    // Fetching doesn’t change the cache key, but the age must not be replayed
+    // from the cache.
+    fs::write(root.join("repo/.git/FETCH_HEAD"), "").unwrap();
+    let output =
+        helpers::git_status_vars(&root, ["--cache", "repo"]).to_string();
+    assert!(output.contains("\nfetch_age_seconds=0\n"), "{output}");
+
+    let output = helpers::git_status_vars(&root, ["--unset-missing", "repo"])
+        .to_string();
+    assert!(output.contains("\nfetch_age_seconds=0\n"), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn online() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    helpers::make_commit(&root, "upstream", 2);
+
+    let args = ["--online", "--only", "upstream,remote"];
+    let tip = helpers::git_status_vars(&root, ["--only=head", "upstream"])
+        .to_string();
+    let tip = tip
+        .lines()
+        .find_map(|line| line.strip_prefix("head_hash="))
+        .unwrap();
+
+    // The new commit hasn’t been fetched, so it can’t be counted.
+    let output = helpers::git_status_vars(
+        &root,
+        args.into_iter().chain(std::iter::once("clone")),
+    )
+    .to_string();
+    assert!(output.contains("\nhead_behind=0\n"), "{output}");
+    assert!(
+        output.contains(&format!("\nremote_tip_hash={tip}\n")),
+        "{output}"
+    );
+    assert!(output.contains("\nhead_behind_remote_tip=''\n"), "{output}");
+
+    helpers::git(&root, "clone", ["fetch"]).unwrap();
+    let output = helpers::git_status_vars(
+        &root,
+        args.into_iter().chain(std::iter::once("clone")),
+    )
+    .to_string();
+    assert!(output.contains("\nhead_behind_remote_tip=1\n"), "{output}");
+
+    // There’s no upstream to check.
+    let output = helpers::git_status_vars(
+        &root,
+        args.into_iter().chain(std::iter::once("upstream")),
+    )
+    .to_string();
+    assert!(
+        output.ends_with(
+            "remote_tip_hash=''\nhead_behind_remote_tip=''\ncolor_hint=green\n"
+        ),
+        "{output}"
+    );
+
+    // Errors are reported. The remote must be missing rather than use an
+    // unknown scheme, since those are treated as SSH with `network`.
+    helpers::git(
+        &root,
+        "clone",
+        ["remote", "set-url", "origin", "../missing"],
+    )
+    .unwrap();
+    let output = helpers::git_status_vars(
+        &root,
+        args.into_iter().chain(std::iter::once("clone")),
+    )
+    .to_string();
+    assert!(output.contains("\nremote_error_class=net\n"), "{output}");
+    assert!(output.ends_with("color_hint=red\n"), "{output}");
+
+    // A remote that never answers runs out the default budget.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("git://{}/upstream", listener.local_addr().unwrap());
+    helpers::git(&root, "clone", ["remote", "set-url", "origin", &url])
+        .unwrap();
+    let output = helpers::git_status_vars(
+        &root,
+        args.into_iter().chain(std::iter::once("clone")),
+    )
+    .to_string();
+    assert!(output.contains("\nremote_timeout=true\n"), "{output}");
+    drop(listener);
+}
+
+#[test]
+#[with_test_dir]
+fn fetch() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    helpers::make_commit(&root, "upstream", 2);
+
+    let output = helpers::git_status_vars(&root, ["clone"]).to_string();
+    assert!(output.contains("\nfetch_age_seconds=''\n"), "{output}");
+    assert!(output.contains("\nhead_behind=0\n"), "{output}");
+
+    let output =
+        helpers::git_status_vars(&root, ["--fetch", "clone"]).to_string();
+    assert!(output.contains("\nfetch_age_seconds=0\n"), "{output}");
+    assert!(output.contains("\nhead_behind=1\n"), "{output}");
+    assert!(!output.contains("fetch_error"), "{output}");
+
+    // Errors are reported, and the rest is still output.
+    helpers::git(
+        &root,
+        "clone",
+        ["remote", "set-url", "origin", "../missing"],
+    )
+    .unwrap();
+    let output =
+        helpers::git_status_vars(&root, ["--fetch", "clone"]).to_string();
+    assert!(output.contains("\nfetch_error_class=net\n"), "{output}");
+    assert!(output.contains("\nhead_behind=1\n"), "{output}");
+
+    // A remote that never answers can be given up on.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("git://{}/upstream", listener.local_addr().unwrap());
+    helpers::git(&root, "clone", ["remote", "set-url", "origin", &url])
+        .unwrap();
+    let output = helpers::git_status_vars(
+        &root,
+        ["--fetch", "--budget", "fetch=100ms", "clone"],
+    )
+    .to_string();
+    assert!(output.contains("\nfetch_timeout=true\n"), "{output}");
+    assert!(output.contains("\nhead_behind=1\n"), "{output}");
+
+    // Even without --budget or --timeout, it gives up eventually.
+    let start = std::time::Instant::now();
+    let output =
+        helpers::git_status_vars(&root, ["--fetch", "clone"]).to_string();
+    assert!(output.contains("\nfetch_timeout=true\n"), "{output}");
+    assert!(output.contains("\nhead_behind=1\n"), "{output}");
+    assert!(start.elapsed() < Duration::from_secs(30));
+    drop(listener);
+}
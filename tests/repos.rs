@@ -1,8 +1,12 @@
 //! Tests results on various example repos.
 
+#![cfg(feature = "cli")]
+
 use std::fs;
 use target_test_dir::with_test_dir;
 
+// We don’t use everything in helpers.
+#[allow(dead_code)]
 mod helpers;
 
 #[test]
@@ -11,7 +15,7 @@ fn nonexistent() {
     let root = get_test_dir!();
     helpers::prepare_root(&root);
     assert_eq!(
-        "repo_state=NotFound\n",
+        "schema_version=1\nrepo_state=NotFound\n",
         helpers::git_status_vars(&root, ["."])
     );
 }
@@ -27,25 +31,37 @@ fn empty() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=true
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=''
-        head_ref1_error='Error { code: -3, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=''
+        head_unborn=true
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -9, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ",
     );
 }
 
@@ -61,25 +77,37 @@ fn empty_untracked() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=true
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=''
-        head_ref1_error='Error { code: -3, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=''
+        head_unborn=true
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -9, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=1
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=yellow
+        ",
     );
 }
 
@@ -96,25 +124,37 @@ fn empty_added() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=true
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=''
-        head_ref1_error='Error { code: -3, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=''
+        head_unborn=true
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -9, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=1
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=yellow
+        ",
     );
 }
 
@@ -132,25 +172,37 @@ fn empty_untracked_added() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=true
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=''
-        head_ref1_error='Error { code: -3, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=''
+        head_unborn=true
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -9, klass: 4, message: "reference '\''refs/heads/main'\'' not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=1
         unstaged_count=0
         staged_count=1
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=yellow
+        ",
     );
 }
 
@@ -166,25 +218,37 @@ fn commit() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ",
     );
 }
 
@@ -201,25 +265,37 @@ fn commit_delete() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=1
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=yellow
+        ",
     );
 }
 
@@ -236,25 +312,37 @@ fn commit_delete_staged() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=1
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=yellow
+        ",
     );
 }
 
@@ -271,25 +359,37 @@ fn commit_modified() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=1
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=yellow
+        ",
     );
 }
 
@@ -307,25 +407,85 @@ fn commit_modified_staged() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=1
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=yellow
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn stash() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo").join("a"), "stashed").unwrap();
+    helpers::git(&root, "repo", ["stash"]).unwrap();
+
+    helpers::assert_git_status_vars(
+        &root,
+        "repo",
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        stash_count=1
+        color_hint=green
+        ",
     );
 }
 
@@ -343,21 +503,31 @@ fn detached() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=0
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -1, klass: 3, message: "reference '\''HEAD'\'' is not a local branch." }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ",
     );
 }
 
@@ -374,25 +544,37 @@ fn branch() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/branch
         head_ref1_short=branch
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.branch.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ",
     );
 }
 
@@ -415,29 +597,43 @@ fn sym_ref() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=2
         head_ref1_name=refs/heads/sym
         head_ref1_short=sym
         head_ref1_kind=symbolic
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_ref2_name=refs/heads/main
         head_ref2_short=main
         head_ref2_kind=direct
         head_ref2_error=''
+        head_ref2_error_code=''
+        head_ref2_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ",
     );
 }
 
@@ -457,21 +653,31 @@ fn tag() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=0
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -1, klass: 3, message: "reference '\''HEAD'\'' is not a local branch." }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ",
     );
 }
 
@@ -493,25 +699,37 @@ fn cherry_pick() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=CherryPick
+        ps1_state='|CHERRY-PICKING'
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=2
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=red
+        ",
     );
 }
 
@@ -535,25 +753,37 @@ fn cherry_pick_staged() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=CherryPick
+        ps1_state='|CHERRY-PICKING'
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=1
         conflicted_count=1
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=red
+        ",
     );
 }
 
@@ -578,25 +808,37 @@ fn cherry_pick_unstaged() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=CherryPick
+        ps1_state='|CHERRY-PICKING'
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=1
         staged_count=0
         conflicted_count=1
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=red
+        ",
     );
 }
 
@@ -618,28 +860,86 @@ fn conflict() {
     helpers::assert_git_status_vars(
         &root,
         "repo",
-        r#"
+        r"
+        schema_version=1
         repo_state=Merge
+        ps1_state='|MERGING'
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=2
-        "#,
+        untracked_truncated=false
+        stash_count=0
+        color_hint=red
+        ",
     );
 }
 
+#[test]
+#[with_test_dir]
+fn ps1_state() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["switch", "-c", "topic"]).unwrap();
+    helpers::make_commit(&root, "repo", 2);
+    helpers::make_commit(&root, "repo", 3);
+    helpers::git(&root, "repo", ["switch", "main"]).unwrap();
+    helpers::make_commit(&root, "repo", 4);
+    helpers::git(&root, "repo", ["switch", "topic"]).unwrap();
+
+    let ps1_state = || {
+        let output =
+            helpers::git_status_vars(&root, ["--only", "stash", "repo"])
+                .to_string();
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix("ps1_state="))
+            .unwrap()
+            .to_owned()
+    };
+    assert_eq!(ps1_state(), "''");
+
+    helpers::git(&root, "repo", ["rebase", "main"])
+        .expect_err("rebase should conflict");
+    assert_eq!(ps1_state(), "'|REBASE 1/2'");
+    helpers::git(&root, "repo", ["rebase", "--abort"]).unwrap();
+
+    helpers::git(&root, "repo", ["rebase", "--apply", "main"])
+        .expect_err("rebase should conflict");
+    assert_eq!(ps1_state(), "'|REBASE 1/2'");
+    helpers::git(&root, "repo", ["rebase", "--abort"]).unwrap();
+
+    helpers::git(&root, "repo", ["revert", "main"])
+        .expect_err("revert should conflict");
+    assert_eq!(ps1_state(), "'|REVERTING'");
+    helpers::git(&root, "repo", ["revert", "--abort"]).unwrap();
+
+    helpers::git(&root, "repo", ["bisect", "start"]).unwrap();
+    assert_eq!(ps1_state(), "'|BISECTING'");
+}
+
 #[test]
 #[with_test_dir]
 fn bare() {
@@ -653,25 +953,36 @@ fn bare() {
     helpers::assert_git_status_vars(
         &root,
         "bare",
-        r#"
+        r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=''
         repo_empty=false
         repo_bare=true
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=''
         head_behind=''
-        head_upstream_error='Error { code: -3, klass: 7, message: "config value '\''branch.main.remote'\'' was not found" }'
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
-        "#,
+        stash_count=0
+        color_hint=green
+        ",
     );
 }
 
@@ -690,23 +1001,35 @@ fn ahead_1() {
         &root,
         "clone",
         r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=''
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=1
         head_behind=0
+        head_ahead_exact=true
         head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
         ",
     );
 }
@@ -728,23 +1051,35 @@ fn ahead_1_behind_1() {
         &root,
         "clone",
         r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=0
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=1
         head_behind=1
+        head_ahead_exact=true
         head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
         ",
     );
 }
@@ -765,23 +1100,635 @@ fn behind_1() {
         &root,
         "clone",
         r"
+        schema_version=1
         repo_state=Clean
+        ps1_state=''
         repo_workdir=@REPO@/
         repo_empty=false
         repo_bare=false
+        fetch_age_seconds=0
         head_ref_length=1
         head_ref1_name=refs/heads/main
         head_ref1_short=main
         head_ref1_kind=direct
         head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
         head_hash=@HASH@
+        head_unborn=false
         head_ahead=0
         head_behind=1
+        head_ahead_exact=true
         head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
         untracked_count=0
         unstaged_count=0
         staged_count=0
         conflicted_count=0
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
         ",
     );
 }
+
+#[test]
+#[with_test_dir]
+fn ahead_inexact() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::make_commit(&root, "upstream", 2);
+    let url = format!("file://{}", root.join("upstream").display());
+    helpers::git(&root, ".", ["clone", "-q", "--depth=1", &url, "clone"])
+        .unwrap();
+    helpers::make_commit(&root, "clone", 3);
+
+    let upstream = |exact: &str| {
+        format!(
+            "head_ahead=1\n\
+            head_behind=0\n\
+            head_ahead_exact={exact}\n\
+            head_upstream_error=''\n\
+            head_upstream_error_code=''\n\
+            head_upstream_error_class=''\n\
+            color_hint=green\n"
+        )
+    };
+    let args = ["--only", "upstream", "clone"];
+
+    // Shallow clone.
+    let output = helpers::git_status_vars(&root, args).to_string();
+    assert!(output.ends_with(&upstream("false")), "{output}");
+
+    helpers::git(&root, "clone", ["fetch", "-q", "--unshallow"]).unwrap();
+    let output = helpers::git_status_vars(&root, args).to_string();
+    assert!(output.ends_with(&upstream("true")), "{output}");
+
+    // Replace references.
+    helpers::git(&root, "clone", ["replace", "HEAD~2", "HEAD~1"]).unwrap();
+    let output = helpers::git_status_vars(&root, args).to_string();
+    assert!(output.ends_with(&upstream("false")), "{output}");
+    helpers::git(&root, "clone", ["replace", "-d", "HEAD~2"]).unwrap();
+
+    // Grafts.
+    fs::create_dir_all(root.join("clone/.git/info")).unwrap();
+    fs::write(root.join("clone/.git/info/grafts"), "").unwrap();
+    let output = helpers::git_status_vars(&root, args).to_string();
+    assert!(output.ends_with(&upstream("false")), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn upstream_gone() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    helpers::git(&root, "clone", ["branch", "-rd", "origin/main"]).unwrap();
+
+    helpers::assert_git_status_vars(
+        &root,
+        "clone",
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        stash_count=0
+        color_hint=green
+        ",
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn corrupt_index() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/.git/index"), "garbage").unwrap();
+
+    // Only the counts section fails.
+    let output = helpers::git_status_vars(&root, ["repo"]).to_string();
+    assert!(
+        output.contains("\nhead_ref1_name=refs/heads/main\n"),
+        "{output}"
+    );
+    assert!(output.contains("\nhead_upstream_error=''\n"), "{output}");
+    assert!(
+        output.contains("\ncounts_error='failed to read index"),
+        "{output}"
+    );
+    assert!(
+        output.contains(
+            "\ncounts_error_code=generic\ncounts_error_class=index\n"
+        ),
+        "{output}"
+    );
+    assert!(
+        output.ends_with("\nstash_count=0\ncolor_hint=red\n"),
+        "{output}"
+    );
+    assert!(!output.contains("repo_error"), "{output}");
+    assert!(!output.contains("untracked_count"), "{output}");
+    assert_eq!(
+        helpers::git_status_vars_exit_code(&root, ["--exit-code", "repo"]),
+        Some(4),
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn corrupt_ref() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    fs::write(root.join("clone/.git/refs/heads/main"), "garbage\n").unwrap();
+
+    // The error is reported on the reference, not again by the upstream.
+    let output = helpers::git_status_vars(&root, ["clone"]).to_string();
+    let expected = helpers::strip_indent(
+        r"
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=''
+        head_ref1_error='corrupted loose reference file: refs/heads/main'
+        head_ref1_error_code=generic
+        head_ref1_error_class=reference
+        head_hash=''
+        head_unborn=false
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
+        ",
+    );
+    assert!(output.contains(&expected), "{output}");
+    // libgit2 can’t compare the index to `HEAD` without reading it.
+    assert!(
+        output.contains("\ncounts_error='corrupted loose reference file"),
+        "{output}"
+    );
+    assert!(
+        output.ends_with("\nstash_count=0\ncolor_hint=red\n"),
+        "{output}"
+    );
+    assert!(!output.contains("repo_error"), "{output}");
+
+    let output =
+        helpers::git_status_vars(&root, ["--head-only", "clone"]).to_string();
+    assert!(
+        output.contains(
+            "\nhead_ref1_error='the reference '\\''refs/heads/main'\\'' \
+            is corrupt'\n"
+        ),
+        "{output}"
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn missing_object() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "upstream");
+    helpers::make_commit(&root, "upstream", 1);
+    helpers::git(&root, ".", ["clone", "upstream", "clone"]).unwrap();
+    let hash = "1234567890123456789012345678901234567890";
+    fs::write(root.join("clone/.git/refs/heads/main"), format!("{hash}\n"))
+        .unwrap();
+
+    let output = helpers::git_status_vars(&root, ["clone"]).to_string();
+    let expected = helpers::strip_indent(&format!(
+        r"
+        head_ref1_kind=direct
+        head_ref1_error='object not found - no match for id ({hash})'
+        head_ref1_error_code=not_found
+        head_ref1_error_class=odb
+        head_hash={hash}
+        head_unborn=false
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error=''
+        "
+    ));
+    assert!(output.contains(&expected), "{output}");
+    // Without the commit, every file in the index looks staged.
+    assert!(
+        output.ends_with("\nstash_count=0\ncolor_hint=yellow\n"),
+        "{output}"
+    );
+
+    // Without the head section, the upstream section reports the error.
+    let output =
+        helpers::git_status_vars(&root, ["--only", "upstream", "clone"])
+            .to_string();
+    assert!(
+        output.contains("\nhead_upstream_error_code=not_found\n"),
+        "{output}"
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn symbolic_ref_loop() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    fs::write(root.join("repo/.git/refs/heads/a"), "ref: refs/heads/b\n")
+        .unwrap();
+    fs::write(root.join("repo/.git/refs/heads/b"), "ref: refs/heads/a\n")
+        .unwrap();
+    helpers::git(&root, "repo", ["symbolic-ref", "HEAD", "refs/heads/a"])
+        .unwrap();
+
+    for args in [&["repo"][..], &["--head-only", "repo"]] {
+        let output = helpers::git_status_vars(&root, args).to_string();
+        let expected = helpers::strip_indent(
+            r"
+            head_ref_length=10
+            head_ref1_name=refs/heads/a
+            ",
+        );
+        assert!(output.contains(&expected), "{output}");
+        assert!(
+            output.contains(
+                "\nhead_ref10_error='too many nested symbolic references at \
+                '\\''refs/heads/b'\\'''\n"
+            ),
+            "{output}"
+        );
+    }
+}
+
+#[test]
+#[with_test_dir]
+fn sha256() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git(&root, ".", ["init", "--object-format=sha256", "repo"])
+        .unwrap();
+    helpers::make_commit(&root, "repo", 1);
+
+    // libgit2 can’t open SHA-256 repositories.
+    let output = helpers::git_status_vars(&root, ["repo"]).to_string();
+    assert!(output.contains("\nrepo_state=Error\n"), "{output}");
+    assert!(output.contains("unknown object format"), "{output}");
+
+    // --head-only reads the files directly.
+    let expected = r"
+        schema_version=1
+        repo_state=Unknown
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        ";
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--head-only"],
+        expected,
+    );
+    helpers::git(&root, "repo", ["pack-refs", "--all"]).unwrap();
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--head-only"],
+        expected,
+    );
+
+    let output = helpers::git_status_vars(&root, ["--head-only", "repo"]);
+    let hash = output
+        .to_string()
+        .lines()
+        .find_map(|line| line.strip_prefix("head_hash="))
+        .map(str::len);
+    assert_eq!(hash, Some(64), "{output}");
+}
+
+#[test]
+#[with_test_dir]
+fn reftable() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    // Convert a repository to reftable the way `git init --ref-format=reftable`
+    // would set it up.
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "core.repositoryformatversion", "1"],
+    )
+    .unwrap();
+    helpers::git(
+        &root,
+        "repo",
+        ["config", "extensions.refstorage", "reftable"],
+    )
+    .unwrap();
+    let main = root.join("repo/.git/refs/heads/main");
+    let hash = fs::read_to_string(&main).unwrap();
+    let hash = hash.trim_end();
+    fs::remove_file(main).unwrap();
+    fs::write(root.join("repo/.git/HEAD"), "ref: refs/heads/.invalid\n")
+        .unwrap();
+    helpers::add_reftable(
+        &root,
+        "repo",
+        "1.ref",
+        &[
+            ("HEAD", "ref: refs/heads/main"),
+            ("refs/heads/main", hash),
+            ("refs/heads/other", hash),
+        ],
+    );
+
+    // libgit2 can’t open reftable repositories.
+    let output = helpers::git_status_vars(&root, ["repo"]).to_string();
+    assert!(output.contains("\nrepo_state=Error\n"), "{output}");
+    assert!(output.contains("extensions.refstorage"), "{output}");
+
+    // --head-only reads the tables directly.
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--head-only"],
+        r"
+        schema_version=1
+        repo_state=Unknown
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        ",
+    );
+
+    // Newer tables take precedence, including deletions.
+    helpers::add_reftable(
+        &root,
+        "repo",
+        "2.ref",
+        &[("HEAD", "ref: refs/heads/other"), ("refs/heads/main", "")],
+    );
+    helpers::assert_git_status_vars_args(
+        &root,
+        "repo",
+        &["--head-only"],
+        r"
+        schema_version=1
+        repo_state=Unknown
+        head_ref_length=1
+        head_ref1_name=refs/heads/other
+        head_ref1_short=other
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        ",
+    );
+    helpers::add_reftable(
+        &root,
+        "repo",
+        "3.ref",
+        &[("HEAD", "ref: refs/heads/main")],
+    );
+    let output = helpers::git_status_vars(&root, ["--head-only", "repo"]);
+    assert!(output.ends_with(b"\nhead_hash=''\nhead_unborn=true\n"));
+}
+
+#[test]
+#[with_test_dir]
+fn reftable_git() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    // git 2.45 and later can make a real reftable repository.
+    if helpers::git(&root, ".", ["init", "--ref-format=reftable", "real"])
+        .is_ok()
+    {
+        helpers::make_commit(&root, "real", 1);
+        helpers::git(&root, "real", ["checkout", "-b", "other"]).unwrap();
+        let output = helpers::git_status_vars(&root, ["--head-only", "real"]);
+        assert!(
+            output
+                .to_string()
+                .contains("\nhead_ref1_name=refs/heads/other\n"),
+            "{output}"
+        );
+    }
+}
+
+#[test]
+#[cfg(unix)]
+#[with_test_dir]
+fn non_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    let repo = OsStr::from_bytes(b"caf\xe9");
+    helpers::git(&root, ".", [OsStr::new("init"), repo]).unwrap();
+    helpers::git(
+        &root,
+        ".",
+        [
+            OsStr::new("-C"),
+            repo,
+            OsStr::new("symbolic-ref"),
+            OsStr::new("HEAD"),
+            OsStr::from_bytes(b"refs/heads/\xff"),
+        ],
+    )
+    .unwrap();
+
+    let output = helpers::git_status_vars(&root, [repo]).to_string();
+    let expected = format!(
+        "
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir={root}/caf\u{FFFD}/
+        repo_workdir_raw='{root}/caf%E9/'
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/\u{FFFD}
+        head_ref1_name_raw='refs/heads/%FF'
+        head_ref1_short=\u{FFFD}
+        head_ref1_kind=''
+        head_ref1_error='reference name is not valid UTF-8'
+        head_ref1_error_code=generic
+        head_ref1_error_class=none
+        ",
+        root = root.display(),
+    );
+    assert!(
+        output.starts_with(&helpers::strip_indent(&expected)),
+        "{output}"
+    );
+}
+
+#[test]
+#[with_test_dir]
+fn linked_worktree() {
+    let root = get_test_dir!();
+    helpers::prepare_root(&root);
+
+    helpers::git_init(&root, "repo");
+    helpers::make_commit(&root, "repo", 1);
+    helpers::git(&root, "repo", ["worktree", "add", "-b", "branch", "../wt"])
+        .unwrap();
+    helpers::make_commit(&root, "wt", 2);
+    helpers::make_commit(&root, "repo", 3);
+
+    // Stashes are shared between worktrees.
+    fs::write(root.join("repo").join("a"), "stashed").unwrap();
+    helpers::git(&root, "repo", ["stash"]).unwrap();
+
+    // Sequencer state is per worktree.
+    helpers::git(&root, "wt", ["cherry-pick", "main"])
+        .expect_err("cherry-pick should fail");
+
+    helpers::assert_git_status_vars(
+        &root,
+        "wt",
+        r"
+        schema_version=1
+        repo_state=CherryPick
+        ps1_state='|CHERRY-PICKING'
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/branch
+        head_ref1_short=branch
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=2
+        untracked_truncated=false
+        stash_count=1
+        color_hint=red
+        ",
+    );
+    helpers::assert_git_status_vars(
+        &root,
+        "repo",
+        r"
+        schema_version=1
+        repo_state=Clean
+        ps1_state=''
+        repo_workdir=@REPO@/
+        repo_empty=false
+        repo_bare=false
+        fetch_age_seconds=''
+        head_ref_length=1
+        head_ref1_name=refs/heads/main
+        head_ref1_short=main
+        head_ref1_kind=direct
+        head_ref1_error=''
+        head_ref1_error_code=''
+        head_ref1_error_class=''
+        head_hash=@HASH@
+        head_unborn=false
+        head_ahead=''
+        head_behind=''
+        head_ahead_exact=''
+        head_upstream_error=''
+        head_upstream_error_code=''
+        head_upstream_error_class=''
+        untracked_count=0
+        unstaged_count=0
+        staged_count=0
+        conflicted_count=0
+        untracked_truncated=false
+        stash_count=1
+        color_hint=green
+        ",
+    );
+
+    // A rebase in the linked worktree doesn’t affect the main worktree.
+    helpers::git(&root, "wt", ["cherry-pick", "--abort"]).unwrap();
+    helpers::git(&root, "wt", ["rebase", "main"])
+        .expect_err("rebase should fail");
+    let state = |repo| {
+        let output = helpers::git_status_vars(&root, ["--only", "head", repo])
+            .to_string();
+        output.lines().nth(1).unwrap().to_owned()
+    };
+    assert_ne!(state("wt"), "repo_state=Clean");
+    assert_eq!(state("repo"), "repo_state=Clean");
+}
@@ -0,0 +1,55 @@
+//! Test the `test-support` feature.
+
+#![cfg(feature = "test-support")]
+
+use git_status_vars::test_support::{TempRepo, CONFLICT_BRANCH};
+use git_status_vars::{summarize_path, RepoState, SummarizeOptions};
+
+#[test]
+fn temp_repo() {
+    let repo = TempRepo::new()
+        .unwrap()
+        .commit("first")
+        .unwrap()
+        .branch("feature")
+        .unwrap()
+        .commit("second")
+        .unwrap()
+        .checkout("main")
+        .unwrap()
+        .write("a", "changed")
+        .unwrap()
+        .stash()
+        .unwrap()
+        .write("dir/untracked", "new")
+        .unwrap();
+
+    let summary =
+        summarize_path(repo.path(), &SummarizeOptions::default()).unwrap();
+    assert_eq!(summary.state, RepoState::Clean);
+    assert_eq!(summary.head.unwrap().trail[1].short(), "main");
+    assert_eq!(summary.stash_count, Some(1));
+    let changes = summary.changes.unwrap();
+    assert_eq!(changes.untracked(), 1);
+    assert_eq!(changes.unstaged(), 0);
+
+    let root = repo.path().parent().unwrap().to_path_buf();
+    drop(repo);
+    assert!(!root.exists());
+}
+
+#[test]
+fn temp_repo_conflict() {
+    let repo = TempRepo::new()
+        .unwrap()
+        .commit("first")
+        .unwrap()
+        .conflict("c")
+        .unwrap();
+
+    let summary =
+        summarize_path(repo.path(), &SummarizeOptions::default()).unwrap();
+    assert_eq!(summary.state, RepoState::Merge);
+    assert_eq!(summary.changes.unwrap().conflicted(), 1);
+    repo.git(["rev-parse", CONFLICT_BRANCH]).unwrap();
+}